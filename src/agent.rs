@@ -1,67 +1,73 @@
 use crate::client::Client;
-use crate::compact::{compact_context, is_context_exhausted_error, CompactionConfig};
+use crate::compact::{compact_context, is_context_exhausted_error, reattach_todos, CompactionConfig};
 use crate::display_sink::{DisplayEvent, DisplaySink};
 use crate::key_rotation::{BadKeyAction, RateLimitAction};
-use crate::models::{FunctionSpec, Message, Tool};
-use crate::output_store::OutputStore;
-use crate::tools::ToolRegistry;
+use crate::models::{FinishReason, Message, ToolCall};
+use crate::output_store::{OutputStore, SpillSlot};
+use crate::redact;
+use crate::tools::{progress_channel, ToolContext, ToolRegistry, GET_OUTPUT_TOOL_NAME};
 use crate::usage::SessionUsage;
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use std::sync::Arc;
 use tokio::sync::watch;
 
-// --- Built-in get_output tool ---
-pub const GET_OUTPUT_TOOL_NAME: &str = "get_output";
-
-/// Get the tool spec for the built-in get_output tool
-pub fn get_get_output_tool_spec() -> Tool {
-    Tool {
-        tool_type: "function".to_string(),
-        function: FunctionSpec {
-            name: GET_OUTPUT_TOOL_NAME.to_string(),
-            description: "Retrieve a range of lines from a previous tool output by its ID. Tool outputs that are too large are automatically truncated, showing the first and last 10 lines. Use this tool to retrieve the middle sections or re-read specific ranges.".to_string(),
-            parameters: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "id": {
-                        "type": "string",
-                        "description": "The output ID (e.g., 'out_001') shown in the truncated output header."
-                    },
-                    "start": {
-                        "type": "integer",
-                        "description": "Start line number (0-indexed). Defaults to 0."
-                    },
-                    "end": {
-                        "type": "integer",
-                        "description": "End line number (exclusive). Defaults to start + 100."
-                    }
-                },
-                "required": ["id"]
-            }),
-        },
+/// How many times a reply cut off at the output token limit is continued
+/// before the loop gives up on it
+pub const MAX_LENGTH_CONTINUATIONS: usize = 3;
+
+/// Sent after a reply that hit the output token limit
+pub const CONTINUE_PROMPT: &str = "Your previous response was cut off at the output token limit. \
+Continue exactly where you left off, without repeating what you already wrote.";
+
+/// Tool calls of a reply cut off at the output limit that can still run.
+/// The last call's arguments may be incomplete JSON; such calls are dropped.
+pub fn complete_tool_calls(calls: Option<&Vec<ToolCall>>) -> Option<Vec<ToolCall>> {
+    let complete: Vec<ToolCall> = calls?
+        .iter()
+        .filter(|call| serde_json::from_str::<serde_json::Value>(&call.function.arguments).is_ok())
+        .cloned()
+        .collect();
+    if complete.is_empty() {
+        None
+    } else {
+        Some(complete)
     }
 }
 
-/// Execute get_output tool to retrieve stored output ranges
-pub fn execute_get_output(
-    output_store: &OutputStore,
+/// Execute a registry tool, showing the output lines it reports (Bash) on
+/// `display` while it runs
+async fn execute_showing_progress(
+    tool_registry: &ToolRegistry,
+    tool_name: &str,
     args: serde_json::Value,
+    ctx: ToolContext<'_>,
+    display: &dyn DisplaySink,
 ) -> Result<String> {
-    let id = args["id"]
-        .as_str()
-        .ok_or_else(|| anyhow!("Missing id parameter"))?;
-
-    let start = args["start"]
-        .as_u64()
-        .map(|n| n as usize)
-        .unwrap_or(0);
-
-    let end = args["end"]
-        .as_u64()
-        .map(|n| Some(n as usize))
-        .unwrap_or(None);
-
-    output_store.get_range(id, start, end)
+    let (progress_tx, mut progress_rx) = progress_channel();
+    let ctx = ToolContext {
+        progress: Some(&progress_tx),
+        ..ctx
+    };
+    let mut masker = tool_registry.redactor().lines();
+    let mut show = |line: String| {
+        if let Some(line) = masker.mask(&line) {
+            display.write_event(DisplayEvent::ToolProgress { line });
+        }
+    };
+    let exec = tool_registry.execute_with_context(tool_name, args, ctx);
+    tokio::pin!(exec);
+    let result = loop {
+        tokio::select! {
+            biased;
+            Some(line) = progress_rx.recv() => show(line),
+            result = &mut exec => break result,
+        }
+    };
+    // Lines sent just before the tool returned
+    while let Ok(line) = progress_rx.try_recv() {
+        show(line);
+    }
+    result
 }
 
 /// Result of running the agent - indicates if it completed or was cancelled
@@ -130,13 +136,16 @@ pub async fn run_agent_cancellable(
     // Track token usage across API calls
     let mut session_usage = SessionUsage::new();
 
+    // Replies continued after hitting the output token limit
+    let mut length_continuations = 0;
+
     loop {
         // Get available tools
         let mut tools = tool_registry.get_tools();
 
-        // Add built-in get_output tool when output_store is enabled
-        if output_store.is_some() {
-            tools.push(get_get_output_tool_spec());
+        // get_output reads the output store, so it is only offered with one
+        if output_store.is_none() {
+            tools.retain(|t| t.function.name != GET_OUTPUT_TOOL_NAME);
         }
 
         let tools_option = if tools.is_empty() { None } else { Some(tools.as_slice()) };
@@ -174,6 +183,8 @@ pub async fn run_agent_cancellable(
                         if streamed_any {
                             display.write_event(DisplayEvent::StreamEnd);
                         }
+                        // Cancelling the session also stops its background jobs
+                        tool_registry.kill_background_jobs();
                         return Ok(AgentResult {
                             status: AgentStatus::Cancelled,
                             usage: session_usage,
@@ -212,6 +223,7 @@ pub async fn run_agent_cancellable(
                     }
                     _ = rx.changed() => {
                         display.write_event(DisplayEvent::ThinkingStop);
+                        tool_registry.kill_background_jobs();
                         return Ok(AgentResult {
                             status: AgentStatus::Cancelled,
                             usage: session_usage,
@@ -308,6 +320,7 @@ pub async fn run_agent_cancellable(
                             let compacted = crate::compact::trim_to_token_budget(conversation_history, target);
                             conversation_history.clear();
                             conversation_history.extend(compacted);
+                            reattach_todos(conversation_history, &tool_registry.todos().items());
 
                             compaction_attempted = true;
                             continue; // Retry with compacted context
@@ -319,6 +332,7 @@ pub async fn run_agent_cancellable(
                                 // Replace conversation history with compacted version
                                 conversation_history.clear();
                                 conversation_history.extend(compacted.messages);
+                                reattach_todos(conversation_history, &tool_registry.todos().items());
 
                                 compaction_attempted = true;
                                 continue; // Retry with compacted context
@@ -339,14 +353,36 @@ pub async fn run_agent_cancellable(
         };
 
         let choice = &response.choices[0];
+        let truncated = choice.finish_reason == Some(FinishReason::Length);
+        if !truncated {
+            length_continuations = 0;
+        }
+
+        // Safety blocks and recitation stops leave no usable reply; say why
+        if let Some(message) = choice.finish_reason.as_ref().and_then(FinishReason::error_message) {
+            display.write_event(DisplayEvent::Error { message });
+        }
+
+        // A reply cut off mid tool call can't run that call
+        let tool_calls = if truncated {
+            complete_tool_calls(choice.message.tool_calls.as_ref())
+        } else {
+            choice.message.tool_calls.clone()
+        };
 
-        // Add assistant response to history
+        // Add assistant response to history, with any built-in tool activity
+        // folded into the content so it is kept with the session
         let assistant_message = Message::Assistant {
-            content: choice.message.content.clone(),
-            tool_calls: choice.message.tool_calls.clone(),
+            content: choice.history_content(),
+            tool_calls: tool_calls.clone(),
         };
         conversation_history.push(assistant_message);
 
+        // Surface what Gemini's built-in tools did this turn
+        for output in &choice.message.builtin_outputs {
+            display.write_event(output.clone().into());
+        }
+
         // Display content if present (skip if we already streamed it)
         if !used_streaming {
             if let Some(content) = &choice.message.content {
@@ -357,14 +393,32 @@ pub async fn run_agent_cancellable(
         }
 
         // Check for tool calls
-        let Some(tool_calls) = &choice.message.tool_calls else {
+        let Some(tool_calls) = tool_calls.filter(|calls| !calls.is_empty()) else {
+            // Ask for the rest of a reply that hit the output limit
+            if truncated {
+                if length_continuations < MAX_LENGTH_CONTINUATIONS {
+                    length_continuations += 1;
+                    display.write_event(DisplayEvent::Info {
+                        message: format!(
+                            "Response hit the output token limit, continuing ({}/{})",
+                            length_continuations, MAX_LENGTH_CONTINUATIONS
+                        ),
+                    });
+                    conversation_history.push(Message::User {
+                        content: CONTINUE_PROMPT.to_string(),
+                    });
+                    continue;
+                }
+                display.write_event(DisplayEvent::Error {
+                    message: format!(
+                        "Response still truncated after {} continuations",
+                        MAX_LENGTH_CONTINUATIONS
+                    ),
+                });
+            }
             break;
         };
 
-        if tool_calls.is_empty() {
-            break;
-        }
-
         // Execute each tool call
         for (tool_idx, tool_call) in tool_calls.iter().enumerate() {
             let tool_name = &tool_call.function.name;
@@ -380,19 +434,25 @@ pub async fn run_agent_cancellable(
             let args: serde_json::Value = serde_json::from_str(arguments).unwrap_or_default();
 
             // Execute tool
-            let result = if tool_name == GET_OUTPUT_TOOL_NAME {
-                // Handle get_output tool
-                if let Some(ref store) = output_store {
-                    execute_get_output(store, args)
-                        .unwrap_or_else(|e| format!("Error: {}", e))
-                } else {
-                    "Error: Output store not available".to_string()
-                }
-            } else if tool_registry.has_tool(tool_name) {
+            let todos_revision = tool_registry.todos().revision();
+            let result = if tool_registry.has_tool(tool_name) {
                 // Execute via ToolRegistry, racing against cancellation so a
                 // long-running tool (e.g. Bash) can be stopped with Escape/Ctrl+C.
                 // Dropping the execute future kills the spawned subprocess.
-                let exec = tool_registry.execute(tool_name, args);
+                // Output too large to return whole is left here for the store
+                let spill = SpillSlot::default();
+                let ctx = ToolContext {
+                    outputs: output_store.as_deref(),
+                    spill: output_store.is_some().then_some(&spill),
+                    ..tool_registry.context()
+                };
+                let exec = execute_showing_progress(
+                    tool_registry,
+                    tool_name,
+                    args,
+                    ctx,
+                    display.as_ref(),
+                );
                 let raw_result = if let Some(mut rx) = cancel_rx.clone() {
                     tokio::select! {
                         r = exec => r.unwrap_or_else(|e| format!("Error: {}", e)),
@@ -409,6 +469,7 @@ pub async fn run_agent_cancellable(
                                     content: "[Cancelled by user]".to_string(),
                                 });
                             }
+                            tool_registry.kill_background_jobs();
                             return Ok(AgentResult {
                                 status: AgentStatus::Cancelled,
                                 usage: session_usage,
@@ -418,12 +479,24 @@ pub async fn run_agent_cancellable(
                 } else {
                     exec.await.unwrap_or_else(|e| format!("Error: {}", e))
                 };
-
-                // Store output if store is enabled
-                if let Some(ref mut store) = output_store {
-                    match store.store(raw_result) {
-                        Ok((_id, truncated)) => truncated,
-                        Err(_) => "Error: Failed to store output".to_string(),
+                // Before the output store, so get_output can't hand a secret back.
+                // A spilled file is stored as the tool wrote it; get_output's
+                // own results are redacted on the way out.
+                let raw_result =
+                    redact_result(tool_registry, tool_name, &raw_result, display.as_ref());
+
+                // Store output if store is enabled. Tools that bound their own
+                // output (Read, Glob, Grep, get_output) pass through untruncated.
+                if let Some(store) = output_store
+                    .as_mut()
+                    .filter(|_| !tool_registry.limits_own_output(tool_name))
+                {
+                    match spill.take() {
+                        Some(spilled) => store.adopt(spilled, &raw_result).1,
+                        None => match store.store(raw_result) {
+                            Ok((_id, truncated)) => truncated,
+                            Err(_) => "Error: Failed to store output".to_string(),
+                        },
                     }
                 } else {
                     raw_result
@@ -432,11 +505,18 @@ pub async fn run_agent_cancellable(
                 format!("Error: Unknown tool '{}'", tool_name)
             };
 
-            // Display result
-            display.write_event(DisplayEvent::ToolResult {
-                result: result.clone(),
-                limit: tool_output_limit,
-            });
+            // Display result; a changed task list is shown as the checklist
+            // rather than as the Todo tool's reply
+            if tool_registry.todos().revision() != todos_revision {
+                display.write_event(DisplayEvent::Todos {
+                    items: tool_registry.todos().items(),
+                });
+            } else {
+                display.write_event(DisplayEvent::ToolResult {
+                    result: result.clone(),
+                    limit: tool_output_limit,
+                });
+            }
 
             // Add tool result to history
             conversation_history.push(Message::Tool {
@@ -452,50 +532,258 @@ pub async fn run_agent_cancellable(
     })
 }
 
+/// Mask secrets in a tool result before the model or the output store sees
+/// it, telling the user how many were masked
+fn redact_result(
+    registry: &ToolRegistry,
+    tool_name: &str,
+    result: &str,
+    display: &dyn DisplaySink,
+) -> String {
+    let redactor = registry.redactor();
+    let (result, count) = redactor.redact(result);
+    if count > 0 {
+        display.write_event(DisplayEvent::Info {
+            message: format!(
+                "Redacted {} from {} output ({} this session)",
+                redact::describe(count),
+                tool_name,
+                redactor.total()
+            ),
+        });
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn call(id: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: crate::models::FunctionCall {
+                name: "Bash".to_string(),
+                arguments: arguments.to_string(),
+            },
+            provider_metadata: None,
+        }
+    }
+
     #[test]
-    fn test_get_output_tool_spec() {
-        let spec = get_get_output_tool_spec();
-        assert_eq!(spec.function.name, GET_OUTPUT_TOOL_NAME);
-        assert_eq!(spec.tool_type, "function");
-        assert!(spec.function.description.contains("Retrieve"));
-        assert!(spec.function.description.contains("truncated"));
-
-        // Check parameters
-        let params = &spec.function.parameters;
-        assert_eq!(params["type"], "object");
-        assert!(params["properties"]["id"]["type"].as_str() == Some("string"));
-        assert!(params["properties"]["start"]["type"].as_str() == Some("integer"));
-        assert!(params["properties"]["end"]["type"].as_str() == Some("integer"));
-
-        // Check required fields - only id is required
-        let required = params["required"].as_array().unwrap();
-        assert!(required.contains(&serde_json::json!("id")));
-        assert!(!required.contains(&serde_json::json!("start")));
-        assert!(!required.contains(&serde_json::json!("end")));
+    fn test_complete_tool_calls_drops_cut_off_arguments() {
+        let calls = vec![call("a", r#"{"command":"ls"}"#), call("b", r#"{"command":"ca"#)];
+        let complete = complete_tool_calls(Some(&calls)).unwrap();
+        assert_eq!(complete.len(), 1);
+        assert_eq!(complete[0].id, "a");
+
+        let only_cut_off = vec![call("b", r#"{"comm"#)];
+        assert!(complete_tool_calls(Some(&only_cut_off)).is_none());
+        assert!(complete_tool_calls(None).is_none());
+    }
+
+    /// Records display events for assertions
+    struct RecordingSink(std::sync::Mutex<Vec<DisplayEvent>>);
+
+    impl DisplaySink for RecordingSink {
+        fn write_event(&self, event: DisplayEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_progress_reaches_display() {
+        let sink = RecordingSink(std::sync::Mutex::new(Vec::new()));
+        let registry = ToolRegistry::new();
+        let result = execute_showing_progress(
+            &registry,
+            "Bash",
+            serde_json::json!({"command": "echo one; echo two >&2"}),
+            registry.context(),
+            &sink,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result, "one\n\n[stderr]\ntwo\n");
+        let mut lines: Vec<String> = sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|e| match e {
+                DisplayEvent::ToolProgress { line } => Some(line.clone()),
+                _ => None,
+            })
+            .collect();
+        lines.sort();
+        assert_eq!(lines, ["one", "two"]);
     }
 
     #[test]
-    fn test_execute_get_output() {
-        let mut store = OutputStore::new();
-
-        // Store some content
-        let lines: Vec<String> = (1..=200).map(|i| format!("line {}", i)).collect();
-        let content = lines.join("\n");
-        let (id, _) = store.store(content).unwrap();
-
-        // Test retrieval
-        let args = serde_json::json!({
-            "id": id,
-            "start": 50,
-            "end": 60
+    fn test_redact_result_masks_and_reports() {
+        let sink = RecordingSink(std::sync::Mutex::new(Vec::new()));
+        let registry = ToolRegistry::new().with_redactor(
+            crate::redact::Redactor::default().with_secret("MY_API_KEY", "key-0123456789"),
+        );
+
+        let result = redact_result(&registry, "Bash", "MY_API_KEY=key-0123456789", &sink);
+        assert_eq!(result, "MY_API_KEY=[REDACTED:MY_API_KEY]");
+        redact_result(&registry, "Read", "key-0123456789 key-0123456789", &sink);
+        assert_eq!(redact_result(&registry, "Bash", "clean", &sink), "clean");
+
+        let messages: Vec<String> = sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|e| match e {
+                DisplayEvent::Info { message } => Some(message.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Redacted 1 secret from Bash output (1 this session)",
+                "Redacted 2 secrets from Read output (3 this session)",
+            ]
+        );
+    }
+
+    /// Serve canned Ollama NDJSON replies, one per request, recording each request body
+    async fn spawn_ollama(replies: Vec<&'static str>) -> (String, Arc<std::sync::Mutex<Vec<serde_json::Value>>>) {
+        use axum::{routing::post, Json, Router};
+
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let app = Router::new().route(
+            "/api/chat",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let seen = seen.clone();
+                let replies = replies.clone();
+                async move {
+                    let mut seen = seen.lock().unwrap();
+                    let reply = replies[seen.len().min(replies.len() - 1)];
+                    seen.push(body);
+                    reply
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
         });
+        (format!("http://{}/", addr), requests)
+    }
+
+    fn ollama_client(base_url: String) -> Client {
+        Client::new(&crate::models::ProviderInfo {
+            provider: crate::models::Provider::Ollama,
+            base_url,
+            api_key: "ollama".to_string(),
+            resolved_model: "test-model".to_string(),
+            use_native_gemini_api: false,
+            azure_api_version: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_truncated_reply_is_continued() {
+        let (base_url, requests) = spawn_ollama(vec![
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Part one\"},\"done\":false}\n\
+             {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"length\"}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\" and two\"},\"done\":true,\"done_reason\":\"stop\"}\n",
+        ])
+        .await;
+        let client = ollama_client(base_url);
+        let sink = Arc::new(RecordingSink(std::sync::Mutex::new(Vec::new())));
+        let mut history = Vec::new();
+
+        run_agent(&client, "test-model", "write", 50, &ToolRegistry::new(), sink.clone(), &mut history, None, None)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let last = requests[1]["messages"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!(last["content"], CONTINUE_PROMPT);
+        assert!(matches!(&history[1], Message::Assistant { content: Some(c), .. } if c == "Part one"));
+        assert!(matches!(&history[3], Message::Assistant { content: Some(c), .. } if c == " and two"));
+        assert!(sink.0.lock().unwrap().iter().any(
+            |e| matches!(e, DisplayEvent::Info { message } if message.contains("output token limit"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_truncation_gives_up_after_max_continuations() {
+        let (base_url, requests) = spawn_ollama(vec![
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"more\"},\"done\":true,\"done_reason\":\"length\"}\n",
+        ])
+        .await;
+        let client = ollama_client(base_url);
+        let sink = Arc::new(RecordingSink(std::sync::Mutex::new(Vec::new())));
+        let mut history = Vec::new();
+
+        run_agent(&client, "test-model", "write", 50, &ToolRegistry::new(), sink.clone(), &mut history, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(requests.lock().unwrap().len(), MAX_LENGTH_CONTINUATIONS + 1);
+        assert!(sink.0.lock().unwrap().iter().any(
+            |e| matches!(e, DisplayEvent::Error { message } if message.contains("still truncated"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_continuations_are_counted_per_reply() {
+        let length = "{\"message\":{\"role\":\"assistant\",\"content\":\"more\"},\"done\":true,\"done_reason\":\"length\"}\n";
+        let (base_url, requests) = spawn_ollama(vec![
+            length,
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\",\"tool_calls\":[{\"function\":{\"name\":\"Nope\",\"arguments\":{}}}]},\"done\":true,\"done_reason\":\"stop\"}\n",
+            length,
+            length,
+            length,
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"done\"},\"done\":true,\"done_reason\":\"stop\"}\n",
+        ])
+        .await;
+        let client = ollama_client(base_url);
+        let sink = Arc::new(RecordingSink(std::sync::Mutex::new(Vec::new())));
+        let mut history = Vec::new();
+
+        run_agent(&client, "test-model", "write", 50, &ToolRegistry::new(), sink.clone(), &mut history, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(requests.lock().unwrap().len(), 6);
+        assert!(!sink.0.lock().unwrap().iter().any(
+            |e| matches!(e, DisplayEvent::Error { message } if message.contains("still truncated"))
+        ));
+    }
 
-        let result = execute_get_output(&store, args).unwrap();
-        assert!(result.contains("line 51")); // 0-indexed
-        assert!(result.contains("line 60"));
+    #[tokio::test]
+    async fn test_task_list_changes_reach_display() {
+        let (base_url, _requests) = spawn_ollama(vec![
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\",\"tool_calls\":[{\"function\":{\"name\":\"Todo\",\"arguments\":{\"todos\":[{\"content\":\"Write tests\",\"status\":\"in_progress\"}]}}}]},\"done\":true,\"done_reason\":\"stop\"}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"done\"},\"done\":true,\"done_reason\":\"stop\"}\n",
+        ])
+        .await;
+        let client = ollama_client(base_url);
+        let sink = Arc::new(RecordingSink(std::sync::Mutex::new(Vec::new())));
+        let mut history = Vec::new();
+
+        run_agent(&client, "test-model", "plan", 50, &ToolRegistry::new(), sink.clone(), &mut history, None, None)
+            .await
+            .unwrap();
+
+        let events = sink.0.lock().unwrap();
+        assert!(events.iter().any(
+            |e| matches!(e, DisplayEvent::Todos { items } if items.len() == 1 && items[0].content == "Write tests")
+        ));
+        assert!(!events.iter().any(|e| matches!(e, DisplayEvent::ToolResult { .. })));
+        assert!(matches!(&history[2], Message::Tool { content, .. } if content.starts_with("[>] Write tests")));
     }
 }
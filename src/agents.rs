@@ -1,12 +1,15 @@
 use anyhow::{anyhow, Result};
 use serde::Deserialize;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table, Value};
+use crate::env_policy::validate_name;
+use crate::models::GeminiBuiltinTool;
+use crate::sandbox::{SandboxPolicy, SandboxProfileSpec, BUILTIN_PROFILES};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, Value};
 
 /// One `[[agent]]` table as written in agents.toml.
 #[derive(Debug, Clone, Deserialize)]
@@ -26,6 +29,21 @@ pub struct AgentSpec {
     pub timeout_secs: u64,
     #[serde(default)]
     pub working_dir: Option<String>,
+    /// Gemini built-in tools to enable for this agent's runs (code_execution,
+    /// google_search, url_context). Ignored by other providers.
+    #[serde(default)]
+    pub gemini_tools: Vec<String>,
+    /// Sandbox profile confining this agent's tools: `workspace`, `offline`,
+    /// or the name of a `[sandbox.<name>]` table. Requires `working_dir`.
+    #[serde(default)]
+    pub sandbox: Option<String>,
+    /// Environment variables set for this agent's Bash and custom tool commands
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Variables this agent's commands inherit even though the environment
+    /// policy denies them, e.g. an API key a skill script needs
+    #[serde(default)]
+    pub pass_env: Vec<String>,
 }
 
 /// Top level of agents.toml.
@@ -34,6 +52,9 @@ pub struct AgentSpec {
 pub struct AgentsFile {
     #[serde(default, rename = "agent")]
     pub agents: Vec<AgentSpec>,
+    /// Custom sandbox profiles, `[sandbox.<name>]`
+    #[serde(default, rename = "sandbox")]
+    pub sandboxes: BTreeMap<String, SandboxProfileSpec>,
 }
 
 /// A fully validated agent, ready to schedule.
@@ -53,6 +74,11 @@ pub struct LoadedAgent {
     pub enabled: bool,
     pub timeout_secs: u64,
     pub working_dir: Option<PathBuf>,
+    pub gemini_tools: Vec<GeminiBuiltinTool>,
+    /// Resolved `sandbox` profile, rooted at `working_dir`
+    pub sandbox: Option<SandboxPolicy>,
+    pub env: BTreeMap<String, String>,
+    pub pass_env: Vec<String>,
 }
 
 /// The validated contents of an agents.toml.
@@ -186,6 +212,18 @@ pub fn validate_text(
         .map(Path::to_path_buf)
         .unwrap_or_else(|| PathBuf::from("."));
 
+    if let Some(name) = parsed
+        .sandboxes
+        .keys()
+        .find(|name| BUILTIN_PROFILES.contains(&name.as_str()))
+    {
+        return Err(anyhow!(
+            "[sandbox.{}]: '{}' is a built-in sandbox profile and cannot be redefined",
+            name,
+            name
+        ));
+    }
+
     let mut seen_names: HashSet<String> = HashSet::new();
     let mut agents = Vec::new();
 
@@ -291,6 +329,27 @@ pub fn validate_text(
             None => None,
         };
 
+        let gemini_tools = GeminiBuiltinTool::parse_list(&spec.gemini_tools)
+            .map_err(|e| anyhow!("agent '{}': gemini_tools: {}", spec.name, e))?;
+
+        let sandbox = match (&spec.sandbox, &working_dir) {
+            (None, _) => None,
+            (Some(_), None) => {
+                return Err(anyhow!(
+                    "agent '{}': sandbox requires working_dir (the directory its tools are confined to)",
+                    spec.name
+                ))
+            }
+            (Some(profile), Some(dir)) => Some(
+                SandboxPolicy::resolve(profile, dir, &parsed.sandboxes)
+                    .map_err(|e| anyhow!("agent '{}': {}", spec.name, e))?,
+            ),
+        };
+
+        for name in spec.env.keys().chain(&spec.pass_env) {
+            validate_name(name).map_err(|e| anyhow!("agent '{}': {}", spec.name, e))?;
+        }
+
         agents.push(LoadedAgent {
             name: spec.name,
             schedule_expr: spec.schedule,
@@ -302,6 +361,10 @@ pub fn validate_text(
             enabled: spec.enabled,
             timeout_secs: spec.timeout_secs,
             working_dir,
+            gemini_tools,
+            sandbox,
+            env: spec.env,
+            pass_env: spec.pass_env,
         });
     }
 
@@ -372,7 +435,7 @@ pub enum AgentMutation {
     /// Create when `original_name` is None, otherwise update that agent in place.
     Upsert {
         original_name: Option<String>,
-        spec: AgentSpec,
+        spec: Box<AgentSpec>,
     },
     Delete {
         name: String,
@@ -494,6 +557,8 @@ fn find_agent(doc: &DocumentMut, name: &str) -> Option<usize> {
 
 /// Overwrite the keys a spec carries, removing those it leaves unset. Keys that
 /// already exist keep their position; new ones land at the end of the table.
+/// `gemini_tools`, `sandbox`, `env` and `pass_env` are not edited here: the web
+/// editor does not expose them, so an update must leave whatever the file says in place.
 fn update_agent_table(table: &mut Table, spec: &AgentSpec) {
     assign(table, "schedule", Value::from(spec.schedule.as_str()));
     set_or_remove(table, "model", spec.model.as_deref());
@@ -552,6 +617,25 @@ fn fill_new_agent_table(table: &mut Table, spec: &AgentSpec) {
     if let Some(working_dir) = &spec.working_dir {
         table["working_dir"] = value(working_dir.as_str());
     }
+    if !spec.gemini_tools.is_empty() {
+        let tools: Array = spec.gemini_tools.iter().map(String::as_str).collect();
+        table["gemini_tools"] = value(tools);
+    }
+    if let Some(sandbox) = &spec.sandbox {
+        table["sandbox"] = value(sandbox.as_str());
+    }
+    if !spec.env.is_empty() {
+        let env: InlineTable = spec
+            .env
+            .iter()
+            .map(|(name, val)| (name.as_str(), Value::from(val.as_str())))
+            .collect();
+        table["env"] = value(env);
+    }
+    if !spec.pass_env.is_empty() {
+        let names: Array = spec.pass_env.iter().map(String::as_str).collect();
+        table["pass_env"] = value(names);
+    }
 }
 
 /// First `max_chars` of the prompt, with trailing whitespace trimmed and an
@@ -1025,6 +1109,105 @@ prompt = "two"
         assert!(err.contains("is not a directory"), "{}", err);
     }
 
+    #[test]
+    fn test_load_parses_gemini_tools() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            "[[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\ngemini_tools = [\"google_search\", \"code_execution\"]\n\n[[agent]]\nname = \"b\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\n",
+        );
+        let config = load_agents_file(&path, &allow_all_models).unwrap();
+        assert_eq!(
+            config.agents[0].gemini_tools,
+            vec![GeminiBuiltinTool::GoogleSearch, GeminiBuiltinTool::CodeExecution]
+        );
+        assert!(config.agents[1].gemini_tools.is_empty());
+    }
+
+    #[test]
+    fn test_load_resolves_sandbox_profiles() {
+        let dir = TempDir::new().unwrap();
+        let work = dir.path().join("work");
+        fs::create_dir_all(work.join("cache")).unwrap();
+        let path = write_config(
+            &dir,
+            &format!(
+                "[sandbox.build]\nwritable = [\"cache\"]\nnetwork = false\n\n\
+                 [[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\nworking_dir = \"{w}\"\nsandbox = \"build\"\n\n\
+                 [[agent]]\nname = \"b\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\nworking_dir = \"{w}\"\nsandbox = \"workspace\"\n",
+                w = work.display()
+            ),
+        );
+        let config = load_agents_file(&path, &allow_all_models).unwrap();
+
+        let build = config.agents[0].sandbox.as_ref().unwrap();
+        assert_eq!(build.profile, "build");
+        assert!(!build.network);
+        assert!(build.check_write(&work.join("cache/x")).is_ok());
+        assert!(build.check_read(&dir.path().join("agents.toml")).is_err());
+        assert!(config.agents[1].sandbox.as_ref().unwrap().network);
+    }
+
+    #[test]
+    fn test_load_rejects_bad_sandbox() {
+        let dir = TempDir::new().unwrap();
+        let agent = "[[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\n";
+        let load = |text: String| {
+            let path = write_config(&dir, &text);
+            load_agents_file(&path, &allow_all_models)
+                .unwrap_err()
+                .to_string()
+        };
+
+        let err = load(format!("{}sandbox = \"workspace\"\n", agent));
+        assert!(err.contains("sandbox requires working_dir"), "{}", err);
+
+        let err = load(format!(
+            "{}working_dir = \"{}\"\nsandbox = \"nope\"\n",
+            agent,
+            dir.path().display()
+        ));
+        assert!(err.contains("unknown sandbox profile 'nope'"), "{}", err);
+
+        let err = load(format!("[sandbox.offline]\nnetwork = true\n\n{}", agent));
+        assert!(err.contains("built-in sandbox profile"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_parses_env_and_pass_env() {
+        let dir = TempDir::new().unwrap();
+        let agent = "[[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\n";
+        let path = write_config(
+            &dir,
+            &format!(
+                "{}env = {{ REGION = \"eu\" }}\npass_env = [\"OPENAI_API_KEY\"]\n",
+                agent
+            ),
+        );
+        let config = load_agents_file(&path, &allow_all_models).unwrap();
+        assert_eq!(config.agents[0].env["REGION"], "eu");
+        assert_eq!(config.agents[0].pass_env, vec!["OPENAI_API_KEY"]);
+
+        let path = write_config(&dir, &format!("{}pass_env = [\"A=B\"]\n", agent));
+        let err = load_agents_file(&path, &allow_all_models)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not a valid environment variable name"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_gemini_tool() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(
+            &dir,
+            "[[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\ngemini_tools = [\"web_browse\"]\n",
+        );
+        let err = load_agents_file(&path, &allow_all_models).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("agent 'a'"), "{}", msg);
+        assert!(msg.contains("web_browse"), "{}", msg);
+    }
+
     #[test]
     fn test_prompt_preview_short_prompt_unchanged() {
         assert_eq!(prompt_preview("  hello there \n", 40), "hello there");
@@ -1056,13 +1239,17 @@ prompt = "two"
             enabled: true,
             timeout_secs: 600,
             working_dir: None,
+            gemini_tools: Vec::new(),
+            sandbox: None,
+            env: BTreeMap::new(),
+            pass_env: Vec::new(),
         }
     }
 
     fn upsert(original_name: Option<&str>, spec: AgentSpec) -> AgentMutation {
         AgentMutation::Upsert {
             original_name: original_name.map(str::to_string),
-            spec,
+            spec: Box::new(spec),
         }
     }
 
@@ -1256,6 +1443,38 @@ prompt = "Watch the repo"
         assert!(updated.starts_with("[[agent]]"), "{:?}", updated);
     }
 
+    #[test]
+    fn test_apply_mutation_create_writes_gemini_tools() {
+        let updated = apply_mutation(
+            "",
+            &upsert(
+                None,
+                AgentSpec {
+                    gemini_tools: vec!["google_search".to_string(), "url_context".to_string()],
+                    ..spec("fresh", "0 9 * * *", "hi")
+                },
+            ),
+        )
+        .unwrap();
+        assert!(
+            updated.contains(r#"gemini_tools = ["google_search", "url_context"]"#),
+            "{}",
+            updated
+        );
+    }
+
+    #[test]
+    fn test_apply_mutation_update_leaves_gemini_tools_alone() {
+        let original = "[[agent]]\nname = \"a\"\nschedule = \"0 9 * * *\"\nprompt = \"hi\"\ngemini_tools = [\"google_search\"]\n";
+        let updated = apply_mutation(
+            original,
+            &upsert(Some("a"), spec("a", "0 10 * * *", "hi")),
+        )
+        .unwrap();
+        assert!(updated.contains(r#"gemini_tools = ["google_search"]"#), "{}", updated);
+        assert!(updated.contains("0 10 * * *"), "{}", updated);
+    }
+
     #[test]
     fn test_apply_mutation_create_uses_prompt_file_when_given() {
         let updated = apply_mutation(
@@ -0,0 +1,510 @@
+//! Per-session file checkpoints. Before Write, Edit or ApplyPatch changes a
+//! file, its previous contents are kept under `~/.eunice/checkpoints/<session>/`,
+//! so a change can be undone even where there is no git history to fall back on.
+//!
+//! Each successful tool call adds one checkpoint: the files it touched and
+//! what they held before (or that they did not exist). Undoing restores
+//! checkpoints newest first and drops them from the store.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// CLI sessions older than this are pruned when a new one starts. Webapp
+/// sessions live as long as the session does.
+const CLI_SESSION_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const CLI_PREFIX: &str = "cli-";
+
+/// One tool call's changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Increasing within a session, starting at 1
+    pub id: u64,
+    pub tool: String,
+    /// Unix seconds
+    pub created: i64,
+    pub files: Vec<FileSnapshot>,
+}
+
+/// A file as it was before the change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub path: PathBuf,
+    /// False when the change created the file; undoing removes it
+    pub existed: bool,
+    /// File in the session directory holding the previous contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blob: Option<String>,
+}
+
+/// Contents read before a tool runs, recorded once it succeeds
+pub struct Pending {
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+/// A session's checkpoint store
+#[derive(Debug)]
+pub struct Checkpoints {
+    session_id: String,
+    dir: PathBuf,
+    /// Serializes index updates within the process
+    lock: Mutex<()>,
+}
+
+/// `~/.eunice/checkpoints`
+pub fn root() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Cannot determine home directory"))?;
+    Ok(home.join(".eunice").join("checkpoints"))
+}
+
+impl Checkpoints {
+    /// Store for a session id (a webapp session, or a CLI session being rewound)
+    pub fn for_session(session_id: &str) -> Result<Self> {
+        Self::in_root(&root()?, session_id)
+    }
+
+    /// Store for a new CLI or chat session, named after its start time.
+    /// Old CLI sessions are pruned first.
+    pub fn new_cli_session() -> Result<Self> {
+        let root = root()?;
+        prune(&root, CLI_SESSION_MAX_AGE);
+        let id = format!(
+            "{}{}-{}",
+            CLI_PREFIX,
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id()
+        );
+        Self::in_root(&root, &id)
+    }
+
+    pub fn in_root(root: &Path, session_id: &str) -> Result<Self> {
+        let valid = !session_id.is_empty()
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            bail!("Invalid session id: '{}'", session_id);
+        }
+        Ok(Self {
+            session_id: session_id.to_string(),
+            dir: root.join(session_id),
+            lock: Mutex::new(()),
+        })
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Read the current contents of `paths` before a tool changes them.
+    /// Directories are left out; the tool itself refuses them.
+    pub fn prepare(&self, paths: &[PathBuf]) -> Result<Pending> {
+        let mut files = Vec::new();
+        for path in paths {
+            let path = std::path::absolute(path)?;
+            if path.is_dir() {
+                continue;
+            }
+            let contents = match std::fs::read(&path) {
+                Ok(bytes) => Some(bytes),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to checkpoint {} before changing it", path.display())
+                    })
+                }
+            };
+            files.push((path, contents));
+        }
+        Ok(Pending { files })
+    }
+
+    /// Save `pending` as a new checkpoint for `tool`
+    pub fn record(&self, tool: &str, pending: Pending) -> Result<Checkpoint> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let mut index = self.read_index()?;
+        let id = index.last().map_or(1, |c| c.id + 1);
+
+        let mut files = Vec::new();
+        for (n, (path, contents)) in pending.files.into_iter().enumerate() {
+            let blob = match contents {
+                Some(bytes) => {
+                    let name = format!("{}-{}.orig", id, n);
+                    std::fs::write(self.dir.join(&name), bytes)
+                        .with_context(|| format!("Failed to write checkpoint {}", name))?;
+                    Some(name)
+                }
+                None => None,
+            };
+            files.push(FileSnapshot {
+                path,
+                existed: blob.is_some(),
+                blob,
+            });
+        }
+
+        let checkpoint = Checkpoint {
+            id,
+            tool: tool.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            files,
+        };
+        index.push(checkpoint.clone());
+        self.write_index(&index)?;
+        Ok(checkpoint)
+    }
+
+    /// Checkpoints, oldest first
+    pub fn list(&self) -> Result<Vec<Checkpoint>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.read_index()
+    }
+
+    /// Undo the last `steps` changes, newest first. Returns the restored
+    /// checkpoints (empty when there is nothing to undo).
+    pub fn undo(&self, steps: usize) -> Result<Vec<Checkpoint>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let index = self.read_index()?;
+        let keep = index.len().saturating_sub(steps);
+        self.restore_from(index, keep)
+    }
+
+    /// Undo checkpoint `id` and every change after it
+    pub fn rewind_to(&self, id: u64) -> Result<Vec<Checkpoint>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let index = self.read_index()?;
+        let keep = index
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or_else(|| anyhow!("No checkpoint {} in session {}", id, self.session_id))?;
+        self.restore_from(index, keep)
+    }
+
+    /// Delete the session's checkpoints (the session was deleted)
+    pub fn remove(&self) -> Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Restore `index[keep..]` newest first, then drop them from the index.
+    /// The index is rewritten after each checkpoint, so a failure leaves the
+    /// ones not yet restored in place.
+    fn restore_from(&self, mut index: Vec<Checkpoint>, keep: usize) -> Result<Vec<Checkpoint>> {
+        let mut restored = Vec::new();
+        while index.len() > keep {
+            let checkpoint = index.pop().expect("index longer than keep");
+            for file in checkpoint.files.iter().rev() {
+                self.restore_file(file)?;
+            }
+            self.write_index(&index)?;
+            for file in &checkpoint.files {
+                if let Some(blob) = &file.blob {
+                    let _ = std::fs::remove_file(self.dir.join(blob));
+                }
+            }
+            restored.push(checkpoint);
+        }
+        Ok(restored)
+    }
+
+    fn restore_file(&self, file: &FileSnapshot) -> Result<()> {
+        match &file.blob {
+            Some(blob) => {
+                let bytes = std::fs::read(self.dir.join(blob)).with_context(|| {
+                    format!("Checkpoint for {} is missing", file.path.display())
+                })?;
+                if let Some(parent) = file.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&file.path, bytes)
+                    .with_context(|| format!("Failed to restore {}", file.path.display()))
+            }
+            None => match std::fs::remove_file(&file.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("Failed to remove {}", file.path.display()))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.json")
+    }
+
+    fn read_index(&self) -> Result<Vec<Checkpoint>> {
+        match std::fs::read_to_string(self.index_path()) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Corrupt checkpoint index in {}", self.dir.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_index(&self, index: &[Checkpoint]) -> Result<()> {
+        let tmp = self.dir.join("index.json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(index)?)?;
+        std::fs::rename(&tmp, self.index_path())?;
+        Ok(())
+    }
+}
+
+/// Sessions under `root` that have checkpoints, most recently changed first:
+/// (session id, checkpoint count, last change)
+pub fn list_sessions(root: &Path) -> Result<Vec<(String, usize, i64)>> {
+    let mut sessions = Vec::new();
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Ok(sessions);
+    };
+    for entry in entries.flatten() {
+        let Some(id) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let Ok(store) = Checkpoints::in_root(root, &id) else {
+            continue;
+        };
+        if let Ok(index) = store.list() {
+            if let Some(last) = index.last() {
+                sessions.push((id, index.len(), last.created));
+            }
+        }
+    }
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.2));
+    Ok(sessions)
+}
+
+/// Remove CLI sessions not modified within `max_age`
+fn prune(root: &Path, max_age: Duration) {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return;
+    };
+    let Some(cutoff) = SystemTime::now().checked_sub(max_age) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_cli = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(CLI_PREFIX));
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified < cutoff);
+        if is_cli && stale {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// One line per checkpoint: `#3 Edit src/main.rs`, with paths relative to the
+/// working directory where possible
+pub fn describe(checkpoints: &[Checkpoint]) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut lines = Vec::new();
+    for checkpoint in checkpoints {
+        let files: Vec<String> = checkpoint
+            .files
+            .iter()
+            .map(|f| {
+                let shown = f.path.strip_prefix(&cwd).unwrap_or(&f.path).display();
+                if f.existed {
+                    shown.to_string()
+                } else {
+                    format!("{} (new file)", shown)
+                }
+            })
+            .collect();
+        lines.push(format!(
+            "#{} {} {}",
+            checkpoint.id,
+            checkpoint.tool,
+            files.join(", ")
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Handle `/undo` and `/rewind [n]` in the chat front ends. None when `input`
+/// is not one of them; otherwise the text to show.
+pub fn slash_command(checkpoints: Option<&Checkpoints>, input: &str) -> Option<String> {
+    let mut words = input.split_whitespace();
+    let command = words.next()?;
+    let argument = words.next();
+    let steps = match (command, argument) {
+        ("/undo", None) => Some(1),
+        ("/rewind", None) => None,
+        ("/rewind", Some(n)) => match n.parse::<usize>() {
+            Ok(n) if n > 0 => Some(n),
+            _ => return Some("Usage: /rewind [n]  (n = number of changes to undo)".to_string()),
+        },
+        ("/undo", Some(_)) => return Some("Usage: /undo".to_string()),
+        _ => return None,
+    };
+    let Some(checkpoints) = checkpoints else {
+        return Some("Checkpoints are not enabled for this session.".to_string());
+    };
+
+    let result = match steps {
+        None => checkpoints.list().map(|list| {
+            if list.is_empty() {
+                "No file changes to rewind.".to_string()
+            } else {
+                let recent = &list[list.len().saturating_sub(20)..];
+                let mut recent = recent.to_vec();
+                recent.reverse();
+                format!(
+                    "File changes, newest first (/rewind <n> undoes the last n):\n{}",
+                    describe(&recent)
+                )
+            }
+        }),
+        Some(steps) => checkpoints.undo(steps).map(|restored| {
+            if restored.is_empty() {
+                "Nothing to undo.".to_string()
+            } else {
+                format!("Restored:\n{}", describe(&restored))
+            }
+        }),
+    };
+    Some(result.unwrap_or_else(|e| format!("Error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(store: &Checkpoints, tool: &str, path: &Path, contents: &str) {
+        let pending = store.prepare(&[path.to_path_buf()]).unwrap();
+        std::fs::write(path, contents).unwrap();
+        store.record(tool, pending).unwrap();
+    }
+
+    #[test]
+    fn test_undo_restores_previous_contents_newest_first() {
+        let root = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let file = work.path().join("notes.txt");
+        std::fs::write(&file, "original").unwrap();
+        let store = Checkpoints::in_root(root.path(), "s1").unwrap();
+
+        change(&store, "Write", &file, "first");
+        change(&store, "Edit", &file, "second");
+        assert_eq!(store.list().unwrap().len(), 2);
+
+        let restored = store.undo(1).unwrap();
+        assert_eq!(restored[0].tool, "Edit");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "first");
+
+        let restored = store.undo(5).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+        assert!(store.undo(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undoing_a_created_file_removes_it() {
+        let root = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let file = work.path().join("new.txt");
+        let store = Checkpoints::in_root(root.path(), "s1").unwrap();
+
+        change(&store, "Write", &file, "hello");
+        assert!(!store.list().unwrap()[0].files[0].existed);
+        store.undo(1).unwrap();
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_rewind_to_restores_that_checkpoint_and_later_ones() {
+        let root = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let a = work.path().join("a.txt");
+        let b = work.path().join("b.txt");
+        std::fs::write(&a, "a0").unwrap();
+        std::fs::write(&b, "b0").unwrap();
+        let store = Checkpoints::in_root(root.path(), "s1").unwrap();
+
+        change(&store, "Write", &a, "a1");
+        change(&store, "Write", &b, "b1");
+        change(&store, "Write", &a, "a2");
+
+        let restored = store.rewind_to(2).unwrap();
+        assert_eq!(
+            restored.iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "a1");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "b0");
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(store.rewind_to(9).is_err());
+
+        // Ids continue from the newest remaining checkpoint
+        change(&store, "Write", &b, "b2");
+        assert_eq!(store.list().unwrap().last().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_sessions_are_listed_and_ids_validated() {
+        let root = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let file = work.path().join("x.txt");
+        for id in ["one", "two"] {
+            let store = Checkpoints::in_root(root.path(), id).unwrap();
+            change(&store, "Write", &file, id);
+        }
+        Checkpoints::in_root(root.path(), "empty").unwrap();
+
+        let sessions = list_sessions(root.path()).unwrap();
+        let mut ids: Vec<&str> = sessions.iter().map(|s| s.0.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["one", "two"]);
+
+        assert!(Checkpoints::in_root(root.path(), "../etc").is_err());
+        assert!(Checkpoints::in_root(root.path(), "").is_err());
+
+        let store = Checkpoints::in_root(root.path(), "one").unwrap();
+        store.remove().unwrap();
+        assert_eq!(list_sessions(root.path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_slash_commands() {
+        let root = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let file = work.path().join("f.txt");
+        let store = Checkpoints::in_root(root.path(), "s1").unwrap();
+        change(&store, "Write", &file, "1");
+        change(&store, "Edit", &file, "2");
+
+        assert!(slash_command(Some(&store), "/help").is_none());
+        assert!(slash_command(None, "/undo")
+            .unwrap()
+            .contains("not enabled"));
+        assert!(slash_command(Some(&store), "/rewind x")
+            .unwrap()
+            .starts_with("Usage"));
+
+        let listing = slash_command(Some(&store), "/rewind").unwrap();
+        assert!(listing.find("#2 Edit").unwrap() < listing.find("#1 Write").unwrap());
+
+        assert!(slash_command(Some(&store), "/undo")
+            .unwrap()
+            .contains("#2 Edit"));
+        assert!(slash_command(Some(&store), "/rewind 3")
+            .unwrap()
+            .contains("(new file)"));
+        assert!(!file.exists());
+        assert_eq!(
+            slash_command(Some(&store), "/undo").unwrap(),
+            "Nothing to undo."
+        );
+    }
+}
@@ -1,12 +1,18 @@
 use crate::compact::{extract_retry_delay, is_rate_limit_error};
 use crate::key_rotation::{is_bad_key_error, is_quota_error, BadKeyAction, KeyPool, RateLimitAction};
 use crate::models::{
-    ChatCompletionRequest, ChatCompletionResponse, GeminiContent, GeminiPart, GeminiRequest, GeminiTool,
-    GeminiResponse, Message, Provider, ProviderInfo, Tool,
+    BuiltinToolOutput, ChatCompletionRequest, ChatCompletionResponse, Citation, FinishReason,
+    GeminiBuiltinTool,
+    GeminiCandidate, GeminiContent, GeminiPart, GeminiPartResponse, GeminiRequest, GeminiTool,
+    GeminiFunctionCall, GeminiResponse, Message, Provider, ProviderInfo, Tool, ToolCall,
+    ToolCallMetadata,
 };
+use crate::ollama::{self, OllamaChatResponse, OllamaConfig};
+use crate::vertex::TokenSource;
 use anyhow::{anyhow, Context, Result};
 use rand::Rng;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -39,6 +45,12 @@ pub struct Client {
     azure_api_version: Option<String>,
     /// Enable debug output
     debug: bool,
+    /// Gemini server-side tools sent alongside our function declarations
+    gemini_tools: Vec<GeminiBuiltinTool>,
+    /// Per-model request options for Ollama's native chat API
+    ollama_config: OllamaConfig,
+    /// Service-account token source for Vertex AI
+    vertex_auth: Option<Arc<TokenSource>>,
 }
 
 impl Client {
@@ -78,6 +90,18 @@ impl Client {
             .build()
             .context("Failed to create HTTP client")?;
 
+        let ollama_config = match provider_info.provider {
+            Provider::Ollama => OllamaConfig::load()?,
+            _ => OllamaConfig::default(),
+        };
+
+        let vertex_auth = match provider_info.provider {
+            Provider::VertexAI => Some(Arc::new(TokenSource::from_file(Path::new(
+                &provider_info.api_key,
+            ))?)),
+            _ => None,
+        };
+
         Ok(Self {
             http,
             base_url: provider_info.base_url.clone(),
@@ -87,6 +111,9 @@ impl Client {
             retry_config: RetryConfig::default(),
             azure_api_version: provider_info.azure_api_version.clone(),
             debug: std::env::var("EUNICE_DEBUG").is_ok(),
+            gemini_tools: Vec::new(),
+            ollama_config,
+            vertex_auth,
         })
     }
 
@@ -95,6 +122,22 @@ impl Client {
         self.debug = debug;
     }
 
+    /// Enable Gemini built-in tools (code execution, Google Search, URL context).
+    /// Only the native Gemini API path can send them; other providers ignore this.
+    pub fn set_gemini_tools(&mut self, tools: Vec<GeminiBuiltinTool>) {
+        self.gemini_tools = tools;
+    }
+
+    /// Every API key this client may send, for the redactor. Vertex AI's
+    /// "key" is the path of its credentials file, which is no secret; the
+    /// access tokens minted from it are masked by pattern.
+    pub fn api_keys(&self) -> &[String] {
+        if self.vertex_auth.is_some() {
+            return &[];
+        }
+        self.key_pool.keys()
+    }
+
     /// Get the current API key
     fn current_api_key(&self) -> &str {
         self.key_pool.current_key()
@@ -137,6 +180,15 @@ impl Client {
         }
     }
 
+    /// Add auth to a native Gemini request: the API key for AI Studio, an
+    /// OAuth access token for Vertex AI
+    async fn gemini_auth(&self, req: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder> {
+        match &self.vertex_auth {
+            Some(tokens) => Ok(req.bearer_auth(tokens.access_token(&self.http).await?)),
+            None => Ok(req.header("x-goog-api-key", self.current_api_key())),
+        }
+    }
+
     /// Calculate backoff delay with jitter for a given attempt
     fn backoff_delay(&self, attempt: u32) -> Duration {
         let base = self.retry_config.initial_delay_ms * 2u64.pow(attempt.saturating_sub(1));
@@ -165,7 +217,17 @@ impl Client {
                 .await;
         }
 
+        // Ollama's native API, so num_ctx and friends can be set
+        if let Provider::Ollama = self.provider {
+            let messages: Vec<Message> = serde_json::from_value(messages)?;
+            return self
+                .chat_completion_ollama_native(model, &messages, tools)
+                .await;
+        }
+
         // Standard OpenAI-compatible API
+        let mut messages = messages;
+        Self::strip_provider_metadata(&mut messages);
         let url = if let Provider::AzureOpenAI = self.provider {
             // Azure OpenAI: {base_url}{deployment}/chat/completions?api-version={version}
             let api_version = self.azure_api_version.as_deref().unwrap_or("2024-02-01");
@@ -264,31 +326,12 @@ impl Client {
         messages: &[Message],
         tools: Option<&[Tool]>,
     ) -> Result<ChatCompletionResponse> {
-        use crate::models::GeminiFunctionDeclaration;
-
         // Convert messages to Gemini format
         let contents = self.convert_messages_to_gemini(messages)?;
 
-        // Convert OpenAI-style tools to Gemini functionDeclarations
-        // Gemini doesn't support all JSON Schema properties, so we need to strip unsupported ones
-        let gemini_tools = tools.map(|t| {
-            let declarations: Vec<GeminiFunctionDeclaration> = t
-                .iter()
-                .map(|tool| GeminiFunctionDeclaration {
-                    name: tool.function.name.clone(),
-                    description: tool.function.description.clone(),
-                    parameters: Self::clean_schema_for_gemini(&tool.function.parameters),
-                })
-                .collect();
-            vec![GeminiTool {
-                function_declarations: Some(declarations),
-                code_execution: None,
-            }]
-        });
-
         let gemini_request = GeminiRequest {
             contents,
-            tools: gemini_tools,
+            tools: self.build_gemini_tools(tools),
         };
 
         // Build URL: base_url already contains /v1beta/models/
@@ -302,9 +345,8 @@ impl Client {
 
             let start = std::time::Instant::now();
             let response = self
-                .http
-                .post(&url)
-                .header("x-goog-api-key", self.current_api_key())
+                .gemini_auth(self.http.post(&url))
+                .await?
                 .header(CONTENT_TYPE, "application/json")
                 .json(&gemini_request)
                 .send()
@@ -378,6 +420,177 @@ impl Client {
         }
     }
 
+    /// Send a chat request using Ollama's native `/api/chat` endpoint
+    async fn chat_completion_ollama_native(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<&[Tool]>,
+    ) -> Result<ChatCompletionResponse> {
+        let settings = self.ollama_config.settings_for(model);
+        let request = ollama::build_request(model, messages, tools, &settings, false);
+        let url = format!("{}api/chat", self.base_url);
+
+        let mut attempt = 0u32;
+        loop {
+            if self.debug {
+                eprintln!("[DEBUG] POST {} (Ollama native, attempt {})", url, attempt + 1);
+                eprintln!("[DEBUG] Model: {}, num_ctx: {}", model, request.options.num_ctx);
+            }
+
+            let start = std::time::Instant::now();
+            let response = self
+                .http
+                .post(&url)
+                .json(&request)
+                .send()
+                .await
+                .context("Failed to send Ollama request")?;
+
+            let elapsed = start.elapsed();
+            let status = response.status().as_u16();
+
+            if self.debug {
+                eprintln!("[DEBUG] Response: {} in {:.2}s", status, elapsed.as_secs_f64());
+            }
+
+            if Self::is_retryable_status(status) {
+                let error_text = response.text().await.unwrap_or_default();
+
+                if attempt >= self.retry_config.max_retries {
+                    return Err(anyhow!(
+                        "Ollama request failed with status {} after {} retries: {}",
+                        status,
+                        attempt,
+                        error_text
+                    ));
+                }
+
+                let delay = self.backoff_delay(attempt);
+                eprintln!(
+                    "⏳ Server error ({}): retrying in {:.1}s (attempt {}/{})...",
+                    status,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    self.retry_config.max_retries
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            if !response.status().is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                return Err(anyhow!(
+                    "Ollama request failed with status {}: {}",
+                    status,
+                    error_text
+                ));
+            }
+
+            let response_body = response
+                .json::<OllamaChatResponse>()
+                .await
+                .context("Failed to parse Ollama response")?;
+
+            return ollama::convert_response(response_body);
+        }
+    }
+
+    /// Stream a chat request from Ollama's native API. Ollama streams
+    /// newline-delimited JSON; tool calls arrive whole in a single line.
+    async fn chat_completion_ollama_streaming<F>(
+        &self,
+        model: &str,
+        messages: &[Message],
+        tools: Option<&[Tool]>,
+        mut on_chunk: F,
+    ) -> Result<ChatCompletionResponse>
+    where
+        F: FnMut(&str),
+    {
+        use futures::StreamExt;
+
+        let settings = self.ollama_config.settings_for(model);
+        let request = ollama::build_request(model, messages, tools, &settings, true);
+        let url = format!("{}api/chat", self.base_url);
+
+        if self.debug {
+            eprintln!("[DEBUG] POST {} (Ollama streaming)", url);
+            eprintln!("[DEBUG] Model: {}, num_ctx: {}", model, request.options.num_ctx);
+        }
+
+        let response = self
+            .http
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .context("Failed to send Ollama streaming request")?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Ollama streaming request failed with status {}: {}",
+                status,
+                error_text
+            ));
+        }
+
+        let mut all_text = String::new();
+        let mut all_tool_calls = Vec::new();
+        let mut prompt_tokens = 0;
+        let mut completion_tokens = 0;
+        let mut done_reason = None;
+
+        let mut stream = response.bytes_stream();
+        let mut buffer: Vec<u8> = Vec::new();
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result.context("Failed to read stream chunk")?;
+            buffer.extend_from_slice(&chunk);
+
+            // Split on bytes so multi-byte characters never straddle a chunk boundary
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let chunk_response: OllamaChatResponse = serde_json::from_str(line)
+                    .with_context(|| format!("Failed to parse Ollama stream line: {}", line))?;
+                if let Some(error) = chunk_response.error {
+                    return Err(anyhow!("Ollama error: {}", error));
+                }
+                if let Some(message) = chunk_response.message {
+                    if !message.content.is_empty() {
+                        on_chunk(&message.content);
+                        all_text.push_str(&message.content);
+                    }
+                    if let Some(calls) = message.tool_calls {
+                        all_tool_calls.extend(ollama::convert_tool_calls(calls));
+                    }
+                }
+                if chunk_response.done {
+                    done_reason = chunk_response.done_reason;
+                    prompt_tokens = chunk_response.prompt_eval_count;
+                    completion_tokens = chunk_response.eval_count;
+                }
+            }
+        }
+
+        Ok(ollama::into_response(
+            all_text,
+            all_tool_calls,
+            done_reason,
+            prompt_tokens,
+            completion_tokens,
+        ))
+    }
+
     /// Send a streaming chat completion request using native Gemini API
     /// Calls the callback for each text chunk as it arrives
     /// Returns the complete response with all function calls
@@ -391,37 +604,27 @@ impl Client {
     where
         F: FnMut(&str),
     {
-        // Only streaming for native Gemini API
+        if let Provider::Ollama = self.provider {
+            let messages: Vec<Message> = serde_json::from_value(messages)?;
+            return self
+                .chat_completion_ollama_streaming(model, &messages, tools, on_chunk)
+                .await;
+        }
+
+        // Otherwise only streaming for native Gemini API
         if !self.use_native_gemini_api {
             // Fall back to non-streaming for other providers
             return self.chat_completion(model, messages, tools).await;
         }
 
-        use crate::models::GeminiFunctionDeclaration;
         use futures::StreamExt;
 
         let messages: Vec<Message> = serde_json::from_value(messages)?;
         let contents = self.convert_messages_to_gemini(&messages)?;
 
-        // Convert tools to Gemini format
-        let gemini_tools = tools.map(|t| {
-            let declarations: Vec<GeminiFunctionDeclaration> = t
-                .iter()
-                .map(|tool| GeminiFunctionDeclaration {
-                    name: tool.function.name.clone(),
-                    description: tool.function.description.clone(),
-                    parameters: Self::clean_schema_for_gemini(&tool.function.parameters),
-                })
-                .collect();
-            vec![GeminiTool {
-                function_declarations: Some(declarations),
-                code_execution: None,
-            }]
-        });
-
         let gemini_request = GeminiRequest {
             contents,
-            tools: gemini_tools,
+            tools: self.build_gemini_tools(tools),
         };
 
         // Use streamGenerateContent endpoint
@@ -434,9 +637,8 @@ impl Client {
 
         let start = std::time::Instant::now();
         let response = self
-            .http
-            .post(&url)
-            .header("x-goog-api-key", self.current_api_key())
+            .gemini_auth(self.http.post(&url))
+            .await?
             .header(CONTENT_TYPE, "application/json")
             .json(&gemini_request)
             .send()
@@ -462,6 +664,9 @@ impl Client {
         let mut all_text = String::new();
         let mut all_tool_calls: Vec<crate::models::ToolCall> = Vec::new();
         let mut usage_metadata: Option<crate::models::GeminiUsageMetadata> = None;
+        let mut code_outputs: Vec<BuiltinToolOutput> = Vec::new();
+        let mut citations: Option<BuiltinToolOutput> = None;
+        let mut finish_reason: Option<FinishReason> = None;
 
         let mut stream = response.bytes_stream();
         let mut buffer = String::new();
@@ -513,9 +718,21 @@ impl Client {
 
                 // Parse the JSON chunk
                 if let Ok(chunk_response) = serde_json::from_str::<GeminiResponse>(json_str) {
+                    if let Some(blocked) = Self::gemini_prompt_blocked(&chunk_response) {
+                        finish_reason = Some(blocked);
+                    }
                     // Extract text and stream it
                     if !chunk_response.candidates.is_empty() {
                         let candidate = &chunk_response.candidates[0];
+                        // Only the final chunk carries a finish reason
+                        if let Some(reason) = Self::gemini_finish_reason(candidate) {
+                            finish_reason = Some(reason);
+                        }
+                        code_outputs.extend(Self::code_execution_outputs(&candidate.content.parts));
+                        // Grounding metadata usually arrives once, on the final chunk
+                        if let Some(found) = Self::citation_output(candidate) {
+                            citations = Some(found);
+                        }
                         for part in &candidate.content.parts {
                             if let Some(ref text) = part.text {
                                 on_chunk(text);
@@ -523,18 +740,7 @@ impl Client {
                             }
                             // Collect function calls
                             if let Some(ref fc) = part.function_call {
-                                let id = match &part.thought_signature {
-                                    Some(sig) => format!("{}::{}", fc.name, sig),
-                                    None => fc.name.clone(),
-                                };
-                                all_tool_calls.push(crate::models::ToolCall {
-                                    id,
-                                    call_type: "function".to_string(),
-                                    function: crate::models::FunctionCall {
-                                        name: fc.name.clone(),
-                                        arguments: fc.args.to_string(),
-                                    },
-                                });
+                                all_tool_calls.push(Self::gemini_tool_call(fc, &part.thought_signature));
                             }
                         }
                     }
@@ -561,7 +767,9 @@ impl Client {
                 message: crate::models::AssistantMessage {
                     content: if all_text.is_empty() { None } else { Some(all_text) },
                     tool_calls,
+                    builtin_outputs: code_outputs.into_iter().chain(citations).collect(),
                 },
+                finish_reason,
             }],
             usage: usage_metadata.map(|u| crate::models::UsageStats {
                 prompt_tokens: u.prompt_token_count,
@@ -574,7 +782,7 @@ impl Client {
 
     /// Check if streaming is supported for the current provider
     pub fn supports_streaming(&self) -> bool {
-        self.use_native_gemini_api
+        self.use_native_gemini_api || matches!(self.provider, Provider::Ollama)
     }
 
     /// Send a chat completion request with an image
@@ -613,17 +821,13 @@ impl Client {
                 }],
                 // Enable Agentic Vision code execution for Gemini image analysis
                 // This allows the model to zoom, crop, annotate, and analyze images with Python code
-                tools: Some(vec![GeminiTool {
-                    function_declarations: None,
-                    code_execution: Some(serde_json::json!({})),
-                }]),
+                tools: Some(vec![GeminiTool::builtin(GeminiBuiltinTool::CodeExecution)]),
             };
 
             let url = format!("{}{}:generateContent", self.base_url, model);
             let response = self
-                .http
-                .post(&url)
-                .header("x-goog-api-key", self.current_api_key())
+                .gemini_auth(self.http.post(&url))
+                .await?
                 .json(&gemini_request)
                 .send()
                 .await
@@ -691,6 +895,100 @@ impl Client {
             .context("Failed to parse OpenAI multimodal response")
     }
 
+    /// Build the Gemini `tools` list: our function declarations, converted from the
+    /// OpenAI-style specs, plus one entry per enabled built-in tool.
+    /// Gemini doesn't support all JSON Schema properties, so unsupported ones are stripped.
+    fn build_gemini_tools(&self, tools: Option<&[Tool]>) -> Option<Vec<GeminiTool>> {
+        use crate::models::GeminiFunctionDeclaration;
+
+        let mut gemini_tools: Vec<GeminiTool> = Vec::new();
+
+        if let Some(t) = tools {
+            let declarations: Vec<GeminiFunctionDeclaration> = t
+                .iter()
+                .map(|tool| GeminiFunctionDeclaration {
+                    name: tool.function.name.clone(),
+                    description: tool.function.description.clone(),
+                    parameters: Self::clean_schema_for_gemini(&tool.function.parameters),
+                })
+                .collect();
+            gemini_tools.push(GeminiTool {
+                function_declarations: Some(declarations),
+                ..Default::default()
+            });
+        }
+
+        gemini_tools.extend(self.gemini_tools.iter().map(|tool| GeminiTool::builtin(*tool)));
+
+        if gemini_tools.is_empty() {
+            None
+        } else {
+            Some(gemini_tools)
+        }
+    }
+
+    /// Code execution parts of a response, in the order the model produced them
+    fn code_execution_outputs(parts: &[GeminiPartResponse]) -> Vec<BuiltinToolOutput> {
+        let mut outputs = Vec::new();
+        for part in parts {
+            if let Some(ref code) = part.executable_code {
+                outputs.push(BuiltinToolOutput::Code {
+                    language: code.language.clone(),
+                    code: code.code.clone(),
+                });
+            }
+            if let Some(ref result) = part.code_execution_result {
+                outputs.push(BuiltinToolOutput::CodeResult {
+                    outcome: result.outcome.clone(),
+                    output: result.output.clone().unwrap_or_default(),
+                });
+            }
+        }
+        outputs
+    }
+
+    /// Search queries and sources from a candidate's grounding and URL context
+    /// metadata, or None when the answer was not grounded
+    fn citation_output(candidate: &GeminiCandidate) -> Option<BuiltinToolOutput> {
+        let mut queries: Vec<String> = Vec::new();
+        let mut sources: Vec<Citation> = Vec::new();
+
+        let add_source = |sources: &mut Vec<Citation>, title: String, uri: String| {
+            if !uri.is_empty() && !sources.iter().any(|s| s.uri == uri) {
+                sources.push(Citation { title, uri });
+            }
+        };
+
+        if let Some(ref grounding) = candidate.grounding_metadata {
+            queries.extend(grounding.web_search_queries.iter().cloned());
+            for chunk in &grounding.grounding_chunks {
+                if let Some(ref web) = chunk.web {
+                    let title = web.title.clone().unwrap_or_else(|| web.uri.clone());
+                    add_source(&mut sources, title, web.uri.clone());
+                }
+            }
+        }
+
+        if let Some(ref url_context) = candidate.url_context_metadata {
+            for url in &url_context.url_metadata {
+                // Failed fetches (paywalls, unsafe pages) contributed nothing to the answer
+                let retrieved = url
+                    .url_retrieval_status
+                    .as_deref()
+                    .is_none_or(|status| status == "URL_RETRIEVAL_STATUS_SUCCESS");
+                if retrieved {
+                    add_source(&mut sources, url.retrieved_url.clone(), url.retrieved_url.clone());
+                }
+            }
+        }
+
+        if queries.is_empty() && sources.is_empty() {
+            None
+        } else {
+            Some(BuiltinToolOutput::Citations { queries, sources })
+        }
+    }
+
     /// Gemini 3.x strictly validates that every functionCall part in history
     /// carries a thoughtSignature, but the API only attaches a signature to the
     /// first functionCall of a parallel batch. Unsigned calls are backfilled
@@ -698,6 +996,85 @@ impl Client {
     /// See https://ai.google.dev/gemini-api/docs/thought-signatures
     const SKIP_THOUGHT_SIGNATURE: &'static str = "skip_thought_signature_validator";
 
+    /// Convert a Gemini functionCall part to a tool call. Gemini assigns no ids,
+    /// so each call gets a fresh one; the thought signature rides in metadata.
+    fn gemini_tool_call(fc: &GeminiFunctionCall, thought_signature: &Option<String>) -> ToolCall {
+        ToolCall {
+            id: ToolCall::new_id(),
+            call_type: "function".to_string(),
+            function: crate::models::FunctionCall {
+                name: fc.name.clone(),
+                arguments: fc.args.to_string(),
+            },
+            provider_metadata: thought_signature.as_ref().map(|sig| ToolCallMetadata {
+                thought_signature: Some(sig.clone()),
+            }),
+        }
+    }
+
+    /// Thought signature of a call in history. Sessions saved before calls carried
+    /// metadata encoded it in the id as "name::signature".
+    fn call_thought_signature(call: &ToolCall) -> Option<String> {
+        call.provider_metadata
+            .as_ref()
+            .and_then(|meta| meta.thought_signature.clone())
+            .or_else(|| {
+                call.id
+                    .split_once("::")
+                    .map(|(_, sig)| sig.to_string())
+                    .filter(|sig| !sig.is_empty())
+            })
+    }
+
+    /// Remove provider metadata from tool calls in a serialized conversation;
+    /// OpenAI-compatible APIs reject fields they don't know
+    fn strip_provider_metadata(messages: &mut serde_json::Value) {
+        let Some(messages) = messages.as_array_mut() else {
+            return;
+        };
+        for message in messages {
+            if let Some(calls) = message.get_mut("tool_calls").and_then(|c| c.as_array_mut()) {
+                for call in calls {
+                    if let Some(call) = call.as_object_mut() {
+                        call.remove("provider_metadata");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finish reason of a Gemini candidate, naming the blocked safety categories
+    fn gemini_finish_reason(candidate: &GeminiCandidate) -> Option<FinishReason> {
+        let reason = candidate.finish_reason.as_deref()?;
+        let blocked: Vec<String> = candidate
+            .safety_ratings
+            .iter()
+            .filter(|rating| rating.blocked)
+            .map(|rating| rating.category.clone())
+            .collect();
+        Some(FinishReason::from_gemini(reason, &blocked))
+    }
+
+    /// Finish reason for a response whose prompt was blocked, with the block
+    /// reason and the categories that caused it
+    fn gemini_prompt_blocked(response: &GeminiResponse) -> Option<FinishReason> {
+        let feedback = response.prompt_feedback.as_ref()?;
+        let block_reason = feedback.block_reason.as_ref()?;
+        let categories: Vec<&str> = feedback
+            .safety_ratings
+            .iter()
+            .flatten()
+            .filter(|r| r.blocked || r.probability == "HIGH" || r.probability == "MEDIUM")
+            .map(|r| r.category.as_str())
+            .collect();
+        let reason = if categories.is_empty() {
+            block_reason.clone()
+        } else {
+            format!("{}: {}", block_reason, categories.join(", "))
+        };
+        Some(FinishReason::PromptBlocked { reason })
+    }
+
     /// Convert OpenAI-style messages to Gemini contents format
     /// Groups consecutive Tool messages into a single content block for parallel function calling
     fn convert_messages_to_gemini(&self, messages: &[Message]) -> Result<Vec<GeminiContent>> {
@@ -705,6 +1082,9 @@ impl Client {
 
         let mut contents = Vec::new();
         let mut pending_tool_parts: Vec<GeminiPart> = Vec::new();
+        // Calls seen so far, so each tool result can find the function it answers
+        let mut calls_by_id: std::collections::HashMap<&str, &ToolCall> =
+            std::collections::HashMap::new();
 
         // Helper to flush pending tool parts
         let flush_tool_parts = |contents: &mut Vec<GeminiContent>, parts: &mut Vec<GeminiPart>| {
@@ -753,21 +1133,16 @@ impl Client {
                     }
 
                     // Add function calls if present
-                    // Extract thought_signature from encoded ID (format: "name::signature")
                     if let Some(calls) = tool_calls {
                         for call in calls {
+                            calls_by_id.insert(&call.id, call);
                             let args: serde_json::Value =
                                 serde_json::from_str(&call.function.arguments).unwrap_or_default();
 
-                            // Extract thought_signature from ID if present;
-                            // backfill unsigned calls (parallel calls after the
+                            // Backfill unsigned calls (parallel calls after the
                             // first, or compacted/migrated history) with the
                             // skip-validation sentinel so Gemini 3.x doesn't 400.
-                            let thought_signature = call
-                                .id
-                                .split_once("::")
-                                .map(|(_, sig)| sig.to_string())
-                                .filter(|sig| !sig.is_empty())
+                            let thought_signature = Self::call_thought_signature(call)
                                 .unwrap_or_else(|| Self::SKIP_THOUGHT_SIGNATURE.to_string());
 
                             parts.push(GeminiPart {
@@ -803,14 +1178,19 @@ impl Client {
                         Err(_) => serde_json::json!({ "result": content }),
                     };
 
-                    // Extract function name and thought_signature from tool_call_id
-                    // The ID may be encoded as "name::signature" or just "name"
-                    let function_name = tool_call_id.split("::").next().unwrap_or(tool_call_id).to_string();
-                    let thought_signature = if tool_call_id.contains("::") {
-                        tool_call_id.split("::").nth(1).map(|s| s.to_string())
-                    } else {
-                        None
-                    };
+                    // Function name and thought signature come from the call this
+                    // result answers. Results without a matching call (legacy ids
+                    // were "name::signature" or just "name") fall back to the id.
+                    let (function_name, thought_signature) =
+                        match calls_by_id.get(tool_call_id.as_str()) {
+                            Some(call) => {
+                                (call.function.name.clone(), Self::call_thought_signature(call))
+                            }
+                            None => match tool_call_id.split_once("::") {
+                                Some((name, sig)) => (name.to_string(), Some(sig.to_string())),
+                                None => (tool_call_id.clone(), None),
+                            },
+                        };
 
                     // Group consecutive tool responses into pending_tool_parts
                     pending_tool_parts.push(GeminiPart {
@@ -839,46 +1219,34 @@ impl Client {
         gemini_response: GeminiResponse,
         raw_response: &str,
     ) -> Result<ChatCompletionResponse> {
-        // Check for prompt-level blocking first
-        if let Some(ref feedback) = gemini_response.prompt_feedback {
-            if let Some(ref block_reason) = feedback.block_reason {
-                eprintln!("⛔ Gemini blocked the prompt: {}", block_reason);
-                return Err(anyhow!("Prompt blocked by Gemini: {}", block_reason));
-            }
+        // A blocked prompt has no candidates; report it as an empty reply so
+        // the agent loop can show why instead of failing the whole request
+        if let Some(finish_reason) = Self::gemini_prompt_blocked(&gemini_response) {
+            return Ok(ChatCompletionResponse {
+                choices: vec![crate::models::Choice {
+                    message: crate::models::AssistantMessage {
+                        content: None,
+                        tool_calls: None,
+                        builtin_outputs: Vec::new(),
+                    },
+                    finish_reason: Some(finish_reason),
+                }],
+                usage: None,
+            });
         }
 
         if gemini_response.candidates.is_empty() {
-            // If no candidates but we have prompt feedback, show safety ratings
-            if let Some(ref feedback) = gemini_response.prompt_feedback {
-                if let Some(ref ratings) = feedback.safety_ratings {
-                    let high_ratings: Vec<_> = ratings
-                        .iter()
-                        .filter(|r| r.probability == "HIGH" || r.probability == "MEDIUM")
-                        .map(|r| format!("{}: {}", r.category, r.probability))
-                        .collect();
-                    if !high_ratings.is_empty() {
-                        eprintln!("⚠️  Gemini safety concerns: {}", high_ratings.join(", "));
-                    }
-                }
-            }
             return Err(anyhow!("Gemini response has no candidates"));
         }
 
         let candidate = &gemini_response.candidates[0];
 
-        // Report non-normal finish reasons
+        // Length, safety and recitation stops are carried in finish_reason for
+        // the agent loop; only report the unusual ones here
         if let Some(ref reason) = candidate.finish_reason {
             match reason.as_str() {
-                "STOP" => {} // Normal completion
-                "MAX_TOKENS" => {
-                    eprintln!("⚠️  Gemini stopped: reached maximum token limit");
-                }
-                "SAFETY" => {
-                    eprintln!("⛔ Gemini stopped: safety filters triggered");
-                }
-                "RECITATION" => {
-                    eprintln!("⛔ Gemini stopped: recitation/copyright concern");
-                }
+                "STOP" | "MAX_TOKENS" | "SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII"
+                | "IMAGE_SAFETY" | "RECITATION" => {}
                 "OTHER" => {
                     eprintln!("⚠️  Gemini stopped: unspecified reason (OTHER)");
                 }
@@ -901,46 +1269,28 @@ impl Client {
             }
         }
 
-        // Extract text content, including code execution results for Agentic Vision
-        let mut text_parts: Vec<String> = Vec::new();
-        for part in &candidate.content.parts {
-            if let Some(ref t) = part.text {
-                text_parts.push(t.clone());
-            }
-            // Include code execution results in the text output
-            if let Some(ref code) = part.executable_code {
-                text_parts.push(format!("\n```{}\n{}\n```", code.language.to_lowercase(), code.code));
-            }
-            if let Some(ref result) = part.code_execution_result {
-                if let Some(ref output) = result.output {
-                    text_parts.push(format!("\n**Code Output ({}):**\n```\n{}\n```", result.outcome, output));
-                }
-            }
-        }
+        // Extract text content. Built-in tool activity is kept apart so callers can
+        // surface it as its own events rather than as part of the answer.
+        let text_parts: Vec<String> = candidate
+            .content
+            .parts
+            .iter()
+            .filter_map(|part| part.text.clone())
+            .collect();
         let text = text_parts.join("\n");
 
+        let mut builtin_outputs = Self::code_execution_outputs(&candidate.content.parts);
+        builtin_outputs.extend(Self::citation_output(candidate));
+
         // Extract function calls and convert to OpenAI tool_calls format
-        // Encode both function name and thought_signature in the ID for Gemini 3 compatibility
-        // Format: "name::signature" or just "name" if no signature
-        let tool_calls: Vec<crate::models::ToolCall> = candidate
+        let tool_calls: Vec<ToolCall> = candidate
             .content
             .parts
             .iter()
             .filter_map(|p| {
-                p.function_call.as_ref().map(|fc| {
-                    let id = match &p.thought_signature {
-                        Some(sig) => format!("{}::{}", fc.name, sig),
-                        None => fc.name.clone(),
-                    };
-                    crate::models::ToolCall {
-                        id,
-                        call_type: "function".to_string(),
-                        function: crate::models::FunctionCall {
-                            name: fc.name.clone(),
-                            arguments: fc.args.to_string(),
-                        },
-                    }
-                })
+                p.function_call
+                    .as_ref()
+                    .map(|fc| Self::gemini_tool_call(fc, &p.thought_signature))
             })
             .collect();
 
@@ -956,7 +1306,9 @@ impl Client {
                 message: crate::models::AssistantMessage {
                     content: if text.is_empty() { None } else { Some(text) },
                     tool_calls,
+                    builtin_outputs,
                 },
+                finish_reason: Self::gemini_finish_reason(candidate),
             }],
             usage: gemini_response.usage_metadata.map(|u| crate::models::UsageStats {
                 prompt_tokens: u.prompt_token_count,
@@ -1219,6 +1571,9 @@ mod tests {
                 },
                 finish_reason: Some("STOP".to_string()),
                 finish_message: None,
+                grounding_metadata: None,
+                url_context_metadata: None,
+                safety_ratings: Vec::new(),
             }],
             prompt_feedback: None,
             usage_metadata: None,
@@ -1261,6 +1616,9 @@ mod tests {
                 },
                 finish_reason: Some("STOP".to_string()),
                 finish_message: None,
+                grounding_metadata: None,
+                url_context_metadata: None,
+                safety_ratings: Vec::new(),
             }],
             prompt_feedback: None,
             usage_metadata: None,
@@ -1291,7 +1649,63 @@ mod tests {
     }
 
     #[test]
-    fn test_thought_signature_on_function_response() {
+    fn test_gemini_safety_stop_names_blocked_category() {
+        // Blocked candidates can arrive without any content
+        let client = create_test_client();
+        let raw = r#"{
+            "candidates": [{
+                "finishReason": "SAFETY",
+                "safetyRatings": [
+                    {"category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE"},
+                    {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true}
+                ]
+            }]
+        }"#;
+        let response: GeminiResponse = serde_json::from_str(raw).unwrap();
+        let converted = client.convert_gemini_to_openai_response(response, raw).unwrap();
+        let choice = &converted.choices[0];
+        assert_eq!(choice.message.content, None);
+        assert_eq!(
+            choice.finish_reason,
+            Some(FinishReason::ContentFilter {
+                category: Some("HARM_CATEGORY_DANGEROUS_CONTENT".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_gemini_max_tokens_is_length() {
+        let client = create_test_client();
+        let raw = r#"{"candidates": [{"content": {"parts": [{"text": "Once upon"}]}, "finishReason": "MAX_TOKENS"}]}"#;
+        let response: GeminiResponse = serde_json::from_str(raw).unwrap();
+        let converted = client.convert_gemini_to_openai_response(response, raw).unwrap();
+        assert_eq!(converted.choices[0].message.content.as_deref(), Some("Once upon"));
+        assert_eq!(converted.choices[0].finish_reason, Some(FinishReason::Length));
+    }
+
+    #[test]
+    fn test_gemini_blocked_prompt_is_a_reply_not_an_error() {
+        let client = create_test_client();
+        let raw = r#"{
+            "promptFeedback": {
+                "blockReason": "SAFETY",
+                "safetyRatings": [{"category": "HARM_CATEGORY_HATE_SPEECH", "probability": "HIGH"}]
+            }
+        }"#;
+        let response: GeminiResponse = serde_json::from_str(raw).unwrap();
+        let converted = client.convert_gemini_to_openai_response(response, raw).unwrap();
+        assert_eq!(
+            converted.choices[0].finish_reason,
+            Some(FinishReason::PromptBlocked {
+                reason: "SAFETY: HARM_CATEGORY_HATE_SPEECH".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_thought_signature_on_function_response_legacy_id() {
+        // Sessions saved before tool calls carried metadata encoded the
+        // signature in the id; a result without a matching call still decodes it
         let client = create_test_client();
         let messages = vec![
             Message::Tool {
@@ -1324,20 +1738,24 @@ mod tests {
                 content: None,
                 tool_calls: Some(vec![
                     crate::models::ToolCall {
-                        id: "sql_list_tables::sig1".to_string(),
+                        id: "call_1".to_string(),
                         call_type: "function".to_string(),
                         function: crate::models::FunctionCall {
                             name: "sql_list_tables".to_string(),
                             arguments: "{}".to_string(),
                         },
+                        provider_metadata: Some(ToolCallMetadata {
+                            thought_signature: Some("sig1".to_string()),
+                        }),
                     },
                     crate::models::ToolCall {
-                        id: "sql_query".to_string(), // no "::signature" suffix
+                        id: "call_2".to_string(), // no signature
                         call_type: "function".to_string(),
                         function: crate::models::FunctionCall {
                             name: "sql_query".to_string(),
                             arguments: "{\"q\":\"select 1\"}".to_string(),
                         },
+                        provider_metadata: None,
                     },
                 ]),
             },
@@ -1362,6 +1780,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_same_function_called_twice_keeps_results_apart() {
+        // Two Bash calls in one turn: each result must pair with its own call,
+        // and the signature must come from metadata rather than the id
+        let client = create_test_client();
+        let response = GeminiResponse {
+            candidates: vec![GeminiCandidate {
+                content: GeminiContentResponse {
+                    parts: vec![
+                        GeminiPartResponse {
+                            text: None,
+                            function_call: Some(GeminiFunctionCall {
+                                name: "Bash".to_string(),
+                                args: serde_json::json!({"command": "ls"}),
+                            }),
+                            thought_signature: Some("sigA".to_string()),
+                            executable_code: None,
+                            code_execution_result: None,
+                        },
+                        GeminiPartResponse {
+                            text: None,
+                            function_call: Some(GeminiFunctionCall {
+                                name: "Bash".to_string(),
+                                args: serde_json::json!({"command": "pwd"}),
+                            }),
+                            thought_signature: None,
+                            executable_code: None,
+                            code_execution_result: None,
+                        },
+                    ],
+                },
+                finish_reason: Some("STOP".to_string()),
+                finish_message: None,
+                grounding_metadata: None,
+                url_context_metadata: None,
+                safety_ratings: Vec::new(),
+            }],
+            usage_metadata: None,
+            prompt_feedback: None,
+        };
+
+        let converted = client.convert_gemini_to_openai_response(response, "{}").unwrap();
+        let calls = converted.choices[0].message.tool_calls.clone().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_ne!(calls[0].id, calls[1].id);
+        assert!(!calls[0].id.contains("::"));
+        assert_eq!(
+            calls[0].provider_metadata.as_ref().unwrap().thought_signature.as_deref(),
+            Some("sigA")
+        );
+        assert!(calls[1].provider_metadata.is_none());
+
+        // Results arrive in reverse order; each must keep its own call's signature
+        let history = vec![
+            Message::Assistant { content: None, tool_calls: Some(calls.clone()) },
+            Message::Tool { tool_call_id: calls[1].id.clone(), content: "/tmp".to_string() },
+            Message::Tool { tool_call_id: calls[0].id.clone(), content: "a.txt".to_string() },
+        ];
+        let contents = client.convert_messages_to_gemini(&history).unwrap();
+        assert_eq!(contents.len(), 2);
+
+        let results = &contents[1].parts;
+        assert_eq!(results[0].function_response.as_ref().unwrap().name, "Bash");
+        assert_eq!(results[0].thought_signature, None);
+        assert_eq!(results[1].function_response.as_ref().unwrap().name, "Bash");
+        assert_eq!(results[1].thought_signature, Some("sigA".to_string()));
+    }
+
+    #[test]
+    fn test_strip_provider_metadata_for_openai_requests() {
+        let history = vec![Message::Assistant {
+            content: None,
+            tool_calls: Some(vec![crate::models::ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: crate::models::FunctionCall {
+                    name: "Bash".to_string(),
+                    arguments: "{}".to_string(),
+                },
+                provider_metadata: Some(ToolCallMetadata {
+                    thought_signature: Some("sig".to_string()),
+                }),
+            }]),
+        }];
+        let mut value = serde_json::to_value(&history).unwrap();
+        assert!(value[0]["tool_calls"][0].get("provider_metadata").is_some());
+
+        Client::strip_provider_metadata(&mut value);
+        assert!(value[0]["tool_calls"][0].get("provider_metadata").is_none());
+        assert_eq!(value[0]["tool_calls"][0]["id"], "call_1");
+    }
+
     #[test]
     fn test_thought_signature_absent_on_function_response() {
         let client = create_test_client();
@@ -1446,6 +1956,9 @@ mod tests {
                 },
                 finish_reason: Some("STOP".to_string()),
                 finish_message: None,
+                grounding_metadata: None,
+                url_context_metadata: None,
+                safety_ratings: Vec::new(),
             }],
             prompt_feedback: None,
             usage_metadata: None,
@@ -1455,9 +1968,31 @@ mod tests {
         assert!(result.is_ok());
 
         let openai_response = result.unwrap();
-        let content = openai_response.choices[0].message.content.as_ref().unwrap();
+        let message = &openai_response.choices[0].message;
 
-        // Verify all parts are included in the response
+        // The answer itself is only the model's text
+        assert_eq!(
+            message.content.as_deref(),
+            Some("Let me analyze the image.\nThe analysis is complete.")
+        );
+
+        // Code and its output are kept apart, in order
+        assert_eq!(
+            message.builtin_outputs,
+            vec![
+                BuiltinToolOutput::Code {
+                    language: "PYTHON".to_string(),
+                    code: "print('Hello from Agentic Vision')".to_string(),
+                },
+                BuiltinToolOutput::CodeResult {
+                    outcome: "OUTCOME_OK".to_string(),
+                    output: "Hello from Agentic Vision".to_string(),
+                },
+            ]
+        );
+
+        // ...and all of it is kept in history
+        let content = message.history_content().unwrap();
         assert!(content.contains("Let me analyze the image."));
         assert!(content.contains("```python"));
         assert!(content.contains("print('Hello from Agentic Vision')"));
@@ -1465,6 +2000,100 @@ mod tests {
         assert!(content.contains("Hello from Agentic Vision"));
         assert!(content.contains("The analysis is complete."));
     }
+
+    #[test]
+    fn test_convert_gemini_to_openai_response_collects_grounding_citations() {
+        let client = create_test_client();
+        let raw = r#"{
+            "candidates": [{
+                "content": {"parts": [{"text": "Rust 1.90 shipped in September."}]},
+                "finishReason": "STOP",
+                "groundingMetadata": {
+                    "webSearchQueries": ["rust 1.90 release date"],
+                    "groundingChunks": [
+                        {"web": {"uri": "https://blog.rust-lang.org/a", "title": "rust-lang.org"}},
+                        {"web": {"uri": "https://blog.rust-lang.org/a", "title": "rust-lang.org"}},
+                        {"web": {"uri": "https://example.com/b"}}
+                    ]
+                },
+                "urlContextMetadata": {
+                    "urlMetadata": [
+                        {"retrievedUrl": "https://example.com/page", "urlRetrievalStatus": "URL_RETRIEVAL_STATUS_SUCCESS"},
+                        {"retrievedUrl": "https://example.com/paywalled", "urlRetrievalStatus": "URL_RETRIEVAL_STATUS_ERROR"}
+                    ]
+                }
+            }]
+        }"#;
+        let gemini_response: GeminiResponse = serde_json::from_str(raw).unwrap();
+
+        let response = client.convert_gemini_to_openai_response(gemini_response, raw).unwrap();
+        let message = &response.choices[0].message;
+
+        assert_eq!(message.content.as_deref(), Some("Rust 1.90 shipped in September."));
+        let BuiltinToolOutput::Citations { queries, sources } = &message.builtin_outputs[0] else {
+            panic!("expected citations, got {:?}", message.builtin_outputs);
+        };
+        assert_eq!(queries, &vec!["rust 1.90 release date".to_string()]);
+        let uris: Vec<&str> = sources.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            vec!["https://blog.rust-lang.org/a", "https://example.com/b", "https://example.com/page"]
+        );
+        // An untitled chunk falls back to its URI
+        assert_eq!(sources[1].title, "https://example.com/b");
+
+        let history = message.history_content().unwrap();
+        assert!(history.starts_with("Rust 1.90 shipped in September."), "{}", history);
+        assert!(history.contains("[rust-lang.org](https://blog.rust-lang.org/a)"), "{}", history);
+    }
+
+    #[test]
+    fn test_build_gemini_tools_appends_enabled_builtin_tools() {
+        let mut client = create_test_client();
+        let tools = vec![Tool {
+            tool_type: "function".to_string(),
+            function: crate::models::FunctionSpec {
+                name: "Bash".to_string(),
+                description: "Run a command".to_string(),
+                parameters: serde_json::json!({"type": "object", "properties": {}}),
+            },
+        }];
+
+        // Without built-in tools, only the function declarations are sent
+        let json = serde_json::to_value(client.build_gemini_tools(Some(&tools))).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["functionDeclarations"][0]["name"], "Bash");
+
+        client.set_gemini_tools(vec![
+            GeminiBuiltinTool::GoogleSearch,
+            GeminiBuiltinTool::UrlContext,
+            GeminiBuiltinTool::CodeExecution,
+        ]);
+        let json = serde_json::to_value(client.build_gemini_tools(Some(&tools))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"functionDeclarations": [{
+                    "name": "Bash",
+                    "description": "Run a command",
+                    "parameters": {"type": "object", "properties": {}}
+                }]},
+                {"google_search": {}},
+                {"url_context": {}},
+                {"code_execution": {}}
+            ])
+        );
+
+        // Built-in tools alone still produce a tools list
+        let json = serde_json::to_value(client.build_gemini_tools(None)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_build_gemini_tools_none_when_nothing_to_send() {
+        let client = create_test_client();
+        assert!(client.build_gemini_tools(None).is_none());
+    }
 }
 
 #[cfg(test)]
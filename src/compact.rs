@@ -9,6 +9,7 @@
 
 use crate::client::Client;
 use crate::models::Message;
+use crate::tools::TodoItem;
 use anyhow::{Context, Result};
 
 /// Compaction configuration
@@ -398,6 +399,22 @@ pub fn trim_to_token_budget(messages: &[Message], target_tokens: usize) -> Vec<M
     out
 }
 
+/// Put the session's task list back into compacted history. Every compaction
+/// leads with a user note, so the list joins it; the summary or trim would
+/// otherwise have dropped the Todo calls that built it.
+pub fn reattach_todos(messages: &mut Vec<Message>, todos: &[TodoItem]) {
+    let Some(note) = crate::tools::todo::context_note(todos) else {
+        return;
+    };
+    match messages.first_mut() {
+        Some(Message::User { content }) => {
+            content.push_str("\n\n");
+            content.push_str(&note);
+        }
+        _ => messages.insert(0, Message::User { content: note }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -581,4 +598,36 @@ mod tests {
         assert!(formatted.contains("Hello"));
         assert!(formatted.contains("Hi!"));
     }
+
+    #[test]
+    fn test_reattach_todos() {
+        use crate::tools::TodoStatus;
+        let todos = vec![TodoItem {
+            content: "Port the parser".to_string(),
+            status: TodoStatus::InProgress,
+        }];
+
+        let mut messages = vec![Message::User {
+            content: "## Context Note".to_string(),
+        }];
+        reattach_todos(&mut messages, &todos);
+        assert_eq!(messages.len(), 1);
+        let Message::User { content } = &messages[0] else {
+            panic!("expected a user message");
+        };
+        assert!(content.starts_with("## Context Note\n\n## Current Task List"));
+        assert!(content.contains("[>] Port the parser"));
+
+        let mut messages = vec![Message::Assistant {
+            content: Some("Hi".to_string()),
+            tool_calls: None,
+        }];
+        reattach_todos(&mut messages, &todos);
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(&messages[0], Message::User { content } if content.contains("Port the parser")));
+
+        // Nothing to add without a list
+        reattach_todos(&mut messages, &[]);
+        assert_eq!(messages.len(), 2);
+    }
 }
@@ -22,6 +22,9 @@ pub const SNAPSHOT_ENV_VARS: &[&str] = &[
     "AZURE_OPENAI_ENDPOINT",
     "AZURE_OPENAI_API_KEY",
     "AZURE_OPENAI_API_VERSION",
+    "GOOGLE_APPLICATION_CREDENTIALS",
+    "GOOGLE_CLOUD_PROJECT",
+    "GOOGLE_CLOUD_LOCATION",
     "GEMMAD_HOST",
     "GEMMAD_PORT",
     "GEMMAD_MODEL_ID",
@@ -36,7 +36,7 @@ pub fn print_model_list() {
         let key_status = get_key_status(&provider, available);
 
         // Check if all models in this provider support tools
-        let all_support_tools = matches!(provider, Provider::OpenAI | Provider::Gemini | Provider::Anthropic | Provider::AzureOpenAI);
+        let all_support_tools = matches!(provider, Provider::OpenAI | Provider::Gemini | Provider::Anthropic | Provider::AzureOpenAI | Provider::VertexAI);
         let tools_note = if all_support_tools { " ✓" } else { "" };
 
         println!(
@@ -93,6 +93,14 @@ fn get_key_status(provider: &Provider, available: bool) -> String {
                 "not set".to_string()
             }
         }
+        Provider::VertexAI => {
+            // Vertex AI authenticates with a service-account key file
+            if env::var("GOOGLE_APPLICATION_CREDENTIALS").is_ok() {
+                "service account".to_string()
+            } else {
+                "not set".to_string()
+            }
+        }
         _ => {
             let key_name = match provider {
                 Provider::OpenAI => "OPENAI_API_KEY",
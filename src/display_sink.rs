@@ -3,7 +3,9 @@
 //! This module provides a trait-based abstraction for display output, allowing
 //! the TUI mode to use SharedWriter while normal mode uses println!().
 
+use crate::models::{BuiltinToolOutput, Citation};
 use crate::theme;
+use crate::tools::{todo, TodoItem};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::Write;
@@ -18,8 +20,18 @@ pub enum DisplayEvent {
     ThinkingStop,
     /// Tool call being made
     ToolCall { name: String, arguments: String },
+    /// A line of output from a tool that is still running (Bash)
+    ToolProgress { line: String },
     /// Tool result received
     ToolResult { result: String, limit: usize },
+    /// The task list after a tool call changed it
+    Todos { items: Vec<TodoItem> },
+    /// Code the model ran server-side (Gemini code execution)
+    CodeExecution { language: String, code: String },
+    /// Outcome and output of server-side code execution
+    CodeExecutionResult { outcome: String, output: String },
+    /// Search queries and sources behind a grounded answer
+    Citations { queries: Vec<String>, sources: Vec<Citation> },
     /// Response content from LLM (complete)
     Response { content: String },
     /// Streaming chunk from LLM (partial, no newline)
@@ -32,6 +44,38 @@ pub enum DisplayEvent {
     Error { message: String },
 }
 
+impl From<BuiltinToolOutput> for DisplayEvent {
+    fn from(output: BuiltinToolOutput) -> Self {
+        match output {
+            BuiltinToolOutput::Code { language, code } => DisplayEvent::CodeExecution { language, code },
+            BuiltinToolOutput::CodeResult { outcome, output } => {
+                DisplayEvent::CodeExecutionResult { outcome, output }
+            }
+            BuiltinToolOutput::Citations { queries, sources } => {
+                DisplayEvent::Citations { queries, sources }
+            }
+        }
+    }
+}
+
+/// Code execution outcomes other than OUTCOME_OK are worth calling out
+fn outcome_failed(outcome: &str) -> bool {
+    outcome != "OUTCOME_OK"
+}
+
+/// The task list as it is shown when it changes: the input box's checklist,
+/// or just the count once every task is done
+fn todo_lines(items: &[TodoItem]) -> Vec<String> {
+    if items.is_empty() {
+        return vec![format!("{}  Task list cleared{}", theme::DIM, theme::RESET)];
+    }
+    let lines = theme::todo_panel_with_width(items, theme::term_cols());
+    if lines.is_empty() {
+        return vec![format!("{}  {}{}", theme::DIM, todo::progress(items), theme::RESET)];
+    }
+    lines
+}
+
 /// Trait for display output sinks
 pub trait DisplaySink: Send + Sync {
     /// Write a display event
@@ -100,6 +144,9 @@ impl DisplaySink for StdDisplaySink {
                     }
                 }
             }
+            DisplayEvent::ToolProgress { line } => {
+                println!("    {}", line.dimmed());
+            }
             DisplayEvent::ToolResult { result, limit } => {
                 let lines: Vec<&str> = result.lines().collect();
                 let output = if limit > 0 && lines.len() > limit {
@@ -122,6 +169,33 @@ impl DisplaySink for StdDisplaySink {
                     println!("{}", output.dimmed());
                 }
             }
+            DisplayEvent::Todos { items } => {
+                for line in todo_lines(&items) {
+                    println!("{}", line);
+                }
+            }
+            DisplayEvent::CodeExecution { language, code } => {
+                println!("  {} {}", "→".blue(), format!("code_execution ({})", language.to_lowercase()).bright_blue());
+                for line in code.lines() {
+                    println!("    {}", line.dimmed());
+                }
+            }
+            DisplayEvent::CodeExecutionResult { outcome, output } => {
+                if outcome_failed(&outcome) {
+                    println!("    {}", outcome.red());
+                }
+                for line in output.lines() {
+                    println!("    {}", line.dimmed());
+                }
+            }
+            DisplayEvent::Citations { queries, sources } => {
+                if !queries.is_empty() {
+                    println!("  {} {}", "→".blue(), format!("google_search: {}", queries.join(", ")).bright_blue());
+                }
+                for (i, source) in sources.iter().enumerate() {
+                    println!("    {}", format!("[{}] {} — {}", i + 1, source.title, source.uri).dimmed());
+                }
+            }
             DisplayEvent::Response { content } => {
                 let trimmed = content.trim();
                 if !trimmed.is_empty() {
@@ -196,6 +270,9 @@ impl DisplaySink for TuiDisplaySink {
                     }
                 }
             }
+            DisplayEvent::ToolProgress { line } => {
+                let _ = writeln!(writer, "    {DIM}{}{RESET}", line);
+            }
             DisplayEvent::ToolResult { result, limit } => {
                 let lines: Vec<&str> = result.lines().collect();
                 let output = if limit > 0 && lines.len() > limit {
@@ -218,6 +295,41 @@ impl DisplaySink for TuiDisplaySink {
                     let _ = writeln!(writer, "{DIM}{}{RESET}", output);
                 }
             }
+            DisplayEvent::Todos { items } => {
+                for line in todo_lines(&items) {
+                    let _ = writeln!(writer, "{}", line);
+                }
+            }
+            DisplayEvent::CodeExecution { language, code } => {
+                let _ = writeln!(
+                    writer,
+                    "  {BLUE}→{RESET} {BRIGHT_BLUE}code_execution ({}){RESET}",
+                    language.to_lowercase()
+                );
+                for line in code.lines() {
+                    let _ = writeln!(writer, "    {DIM}{}{RESET}", line);
+                }
+            }
+            DisplayEvent::CodeExecutionResult { outcome, output } => {
+                if outcome_failed(&outcome) {
+                    let _ = writeln!(writer, "    {RED}{}{RESET}", outcome);
+                }
+                for line in output.lines() {
+                    let _ = writeln!(writer, "    {DIM}{}{RESET}", line);
+                }
+            }
+            DisplayEvent::Citations { queries, sources } => {
+                if !queries.is_empty() {
+                    let _ = writeln!(
+                        writer,
+                        "  {BLUE}→{RESET} {BRIGHT_BLUE}google_search: {}{RESET}",
+                        queries.join(", ")
+                    );
+                }
+                for (i, source) in sources.iter().enumerate() {
+                    let _ = writeln!(writer, "    {DIM}[{}] {} — {}{RESET}", i + 1, source.title, source.uri);
+                }
+            }
             DisplayEvent::Response { content } => {
                 let trimmed = content.trim();
                 if !trimmed.is_empty() {
@@ -0,0 +1,232 @@
+//! Which environment variables tool subprocesses inherit.
+//!
+//! Bash commands, background jobs, the persistent shell and custom tools are
+//! spawned from eunice's own environment, which holds the provider API keys it
+//! was started with. By default those are removed: a command that runs `env`
+//! or a script that posts its environment somewhere never sees them.
+//!
+//! `~/.eunice/env.toml` changes the defaults:
+//!
+//! ```toml
+//! # "deny" (default): inherit everything except eunice's credentials and `deny`
+//! # "allow": inherit only PATH, HOME, locale and the like, plus `allow`
+//! mode = "deny"
+//! allow = ["CARGO_HOME"]
+//! deny = ["DATABASE_URL"]
+//! # Inherited even though denied, e.g. for a skill script that calls the API
+//! pass = ["OPENAI_API_KEY"]
+//! ```
+//!
+//! `--pass-env NAME` adds to `pass` for one run, and an agent in agents.toml
+//! can set `env` (variables to add) and `pass_env`.
+//!
+//! This keeps keys out of what commands print and send; it is not a boundary
+//! against a command that goes looking, which can still read the eunice
+//! process's own environment under `/proc` unless a sandbox profile stops it.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// eunice's own credentials, never inherited unless passed explicitly
+pub const DEFAULT_DENY: &[&str] = &[
+    "OPENAI_API_KEY",
+    "ANTHROPIC_API_KEY",
+    "GEMINI_API_KEY",
+    "GOOGLE_API_KEY",
+    "AZURE_OPENAI_API_KEY",
+    "GEMMAD_API_KEY",
+    "GEMMAD_KEYS_FILE",
+    "GOOGLE_APPLICATION_CREDENTIALS",
+];
+
+/// Inherited in allow mode without being listed
+const BASE_ALLOW: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "LANG", "LANGUAGE", "TZ", "TMPDIR", "PWD",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvMode {
+    /// Inherit everything not denied
+    #[default]
+    Deny,
+    /// Inherit only the base variables and the allowlist
+    Allow,
+}
+
+/// `~/.eunice/env.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvConfig {
+    #[serde(default)]
+    pub mode: EnvMode,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub pass: Vec<String>,
+}
+
+/// Path of the environment policy file
+pub fn config_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".eunice")
+        .join("env.toml")
+}
+
+impl EnvConfig {
+    /// Load `path`; a missing file means the default policy
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read '{}': {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| anyhow!("'{}': {}", path.display(), e))
+    }
+}
+
+/// Check that `name` can be an environment variable name
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains('=') || name.contains('\0') {
+        return Err(anyhow!(
+            "'{}' is not a valid environment variable name",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// The environment a tool subprocess starts with: what it inherits from
+/// eunice, plus variables set outright
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvPolicy {
+    mode: EnvMode,
+    allow: BTreeSet<String>,
+    deny: BTreeSet<String>,
+    pass: BTreeSet<String>,
+    set: BTreeMap<String, String>,
+}
+
+impl Default for EnvPolicy {
+    /// Deny mode with eunice's credentials denied
+    fn default() -> Self {
+        Self {
+            mode: EnvMode::Deny,
+            allow: BTreeSet::new(),
+            deny: DEFAULT_DENY.iter().map(|name| name.to_string()).collect(),
+            pass: BTreeSet::new(),
+            set: BTreeMap::new(),
+        }
+    }
+}
+
+impl EnvPolicy {
+    /// The default policy extended by an env.toml
+    pub fn from_config(config: EnvConfig) -> Result<Self> {
+        for name in config.allow.iter().chain(&config.deny).chain(&config.pass) {
+            validate_name(name)?;
+        }
+        let mut policy = Self {
+            mode: config.mode,
+            allow: config.allow.into_iter().collect(),
+            ..Self::default()
+        };
+        policy.deny.extend(config.deny);
+        Ok(policy.with_pass(config.pass))
+    }
+
+    /// Also inherit `names`, even when denied or outside the allowlist
+    pub fn with_pass(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.pass.extend(names);
+        self
+    }
+
+    /// Also set `vars`, overriding inherited values
+    pub fn with_vars(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.set.extend(vars);
+        self
+    }
+
+    /// Whether a subprocess keeps `name` from eunice's environment
+    pub fn inherits(&self, name: &str) -> bool {
+        if self.pass.contains(name) {
+            return true;
+        }
+        if self.deny.contains(name) {
+            return false;
+        }
+        match self.mode {
+            EnvMode::Deny => true,
+            EnvMode::Allow => {
+                BASE_ALLOW.contains(&name) || name.starts_with("LC_") || self.allow.contains(name)
+            }
+        }
+    }
+
+    /// Remove what `cmd` must not inherit and add the set variables. Call
+    /// before setting per-call variables on `cmd`, which this leaves alone.
+    pub fn apply(&self, cmd: &mut tokio::process::Command) {
+        for (name, _) in std::env::vars_os() {
+            if !self.inherits(&name.to_string_lossy()) {
+                cmd.env_remove(&name);
+            }
+        }
+        cmd.envs(&self.set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_denies_credentials_only() {
+        let policy = EnvPolicy::default();
+        assert!(!policy.inherits("OPENAI_API_KEY"));
+        assert!(!policy.inherits("GEMMAD_KEYS_FILE"));
+        assert!(policy.inherits("PATH"));
+        assert!(policy.inherits("CARGO_HOME"));
+        assert!(policy
+            .with_pass(["OPENAI_API_KEY".to_string()])
+            .inherits("OPENAI_API_KEY"));
+    }
+
+    #[test]
+    fn test_allow_mode_keeps_base_and_allowlist() {
+        let config: EnvConfig = toml::from_str(
+            "mode = \"allow\"\nallow = [\"CARGO_HOME\"]\npass = [\"GEMINI_API_KEY\"]",
+        )
+        .unwrap();
+        let policy = EnvPolicy::from_config(config).unwrap();
+        assert!(policy.inherits("PATH"));
+        assert!(policy.inherits("LC_ALL"));
+        assert!(policy.inherits("CARGO_HOME"));
+        assert!(policy.inherits("GEMINI_API_KEY"));
+        assert!(!policy.inherits("SSH_AUTH_SOCK"));
+        assert!(!policy.inherits("OPENAI_API_KEY"));
+
+        let bad: EnvConfig = toml::from_str("deny = [\"A=B\"]").unwrap();
+        assert!(EnvPolicy::from_config(bad).is_err());
+        assert!(toml::from_str::<EnvConfig>("mode = \"open\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_strips_denied_and_sets_vars() {
+        // PATH is always present, so deny it rather than mutate this process's env
+        let config: EnvConfig = toml::from_str("deny = [\"PATH\"]").unwrap();
+        let policy = EnvPolicy::from_config(config)
+            .unwrap()
+            .with_vars([("EUNICE_TEST_VAR".to_string(), "set".to_string())]);
+        let mut cmd = tokio::process::Command::new("/usr/bin/env");
+        policy.apply(&mut cmd);
+        let output = cmd.output().await.unwrap();
+        let env = String::from_utf8_lossy(&output.stdout);
+        assert!(!env.lines().any(|line| line.starts_with("PATH=")));
+        assert!(env.lines().any(|line| line == "EUNICE_TEST_VAR=set"));
+    }
+}
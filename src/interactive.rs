@@ -1,4 +1,5 @@
 use crate::agent::{run_agent_cancellable, AgentStatus};
+use crate::checkpoints;
 use crate::client::Client;
 use crate::compact::CompactionConfig;
 use crate::display;
@@ -18,7 +19,7 @@ use std::time::Duration;
 use tokio::sync::watch;
 
 /// Available slash commands for autocomplete
-const SLASH_COMMANDS: &[&str] = &["/help", "/clear", "/status"];
+const SLASH_COMMANDS: &[&str] = &["/help", "/clear", "/status", "/undo", "/rewind"];
 
 /// Get matching slash commands for autocomplete
 fn get_matching_commands(prefix: &str) -> Vec<&'static str> {
@@ -80,6 +81,8 @@ fn print_help() {
     println!("  {}       Show this help message", "/help".cyan());
     println!("  {}      Clear conversation history", "/clear".cyan());
     println!("  {}     Show current status", "/status".cyan());
+    println!("  {}       Undo the last file change", "/undo".cyan());
+    println!("  {} List file changes, or undo the last n", "/rewind [n]".cyan());
     println!("  {}       Exit interactive mode", "exit".cyan());
     println!("  {}       Exit interactive mode", "quit".cyan());
     println!();
@@ -372,14 +375,13 @@ pub async fn interactive_mode(
     client: &Client,
     model: &str,
     initial_prompt: Option<&str>,
+    tool_registry: &ToolRegistry,
 ) -> Result<()> {
     let mut conversation_history: Vec<Message> = Vec::new();
     let mut input_history: Vec<String> = Vec::new();
     let mut output_store = OutputStore::new();
     let mut session_usage = SessionUsage::new();
 
-    // Create tool registry
-    let tool_registry = ToolRegistry::new();
     let tool_count = tool_registry.get_tools().len();
 
     // Show model info once at startup
@@ -399,7 +401,7 @@ pub async fn interactive_mode(
             client,
             model,
             prompt,
-            &tool_registry,
+            tool_registry,
             &mut conversation_history,
             Some(cancel_rx),
             &mut output_store,
@@ -446,6 +448,7 @@ pub async fn interactive_mode(
 
         if input.eq_ignore_ascii_case("/clear") {
             conversation_history.clear();
+            tool_registry.todos().replace(Vec::new());
             println!("\n{}\n", "Conversation history cleared.".green());
             continue;
         }
@@ -455,6 +458,11 @@ pub async fn interactive_mode(
             continue;
         }
 
+        if let Some(text) = checkpoints::slash_command(tool_registry.checkpoints(), input) {
+            println!("\n{}\n", text);
+            continue;
+        }
+
         // Add to history (avoid duplicates of last entry, skip commands)
         if !input.starts_with('/') && input_history.last().map(|s| s.as_str()) != Some(input) {
             input_history.push(input.to_string());
@@ -473,7 +481,7 @@ pub async fn interactive_mode(
             client,
             model,
             input,
-            &tool_registry,
+            tool_registry,
             &mut conversation_history,
             Some(cancel_rx),
             &mut output_store,
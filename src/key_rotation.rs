@@ -151,6 +151,11 @@ impl KeyPool {
         (self.current_index.load(Ordering::SeqCst) % self.keys.len()) + 1
     }
 
+    /// Every key in the pool, so they can be masked in tool output
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
     /// Get total number of keys
     pub fn key_count(&self) -> usize {
         self.keys.len()
@@ -1,17 +1,24 @@
 pub mod agent;
 pub mod agents;
+pub mod checkpoints;
 pub mod client;
 pub mod compact;
 pub mod display;
 pub mod display_sink;
+pub mod env_policy;
 pub mod gemmad;
 pub mod interactive;
 pub mod key_rotation;
 pub mod local;
+pub mod mcp;
 pub mod models;
+pub mod ollama;
 pub mod output_store;
 pub mod provider;
+pub mod redact;
+pub mod sandbox;
 pub mod skills;
 pub mod theme;
 pub mod tools;
 pub mod usage;
+pub mod vertex;
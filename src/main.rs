@@ -1,22 +1,29 @@
 mod agent;
 mod agents;
+mod checkpoints;
 mod client;
 mod compact;
 mod daemon;
 mod display;
 mod display_sink;
+mod env_policy;
 mod gemmad;
 mod interactive;
 mod key_rotation;
 mod local;
+mod mcp;
 mod models;
+mod ollama;
 mod output_store;
 mod provider;
+mod redact;
+mod sandbox;
 mod skills;
 mod theme;
 mod tools;
 mod tui;
 mod usage;
+mod vertex;
 mod webapp;
 
 use crate::client::Client;
@@ -26,6 +33,7 @@ use crate::provider::{detect_provider, get_smart_default_model, supports_tools};
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use std::path::Path;
+use std::sync::Arc;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -78,7 +86,7 @@ struct Args {
     #[arg(long)]
     list_models: bool,
 
-    /// List the 4 built-in tools
+    /// List the 14 built-in tools
     #[arg(long)]
     list_tools: bool,
 
@@ -106,6 +114,37 @@ struct Args {
     #[arg(long)]
     debug: bool,
 
+    /// Keep one shell alive across Bash calls so cd, exports and activated environments persist
+    #[arg(long)]
+    persistent_shell: bool,
+
+    /// Let Bash and custom tool commands inherit this environment variable even
+    /// when denied, e.g. an API key a skill script needs (repeatable)
+    #[arg(long = "pass-env", value_name = "NAME")]
+    pass_env: Vec<String>,
+
+    /// What AskUser tells the model in runs with nobody to answer (single-shot
+    /// prompts and scheduled agents); by default, to proceed on its own judgement
+    #[arg(long, value_name = "TEXT")]
+    ask_user_reply: Option<String>,
+
+    /// MCP servers file (default: ~/.eunice/mcp.toml)
+    #[arg(long)]
+    mcp_config: Option<String>,
+
+    /// Undo a session's file changes: lists sessions with checkpoints, or a
+    /// SESSION's checkpoints; with --steps, restores the last N changes
+    #[arg(long, value_name = "SESSION", num_args = 0..=1, default_missing_value = "")]
+    rewind: Option<String>,
+
+    /// Number of changes --rewind undoes
+    #[arg(long, value_name = "N", requires = "rewind")]
+    steps: Option<usize>,
+
+    /// Gemini built-in tools to enable: code_execution, google_search, url_context (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    gemini_tools: Vec<String>,
+
     /// Download a local model (e.g., hf:gemma4:e4b)
     #[arg(long)]
     download: Option<String>,
@@ -183,6 +222,101 @@ fn resolve_prompt(args: &Args) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Start the MCP servers in `--mcp-config` or ~/.eunice/mcp.toml. None when
+/// no server is configured; a server that fails to start is reported and offers
+/// no tools.
+async fn start_mcp_servers(config: Option<&str>) -> Result<Option<Arc<mcp::McpServers>>> {
+    let config = mcp::McpConfig::load(config.map(Path::new))?;
+    let (servers, errors) = mcp::McpServers::start(&config).await;
+    for error in errors {
+        eprintln!("Warning: {}", error);
+    }
+    Ok((!servers.is_empty()).then(|| Arc::new(servers)))
+}
+
+/// Load the custom tools in ~/.eunice/tools and ./.eunice/tools, reporting
+/// files that fail to load
+fn load_custom_tools() -> tools::CustomTools {
+    let (custom, errors) = tools::CustomTools::load(None);
+    for error in errors {
+        eprintln!("Warning: {}", error);
+    }
+    custom
+}
+
+fn load_redactor(client: &Client) -> redact::Redactor {
+    let (redactor, errors) = redact::Redactor::load(client.api_keys());
+    for error in errors {
+        eprintln!("Warning: {}", error);
+    }
+    redactor
+}
+
+/// The environment tool subprocesses start with: ~/.eunice/env.toml plus
+/// `--pass-env`. An env.toml that fails to load is reported and the default
+/// (eunice's credentials removed) used instead.
+fn load_env_policy(pass: &[String]) -> Result<env_policy::EnvPolicy> {
+    for name in pass {
+        env_policy::validate_name(name)?;
+    }
+    let policy = env_policy::EnvConfig::load(&env_policy::config_path())
+        .and_then(env_policy::EnvPolicy::from_config)
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            env_policy::EnvPolicy::default()
+        });
+    Ok(policy.with_pass(pass.iter().cloned()))
+}
+
+/// `--rewind [SESSION] [--steps N]`
+fn run_rewind(session: &str, steps: Option<usize>) -> Result<()> {
+    if session.is_empty() {
+        let sessions = checkpoints::list_sessions(&checkpoints::root()?)?;
+        if sessions.is_empty() {
+            println!("No sessions have file checkpoints.");
+        }
+        for (id, count, last) in sessions {
+            let last = chrono::DateTime::from_timestamp(last, 0)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            println!("{}  {} change(s), last {}", id, count, last);
+        }
+        return Ok(());
+    }
+
+    let store = checkpoints::Checkpoints::for_session(session)?;
+    match steps {
+        Some(steps) => {
+            let restored = store.undo(steps)?;
+            if restored.is_empty() {
+                println!("Nothing to undo in session {}.", session);
+            } else {
+                println!("Restored:\n{}", checkpoints::describe(&restored));
+            }
+        }
+        None => {
+            let mut list = store.list()?;
+            if list.is_empty() {
+                return Err(anyhow!("No checkpoints for session '{}'", session));
+            }
+            list.reverse();
+            println!("{}", checkpoints::describe(&list));
+            println!("\nUndo the last N with: eunice --rewind {} --steps N", session);
+        }
+    }
+    Ok(())
+}
+
+/// After a session that changed files, say how to undo the changes
+fn print_rewind_hint(tool_registry: &tools::ToolRegistry) {
+    let Some(store) = tool_registry.checkpoints() else {
+        return;
+    };
+    if store.list().is_ok_and(|list| !list.is_empty()) {
+        eprintln!("File changes can be undone with: eunice --rewind {}", store.session_id());
+    }
+}
+
 /// Fetch the remote version from longrunningagents.com
 fn fetch_remote_version() -> Option<String> {
     let url = "https://longrunningagents.com/version.txt";
@@ -361,16 +495,39 @@ async fn main() -> Result<()> {
 
     // Handle --list-tools
     if args.list_tools {
-        let registry = tools::ToolRegistry::new();
+        // get_output only reads back truncated results, so it isn't listed
+        let registry = tools::ToolRegistry::new().without_tool(tools::GET_OUTPUT_TOOL_NAME);
         let tool_specs = registry.get_tools();
         println!("Built-in tools ({}):\n", tool_specs.len());
         for tool in tool_specs {
             println!("  {}", tool.function.name);
             println!("    {}\n", tool.function.description);
         }
+        let custom = load_custom_tools();
+        if !custom.is_empty() {
+            let tool_specs = custom.get_specs();
+            println!("Custom tools ({}):\n", tool_specs.len());
+            for tool in tool_specs {
+                println!("  {}", tool.function.name);
+                println!("    {}\n", tool.function.description);
+            }
+        }
+        if let Some(mcp) = start_mcp_servers(args.mcp_config.as_deref()).await? {
+            let tool_specs = mcp.get_tools();
+            println!("MCP tools ({}):\n", tool_specs.len());
+            for tool in tool_specs {
+                println!("  {}", tool.function.name);
+                println!("    {}\n", tool.function.description);
+            }
+        }
         return Ok(());
     }
 
+    // Handle --rewind
+    if let Some(ref session) = args.rewind {
+        return run_rewind(session, args.steps);
+    }
+
     // Handle --list-skills
     if args.list_skills {
         // Ensure skills are installed first
@@ -438,6 +595,10 @@ async fn main() -> Result<()> {
     if args.webapp && args.chat {
         return Err(anyhow!("--webapp and --chat cannot be used together"));
     }
+    if args.webapp && args.persistent_shell {
+        // The server's tool registry is shared by every session
+        return Err(anyhow!("--persistent-shell is not supported with --webapp"));
+    }
     if args.chat && !atty::is(atty::Stream::Stdin) {
         return Err(anyhow!("--chat requires an interactive terminal (TTY)"));
     }
@@ -484,7 +645,14 @@ async fn main() -> Result<()> {
     }
 
     // Detect provider and check tool support
-    let provider_info = detect_provider(&model)?;
+    let mut provider_info = detect_provider(&model)?;
+
+    // Gemini built-in tools for this run; they are only reachable on the native API
+    let gemini_tools =
+        models::GeminiBuiltinTool::parse_list(&args.gemini_tools).map_err(|e| anyhow!(e))?;
+    if !gemini_tools.is_empty() {
+        provider::require_native_gemini_api(&mut provider_info)?;
+    }
 
     // If local provider, start gemma4-server
     let mut _local_server: Option<std::process::Child> = if provider_info.provider == models::Provider::Local {
@@ -503,6 +671,7 @@ async fn main() -> Result<()> {
 
     // Create client
     let mut client = Client::new(&provider_info)?;
+    client.set_gemini_tools(gemini_tools);
     if args.debug {
         client.set_debug(true);
         eprintln!("[DEBUG] Debug mode enabled");
@@ -510,6 +679,10 @@ async fn main() -> Result<()> {
         eprintln!("[DEBUG] Base URL: {}", provider_info.base_url);
     }
 
+    // MCP servers live for the whole process and are shared by every registry
+    let mcp_servers = start_mcp_servers(args.mcp_config.as_deref()).await?;
+    let env_policy = load_env_policy(&args.pass_env)?;
+
     // Webapp mode
     if args.webapp {
         let webapp_config = models::WebappConfig {
@@ -521,13 +694,14 @@ async fn main() -> Result<()> {
             Some(ref file) => Some(agents::load_agents(Path::new(file))?),
             None => None,
         };
-        let result = webapp::run_server(
-            webapp_config,
-            client,
-            provider_info,
-            prompt.clone(),
-            agents_config,
-        ).await;
+        let options = webapp::ServerOptions {
+            system_prompt: prompt.clone(),
+            agents: agents_config,
+            mcp: mcp_servers,
+            env: env_policy,
+            ask_user_reply: args.ask_user_reply.clone(),
+        };
+        let result = webapp::run_server(webapp_config, client, provider_info, options).await;
         if let Some(ref mut child) = _local_server {
             let _ = child.kill();
             let _ = child.wait();
@@ -535,13 +709,38 @@ async fn main() -> Result<()> {
         return result;
     }
 
+    // One registry for the whole CLI/chat session, so a persistent shell
+    // lives as long as the session does
+    let mut tool_registry = tools::ToolRegistry::new()
+        .with_custom_tools(load_custom_tools())
+        .with_redactor(load_redactor(&client))
+        .with_env_policy(env_policy);
+    match checkpoints::Checkpoints::new_cli_session() {
+        Ok(store) => tool_registry = tool_registry.with_checkpoints(store),
+        Err(e) => eprintln!("Warning: file checkpoints disabled: {}", e),
+    }
+    if args.persistent_shell {
+        tool_registry = tool_registry.with_persistent_shell();
+    }
+    if let Some(mcp) = mcp_servers {
+        tool_registry = tool_registry.with_mcp(mcp);
+    }
+    // AskUser prompts in chat; single-shot runs have nobody to ask
+    if use_tui {
+        tool_registry = tool_registry.with_user(tui::terminal_user());
+    } else if let Some(reply) = args.ask_user_reply.clone() {
+        tool_registry = tool_registry.with_user(Arc::new(tools::Unattended::new(reply)));
+    }
+
     // TUI mode
     if use_tui {
         let result = tui::run_tui_mode(
             &client,
             &provider_info,
             prompt.as_deref(),
+            &tool_registry,
         ).await;
+        print_rewind_hint(&tool_registry);
         if let Some(ref mut child) = _local_server {
             let _ = child.kill();
             let _ = child.wait();
@@ -558,9 +757,6 @@ async fn main() -> Result<()> {
     // Create display sink
     let display = create_display_sink();
 
-    // Create tool registry
-    let tool_registry = tools::ToolRegistry::new();
-
     // Output store for truncating large tool outputs
     let mut output_store = output_store::OutputStore::new();
 
@@ -580,6 +776,7 @@ async fn main() -> Result<()> {
         Some(&mut output_store),
     )
     .await?;
+    print_rewind_hint(&tool_registry);
 
     // Kill local server if running
     if let Some(ref mut child) = _local_server {
@@ -607,6 +804,16 @@ mod tests {
         assert!(args.list_tools);
     }
 
+    #[test]
+    fn test_args_rewind() {
+        let args = Args::try_parse_from(["eunice", "--rewind"]).unwrap();
+        assert_eq!(args.rewind.as_deref(), Some(""));
+        let args = Args::try_parse_from(["eunice", "--rewind", "cli-1", "--steps", "2"]).unwrap();
+        assert_eq!(args.rewind.as_deref(), Some("cli-1"));
+        assert_eq!(args.steps, Some(2));
+        assert!(Args::try_parse_from(["eunice", "--steps", "2"]).is_err());
+    }
+
     #[test]
     fn test_args_chat() {
         let args = Args::try_parse_from(["eunice", "--chat"]).unwrap();
@@ -666,6 +873,21 @@ mod tests {
         assert!(!args.no_gemmad);
     }
 
+    #[test]
+    fn test_args_gemini_tools_comma_separated() {
+        let args =
+            Args::try_parse_from(["eunice", "--gemini-tools", "google_search,url_context", "hi"])
+                .unwrap();
+        assert_eq!(args.gemini_tools, vec!["google_search", "url_context"]);
+        assert_eq!(args.prompt_positional, Some("hi".to_string()));
+    }
+
+    #[test]
+    fn test_args_gemini_tools_default_empty() {
+        let args = Args::try_parse_from(["eunice", "hi"]).unwrap();
+        assert!(args.gemini_tools.is_empty());
+    }
+
     #[test]
     fn test_args_agents() {
         let args =
@@ -738,4 +960,12 @@ mod tests {
         assert_eq!(args.agents, Some("/tmp/agents.toml".to_string()));
         assert!(args.no_persist);
     }
+
+    #[test]
+    fn test_args_persistent_shell() {
+        let args = Args::try_parse_from(["eunice", "--persistent-shell", "hello"]).unwrap();
+        assert!(args.persistent_shell);
+        let args = Args::try_parse_from(["eunice", "hello"]).unwrap();
+        assert!(!args.persistent_shell);
+    }
 }
@@ -0,0 +1,1357 @@
+//! Model Context Protocol client.
+//!
+//! Servers are declared in `~/.eunice/mcp.toml` (or the file given with
+//! `--mcp-config`) and reached over stdio or streamable HTTP:
+//!
+//! ```toml
+//! [servers.tickets]
+//! command = "ticket-mcp"
+//! args = ["--db", "/var/lib/tickets.db"]
+//! env = { TICKETS_TOKEN = "..." }
+//!
+//! [servers.deploy]
+//! url = "https://deploy.internal/mcp"
+//! headers = { Authorization = "Bearer ..." }
+//! timeout_secs = 300
+//! ```
+//!
+//! Every server's tools are offered to the model as `mcp__<server>__<tool>`.
+//! A server that exits is restarted on the next call to one of its tools, and
+//! `notifications/tools/list_changed` refreshes its tool list mid-session.
+
+use crate::models::Tool;
+use crate::tools::make_tool;
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+/// Protocol revision requested in `initialize`
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Seconds a tool call may take unless the server sets `timeout_secs`
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Seconds allowed for `initialize` and each `tools/list` page
+const STARTUP_TIMEOUT_SECS: u64 = 30;
+
+/// Prefix of every MCP tool name the model sees
+const TOOL_PREFIX: &str = "mcp__";
+
+/// Longest tool name the chat APIs accept
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// stderr lines of a stdio server kept to explain why it exited
+const STDERR_TAIL_LINES: usize = 10;
+
+/// Contents of `~/.eunice/mcp.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct McpConfig {
+    #[serde(default)]
+    pub servers: BTreeMap<String, McpServerConfig>,
+}
+
+/// One `[servers.<name>]` table: either `command` (stdio) or `url` (HTTP)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct McpServerConfig {
+    /// Program that speaks MCP on stdin/stdout
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Added to the inherited environment
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// Streamable HTTP endpoint
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Sent with every HTTP request, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Seconds a tool call may take (default: 60)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Path of the MCP servers file
+pub fn config_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".eunice")
+        .join("mcp.toml")
+}
+
+impl McpConfig {
+    /// Load `path`, or `~/.eunice/mcp.toml` when None. A missing default file
+    /// means no servers; a missing explicit file is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) if !path.exists() => {
+                bail!("MCP config '{}' does not exist", path.display())
+            }
+            Some(path) => path.to_path_buf(),
+            None => config_path(),
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("failed to read '{}': {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| anyhow!("'{}': {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|e| anyhow!("{}", e))?;
+        for (name, server) in &config.servers {
+            let valid_name = !name.is_empty()
+                && !name.contains("__")
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid_name {
+                bail!(
+                    "server name '{}' must be letters, digits, '-' and '_' (no '__')",
+                    name
+                );
+            }
+            match (&server.command, &server.url) {
+                (Some(_), Some(_)) => bail!("server '{}': set command or url, not both", name),
+                (None, None) => bail!("server '{}': set command (stdio) or url (HTTP)", name),
+                (Some(_), None) if !server.headers.is_empty() => {
+                    bail!("server '{}': headers only apply to url servers", name)
+                }
+                (None, Some(url)) => {
+                    let parsed = Url::parse(url)
+                        .map_err(|e| anyhow!("server '{}': invalid url: {}", name, e))?;
+                    if !matches!(parsed.scheme(), "http" | "https") {
+                        bail!("server '{}': url must be http or https", name);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// The configured MCP servers and the tools they offer
+pub struct McpServers {
+    servers: Vec<Arc<McpServer>>,
+}
+
+impl McpServers {
+    /// Start every enabled server concurrently. A server that cannot start
+    /// offers no tools; its error is returned for the caller to report.
+    pub async fn start(config: &McpConfig) -> (Self, Vec<String>) {
+        let servers: Vec<Arc<McpServer>> = config
+            .servers
+            .iter()
+            .filter(|(_, server)| server.enabled)
+            .map(|(name, server)| {
+                Arc::new(McpServer {
+                    name: name.clone(),
+                    config: server.clone(),
+                    connection: tokio::sync::Mutex::new(None),
+                    tools: std::sync::RwLock::new(Vec::new()),
+                })
+            })
+            .collect();
+
+        let results =
+            futures::future::join_all(servers.iter().map(|server| server.connection())).await;
+        let errors = servers
+            .iter()
+            .zip(results)
+            .filter_map(|(server, result)| {
+                result
+                    .err()
+                    .map(|e| format!("MCP server '{}': {:#}", server.name, e))
+            })
+            .collect();
+        (Self { servers }, errors)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// Specs of every tool the servers currently offer
+    pub fn get_tools(&self) -> Vec<Tool> {
+        self.servers
+            .iter()
+            .flat_map(|server| {
+                server
+                    .tools
+                    .read()
+                    .map(|tools| tools.iter().map(McpTool::spec).collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// Call the tool the model knows as `name`
+    pub async fn execute(&self, name: &str, args: Value) -> Result<String> {
+        let (server, tool) = self
+            .find(name)
+            .ok_or_else(|| anyhow!("Unknown tool: {}", name))?;
+        server.call(&tool, args).await
+    }
+
+    /// The server offering `name`, and the tool's name on that server
+    fn find(&self, name: &str) -> Option<(Arc<McpServer>, String)> {
+        self.servers.iter().find_map(|server| {
+            let tools = server.tools.read().ok()?;
+            tools
+                .iter()
+                .find(|tool| tool.exposed == name)
+                .map(|tool| (server.clone(), tool.name.clone()))
+        })
+    }
+}
+
+/// A tool as listed by a server
+#[derive(Debug, Clone)]
+struct McpTool {
+    /// Name on the server
+    name: String,
+    /// `mcp__<server>__<name>`, as the model sees it
+    exposed: String,
+    description: String,
+    input_schema: Value,
+}
+
+impl McpTool {
+    fn spec(&self) -> Tool {
+        make_tool(&self.exposed, &self.description, self.input_schema.clone())
+    }
+}
+
+/// `tools/list` result page
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolsPage {
+    #[serde(default)]
+    tools: Vec<ListedTool>,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedTool {
+    name: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    input_schema: Option<Value>,
+}
+
+/// `tools/call` result
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CallResult {
+    #[serde(default)]
+    content: Vec<Value>,
+    #[serde(default)]
+    structured_content: Option<Value>,
+    #[serde(default)]
+    is_error: bool,
+}
+
+struct McpServer {
+    name: String,
+    config: McpServerConfig,
+    /// None until started, and after a failed restart
+    connection: tokio::sync::Mutex<Option<Arc<Connection>>>,
+    tools: std::sync::RwLock<Vec<McpTool>>,
+}
+
+impl McpServer {
+    /// The live connection, starting the server if it is not running. The
+    /// flag says whether a connection that died was replaced.
+    async fn connection(self: &Arc<Self>) -> Result<(Arc<Connection>, bool)> {
+        let mut slot = self.connection.lock().await;
+        if let Some(conn) = slot.as_ref().filter(|conn| conn.is_alive()) {
+            return Ok((conn.clone(), false));
+        }
+        let restarted = slot.take().is_some();
+
+        let (conn, notifications) = Connection::open(&self.config).await?;
+        self.refresh_tools(&conn).await?;
+        self.watch_notifications(&conn, notifications);
+        *slot = Some(conn.clone());
+        Ok((conn, restarted))
+    }
+
+    /// Re-read the server's tool list
+    async fn refresh_tools(&self, conn: &Connection) -> Result<()> {
+        let mut listed = Vec::new();
+        let mut cursor: Option<String> = None;
+        // Bounded so a server that keeps handing out cursors cannot stall us
+        for _ in 0..100 {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = conn
+                .request("tools/list", params, startup_timeout())
+                .await
+                .context("tools/list failed")?;
+            let page: ToolsPage =
+                serde_json::from_value(result).context("invalid tools/list result")?;
+            listed.extend(page.tools);
+            match page.next_cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        let mut tools: Vec<McpTool> = Vec::new();
+        for tool in listed {
+            let base = exposed_name(&self.name, &tool.name);
+            let mut exposed = base.clone();
+            let mut salt = 0;
+            while tools.iter().any(|t| t.exposed == exposed) {
+                exposed = disambiguated(&base, &tool.name, salt);
+                salt += 1;
+            }
+            let description = tool
+                .description
+                .or(tool.title)
+                .unwrap_or_else(|| format!("{} (from MCP server '{}')", tool.name, self.name));
+            tools.push(McpTool {
+                name: tool.name,
+                exposed,
+                description,
+                input_schema: object_schema(tool.input_schema),
+            });
+        }
+        if let Ok(mut current) = self.tools.write() {
+            *current = tools;
+        }
+        Ok(())
+    }
+
+    /// Refresh the tool list whenever the server says it changed. Ends with
+    /// the connection.
+    fn watch_notifications(
+        self: &Arc<Self>,
+        conn: &Arc<Connection>,
+        mut notifications: mpsc::UnboundedReceiver<String>,
+    ) {
+        let server = Arc::downgrade(self);
+        let conn = Arc::downgrade(conn);
+        tokio::spawn(async move {
+            while let Some(method) = notifications.recv().await {
+                if method != "notifications/tools/list_changed" {
+                    continue;
+                }
+                let (Some(server), Some(conn)) = (server.upgrade(), conn.upgrade()) else {
+                    break;
+                };
+                // A failed refresh keeps the old list; calls to vanished
+                // tools then fail on the server side
+                let _ = server.refresh_tools(&conn).await;
+            }
+        });
+    }
+
+    async fn call(self: &Arc<Self>, tool: &str, args: Value) -> Result<String> {
+        let (conn, restarted) = self
+            .connection()
+            .await
+            .map_err(|e| anyhow!("MCP server '{}' is not running: {:#}", self.name, e))?;
+
+        let arguments = if args.is_null() { json!({}) } else { args };
+        let timeout = Duration::from_secs(self.config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let result = conn
+            .request(
+                "tools/call",
+                json!({ "name": tool, "arguments": arguments }),
+                timeout,
+            )
+            .await
+            .map_err(|e| {
+                if conn.is_alive() {
+                    anyhow!("MCP server '{}': {}", self.name, e)
+                } else {
+                    anyhow!(
+                        "MCP server '{}': {}; it will be restarted on the next call",
+                        self.name,
+                        e
+                    )
+                }
+            })?;
+
+        let result: CallResult = serde_json::from_value(result)
+            .with_context(|| format!("MCP server '{}': invalid tools/call result", self.name))?;
+        let text = format_content(&result);
+        if result.is_error {
+            bail!("{}", text);
+        }
+        Ok(if restarted {
+            format!("[MCP server '{}' was restarted]\n{}", self.name, text)
+        } else {
+            text
+        })
+    }
+}
+
+/// `mcp__<server>__<tool>`, with characters the chat APIs reject replaced
+fn exposed_name(server: &str, tool: &str) -> String {
+    let tool: String = tool
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut name = format!("{}{}__{}", TOOL_PREFIX, server, tool);
+    name.truncate(MAX_TOOL_NAME_LEN);
+    name
+}
+
+/// `exposed` for a tool whose name another tool's already became (after
+/// replacing characters or truncating): the end gives way to a hash of `tool`
+fn disambiguated(exposed: &str, tool: &str, salt: u32) -> String {
+    // FNV-1a, so a tool keeps its name from one run to the next
+    let hash = tool
+        .bytes()
+        .chain(salt.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let suffix = format!("_{:08x}", hash as u32);
+    let keep = exposed.len().min(MAX_TOOL_NAME_LEN - suffix.len());
+    format!("{}{}", &exposed[..keep], suffix)
+}
+
+/// A tool's input schema as the chat APIs want it: an object with properties
+fn object_schema(schema: Option<Value>) -> Value {
+    let mut schema = match schema {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    schema
+        .entry("type")
+        .or_insert_with(|| Value::String("object".to_string()));
+    schema.entry("properties").or_insert_with(|| json!({}));
+    Value::Object(schema)
+}
+
+/// A tool result's content blocks as text
+fn format_content(result: &CallResult) -> String {
+    let blocks: Vec<String> = result
+        .content
+        .iter()
+        .map(|block| match block["type"].as_str().unwrap_or("") {
+            "text" => block["text"].as_str().unwrap_or("").to_string(),
+            kind @ ("image" | "audio") => format!(
+                "[{}: {}, {} bytes base64]",
+                kind,
+                block["mimeType"].as_str().unwrap_or("unknown type"),
+                block["data"].as_str().map_or(0, str::len)
+            ),
+            "resource" => match block["resource"]["text"].as_str() {
+                Some(text) => text.to_string(),
+                None => format!(
+                    "[resource: {}]",
+                    block["resource"]["uri"].as_str().unwrap_or("")
+                ),
+            },
+            "resource_link" => format!("[resource: {}]", block["uri"].as_str().unwrap_or("")),
+            other => format!("[unsupported {} content]", other),
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        if let Some(structured) = &result.structured_content {
+            return serde_json::to_string_pretty(structured).unwrap_or_default();
+        }
+    }
+    blocks.join("\n")
+}
+
+fn startup_timeout() -> Duration {
+    Duration::from_secs(STARTUP_TIMEOUT_SECS)
+}
+
+/// JSON-RPC bookkeeping shared by both transports: requests awaiting a
+/// response, and where server notifications go
+struct Peer {
+    pending: std::sync::Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>,
+    next_id: AtomicU64,
+    notifications: mpsc::UnboundedSender<String>,
+}
+
+impl Peer {
+    fn new(notifications: mpsc::UnboundedSender<String>) -> Self {
+        Self {
+            pending: std::sync::Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            notifications,
+        }
+    }
+
+    /// Handle one message from the server. Returns the reply owed when the
+    /// server sent a request.
+    fn route(&self, msg: Value) -> Option<Value> {
+        let method = msg.get("method").and_then(Value::as_str);
+        match (method, msg.get("id")) {
+            (Some(method), Some(id)) => Some(match method {
+                "ping" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+                _ => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("method not supported: {}", method) }
+                }),
+            }),
+            (Some(method), None) => {
+                let _ = self.notifications.send(method.to_string());
+                None
+            }
+            (None, Some(id)) => {
+                let waiter = id
+                    .as_u64()
+                    .and_then(|id| self.pending.lock().ok()?.remove(&id));
+                if let Some(waiter) = waiter {
+                    let result = match msg.get("error") {
+                        Some(error) => Err(anyhow!(
+                            "{} (error {})",
+                            error["message"].as_str().unwrap_or("request failed"),
+                            error["code"]
+                        )),
+                        None => Ok(msg.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    let _ = waiter.send(result);
+                }
+                None
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Fail every outstanding request (the server went away)
+    fn fail_all(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+}
+
+/// An initialized session with one server
+struct Connection {
+    transport: Transport,
+    peer: Arc<Peer>,
+}
+
+enum Transport {
+    Stdio(Arc<StdioTransport>),
+    Http(Arc<HttpTransport>),
+}
+
+impl Connection {
+    /// Start the server (or open the HTTP session) and run the handshake
+    async fn open(
+        config: &McpServerConfig,
+    ) -> Result<(Arc<Self>, mpsc::UnboundedReceiver<String>)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let peer = Arc::new(Peer::new(tx));
+        let transport = match (&config.command, &config.url) {
+            (Some(command), _) => Transport::Stdio(StdioTransport::spawn(command, config, &peer)?),
+            (None, Some(url)) => Transport::Http(HttpTransport::new(url, &config.headers, &peer)?),
+            (None, None) => bail!("no command or url"),
+        };
+        let conn = Arc::new(Self { transport, peer });
+
+        let init = conn
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "eunice", "version": env!("CARGO_PKG_VERSION") }
+                }),
+                startup_timeout(),
+            )
+            .await
+            .context("initialize failed")?;
+        if let Transport::Http(http) = &conn.transport {
+            let version = init["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION);
+            http.set_protocol_version(version);
+        }
+        conn.notify("notifications/initialized", json!({})).await?;
+        if let Transport::Http(http) = &conn.transport {
+            http.listen();
+        }
+        Ok((conn, rx))
+    }
+
+    fn is_alive(&self) -> bool {
+        match &self.transport {
+            Transport::Stdio(stdio) => stdio.alive.load(Ordering::SeqCst),
+            Transport::Http(http) => http.alive.load(Ordering::SeqCst),
+        }
+    }
+
+    async fn send(&self, msg: &Value) -> Result<()> {
+        match &self.transport {
+            Transport::Stdio(stdio) => stdio.send(msg).await,
+            Transport::Http(http) => http.send(msg).await,
+        }
+    }
+
+    /// Why requests to this server fail: it exited, plus its last words
+    fn gone(&self) -> anyhow::Error {
+        match &self.transport {
+            Transport::Stdio(stdio) => stdio.exit_error(),
+            Transport::Http(_) => anyhow!("the connection was closed"),
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value, timeout: Duration) -> Result<Value> {
+        let id = self.peer.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.peer.pending.lock() {
+            pending.insert(id, tx);
+        }
+        let msg = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+        let exchange = async {
+            self.send(&msg).await?;
+            rx.await.map_err(|_| self.gone())?
+        };
+        match tokio::time::timeout(timeout, exchange).await {
+            Ok(result) => {
+                self.peer.forget(id);
+                result
+            }
+            Err(_) => {
+                self.peer.forget(id);
+                let _ = self
+                    .notify(
+                        "notifications/cancelled",
+                        json!({ "requestId": id, "reason": "timed out" }),
+                    )
+                    .await;
+                Err(anyhow!("{} timed out after {}s", method, timeout.as_secs()))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+}
+
+/// A server running as a child process, one JSON message per line
+struct StdioTransport {
+    stdin: tokio::sync::Mutex<tokio::process::ChildStdin>,
+    alive: AtomicBool,
+    stderr_tail: Arc<std::sync::Mutex<VecDeque<String>>>,
+    /// Killed when the connection is dropped
+    _child: tokio::process::Child,
+}
+
+impl StdioTransport {
+    fn spawn(command: &str, config: &McpServerConfig, peer: &Arc<Peer>) -> Result<Arc<Self>> {
+        let mut cmd = tokio::process::Command::new(command);
+        cmd.args(&config.args)
+            .envs(&config.env)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &config.cwd {
+            cmd.current_dir(cwd);
+        }
+        // Its own process group, so Ctrl+C in the terminal cancels the agent
+        // without killing the server
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to run '{}'", command))?;
+        let stdin = child.stdin.take().context("no stdin")?;
+        let stdout = child.stdout.take().context("no stdout")?;
+        let stderr = child.stderr.take().context("no stderr")?;
+
+        let stderr_tail = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+        let transport = Arc::new(Self {
+            stdin: tokio::sync::Mutex::new(stdin),
+            alive: AtomicBool::new(true),
+            stderr_tail: stderr_tail.clone(),
+            _child: child,
+        });
+
+        let stderr_reader = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(mut tail) = stderr_tail.lock() else {
+                    break;
+                };
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        });
+
+        let reader = Arc::downgrade(&transport);
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(msg) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                if let Some(reply) = peer.route(msg) {
+                    if let Some(transport) = reader.upgrade() {
+                        let _ = transport.send(&reply).await;
+                    }
+                }
+            }
+            // Let the dying server's last stderr lines land before callers
+            // are told it exited
+            let _ = tokio::time::timeout(Duration::from_secs(1), stderr_reader).await;
+            if let Some(transport) = reader.upgrade() {
+                transport.alive.store(false, Ordering::SeqCst);
+            }
+            peer.fail_all();
+        });
+
+        Ok(transport)
+    }
+
+    async fn send(&self, msg: &Value) -> Result<()> {
+        if !self.alive.load(Ordering::SeqCst) {
+            return Err(self.exit_error());
+        }
+        let mut line = serde_json::to_vec(msg)?;
+        line.push(b'\n');
+        let mut stdin = self.stdin.lock().await;
+        let written = async {
+            stdin.write_all(&line).await?;
+            stdin.flush().await
+        };
+        written.await.map_err(|_| self.exit_error())
+    }
+
+    fn exit_error(&self) -> anyhow::Error {
+        let tail = self
+            .stderr_tail
+            .lock()
+            .map(|tail| tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default();
+        if tail.trim().is_empty() {
+            anyhow!("the server exited")
+        } else {
+            anyhow!("the server exited; its last output was:\n{}", tail)
+        }
+    }
+}
+
+/// A server reached over streamable HTTP: every message is a POST, answered
+/// with JSON or an SSE stream, and server notifications arrive on a GET stream
+struct HttpTransport {
+    client: reqwest::Client,
+    url: Url,
+    headers: HeaderMap,
+    session_id: std::sync::Mutex<Option<String>>,
+    protocol_version: std::sync::Mutex<Option<String>>,
+    alive: AtomicBool,
+    peer: Arc<Peer>,
+    listener: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl HttpTransport {
+    fn new(url: &str, headers: &BTreeMap<String, String>, peer: &Arc<Peer>) -> Result<Arc<Self>> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("invalid header name '{}'", name))?,
+                HeaderValue::from_str(value)
+                    .with_context(|| format!("invalid value for header '{}'", name))?,
+            );
+        }
+        Ok(Arc::new(Self {
+            client: reqwest::Client::new(),
+            url: Url::parse(url)?,
+            headers: header_map,
+            session_id: std::sync::Mutex::new(None),
+            protocol_version: std::sync::Mutex::new(None),
+            alive: AtomicBool::new(true),
+            peer: peer.clone(),
+            listener: std::sync::Mutex::new(None),
+        }))
+    }
+
+    fn set_protocol_version(&self, version: &str) {
+        if let Ok(mut current) = self.protocol_version.lock() {
+            *current = Some(version.to_string());
+        }
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let mut builder = builder.headers(self.headers.clone());
+        if let Some(id) = self.session_id.lock().ok().and_then(|id| id.clone()) {
+            builder = builder.header("Mcp-Session-Id", id);
+        }
+        if let Some(version) = self.protocol_version.lock().ok().and_then(|v| v.clone()) {
+            builder = builder.header("MCP-Protocol-Version", version);
+        }
+        builder
+    }
+
+    async fn send(self: &Arc<Self>, msg: &Value) -> Result<()> {
+        let had_session = self
+            .session_id
+            .lock()
+            .map(|id| id.is_some())
+            .unwrap_or(false);
+        let response = self
+            .request(self.client.post(self.url.clone()))
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(msg)
+            .send()
+            .await
+            .with_context(|| format!("POST {} failed", self.url))?;
+
+        if let Some(id) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            if let Ok(mut session) = self.session_id.lock() {
+                *session = Some(id.to_string());
+            }
+        }
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && had_session {
+            self.alive.store(false, Ordering::SeqCst);
+            bail!("the server ended the session");
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let body: String = body.chars().take(500).collect();
+            bail!("HTTP {}: {}", status, body.trim());
+        }
+        if status == reqwest::StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let is_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if is_stream {
+            // The response (and anything the server sends first) arrives on
+            // the stream; the caller waits for it
+            tokio::spawn(read_events(
+                response,
+                self.peer.clone(),
+                Arc::downgrade(self),
+            ));
+        } else {
+            let body = response.bytes().await?;
+            if !body.is_empty() {
+                let msg: Value = serde_json::from_slice(&body).context("invalid JSON response")?;
+                self.dispatch(msg);
+            }
+        }
+        Ok(())
+    }
+
+    /// Route a server message, POSTing any reply it owes
+    fn dispatch(self: &Arc<Self>, msg: Value) {
+        if let Some(reply) = self.peer.route(msg) {
+            let transport = self.clone();
+            tokio::spawn(async move {
+                let _ = transport.send(&reply).await;
+            });
+        }
+    }
+
+    /// Open the GET stream servers use for notifications (list changes).
+    /// Servers without one answer 405, which is fine.
+    fn listen(self: &Arc<Self>) {
+        let transport = Arc::downgrade(self);
+        let request = self
+            .request(self.client.get(self.url.clone()))
+            .header(ACCEPT, "text/event-stream");
+        let peer = self.peer.clone();
+        let handle = tokio::spawn(async move {
+            let Ok(response) = request.send().await else {
+                return;
+            };
+            if response.status().is_success() {
+                read_events(response, peer, transport).await;
+            }
+        });
+        if let Ok(mut listener) = self.listener.lock() {
+            *listener = Some(handle);
+        }
+    }
+}
+
+impl Drop for HttpTransport {
+    fn drop(&mut self) {
+        if let Ok(mut listener) = self.listener.lock() {
+            if let Some(handle) = listener.take() {
+                handle.abort();
+            }
+        }
+    }
+}
+
+/// Route every message in an SSE response body
+async fn read_events(
+    mut response: reqwest::Response,
+    peer: Arc<Peer>,
+    transport: Weak<HttpTransport>,
+) {
+    // Bytes, so a character split across chunks is decoded whole
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Ok(Some(chunk)) = response.chunk().await {
+        buffer.extend_from_slice(&chunk);
+        while let Some(event) = take_event(&mut buffer) {
+            let Ok(event) = String::from_utf8(event) else {
+                continue;
+            };
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            let Ok(msg) = serde_json::from_str::<Value>(&data.join("\n")) else {
+                continue;
+            };
+            match transport.upgrade() {
+                Some(transport) => transport.dispatch(msg),
+                None => {
+                    peer.route(msg);
+                }
+            }
+        }
+    }
+}
+
+/// The first complete SSE event in `buffer`, through the blank line that
+/// ends it, removed from the buffer
+fn take_event(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let end = (0..buffer.len()).find_map(|i| {
+        let rest = &buffer[i..];
+        if rest.starts_with(b"\n\n") {
+            Some(i + 2)
+        } else if rest.starts_with(b"\r\n\r\n") {
+            Some(i + 4)
+        } else {
+            None
+        }
+    })?;
+    Some(buffer.drain(..end).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stdio MCP server in sh. `grow` announces a new tool, `crash` exits,
+    /// `slow` never answers.
+    const FAKE_SERVER: &str = r#"
+grown=0
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{"listChanged":true}},"serverInfo":{"name":"fake","version":"1"}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      extra=''
+      [ "$grown" = 1 ] && extra=',{"name":"added"}'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo text","inputSchema":{"type":"object","properties":{"text":{"type":"string"}}}},{"name":"fail"},{"name":"crash"},{"name":"slow"},{"name":"grow"},{"name":"get.status"}%s]}}\n' "$id" "$extra" ;;
+    *'"name":"echo"'*)
+      text=$(printf '%s' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"echo: %s"}]}}\n' "$id" "$text" ;;
+    *'"name":"fail"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"isError":true,"content":[{"type":"text","text":"no such ticket"}]}}\n' "$id" ;;
+    *'"name":"crash"'*)
+      echo "fatal: out of cheese" >&2
+      exit 1 ;;
+    *'"name":"grow"'*)
+      grown=1
+      printf '{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"grown"}]}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    async fn start_fake(dir: &tempfile::TempDir, timeout_secs: Option<u64>) -> McpServers {
+        let script = dir.path().join("server.sh");
+        std::fs::write(&script, FAKE_SERVER).unwrap();
+        let mut config = McpConfig::default();
+        config.servers.insert(
+            "fake".to_string(),
+            McpServerConfig {
+                command: Some("sh".to_string()),
+                args: vec![script.display().to_string()],
+                env: BTreeMap::new(),
+                cwd: None,
+                url: None,
+                headers: BTreeMap::new(),
+                timeout_secs,
+                enabled: true,
+            },
+        );
+        let (servers, errors) = McpServers::start(&config).await;
+        assert!(errors.is_empty(), "{:?}", errors);
+        servers
+    }
+
+    fn tool_names(servers: &McpServers) -> Vec<String> {
+        servers
+            .get_tools()
+            .into_iter()
+            .map(|t| t.function.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = McpConfig::parse(
+            "[servers.tickets]\ncommand = \"ticket-mcp\"\nargs = [\"--db\", \"x\"]\n\n\
+             [servers.deploy]\nurl = \"https://deploy.example/mcp\"\n\
+             headers = { Authorization = \"Bearer t\" }\ntimeout_secs = 300\nenabled = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.servers.len(), 2);
+        assert!(!config.servers["deploy"].enabled);
+        assert_eq!(config.servers["deploy"].timeout_secs, Some(300));
+
+        for (text, expected) in [
+            ("[servers.a]\n", "set command (stdio) or url"),
+            (
+                "[servers.a]\ncommand = \"x\"\nurl = \"http://h\"\n",
+                "not both",
+            ),
+            ("[servers.a]\nurl = \"ftp://h\"\n", "http or https"),
+            ("[servers.\"a b\"]\ncommand = \"x\"\n", "must be letters"),
+            ("[servers.a__b]\ncommand = \"x\"\n", "must be letters"),
+            (
+                "[servers.a]\ncommand = \"x\"\nheaders = { A = \"b\" }\n",
+                "only apply to url",
+            ),
+            ("[servers.a]\ncommand = \"x\"\ntypo = 1\n", "unknown field"),
+        ] {
+            let err = McpConfig::parse(text).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn test_exposed_names_and_schemas() {
+        assert_eq!(exposed_name("fake", "get.status"), "mcp__fake__get_status");
+        assert_eq!(exposed_name("s", &"x".repeat(100)).len(), MAX_TOOL_NAME_LEN);
+
+        let taken = exposed_name("fake", "get_status");
+        let other = disambiguated(&taken, "get.status", 0);
+        assert!(other.starts_with("mcp__fake__get_status_"), "{}", other);
+        assert_ne!(other, disambiguated(&taken, "get.status", 1));
+        let long = exposed_name("s", &"x".repeat(100));
+        assert_eq!(
+            disambiguated(&long, &"x".repeat(101), 0).len(),
+            MAX_TOOL_NAME_LEN
+        );
+        assert_eq!(
+            object_schema(None),
+            json!({ "type": "object", "properties": {} })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stdio_server_tools_and_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let servers = start_fake(&dir, None).await;
+
+        let names = tool_names(&servers);
+        assert!(
+            names.contains(&"mcp__fake__echo".to_string()),
+            "{:?}",
+            names
+        );
+        assert!(names.contains(&"mcp__fake__get_status".to_string()));
+        let echo = servers
+            .get_tools()
+            .into_iter()
+            .find(|t| t.function.name == "mcp__fake__echo")
+            .unwrap();
+        assert_eq!(echo.function.description, "Echo text");
+        assert!(servers.has_tool("mcp__fake__echo"));
+        assert!(!servers.has_tool("echo"));
+
+        // Routed through the tool registry like any built-in
+        let servers = Arc::new(servers);
+        let registry = crate::tools::ToolRegistry::new().with_mcp(servers.clone());
+        assert!(registry.has_tool("mcp__fake__echo"));
+        let out = registry
+            .execute("mcp__fake__echo", json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(out, "echo: hi");
+
+        let err = servers
+            .execute("mcp__fake__fail", Value::Null)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "no such ticket");
+
+        // Tool list changes are picked up without a restart
+        assert_eq!(
+            servers.execute("mcp__fake__grow", json!({})).await.unwrap(),
+            "grown"
+        );
+        for _ in 0..100 {
+            if servers.has_tool("mcp__fake__added") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(servers.has_tool("mcp__fake__added"));
+    }
+
+    #[tokio::test]
+    async fn test_crashed_server_is_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let servers = start_fake(&dir, None).await;
+
+        let err = servers
+            .execute("mcp__fake__crash", json!({}))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("out of cheese"), "{}", err);
+        assert!(err.contains("restarted on the next call"), "{}", err);
+
+        let out = servers
+            .execute("mcp__fake__echo", json!({"text": "back"}))
+            .await
+            .unwrap();
+        assert_eq!(out, "[MCP server 'fake' was restarted]\necho: back");
+        let out = servers
+            .execute("mcp__fake__echo", json!({"text": "again"}))
+            .await
+            .unwrap();
+        assert_eq!(out, "echo: again");
+    }
+
+    #[tokio::test]
+    async fn test_call_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let servers = start_fake(&dir, Some(1)).await;
+
+        let err = servers
+            .execute("mcp__fake__slow", json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out after 1s"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_is_reported() {
+        let mut config = McpConfig::default();
+        config.servers.insert(
+            "missing".to_string(),
+            McpServerConfig {
+                command: Some("/nonexistent/mcp-server".to_string()),
+                args: Vec::new(),
+                env: BTreeMap::new(),
+                cwd: None,
+                url: None,
+                headers: BTreeMap::new(),
+                timeout_secs: None,
+                enabled: true,
+            },
+        );
+        let (servers, errors) = McpServers::start(&config).await;
+        assert!(servers.get_tools().is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("MCP server 'missing'"),
+            "{}",
+            errors[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_session_and_sse() {
+        use axum::{
+            http::{HeaderMap as AxumHeaders, StatusCode},
+            response::{IntoResponse, Response},
+            routing::post,
+            Json, Router,
+        };
+
+        async fn handle(headers: AxumHeaders, Json(msg): Json<Value>) -> Response {
+            let id = msg["id"].clone();
+            let method = msg["method"].as_str().unwrap_or("");
+            if method == "initialize" {
+                let body = json!({"jsonrpc": "2.0", "id": id, "result": {
+                    "protocolVersion": "2025-06-18", "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "remote", "version": "1"}}});
+                return ([("mcp-session-id", "s1")], Json(body)).into_response();
+            }
+            if headers.get("mcp-session-id").and_then(|v| v.to_str().ok()) != Some("s1")
+                || headers.get("authorization").and_then(|v| v.to_str().ok()) != Some("Bearer t")
+            {
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+            match method {
+                "tools/list" => Json(json!({"jsonrpc": "2.0", "id": id, "result": {
+                    "tools": [{"name": "lookup", "inputSchema": {"type": "object"}}]}}))
+                .into_response(),
+                "tools/call" => {
+                    let result = json!({"jsonrpc": "2.0", "id": id, "result": {
+                        "content": [{"type": "text", "text": format!("found {}", msg["params"]["arguments"]["key"])}]}});
+                    let body = format!(
+                        "event: message\ndata: {}\n\ndata: {}\n\n",
+                        json!({"jsonrpc": "2.0", "method": "notifications/progress"}),
+                        result
+                    );
+                    ([("content-type", "text/event-stream")], body).into_response()
+                }
+                _ => StatusCode::ACCEPTED.into_response(),
+            }
+        }
+
+        let app = Router::new().route("/mcp", post(handle));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = McpConfig::parse(&format!(
+            "[servers.remote]\nurl = \"http://{}/mcp\"\nheaders = {{ Authorization = \"Bearer t\" }}\n",
+            addr
+        ))
+        .unwrap();
+        let (servers, errors) = McpServers::start(&config).await;
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(tool_names(&servers), ["mcp__remote__lookup"]);
+
+        let out = servers
+            .execute("mcp__remote__lookup", json!({"key": 7}))
+            .await
+            .unwrap();
+        assert_eq!(out, "found 7");
+    }
+
+    #[tokio::test]
+    async fn test_http_sse_character_split_across_chunks() {
+        use axum::{
+            body::Body,
+            response::{IntoResponse, Response},
+            routing::post,
+            Json, Router,
+        };
+
+        async fn handle(Json(msg): Json<Value>) -> Response {
+            let id = msg["id"].clone();
+            match msg["method"].as_str().unwrap_or("") {
+                "initialize" => Json(json!({"jsonrpc": "2.0", "id": id, "result": {
+                    "protocolVersion": "2025-06-18", "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "remote", "version": "1"}}}))
+                .into_response(),
+                "tools/list" => Json(json!({"jsonrpc": "2.0", "id": id, "result": {
+                    "tools": [{"name": "greet", "inputSchema": {"type": "object"}}]}}))
+                .into_response(),
+                "tools/call" => {
+                    let result = json!({"jsonrpc": "2.0", "id": id, "result": {
+                        "content": [{"type": "text", "text": "grüße"}]}});
+                    let body = format!("data: {}\r\n\r\n", result).into_bytes();
+                    // Cut inside the two bytes of 'ü'
+                    let cut = body.windows(2).position(|w| w == "ü".as_bytes()).unwrap() + 1;
+                    let (first, second) = (body[..cut].to_vec(), body[cut..].to_vec());
+                    let chunks = futures::stream::unfold(0, move |sent| {
+                        let (first, second) = (first.clone(), second.clone());
+                        async move {
+                            let chunk = match sent {
+                                0 => first,
+                                1 => {
+                                    // Separate reads on the client side
+                                    tokio::time::sleep(Duration::from_millis(50)).await;
+                                    second
+                                }
+                                _ => return None,
+                            };
+                            Some((Ok::<_, std::io::Error>(chunk), sent + 1))
+                        }
+                    });
+                    (
+                        [("content-type", "text/event-stream")],
+                        Body::from_stream(chunks),
+                    )
+                        .into_response()
+                }
+                _ => axum::http::StatusCode::ACCEPTED.into_response(),
+            }
+        }
+
+        let app = Router::new().route("/mcp", post(handle));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = McpConfig::parse(&format!(
+            "[servers.remote]\nurl = \"http://{}/mcp\"\n",
+            addr
+        ))
+        .unwrap();
+        let (servers, errors) = McpServers::start(&config).await;
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let out = servers
+            .execute("mcp__remote__greet", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(out, "grüße");
+    }
+}
@@ -10,6 +10,7 @@ pub enum Provider {
     AzureOpenAI,
     Local,
     Gemmad,
+    VertexAI,
 }
 
 impl std::fmt::Display for Provider {
@@ -22,6 +23,7 @@ impl std::fmt::Display for Provider {
             Provider::AzureOpenAI => write!(f, "Azure OpenAI"),
             Provider::Local => write!(f, "Local"),
             Provider::Gemmad => write!(f, "Gemmad"),
+            Provider::VertexAI => write!(f, "Vertex AI"),
         }
     }
 }
@@ -36,6 +38,7 @@ impl Provider {
             Provider::AzureOpenAI => "☁️",
             Provider::Local => "💻",
             Provider::Gemmad => "💻",
+            Provider::VertexAI => "💎",
         }
     }
 }
@@ -45,6 +48,7 @@ impl Provider {
 pub struct ProviderInfo {
     pub provider: Provider,
     pub base_url: String,
+    /// API key or bearer token; for Vertex AI, the service-account key file path
     pub api_key: String,
     pub resolved_model: String,
     pub use_native_gemini_api: bool,
@@ -74,10 +78,30 @@ pub enum Message {
 /// A tool call made by the assistant
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
+    /// Unique within the conversation; tool results refer back to it
     pub id: String,
     #[serde(rename = "type")]
     pub call_type: String,
     pub function: FunctionCall,
+    /// Provider state that must be sent back with the call (never part of the id).
+    /// Stripped before requests to OpenAI-compatible APIs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_metadata: Option<ToolCallMetadata>,
+}
+
+impl ToolCall {
+    /// Mint an id for providers that don't assign their own
+    pub fn new_id() -> String {
+        format!("call_{}", uuid::Uuid::new_v4().simple())
+    }
+}
+
+/// Provider-specific data attached to a tool call
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallMetadata {
+    /// Gemini 3 thought signature, replayed on the functionCall part
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought_signature: Option<String>,
 }
 
 /// Function call details
@@ -141,6 +165,96 @@ pub struct UsageStats {
 #[derive(Debug, Deserialize)]
 pub struct Choice {
     pub message: AssistantMessage,
+    /// Why generation stopped (not every provider reports it)
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
+}
+
+impl Choice {
+    /// The content to keep in conversation history for this reply. A reply
+    /// stopped by a safety block or recitation with nothing else in it keeps
+    /// the reason instead of an empty assistant turn.
+    pub fn history_content(&self) -> Option<String> {
+        let content = self.message.history_content();
+        let empty = content.as_deref().is_none_or(str::is_empty)
+            && self.message.tool_calls.as_ref().is_none_or(Vec::is_empty);
+        match self.finish_reason.as_ref().and_then(FinishReason::error_message) {
+            Some(message) if empty => Some(format!("[{}]", message)),
+            _ => content,
+        }
+    }
+}
+
+/// Why the model stopped generating, normalized across providers
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "String")]
+pub enum FinishReason {
+    /// Natural end of the turn
+    Stop,
+    /// Hit the output token limit; the reply is truncated
+    Length,
+    /// Stopped to call tools
+    ToolCalls,
+    /// The reply was blocked by a safety or content filter
+    ContentFilter { category: Option<String> },
+    /// The reply was stopped for reciting training data
+    Recitation,
+    /// The prompt itself was blocked, so no reply was generated
+    PromptBlocked { reason: String },
+    /// Anything else, as reported by the provider
+    Other(String),
+}
+
+impl From<String> for FinishReason {
+    /// OpenAI-compatible `finish_reason` values (plus Anthropic's and Ollama's spellings)
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "stop" | "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "length" | "max_tokens" => FinishReason::Length,
+            "tool_calls" | "function_call" | "tool_use" => FinishReason::ToolCalls,
+            "content_filter" => FinishReason::ContentFilter { category: None },
+            _ => FinishReason::Other(reason),
+        }
+    }
+}
+
+impl FinishReason {
+    /// Map a Gemini `finishReason`. `blocked_categories` are the harm categories
+    /// whose safety ratings were marked blocked.
+    pub fn from_gemini(reason: &str, blocked_categories: &[String]) -> Self {
+        match reason {
+            "STOP" => FinishReason::Stop,
+            "MAX_TOKENS" => FinishReason::Length,
+            "RECITATION" => FinishReason::Recitation,
+            "SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII" | "IMAGE_SAFETY" => {
+                let category = if blocked_categories.is_empty() {
+                    reason.to_string()
+                } else {
+                    blocked_categories.join(", ")
+                };
+                FinishReason::ContentFilter { category: Some(category) }
+            }
+            other => FinishReason::Other(other.to_string()),
+        }
+    }
+
+    /// Message to show the user when this stop means the reply is missing or
+    /// unusable; None for stops the agent loop handles itself
+    pub fn error_message(&self) -> Option<String> {
+        match self {
+            FinishReason::ContentFilter { category } => Some(match category {
+                Some(category) => format!("Response blocked by the safety filter ({})", category),
+                None => "Response blocked by the content filter".to_string(),
+            }),
+            FinishReason::Recitation => Some(
+                "Response stopped: it recited training data too closely (RECITATION)".to_string(),
+            ),
+            FinishReason::PromptBlocked { reason } => {
+                Some(format!("Prompt blocked by the safety filter ({})", reason))
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Assistant message from the API
@@ -148,6 +262,144 @@ pub struct Choice {
 pub struct AssistantMessage {
     pub content: Option<String>,
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Output of Gemini built-in tools that ran server-side during this turn.
+    /// Never part of an OpenAI-compatible payload; filled in by the Gemini path.
+    #[serde(skip)]
+    pub builtin_outputs: Vec<BuiltinToolOutput>,
+}
+
+impl AssistantMessage {
+    /// The content to keep in conversation history: executed code and its output
+    /// ahead of the text, citations after it, all rendered as markdown so they
+    /// survive in any SessionStorage and read naturally when replayed.
+    pub fn history_content(&self) -> Option<String> {
+        if self.builtin_outputs.is_empty() {
+            return self.content.clone();
+        }
+
+        let mut sections: Vec<String> = Vec::new();
+        for output in &self.builtin_outputs {
+            match output {
+                BuiltinToolOutput::Code { .. } | BuiltinToolOutput::CodeResult { .. } => {
+                    sections.push(output.to_markdown())
+                }
+                BuiltinToolOutput::Citations { .. } => {}
+            }
+        }
+        if let Some(text) = self.content.as_deref().filter(|t| !t.is_empty()) {
+            sections.push(text.to_string());
+        }
+        for output in &self.builtin_outputs {
+            if let BuiltinToolOutput::Citations { .. } = output {
+                sections.push(output.to_markdown());
+            }
+        }
+
+        Some(sections.join("\n\n"))
+    }
+}
+
+/// Gemini's server-side tools, enabled per run (`--gemini-tools`) or per agent
+/// (`gemini_tools` in agents.toml). They run alongside our function declarations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GeminiBuiltinTool {
+    CodeExecution,
+    GoogleSearch,
+    UrlContext,
+}
+
+impl GeminiBuiltinTool {
+    pub const ALL: [GeminiBuiltinTool; 3] = [
+        GeminiBuiltinTool::CodeExecution,
+        GeminiBuiltinTool::GoogleSearch,
+        GeminiBuiltinTool::UrlContext,
+    ];
+
+    /// Name as written on the command line and in agents.toml
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeminiBuiltinTool::CodeExecution => "code_execution",
+            GeminiBuiltinTool::GoogleSearch => "google_search",
+            GeminiBuiltinTool::UrlContext => "url_context",
+        }
+    }
+
+    /// Parse a list of names, rejecting unknown ones and dropping duplicates
+    pub fn parse_list<S: AsRef<str>>(names: &[S]) -> Result<Vec<GeminiBuiltinTool>, String> {
+        let mut tools = Vec::new();
+        for name in names {
+            let tool = name.as_ref().parse::<GeminiBuiltinTool>()?;
+            if !tools.contains(&tool) {
+                tools.push(tool);
+            }
+        }
+        Ok(tools)
+    }
+}
+
+impl std::str::FromStr for GeminiBuiltinTool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        GeminiBuiltinTool::ALL
+            .into_iter()
+            .find(|tool| tool.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown Gemini tool '{}' (expected code_execution, google_search or url_context)",
+                    name
+                )
+            })
+    }
+}
+
+/// Something a Gemini built-in tool did during a turn
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuiltinToolOutput {
+    /// Code the model wrote and ran with code execution
+    Code { language: String, code: String },
+    /// Outcome and output of the preceding code
+    CodeResult { outcome: String, output: String },
+    /// Search queries issued and the sources the answer is grounded in
+    Citations {
+        queries: Vec<String>,
+        sources: Vec<Citation>,
+    },
+}
+
+/// A grounding source: a search hit or a URL fetched with URL context
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Citation {
+    pub title: String,
+    pub uri: String,
+}
+
+impl BuiltinToolOutput {
+    /// Markdown rendering used for conversation history
+    pub fn to_markdown(&self) -> String {
+        match self {
+            BuiltinToolOutput::Code { language, code } => {
+                format!("```{}\n{}\n```", language.to_lowercase(), code.trim_end())
+            }
+            BuiltinToolOutput::CodeResult { outcome, output } => {
+                format!("**Code Output ({}):**\n```\n{}\n```", outcome, output.trim_end())
+            }
+            BuiltinToolOutput::Citations { queries, sources } => {
+                let mut lines = Vec::new();
+                if !queries.is_empty() {
+                    lines.push(format!("**Searched:** {}", queries.join(", ")));
+                }
+                if !sources.is_empty() {
+                    lines.push("**Sources:**".to_string());
+                    for (i, source) in sources.iter().enumerate() {
+                        lines.push(format!("{}. [{}]({})", i + 1, source.title, source.uri));
+                    }
+                }
+                lines.join("\n")
+            }
+        }
+    }
 }
 
 /// Configuration for the webapp server
@@ -208,14 +460,32 @@ pub struct GeminiRequest {
     pub tools: Option<Vec<GeminiTool>>,
 }
 
-/// Gemini tool container - supports function declarations and code execution
-#[derive(Debug, Serialize)]
+/// Gemini tool container - supports function declarations and the built-in tools
+#[derive(Debug, Default, Serialize)]
 pub struct GeminiTool {
     #[serde(rename = "functionDeclarations", skip_serializing_if = "Option::is_none")]
     pub function_declarations: Option<Vec<GeminiFunctionDeclaration>>,
-    /// Agentic Vision code execution tool (empty object to enable)
+    /// Code execution tool (empty object to enable)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code_execution: Option<serde_json::Value>,
+    /// Google Search grounding tool (empty object to enable)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_search: Option<serde_json::Value>,
+    /// URL context tool (empty object to enable)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url_context: Option<serde_json::Value>,
+}
+
+impl GeminiTool {
+    /// A tool entry that switches on one built-in tool
+    pub fn builtin(tool: GeminiBuiltinTool) -> Self {
+        let enabled = Some(serde_json::json!({}));
+        match tool {
+            GeminiBuiltinTool::CodeExecution => Self { code_execution: enabled, ..Default::default() },
+            GeminiBuiltinTool::GoogleSearch => Self { google_search: enabled, ..Default::default() },
+            GeminiBuiltinTool::UrlContext => Self { url_context: enabled, ..Default::default() },
+        }
+    }
 }
 
 /// Gemini function declaration (equivalent to OpenAI's function spec)
@@ -275,6 +545,8 @@ pub struct GeminiFunctionResponse {
 /// Gemini API response format
 #[derive(Debug, Deserialize)]
 pub struct GeminiResponse {
+    /// Absent when the prompt was blocked
+    #[serde(default)]
     pub candidates: Vec<GeminiCandidate>,
     /// Feedback about the prompt (may contain block reason)
     #[serde(rename = "promptFeedback")]
@@ -300,6 +572,8 @@ pub struct GeminiUsageMetadata {
 /// Gemini candidate structure
 #[derive(Debug, Deserialize)]
 pub struct GeminiCandidate {
+    /// Missing when the candidate was blocked before producing anything
+    #[serde(default)]
     pub content: GeminiContentResponse,
     /// Why the model stopped generating
     #[serde(rename = "finishReason")]
@@ -307,6 +581,56 @@ pub struct GeminiCandidate {
     /// Additional detail about the finish reason
     #[serde(rename = "finishMessage")]
     pub finish_message: Option<String>,
+    /// Search queries and sources behind a Google Search grounded answer
+    #[serde(rename = "groundingMetadata", default)]
+    pub grounding_metadata: Option<GeminiGroundingMetadata>,
+    /// URLs fetched by the URL context tool
+    #[serde(rename = "urlContextMetadata", default)]
+    pub url_context_metadata: Option<GeminiUrlContextMetadata>,
+    /// Per-category safety ratings; blocked ones explain a SAFETY stop
+    #[serde(rename = "safetyRatings", default)]
+    pub safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+/// Gemini grounding metadata (Google Search)
+#[derive(Debug, Deserialize)]
+pub struct GeminiGroundingMetadata {
+    #[serde(rename = "webSearchQueries", default)]
+    pub web_search_queries: Vec<String>,
+    #[serde(rename = "groundingChunks", default)]
+    pub grounding_chunks: Vec<GeminiGroundingChunk>,
+}
+
+/// One grounding source
+#[derive(Debug, Deserialize)]
+pub struct GeminiGroundingChunk {
+    #[serde(default)]
+    pub web: Option<GeminiWebSource>,
+}
+
+/// A web page used as a grounding source
+#[derive(Debug, Deserialize)]
+pub struct GeminiWebSource {
+    #[serde(default)]
+    pub uri: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// Gemini URL context metadata
+#[derive(Debug, Deserialize)]
+pub struct GeminiUrlContextMetadata {
+    #[serde(rename = "urlMetadata", default)]
+    pub url_metadata: Vec<GeminiUrlMetadata>,
+}
+
+/// One URL the URL context tool tried to fetch
+#[derive(Debug, Deserialize)]
+pub struct GeminiUrlMetadata {
+    #[serde(rename = "retrievedUrl", default)]
+    pub retrieved_url: String,
+    #[serde(rename = "urlRetrievalStatus", default)]
+    pub url_retrieval_status: Option<String>,
 }
 
 /// Gemini prompt feedback (may indicate blocked prompts)
@@ -324,11 +648,15 @@ pub struct GeminiPromptFeedback {
 #[derive(Debug, Deserialize)]
 pub struct GeminiSafetyRating {
     pub category: String,
+    #[serde(default)]
     pub probability: String,
+    /// Whether this category caused the block
+    #[serde(default)]
+    pub blocked: bool,
 }
 
 /// Gemini content response structure
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct GeminiContentResponse {
     #[serde(default)]
     pub parts: Vec<GeminiPartResponse>,
@@ -344,22 +672,22 @@ pub struct GeminiPartResponse {
     /// Thought signature for Gemini 3 models - must be passed back with function responses
     #[serde(rename = "thoughtSignature")]
     pub thought_signature: Option<String>,
-    /// Agentic Vision: code generated and executed by the model
+    /// Code generated and executed by the model (code execution tool)
     #[serde(rename = "executableCode")]
     pub executable_code: Option<GeminiExecutableCode>,
-    /// Agentic Vision: result of code execution
+    /// Result of code execution
     #[serde(rename = "codeExecutionResult")]
     pub code_execution_result: Option<GeminiCodeExecutionResult>,
 }
 
-/// Agentic Vision: executable code generated by Gemini
+/// Executable code generated by Gemini
 #[derive(Debug, Deserialize)]
 pub struct GeminiExecutableCode {
     pub language: String,
     pub code: String,
 }
 
-/// Agentic Vision: result of code execution
+/// Result of code execution
 #[derive(Debug, Deserialize)]
 pub struct GeminiCodeExecutionResult {
     pub outcome: String,
@@ -461,6 +789,148 @@ mod tests {
         assert_eq!(json["content"], "Response");
     }
 
+    #[test]
+    fn test_gemini_builtin_tool_parse_list() {
+        let tools = GeminiBuiltinTool::parse_list(&["google_search", " url_context", "google_search"]).unwrap();
+        assert_eq!(tools, vec![GeminiBuiltinTool::GoogleSearch, GeminiBuiltinTool::UrlContext]);
+
+        let err = GeminiBuiltinTool::parse_list(&["web_search"]).unwrap_err();
+        assert!(err.contains("web_search"), "{}", err);
+    }
+
+    #[test]
+    fn test_history_content_without_builtin_outputs_is_the_content() {
+        let message = AssistantMessage {
+            content: Some("Plain answer".to_string()),
+            tool_calls: None,
+            builtin_outputs: Vec::new(),
+        };
+        assert_eq!(message.history_content(), Some("Plain answer".to_string()));
+    }
+
+    #[test]
+    fn test_history_content_orders_code_before_text_and_citations_after() {
+        let message = AssistantMessage {
+            content: Some("The answer is 4.".to_string()),
+            tool_calls: None,
+            builtin_outputs: vec![
+                BuiltinToolOutput::Citations {
+                    queries: vec!["two plus two".to_string()],
+                    sources: vec![Citation {
+                        title: "Arithmetic".to_string(),
+                        uri: "https://example.com/math".to_string(),
+                    }],
+                },
+                BuiltinToolOutput::Code {
+                    language: "PYTHON".to_string(),
+                    code: "print(2 + 2)\n".to_string(),
+                },
+                BuiltinToolOutput::CodeResult {
+                    outcome: "OUTCOME_OK".to_string(),
+                    output: "4\n".to_string(),
+                },
+            ],
+        };
+
+        assert_eq!(
+            message.history_content().unwrap(),
+            "```python\nprint(2 + 2)\n```\n\n\
+             **Code Output (OUTCOME_OK):**\n```\n4\n```\n\n\
+             The answer is 4.\n\n\
+             **Searched:** two plus two\n**Sources:**\n1. [Arithmetic](https://example.com/math)"
+        );
+    }
+
+    #[test]
+    fn test_history_content_with_only_builtin_outputs() {
+        let message = AssistantMessage {
+            content: None,
+            tool_calls: None,
+            builtin_outputs: vec![BuiltinToolOutput::CodeResult {
+                outcome: "OUTCOME_FAILED".to_string(),
+                output: "ZeroDivisionError".to_string(),
+            }],
+        };
+        assert_eq!(
+            message.history_content().unwrap(),
+            "**Code Output (OUTCOME_FAILED):**\n```\nZeroDivisionError\n```"
+        );
+    }
+
+    #[test]
+    fn test_finish_reason_from_openai_choice() {
+        let choice: Choice = serde_json::from_str(
+            r#"{"message": {"content": "Hel", "tool_calls": null}, "finish_reason": "length"}"#,
+        )
+        .unwrap();
+        assert_eq!(choice.finish_reason, Some(FinishReason::Length));
+
+        let choice: Choice =
+            serde_json::from_str(r#"{"message": {"content": null, "tool_calls": null}, "finish_reason": null}"#)
+                .unwrap();
+        assert_eq!(choice.finish_reason, None);
+
+        let choice: Choice = serde_json::from_str(r#"{"message": {"content": "hi", "tool_calls": null}}"#).unwrap();
+        assert_eq!(choice.finish_reason, None);
+
+        assert_eq!(FinishReason::from("content_filter".to_string()), FinishReason::ContentFilter { category: None });
+        assert_eq!(FinishReason::from("end_turn".to_string()), FinishReason::Stop);
+        assert_eq!(FinishReason::from("weird".to_string()), FinishReason::Other("weird".to_string()));
+    }
+
+    #[test]
+    fn test_finish_reason_from_gemini() {
+        assert_eq!(FinishReason::from_gemini("MAX_TOKENS", &[]), FinishReason::Length);
+        assert_eq!(FinishReason::from_gemini("RECITATION", &[]), FinishReason::Recitation);
+        assert_eq!(
+            FinishReason::from_gemini("SAFETY", &["HARM_CATEGORY_DANGEROUS_CONTENT".to_string()]),
+            FinishReason::ContentFilter { category: Some("HARM_CATEGORY_DANGEROUS_CONTENT".to_string()) }
+        );
+        assert_eq!(
+            FinishReason::from_gemini("BLOCKLIST", &[]),
+            FinishReason::ContentFilter { category: Some("BLOCKLIST".to_string()) }
+        );
+        assert_eq!(
+            FinishReason::from_gemini("MALFORMED_FUNCTION_CALL", &[]),
+            FinishReason::Other("MALFORMED_FUNCTION_CALL".to_string())
+        );
+    }
+
+    #[test]
+    fn test_finish_reason_error_message() {
+        let blocked = FinishReason::ContentFilter { category: Some("HARM_CATEGORY_HARASSMENT".to_string()) };
+        assert!(blocked.error_message().unwrap().contains("HARM_CATEGORY_HARASSMENT"));
+        assert!(FinishReason::Recitation.error_message().unwrap().contains("RECITATION"));
+        assert!(FinishReason::PromptBlocked { reason: "SAFETY".to_string() }
+            .error_message()
+            .unwrap()
+            .contains("Prompt blocked"));
+        assert_eq!(FinishReason::Stop.error_message(), None);
+        assert_eq!(FinishReason::Length.error_message(), None);
+    }
+
+    #[test]
+    fn test_blocked_reply_keeps_reason_in_history() {
+        let choice = |content: Option<&str>, finish_reason| Choice {
+            message: AssistantMessage {
+                content: content.map(str::to_string),
+                tool_calls: None,
+                builtin_outputs: Vec::new(),
+            },
+            finish_reason,
+        };
+        let blocked = || Some(FinishReason::ContentFilter { category: None });
+        assert_eq!(
+            choice(Some(""), blocked()).history_content().as_deref(),
+            Some("[Response blocked by the content filter]")
+        );
+        assert_eq!(
+            choice(Some("partial"), blocked()).history_content().as_deref(),
+            Some("partial")
+        );
+        assert_eq!(choice(None, Some(FinishReason::Stop)).history_content(), None);
+    }
+
     #[test]
     fn test_message_tool_serialization() {
         let message = Message::Tool {
@@ -0,0 +1,523 @@
+//! Native Ollama chat API (`/api/chat`).
+//!
+//! Ollama's OpenAI-compatible `/v1/` endpoint cannot set `num_ctx`, so long
+//! agent conversations were silently truncated at Ollama's small default
+//! context window. The native endpoint takes per-request `options` and
+//! `keep_alive`, and supports tool calls and streaming.
+//!
+//! Settings come from `~/.eunice/ollama.toml`; top-level keys apply to every
+//! model and a `[models."<name>"]` table overrides them for one model:
+//!
+//! ```toml
+//! num_ctx = 32768
+//! keep_alive = "30m"
+//!
+//! [models."qwen3:32b"]
+//! num_ctx = 65536
+//! temperature = 0.2
+//! ```
+
+use crate::models::{
+    AssistantMessage, ChatCompletionResponse, Choice, FinishReason, FunctionCall, Message, Tool,
+    ToolCall, UsageStats,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Context window requested when nothing is configured. Ollama's own default
+/// (2048-4096 depending on version) is far too small for an agent loop.
+pub const DEFAULT_NUM_CTX: u64 = 16384;
+
+/// `keep_alive` as Ollama accepts it: a duration string ("30m") or seconds
+/// (0 unloads immediately, negative keeps the model loaded indefinitely).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Duration(String),
+    Seconds(i64),
+}
+
+/// Request settings for one model. Unset fields fall back to the file's
+/// top-level defaults, then to Ollama's own.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaSettings {
+    #[serde(default)]
+    pub num_ctx: Option<u64>,
+    #[serde(default)]
+    pub num_predict: Option<i64>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub keep_alive: Option<KeepAlive>,
+}
+
+impl OllamaSettings {
+    /// Fields set here win; the rest come from `fallback`
+    fn or(&self, fallback: &OllamaSettings) -> OllamaSettings {
+        OllamaSettings {
+            num_ctx: self.num_ctx.or(fallback.num_ctx),
+            num_predict: self.num_predict.or(fallback.num_predict),
+            temperature: self.temperature.or(fallback.temperature),
+            keep_alive: self.keep_alive.clone().or_else(|| fallback.keep_alive.clone()),
+        }
+    }
+}
+
+/// Contents of `~/.eunice/ollama.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaConfig {
+    #[serde(default)]
+    pub num_ctx: Option<u64>,
+    #[serde(default)]
+    pub num_predict: Option<i64>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub keep_alive: Option<KeepAlive>,
+    /// Per-model overrides, keyed by model name ("qwen3:32b") or family ("qwen3")
+    #[serde(default)]
+    pub models: HashMap<String, OllamaSettings>,
+}
+
+/// Path of the Ollama settings file
+pub fn config_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".eunice")
+        .join("ollama.toml")
+}
+
+impl OllamaConfig {
+    /// Load `~/.eunice/ollama.toml`. A missing file means defaults; a file that
+    /// does not parse is an error rather than a silent fallback to a small context.
+    pub fn load() -> Result<Self> {
+        Self::load_from(&config_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read '{}': {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| anyhow!("failed to parse '{}': {}", path.display(), e))
+    }
+
+    /// Effective settings for a model: its own table (exact name, then the name
+    /// without a `:latest` tag, then the family before the tag) over the
+    /// top-level defaults, with `num_ctx` always set.
+    pub fn settings_for(&self, model: &str) -> OllamaSettings {
+        let defaults = OllamaSettings {
+            num_ctx: self.num_ctx,
+            num_predict: self.num_predict,
+            temperature: self.temperature,
+            keep_alive: self.keep_alive.clone(),
+        };
+
+        let family = model.split(':').next().unwrap_or(model);
+        let candidates = [model, model.strip_suffix(":latest").unwrap_or(model), family];
+        let mut settings = candidates
+            .iter()
+            .find_map(|name| self.models.get(*name))
+            .map(|own| own.or(&defaults))
+            .unwrap_or(defaults);
+
+        settings.num_ctx.get_or_insert(DEFAULT_NUM_CTX);
+        settings
+    }
+}
+
+/// `options` object of a chat request
+#[derive(Debug, Serialize)]
+pub struct OllamaOptions {
+    pub num_ctx: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+/// `/api/chat` request body
+#[derive(Debug, Serialize)]
+pub struct OllamaChatRequest<'a> {
+    pub model: &'a str,
+    pub messages: Vec<OllamaMessage>,
+    /// Same shape as the OpenAI tool specs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<&'a [Tool]>,
+    pub stream: bool,
+    pub options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<KeepAlive>,
+}
+
+/// A chat message in Ollama's format (requests and responses)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OllamaMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<OllamaToolCall>>,
+    /// Which function a `tool` message answers; Ollama has no call ids
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OllamaToolCall {
+    pub function: OllamaFunctionCall,
+}
+
+/// Ollama passes arguments as a JSON object, not an encoded string
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OllamaFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+/// `/api/chat` response, or one line of a streamed response
+#[derive(Debug, Deserialize)]
+pub struct OllamaChatResponse {
+    #[serde(default)]
+    pub message: Option<OllamaMessage>,
+    #[serde(default)]
+    pub done: bool,
+    /// "stop", or "length" when `num_predict` cut the reply short
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    #[serde(default)]
+    pub prompt_eval_count: u64,
+    #[serde(default)]
+    pub eval_count: u64,
+    /// Set instead of a message when the request failed mid-stream
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Build an `/api/chat` request
+pub fn build_request<'a>(
+    model: &'a str,
+    messages: &[Message],
+    tools: Option<&'a [Tool]>,
+    settings: &OllamaSettings,
+    stream: bool,
+) -> OllamaChatRequest<'a> {
+    OllamaChatRequest {
+        model,
+        messages: convert_messages(messages),
+        tools,
+        stream,
+        options: OllamaOptions {
+            num_ctx: settings.num_ctx.unwrap_or(DEFAULT_NUM_CTX),
+            num_predict: settings.num_predict,
+            temperature: settings.temperature,
+        },
+        keep_alive: settings.keep_alive.clone(),
+    }
+}
+
+/// Convert history to Ollama messages. Tool results carry the function name
+/// instead of a call id, so it is looked up from the call that requested them.
+pub fn convert_messages(messages: &[Message]) -> Vec<OllamaMessage> {
+    let mut call_names: HashMap<&str, &str> = HashMap::new();
+    let mut converted = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        match message {
+            Message::User { content } => converted.push(OllamaMessage {
+                role: "user".to_string(),
+                content: content.clone(),
+                tool_calls: None,
+                tool_name: None,
+            }),
+            Message::Assistant { content, tool_calls } => {
+                let calls = tool_calls.as_ref().map(|calls| {
+                    calls
+                        .iter()
+                        .map(|call| {
+                            call_names.insert(&call.id, &call.function.name);
+                            OllamaToolCall {
+                                function: OllamaFunctionCall {
+                                    name: call.function.name.clone(),
+                                    arguments: serde_json::from_str(&call.function.arguments)
+                                        .unwrap_or_else(|_| serde_json::json!({})),
+                                },
+                            }
+                        })
+                        .collect()
+                });
+                converted.push(OllamaMessage {
+                    role: "assistant".to_string(),
+                    content: content.clone().unwrap_or_default(),
+                    tool_calls: calls,
+                    tool_name: None,
+                });
+            }
+            Message::Tool { tool_call_id, content } => converted.push(OllamaMessage {
+                role: "tool".to_string(),
+                content: content.clone(),
+                tool_calls: None,
+                tool_name: call_names.get(tool_call_id.as_str()).map(|name| name.to_string()),
+            }),
+        }
+    }
+
+    converted
+}
+
+/// Convert Ollama tool calls, minting the ids Ollama does not provide
+pub fn convert_tool_calls(calls: Vec<OllamaToolCall>) -> Vec<ToolCall> {
+    calls
+        .into_iter()
+        .map(|call| ToolCall {
+            id: ToolCall::new_id(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: call.function.name,
+                arguments: call.function.arguments.to_string(),
+            },
+            provider_metadata: None,
+        })
+        .collect()
+}
+
+/// Assemble the OpenAI-compatible response the agent loop consumes
+pub fn into_response(
+    text: String,
+    tool_calls: Vec<ToolCall>,
+    done_reason: Option<String>,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+) -> ChatCompletionResponse {
+    ChatCompletionResponse {
+        choices: vec![Choice {
+            message: AssistantMessage {
+                content: if text.is_empty() { None } else { Some(text) },
+                tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+                builtin_outputs: Vec::new(),
+            },
+            finish_reason: done_reason.map(FinishReason::from),
+        }],
+        usage: Some(UsageStats {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            cached_tokens: 0,
+        }),
+    }
+}
+
+/// Convert a non-streamed `/api/chat` response
+pub fn convert_response(response: OllamaChatResponse) -> Result<ChatCompletionResponse> {
+    if let Some(error) = response.error {
+        return Err(anyhow!("Ollama error: {}", error));
+    }
+    let message = response
+        .message
+        .ok_or_else(|| anyhow!("Ollama response has no message"))?;
+    Ok(into_response(
+        message.content,
+        convert_tool_calls(message.tool_calls.unwrap_or_default()),
+        response.done_reason,
+        response.prompt_eval_count,
+        response.eval_count,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FunctionSpec;
+    use tempfile::TempDir;
+
+    fn config(text: &str) -> OllamaConfig {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_settings_default_num_ctx_when_unconfigured() {
+        let settings = OllamaConfig::default().settings_for("llama3.1");
+        assert_eq!(settings.num_ctx, Some(DEFAULT_NUM_CTX));
+        assert_eq!(settings.temperature, None);
+        assert_eq!(settings.keep_alive, None);
+    }
+
+    #[test]
+    fn test_settings_model_table_overrides_defaults() {
+        let config = config(
+            r#"
+num_ctx = 32768
+keep_alive = "30m"
+temperature = 0.7
+
+[models."qwen3:32b"]
+num_ctx = 65536
+temperature = 0.2
+"#,
+        );
+
+        let qwen = config.settings_for("qwen3:32b");
+        assert_eq!(qwen.num_ctx, Some(65536));
+        assert_eq!(qwen.temperature, Some(0.2));
+        assert_eq!(qwen.keep_alive, Some(KeepAlive::Duration("30m".to_string())));
+
+        let other = config.settings_for("llama3.1:8b");
+        assert_eq!(other.num_ctx, Some(32768));
+        assert_eq!(other.temperature, Some(0.7));
+    }
+
+    #[test]
+    fn test_settings_match_latest_tag_and_family() {
+        let config = config(
+            r#"
+[models.glm4]
+num_ctx = 8192
+
+[models."mistral-nemo"]
+num_predict = 2048
+keep_alive = -1
+"#,
+        );
+
+        assert_eq!(config.settings_for("glm4:9b").num_ctx, Some(8192));
+        let nemo = config.settings_for("mistral-nemo:latest");
+        assert_eq!(nemo.num_predict, Some(2048));
+        assert_eq!(nemo.keep_alive, Some(KeepAlive::Seconds(-1)));
+        assert_eq!(nemo.num_ctx, Some(DEFAULT_NUM_CTX));
+    }
+
+    #[test]
+    fn test_load_missing_file_is_default() {
+        let dir = TempDir::new().unwrap();
+        let config = OllamaConfig::load_from(&dir.path().join("ollama.toml")).unwrap();
+        assert!(config.models.is_empty());
+        assert_eq!(config.num_ctx, None);
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_keys() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("ollama.toml");
+        std::fs::write(&path, "[models.qwen3]\ncontext = 8192\n").unwrap();
+        let err = OllamaConfig::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("ollama.toml"), "{}", err);
+    }
+
+    #[test]
+    fn test_build_request_serializes_options_and_tools() {
+        let tools = vec![Tool {
+            tool_type: "function".to_string(),
+            function: FunctionSpec {
+                name: "Bash".to_string(),
+                description: "Run a command".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            },
+        }];
+        let settings = OllamaSettings {
+            num_ctx: Some(32768),
+            num_predict: None,
+            temperature: Some(0.1),
+            keep_alive: Some(KeepAlive::Duration("10m".to_string())),
+        };
+        let messages = vec![Message::User { content: "hi".to_string() }];
+
+        let request = build_request("qwen3", &messages, Some(&tools), &settings, true);
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["model"], "qwen3");
+        assert_eq!(json["stream"], true);
+        assert_eq!(json["options"], serde_json::json!({"num_ctx": 32768, "temperature": 0.1}));
+        assert_eq!(json["keep_alive"], "10m");
+        assert_eq!(json["tools"][0]["function"]["name"], "Bash");
+        assert_eq!(json["messages"][0], serde_json::json!({"role": "user", "content": "hi"}));
+    }
+
+    #[test]
+    fn test_convert_messages_names_tool_results_and_decodes_arguments() {
+        let messages = vec![
+            Message::User { content: "list files".to_string() },
+            Message::Assistant {
+                content: None,
+                tool_calls: Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    call_type: "function".to_string(),
+                    function: FunctionCall {
+                        name: "Bash".to_string(),
+                        arguments: r#"{"command":"ls"}"#.to_string(),
+                    },
+                    provider_metadata: None,
+                }]),
+            },
+            Message::Tool {
+                tool_call_id: "call_1".to_string(),
+                content: "a.txt".to_string(),
+            },
+        ];
+
+        let converted = convert_messages(&messages);
+        let json = serde_json::to_value(&converted).unwrap();
+
+        assert_eq!(json[1]["role"], "assistant");
+        assert_eq!(json[1]["content"], "");
+        assert_eq!(
+            json[1]["tool_calls"][0]["function"]["arguments"],
+            serde_json::json!({"command": "ls"})
+        );
+        assert_eq!(json[2]["role"], "tool");
+        assert_eq!(json[2]["tool_name"], "Bash");
+        assert_eq!(json[2]["content"], "a.txt");
+    }
+
+    #[test]
+    fn test_convert_response_with_tool_calls_and_usage() {
+        let response: OllamaChatResponse = serde_json::from_str(
+            r#"{
+                "model": "qwen3",
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [
+                        {"function": {"name": "Read", "arguments": {"file_path": "a.txt"}}},
+                        {"function": {"name": "Read", "arguments": {"file_path": "b.txt"}}}
+                    ]
+                },
+                "done": true,
+                "done_reason": "stop",
+                "prompt_eval_count": 120,
+                "eval_count": 30
+            }"#,
+        )
+        .unwrap();
+
+        let converted = convert_response(response).unwrap();
+        let message = &converted.choices[0].message;
+        assert_eq!(message.content, None);
+
+        let calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].function.name, "Read");
+        assert_eq!(calls[0].function.arguments, r#"{"file_path":"a.txt"}"#);
+        // Ollama has no call ids, so each call gets its own
+        assert_ne!(calls[0].id, calls[1].id);
+        assert_eq!(converted.choices[0].finish_reason, Some(FinishReason::Stop));
+
+        let usage = converted.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 120);
+        assert_eq!(usage.completion_tokens, 30);
+        assert_eq!(usage.total_tokens, 150);
+    }
+
+    #[test]
+    fn test_convert_response_surfaces_error() {
+        let response: OllamaChatResponse =
+            serde_json::from_str(r#"{"error": "model 'nope' not found"}"#).unwrap();
+        let err = convert_response(response).unwrap_err();
+        assert!(err.to_string().contains("model 'nope' not found"));
+    }
+}
@@ -6,9 +6,10 @@
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use tempfile::TempDir;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tempfile::{TempDir, TempPath};
 
 /// Maximum size for in-memory storage (1MB)
 const MAX_MEMORY_SIZE: usize = 1024 * 1024;
@@ -23,6 +24,36 @@ enum OutputStorage {
     InMemory(String),
     /// Large outputs stored in temp file
     TempFile(PathBuf),
+    /// A tool's own temp file, adopted as is
+    Spilled(TempPath),
+}
+
+/// A tool result too large to return whole, left in a temp file by the tool
+/// (see `OutputStore::adopt`). The file is deleted when this is dropped.
+pub struct SpilledOutput {
+    path: TempPath,
+    bytes: u64,
+    lines: usize,
+}
+
+impl SpilledOutput {
+    pub fn new(path: TempPath, bytes: u64, lines: usize) -> Self {
+        Self { path, bytes, lines }
+    }
+}
+
+/// Where a tool call leaves its `SpilledOutput` for the caller to collect
+#[derive(Default)]
+pub struct SpillSlot(Mutex<Option<SpilledOutput>>);
+
+impl SpillSlot {
+    pub fn put(&self, output: SpilledOutput) {
+        *self.0.lock().unwrap() = Some(output);
+    }
+
+    pub fn take(&self) -> Option<SpilledOutput> {
+        self.0.lock().unwrap().take()
+    }
 }
 
 /// Stored output with metadata
@@ -93,6 +124,29 @@ impl OutputStore {
         Ok((id, truncated))
     }
 
+    /// Take ownership of a spilled result's file without copying it.
+    ///
+    /// Returns (output_id, preview) where preview is the tool's own preview
+    /// of the file, headed with the ID get_output reads it by.
+    pub fn adopt(&mut self, spilled: SpilledOutput, preview: &str) -> (String, String) {
+        let id = format!("out_{:03}", self.next_id);
+        self.next_id += 1;
+
+        let view = format!(
+            "[Output ID: {}]\n{}\n[Use get_output(id=\"{}\", start=N, end=M) to retrieve any of the {} lines]",
+            id, preview, id, spilled.lines
+        );
+        self.outputs.insert(
+            id.clone(),
+            StoredOutput {
+                storage: OutputStorage::Spilled(spilled.path),
+                total_lines: spilled.lines,
+                total_bytes: spilled.bytes as usize,
+            },
+        );
+        (id, view)
+    }
+
     /// Store shell output with exit code prominently displayed
     #[allow(dead_code)]
     pub fn store_shell_output(
@@ -140,19 +194,8 @@ impl OutputStore {
             .get(id)
             .ok_or_else(|| anyhow!("Output ID '{}' not found", id))?;
 
-        let content = match &stored.storage {
-            OutputStorage::InMemory(s) => s.clone(),
-            OutputStorage::TempFile(path) => {
-                let mut file = File::open(path)?;
-                let mut content = String::new();
-                file.read_to_string(&mut content)?;
-                content
-            }
-        };
-
-        let lines: Vec<&str> = content.lines().collect();
-        let end = end.unwrap_or(start + 100).min(lines.len());
-        let start = start.min(lines.len());
+        let end = end.unwrap_or(start + 100).min(stored.total_lines);
+        let start = start.min(stored.total_lines);
 
         if start >= end {
             return Ok(format!(
@@ -161,7 +204,16 @@ impl OutputStore {
             ));
         }
 
-        let range_lines: Vec<&str> = lines[start..end].to_vec();
+        let range_lines: Vec<String> = match &stored.storage {
+            OutputStorage::InMemory(s) => s
+                .lines()
+                .skip(start)
+                .take(end - start)
+                .map(str::to_string)
+                .collect(),
+            OutputStorage::TempFile(path) => read_line_range(path, start, end)?,
+            OutputStorage::Spilled(path) => read_line_range(path, start, end)?,
+        };
         let result = format!(
             "[Output ID: {}]\nLines {}-{} of {}:\n─────────────────────────────────────────────\n{}\n─────────────────────────────────────────────",
             id,
@@ -233,6 +285,25 @@ impl OutputStore {
     }
 }
 
+/// Lines `start..end` of a file, read without loading the rest of it
+fn read_line_range(path: &Path, start: usize, end: usize) -> Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    for index in 0..end {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if index >= start {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            lines.push(text.strip_suffix('\r').unwrap_or(text).to_string());
+        }
+    }
+    Ok(lines)
+}
+
 impl Default for OutputStore {
     fn default() -> Self {
         Self::new()
@@ -308,6 +379,30 @@ mod tests {
         assert!(!range.contains("line 61"));
     }
 
+    #[test]
+    fn test_adopt_spilled_file() {
+        let mut store = OutputStore::new();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for i in 1..=500 {
+            writeln!(file, "line {}", i).unwrap();
+        }
+        let path = file.into_temp_path();
+        let kept = path.to_path_buf();
+        let bytes = std::fs::metadata(&kept).unwrap().len();
+
+        let (id, view) = store.adopt(SpilledOutput::new(path, bytes, 500), "line 1\n[...]\nline 500");
+        assert!(view.starts_with(&format!("[Output ID: {}]\nline 1", id)));
+        assert!(view.contains("any of the 500 lines"));
+
+        let range = store.get_range(&id, 250, Some(252)).unwrap();
+        assert!(range.contains("line 251\nline 252\n"));
+        assert!(range.contains("of 500"));
+
+        // The store owns the file from here on
+        drop(store);
+        assert!(!kept.exists());
+    }
+
     #[test]
     fn test_shell_output_with_exit_code() {
         let mut store = OutputStore::new();
@@ -11,6 +11,9 @@ pub fn supports_tools(provider: &Provider, model: &str) -> bool {
         // All Gemini models we support have tool capability
         Provider::Gemini => true,
 
+        // Same Gemini models, served through Vertex AI
+        Provider::VertexAI => true,
+
         // All Claude models support tools
         Provider::Anthropic => true,
 
@@ -100,6 +103,31 @@ fn resolve_gemini_alias(model: &str) -> &str {
     }
 }
 
+/// Native Gemini API endpoint; models are appended to it
+const GEMINI_NATIVE_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/";
+
+/// Move a Gemini provider onto the native API, which is the only place Gemini's
+/// built-in tools (code execution, Google Search, URL context) can be enabled.
+/// Models before 3.x default to the OpenAI-compatible endpoint; Vertex AI is
+/// always native.
+pub fn require_native_gemini_api(info: &mut ProviderInfo) -> Result<()> {
+    if info.provider == Provider::VertexAI {
+        return Ok(());
+    }
+    if info.provider != Provider::Gemini {
+        return Err(anyhow!(
+            "Gemini built-in tools need a Gemini model, not '{}' ({})",
+            info.resolved_model,
+            info.provider
+        ));
+    }
+    if !info.use_native_gemini_api {
+        info.use_native_gemini_api = true;
+        info.base_url = GEMINI_NATIVE_BASE_URL.to_string();
+    }
+    Ok(())
+}
+
 /// Detect the provider based on model name
 pub fn detect_provider(model: &str) -> Result<ProviderInfo> {
     let ollama_host = env::var("OLLAMA_HOST").unwrap_or_else(|_| "http://localhost:11434".to_string());
@@ -138,7 +166,7 @@ pub fn detect_provider(model: &str) -> Result<ProviderInfo> {
         // OpenAI-compatible endpoint cannot round-trip.
         let use_native_api = resolved_model.starts_with("gemini-3");
         let base_url = if use_native_api {
-            "https://generativelanguage.googleapis.com/v1beta/models/".to_string()
+            GEMINI_NATIVE_BASE_URL.to_string()
         } else {
             "https://generativelanguage.googleapis.com/v1beta/openai/".to_string()
         };
@@ -200,6 +228,33 @@ pub fn detect_provider(model: &str) -> Result<ProviderInfo> {
         });
     }
 
+    // 3b. Check for Vertex AI Gemini models (vertex:<model>), authenticated with
+    // a service account instead of GEMINI_API_KEY
+    if let Some(vertex_model) = model.strip_prefix("vertex:") {
+        let credentials = env::var("GOOGLE_APPLICATION_CREDENTIALS").map_err(|_| {
+            anyhow!("GOOGLE_APPLICATION_CREDENTIALS (service-account key file) required for Vertex AI models")
+        })?;
+        let project = match env::var("GOOGLE_CLOUD_PROJECT") {
+            Ok(project) => project,
+            Err(_) => crate::vertex::ServiceAccount::load(std::path::Path::new(&credentials))?
+                .project_id
+                .ok_or_else(|| {
+                    anyhow!("GOOGLE_CLOUD_PROJECT required: the service-account key has no project_id")
+                })?,
+        };
+        let location = env::var("GOOGLE_CLOUD_LOCATION")
+            .unwrap_or_else(|_| crate::vertex::DEFAULT_LOCATION.to_string());
+
+        return Ok(ProviderInfo {
+            provider: Provider::VertexAI,
+            base_url: crate::vertex::base_url(&project, &location),
+            api_key: credentials,
+            resolved_model: resolve_gemini_alias(vertex_model).to_string(),
+            use_native_gemini_api: true,
+            azure_api_version: None,
+        });
+    }
+
     // 4. Check for HuggingFace local models (hf:gemma4:*)
     if let Some(hf_model) = model.strip_prefix("hf:") {
         let resolved = crate::local::resolve_hf_alias(hf_model);
@@ -233,7 +288,7 @@ pub fn detect_provider(model: &str) -> Result<ProviderInfo> {
     if check_ollama_available(Some(model)).is_ok() {
         return Ok(ProviderInfo {
             provider: Provider::Ollama,
-            base_url: format!("{}/v1/", ollama_host),
+            base_url: format!("{}/", ollama_host),
             api_key: "ollama".to_string(),
             resolved_model: model.to_string(),
             use_native_gemini_api: false,
@@ -270,7 +325,7 @@ pub fn detect_provider(model: &str) -> Result<ProviderInfo> {
     if check_ollama_available(None).is_ok() {
         Ok(ProviderInfo {
             provider: Provider::Ollama,
-            base_url: format!("{}/v1/", ollama_host),
+            base_url: format!("{}/", ollama_host),
             api_key: "ollama".to_string(),
             resolved_model: model.to_string(),
             use_native_gemini_api: false,
@@ -384,6 +439,15 @@ pub fn get_available_models() -> Vec<(Provider, Vec<String>, bool)> {
     };
     result.push((Provider::AzureOpenAI, azure_models, azure_available));
 
+    // Vertex AI
+    let vertex_available = env::var("GOOGLE_APPLICATION_CREDENTIALS").is_ok();
+    let vertex_models = if vertex_available {
+        vec!["vertex:<gemini-model> (e.g. vertex:gemini-2.5-pro)".to_string()]
+    } else {
+        vec![]
+    };
+    result.push((Provider::VertexAI, vertex_models, vertex_available));
+
     // Ollama
     let ollama_models = check_ollama_available(None).unwrap_or_default();
     let ollama_available = !ollama_models.is_empty();
@@ -629,6 +693,35 @@ mod tests {
         std::env::remove_var("GEMINI_API_KEY");
     }
 
+    #[test]
+    fn test_require_native_gemini_api_switches_openai_compatible_models() {
+        let _lock = ENV_LOCK.lock().unwrap();
+        std::env::set_var("GEMINI_API_KEY", "test-key");
+
+        let mut info = detect_provider("gemini-2.5-flash").unwrap();
+        assert!(!info.use_native_gemini_api);
+        require_native_gemini_api(&mut info).unwrap();
+        assert!(info.use_native_gemini_api);
+        assert_eq!(info.base_url, "https://generativelanguage.googleapis.com/v1beta/models/");
+        assert_eq!(info.resolved_model, "gemini-2.5-flash");
+
+        std::env::remove_var("GEMINI_API_KEY");
+    }
+
+    #[test]
+    fn test_require_native_gemini_api_rejects_other_providers() {
+        let mut info = ProviderInfo {
+            provider: Provider::Anthropic,
+            base_url: "https://api.anthropic.com/v1/".to_string(),
+            api_key: "test-key".to_string(),
+            resolved_model: "claude-sonnet-4-20250514".to_string(),
+            use_native_gemini_api: false,
+            azure_api_version: None,
+        };
+        let err = require_native_gemini_api(&mut info).unwrap_err();
+        assert!(err.to_string().contains("need a Gemini model"), "{}", err);
+    }
+
     #[test]
     fn test_anthropic_detection() {
         let _lock = ENV_LOCK.lock().unwrap();
@@ -684,6 +777,50 @@ mod tests {
         std::env::remove_var("AZURE_OPENAI_API_KEY");
     }
 
+    #[test]
+    fn test_vertex_detection() {
+        let _lock = ENV_LOCK.lock().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let key_path = dir.path().join("sa.json");
+        let key = serde_json::json!({
+            "client_email": "sa@key-project.iam.gserviceaccount.com",
+            "private_key": "unused",
+            "project_id": "key-project",
+        });
+        std::fs::write(&key_path, key.to_string()).unwrap();
+        std::env::set_var("GOOGLE_APPLICATION_CREDENTIALS", &key_path);
+        std::env::remove_var("GOOGLE_CLOUD_PROJECT");
+        std::env::set_var("GOOGLE_CLOUD_LOCATION", "europe-west1");
+
+        let provider_info = detect_provider("vertex:pro").unwrap();
+        assert_eq!(provider_info.provider, Provider::VertexAI);
+        assert!(provider_info.use_native_gemini_api);
+        assert_eq!(provider_info.resolved_model, resolve_gemini_alias("pro"));
+        assert_eq!(
+            provider_info.base_url,
+            "https://europe-west1-aiplatform.googleapis.com/v1/projects/key-project/locations/europe-west1/publishers/google/models/"
+        );
+
+        // An explicit project wins over the key's own
+        std::env::set_var("GOOGLE_CLOUD_PROJECT", "other-project");
+        let provider_info = detect_provider("vertex:gemini-2.5-flash").unwrap();
+        assert!(provider_info.base_url.contains("/projects/other-project/"));
+        assert_eq!(provider_info.resolved_model, "gemini-2.5-flash");
+
+        std::env::remove_var("GOOGLE_APPLICATION_CREDENTIALS");
+        std::env::remove_var("GOOGLE_CLOUD_PROJECT");
+        std::env::remove_var("GOOGLE_CLOUD_LOCATION");
+    }
+
+    #[test]
+    fn test_vertex_requires_credentials() {
+        let _lock = ENV_LOCK.lock().unwrap();
+        std::env::remove_var("GOOGLE_APPLICATION_CREDENTIALS");
+
+        let err = detect_provider("vertex:gemini-2.5-pro").unwrap_err();
+        assert!(err.to_string().contains("GOOGLE_APPLICATION_CREDENTIALS"), "{}", err);
+    }
+
     #[test]
     fn test_azure_openai_requires_env_vars() {
         let _lock = ENV_LOCK.lock().unwrap();
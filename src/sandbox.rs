@@ -0,0 +1,352 @@
+//! Opt-in sandbox for the tools a scheduled agent runs.
+//!
+//! A profile, chosen per agent in agents.toml, confines the filesystem tools
+//! (Read, Write, Edit, Glob, ListDir, Grep) to a set of roots, by default just
+//! the agent's `working_dir`. Bash runs under a Landlock ruleset: system paths are
+//! read-only, the workspace and /tmp are writable, everything else (including
+//! `~/.eunice`) is off limits, and TCP can be denied altogether.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Profiles that need no `[sandbox.<name>]` table
+pub const BUILTIN_PROFILES: &[&str] = &["workspace", "offline"];
+
+/// Read-only for sandboxed commands: enough to run the usual toolchains
+#[cfg(target_os = "linux")]
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/bin", "/sbin", "/usr", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/nix", "/proc", "/sys",
+    "/dev", "/run",
+];
+
+/// Writable for sandboxed commands in addition to the profile's roots
+#[cfg(target_os = "linux")]
+const SYSTEM_WRITE_PATHS: &[&str] = &[
+    "/tmp",
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/tty",
+    "/dev/pts",
+    "/dev/shm",
+];
+
+/// A `[sandbox.<name>]` table in agents.toml. Relative paths are resolved
+/// against the agent's `working_dir`, and `~` against the home directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SandboxProfileSpec {
+    /// Extra directories tools may read (e.g. a toolchain in `~/.cargo`)
+    #[serde(default)]
+    pub readable: Vec<String>,
+    /// Extra directories tools may write
+    #[serde(default)]
+    pub writable: Vec<String>,
+    /// Allow Bash to open TCP connections and listen (default: true)
+    #[serde(default = "default_network")]
+    pub network: bool,
+}
+
+fn default_network() -> bool {
+    true
+}
+
+impl Default for SandboxProfileSpec {
+    fn default() -> Self {
+        Self {
+            readable: Vec::new(),
+            writable: Vec::new(),
+            network: default_network(),
+        }
+    }
+}
+
+/// What a sandboxed agent's tools may touch. All roots are canonical.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    /// Profile name, for messages
+    pub profile: String,
+    /// Roots the tools may read; includes every writable root
+    readable: Vec<PathBuf>,
+    writable: Vec<PathBuf>,
+    pub network: bool,
+}
+
+impl SandboxPolicy {
+    /// Resolve profile `name` (built-in or from `profiles`) for an agent
+    /// working in `workspace`
+    pub fn resolve(
+        name: &str,
+        workspace: &Path,
+        profiles: &BTreeMap<String, SandboxProfileSpec>,
+    ) -> Result<Self> {
+        let spec = match name {
+            "workspace" => SandboxProfileSpec::default(),
+            "offline" => SandboxProfileSpec {
+                network: false,
+                ..SandboxProfileSpec::default()
+            },
+            _ => profiles.get(name).cloned().ok_or_else(|| {
+                anyhow!(
+                    "unknown sandbox profile '{}' (built-in: {}, or define [sandbox.{}])",
+                    name,
+                    BUILTIN_PROFILES.join(", "),
+                    name
+                )
+            })?,
+        };
+
+        let workspace = workspace.canonicalize().map_err(|e| {
+            anyhow!(
+                "sandbox '{}': workspace '{}': {}",
+                name,
+                workspace.display(),
+                e
+            )
+        })?;
+        let resolve_root = |path: &str| -> Result<PathBuf> {
+            let expanded = match path.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir()
+                    .ok_or_else(|| anyhow!("no home directory to expand '{}'", path))?
+                    .join(rest),
+                None => workspace.join(path),
+            };
+            expanded.canonicalize().map_err(|e| {
+                anyhow!(
+                    "sandbox '{}': path '{}' could not be resolved: {}",
+                    name,
+                    path,
+                    e
+                )
+            })
+        };
+
+        let mut writable = vec![workspace.clone()];
+        for path in &spec.writable {
+            writable.push(resolve_root(path)?);
+        }
+        let mut readable = writable.clone();
+        for path in &spec.readable {
+            readable.push(resolve_root(path)?);
+        }
+
+        Ok(Self {
+            profile: name.to_string(),
+            readable,
+            writable,
+            network: spec.network,
+        })
+    }
+
+    /// Fail unless `path` is under a readable root
+    pub fn check_read(&self, path: &Path) -> Result<()> {
+        self.check(path, &self.readable, "read")
+    }
+
+    /// Fail unless `path` is under a writable root
+    pub fn check_write(&self, path: &Path) -> Result<()> {
+        self.check(path, &self.writable, "write")
+    }
+
+    fn check(&self, path: &Path, roots: &[PathBuf], access: &str) -> Result<()> {
+        let resolved = resolve_path(path)?;
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            return Ok(());
+        }
+        let roots: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+        Err(anyhow!(
+            "{} is outside the sandbox: profile '{}' allows {} access only under {}",
+            path.display(),
+            self.profile,
+            access,
+            roots.join(", ")
+        ))
+    }
+
+    /// Confine the process `cmd` spawns with Landlock. Fails, rather than
+    /// running the command unconfined, if the kernel cannot enforce the rules.
+    #[cfg(target_os = "linux")]
+    pub fn restrict_command(&self, cmd: &mut tokio::process::Command) -> Result<()> {
+        use landlock::{
+            path_beneath_rules, Access, AccessFs, AccessNet, CompatLevel, Compatible, Ruleset,
+            RulesetAttr, RulesetCreatedAttr, RulesetStatus, ABI,
+        };
+
+        let abi = ABI::V5;
+        let unsupported = |e: landlock::RulesetError| {
+            anyhow!(
+                "sandbox '{}': the kernel cannot enforce it ({}); Landlock (Linux 5.13+, or 6.7+ for network = false) is required",
+                self.profile,
+                e
+            )
+        };
+
+        // Landlock itself is required; newer access rights are best effort
+        let mut ruleset = Ruleset::default()
+            .set_compatibility(CompatLevel::HardRequirement)
+            .handle_access(AccessFs::from_all(ABI::V1))
+            .map_err(unsupported)?
+            .set_compatibility(CompatLevel::BestEffort)
+            .handle_access(AccessFs::from_all(abi))
+            .map_err(unsupported)?;
+        if !self.network {
+            // Handled with no port rules: every TCP bind and connect is denied
+            ruleset = ruleset
+                .set_compatibility(CompatLevel::HardRequirement)
+                .handle_access(AccessNet::from_all(ABI::V4))
+                .map_err(unsupported)?
+                .set_compatibility(CompatLevel::BestEffort);
+        }
+
+        let read_paths = SYSTEM_READ_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(self.readable.iter().cloned());
+        let write_paths = SYSTEM_WRITE_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(self.writable.iter().cloned());
+        let ruleset = ruleset
+            .create()
+            .map_err(unsupported)?
+            .add_rules(path_beneath_rules(read_paths, AccessFs::from_read(abi)))
+            .map_err(unsupported)?
+            .add_rules(path_beneath_rules(write_paths, AccessFs::from_all(abi)))
+            .map_err(unsupported)?;
+
+        // Built here, applied in the child between fork and exec, where only
+        // the restrict syscalls run
+        let ruleset = std::sync::Mutex::new(Some(ruleset));
+        unsafe {
+            cmd.pre_exec(move || {
+                let denied = || std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+                let ruleset = ruleset
+                    .lock()
+                    .map_err(|_| denied())?
+                    .take()
+                    .ok_or_else(denied)?;
+                match ruleset.restrict_self() {
+                    Ok(status) if status.ruleset != RulesetStatus::NotEnforced => Ok(()),
+                    _ => Err(denied()),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn restrict_command(&self, _cmd: &mut tokio::process::Command) -> Result<()> {
+        Err(anyhow!(
+            "sandbox '{}': sandboxed Bash is only supported on Linux",
+            self.profile
+        ))
+    }
+}
+
+/// `path` made absolute with symlinks resolved. A path that does not exist
+/// yet (a file about to be written) resolves through its nearest existing
+/// ancestor.
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            let mut resolved = resolved;
+            for part in missing.iter().rev() {
+                resolved.push(part);
+            }
+            return Ok(resolved);
+        }
+        match (existing.components().next_back(), existing.parent()) {
+            (Some(Component::Normal(part)), Some(parent)) => {
+                missing.push(part.to_os_string());
+                existing = parent;
+            }
+            _ => return Err(anyhow!("cannot resolve path {}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn policy(
+        dir: &TempDir,
+        name: &str,
+        profiles: &BTreeMap<String, SandboxProfileSpec>,
+    ) -> SandboxPolicy {
+        SandboxPolicy::resolve(name, dir.path(), profiles).unwrap()
+    }
+
+    #[test]
+    fn test_workspace_profile_confines_to_working_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let policy = policy(&dir, "workspace", &BTreeMap::new());
+
+        assert!(policy.network);
+        assert!(policy.check_read(&dir.path().join("a.txt")).is_ok());
+        assert!(policy
+            .check_write(&dir.path().join("new/dir/b.txt"))
+            .is_ok());
+        assert!(policy.check_read(Path::new("/etc/hostname")).is_err());
+        let err = policy
+            .check_write(&dir.path().join("../escape.txt"))
+            .unwrap_err();
+        assert!(err.to_string().contains("outside the sandbox"), "{}", err);
+    }
+
+    #[test]
+    fn test_symlink_out_of_workspace_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        let policy = policy(&dir, "workspace", &BTreeMap::new());
+
+        assert!(policy.check_write(&dir.path().join("link/x.txt")).is_err());
+    }
+
+    #[test]
+    fn test_custom_profile_roots_and_network() {
+        let dir = TempDir::new().unwrap();
+        let shared = TempDir::new().unwrap();
+        let cache = TempDir::new().unwrap();
+        let mut profiles = BTreeMap::new();
+        profiles.insert(
+            "build".to_string(),
+            SandboxProfileSpec {
+                readable: vec![shared.path().display().to_string()],
+                writable: vec![cache.path().display().to_string()],
+                network: false,
+            },
+        );
+        let policy = policy(&dir, "build", &profiles);
+
+        assert!(!policy.network);
+        assert!(policy.check_read(&shared.path().join("x")).is_ok());
+        assert!(policy.check_write(&shared.path().join("x")).is_err());
+        assert!(policy.check_write(&cache.path().join("x")).is_ok());
+
+        assert!(
+            !SandboxPolicy::resolve("offline", dir.path(), &profiles)
+                .unwrap()
+                .network
+        );
+        let err = SandboxPolicy::resolve("nope", dir.path(), &profiles).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown sandbox profile"),
+            "{}",
+            err
+        );
+    }
+}
@@ -4,6 +4,7 @@
 //! adapt to the user's terminal theme instead of hardcoding a background.
 #![allow(dead_code)] // full palette is kept for reuse; not every color is wired in yet.
 
+use crate::tools::{todo, TodoItem, TodoStatus};
 use crossterm::terminal;
 
 // 256-color SGR foreground codes + attributes.
@@ -93,6 +94,38 @@ pub fn thinking_line(glyph: &str, verb: &str, elapsed_secs: Option<u64>, effort:
     format!("{ACCENT}{} {}…{}{RESET}", glyph, verb, suffix)
 }
 
+/// Most tasks the input box's checklist shows.
+const TODO_PANEL_ITEMS: usize = 5;
+
+/// The task list as lines for the input box: progress, then the tasks (only the unfinished ones
+/// when they don't all fit). Empty once every task is done. Lines never wrap at `cols`.
+pub fn todo_panel_with_width(items: &[TodoItem], cols: usize) -> Vec<String> {
+    if items.iter().all(|item| item.status == TodoStatus::Done) {
+        return Vec::new();
+    }
+    let shown: Vec<&TodoItem> = if items.len() <= TODO_PANEL_ITEMS {
+        items.iter().collect()
+    } else {
+        items.iter().filter(|item| item.status != TodoStatus::Done).collect()
+    };
+    let text_cols = cols.saturating_sub(4).max(1);
+
+    let mut lines = vec![format!("{DIM}  {}{RESET}", todo::progress(items))];
+    for item in shown.iter().take(TODO_PANEL_ITEMS) {
+        let (glyph, color) = match item.status {
+            TodoStatus::Done => ("✓", DIM),
+            TodoStatus::InProgress => ("▸", ACCENT),
+            TodoStatus::Pending => ("○", ""),
+        };
+        let text: String = item.content.chars().take(text_cols).collect();
+        lines.push(format!("{color}  {glyph} {text}{RESET}"));
+    }
+    if shown.len() > TODO_PANEL_ITEMS {
+        lines.push(format!("{DIM}  … {} more{RESET}", shown.len() - TODO_PANEL_ITEMS));
+    }
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +186,34 @@ mod tests {
         assert!(bare.contains("Thinking…"));
         assert!(!bare.contains('('));
     }
+
+    fn task(content: &str, status: TodoStatus) -> TodoItem {
+        TodoItem { content: content.to_string(), status }
+    }
+
+    #[test]
+    fn todo_panel_shows_unfinished_lists() {
+        assert!(todo_panel_with_width(&[], 40).is_empty());
+        assert!(todo_panel_with_width(&[task("a", TodoStatus::Done)], 40).is_empty());
+
+        let lines = todo_panel_with_width(
+            &[task("plan", TodoStatus::Done), task("build", TodoStatus::InProgress)],
+            40,
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("1/2 tasks done"));
+        assert!(lines[1].contains("✓ plan"));
+        assert!(lines[2].contains("▸ build"));
+    }
+
+    #[test]
+    fn todo_panel_drops_done_tasks_and_long_text_to_fit() {
+        let mut items: Vec<TodoItem> = (0..4).map(|i| task(&format!("old {i}"), TodoStatus::Done)).collect();
+        items.extend((0..7).map(|i| task(&format!("new {i} {}", "x".repeat(80)), TodoStatus::Pending)));
+        let lines = todo_panel_with_width(&items, 30);
+        assert_eq!(lines.len(), 1 + TODO_PANEL_ITEMS + 1);
+        assert!(!lines.iter().any(|l| l.contains("old")));
+        assert!(lines.last().unwrap().contains("2 more"));
+        assert!(lines.iter().all(|l| visible_len(l) <= 30));
+    }
 }
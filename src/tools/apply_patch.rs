@@ -0,0 +1,1141 @@
+//! `ApplyPatch`: a unified diff (`diff -u`, `git diff`) applied across files,
+//! including created, deleted and renamed ones.
+//!
+//! Hunks are found by their context rather than their line numbers, which
+//! models rarely get right: the nearest exact match wins, then one ignoring
+//! whitespace, then one with up to two context lines dropped from each end
+//! (patch's "fuzz"). Every file is patched in memory first, so when a hunk
+//! fails nothing is written and the reply lists what failed.
+
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{checkpointed, make_tool, ToolContext};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Context lines a hunk may lose from either end and still apply
+const MAX_FUZZ: usize = 2;
+
+/// ApplyPatch tool for multi-file unified diffs
+pub struct ApplyPatchTool {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+/// One file's section of a patch
+#[derive(Debug, Default)]
+struct FilePatch {
+    /// `None` when the file is created
+    old_path: Option<String>,
+    /// `None` when the file is deleted
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+    /// Started by `diff --git`, whose paths carry `a/` and `b/` prefixes
+    git: bool,
+    /// `---`/`+++` lines seen, so a later pair starts the next file
+    has_headers: bool,
+    binary: bool,
+}
+
+#[derive(Debug)]
+struct Hunk {
+    /// The `@@ ... @@` line, for messages
+    header: String,
+    /// First old line, 1-based; `None` when the header has no numbers
+    old_start: Option<usize>,
+    lines: Vec<(Line, String)>,
+    /// `\ No newline at end of file` after an old or a new line
+    old_no_eol: bool,
+    new_no_eol: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line {
+    Context,
+    Remove,
+    Add,
+}
+
+/// A file as it reads, or `None` for one that does not exist
+type Contents = Option<FileContents>;
+
+/// A file's bytes, and the permissions of the file they were read from, which
+/// a file written afresh (renamed, or restored after a deletion) is given
+#[derive(Debug, Clone)]
+struct FileContents {
+    bytes: Vec<u8>,
+    permissions: Option<std::fs::Permissions>,
+}
+
+impl FileContents {
+    fn new(bytes: Vec<u8>, permissions: Option<std::fs::Permissions>) -> Self {
+        Self { bytes, permissions }
+    }
+
+    /// The bytes as text, for hunks to apply to
+    fn text(&self, name: &str) -> Result<&str> {
+        std::str::from_utf8(&self.bytes)
+            .map_err(|_| anyhow!("{}: cannot patch a binary file", name))
+    }
+}
+
+/// The checked-out result of a patch, written only when every hunk applied
+struct Plan {
+    /// Touched files in patch order, with the path as the patch names it
+    order: Vec<(PathBuf, String)>,
+    /// Touched files as they read before the patch, to roll back to
+    original: HashMap<PathBuf, Contents>,
+    state: HashMap<PathBuf, Contents>,
+    summary: Vec<String>,
+    notes: Vec<String>,
+}
+
+impl ApplyPatchTool {
+    pub fn new() -> Self {
+        Self::with_cwd(None)
+    }
+
+    /// Resolve the patch's paths against `cwd` instead of the process
+    /// working directory
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.cwd {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "ApplyPatch",
+            "Apply a unified diff (as from `diff -u` or `git diff`) to one or more files. \
+             Handles changed, created (--- /dev/null), deleted (+++ /dev/null) and renamed files; \
+             a/ and b/ path prefixes are stripped. Hunks are located by their context, so line \
+             numbers may be approximate. If any hunk does not apply, no file is changed and the \
+             failures are listed. Prefer this over several Write calls for changes spanning files.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "patch": {
+                        "type": "string",
+                        "description": "The diff, with ---/+++ file headers and @@ hunks"
+                    }
+                },
+                "required": ["patch"]
+            }),
+        )
+    }
+
+    /// Patch every file in memory, failing with the full list of problems
+    /// if anything does not apply
+    fn plan(&self, args: &serde_json::Value) -> Result<Plan> {
+        let patch = args["patch"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Missing 'patch' parameter"))?;
+        let files = parse(patch)?;
+
+        let mut plan = Plan {
+            order: Vec::new(),
+            original: HashMap::new(),
+            state: HashMap::new(),
+            summary: Vec::new(),
+            notes: Vec::new(),
+        };
+        let mut failures = Vec::new();
+        let hunk_count: usize = files.iter().map(|f| f.hunks.len()).sum();
+        for file in &files {
+            if let Err(e) = self.plan_file(file, &mut plan) {
+                failures.push(format!("- {}", e));
+            }
+        }
+
+        if !failures.is_empty() {
+            bail!(
+                "Patch not applied; no files were changed ({} file section(s), {} hunk(s)):\n{}",
+                files.len(),
+                hunk_count,
+                failures.join("\n")
+            );
+        }
+        Ok(plan)
+    }
+
+    fn plan_file(&self, file: &FilePatch, plan: &mut Plan) -> Result<()> {
+        let name = file
+            .new_path
+            .as_deref()
+            .or(file.old_path.as_deref())
+            .ok_or_else(|| anyhow!("a file section names no file"))?;
+        if file.binary {
+            bail!("{}: binary patches are not supported", name);
+        }
+
+        match (&file.old_path, &file.new_path) {
+            (None, Some(new)) => {
+                let path = self.touch(new, plan)?;
+                if plan.state[&path].is_some() {
+                    bail!("{}: cannot create, the file already exists", new);
+                }
+                let contents = apply_hunks(new, "", &file.hunks, &mut plan.notes)?;
+                plan.state
+                    .insert(path, Some(FileContents::new(contents.into_bytes(), None)));
+                plan.summary.push(format!("A {}", new));
+            }
+            (Some(old), None) => {
+                let path = self.touch(old, plan)?;
+                let current = existing(old, &plan.state[&path])?;
+                if !file.hunks.is_empty() {
+                    let rest = apply_hunks(old, current.text(old)?, &file.hunks, &mut plan.notes)?;
+                    if !rest.trim().is_empty() {
+                        bail!(
+                            "{}: the file has lines the deletion does not list; not deleting it",
+                            old
+                        );
+                    }
+                }
+                plan.state.insert(path, None);
+                plan.summary.push(format!("D {}", old));
+            }
+            (Some(old), Some(new)) if old == new => {
+                let path = self.touch(new, plan)?;
+                let current = existing(new, &plan.state[&path])?;
+                if file.hunks.is_empty() {
+                    plan.notes.push(format!("{}: no content changes", new));
+                    return Ok(());
+                }
+                let contents = apply_hunks(new, current.text(new)?, &file.hunks, &mut plan.notes)?;
+                let permissions = current.permissions.clone();
+                plan.state.insert(
+                    path,
+                    Some(FileContents::new(contents.into_bytes(), permissions)),
+                );
+                plan.summary
+                    .push(format!("M {} ({})", new, hunks(file.hunks.len())));
+            }
+            (Some(old), Some(new)) => {
+                let from = self.touch(old, plan)?;
+                let to = self.touch(new, plan)?;
+                let current = existing(old, &plan.state[&from])?.clone();
+                if plan.state[&to].is_some() {
+                    bail!("{}: cannot rename {} onto an existing file", new, old);
+                }
+                // A pure rename moves the bytes as they are, binary or not
+                let contents = if file.hunks.is_empty() {
+                    current
+                } else {
+                    let text = apply_hunks(new, current.text(old)?, &file.hunks, &mut plan.notes)?;
+                    FileContents::new(text.into_bytes(), current.permissions)
+                };
+                plan.state.insert(from, None);
+                plan.state.insert(to, Some(contents));
+                plan.summary.push(format!("R {} -> {}", old, new));
+            }
+            (None, None) => unreachable!("a section without paths has no name"),
+        }
+        Ok(())
+    }
+
+    /// Resolve and check `path`, reading it into the plan the first time
+    fn touch(&self, name: &str, plan: &mut Plan) -> Result<PathBuf> {
+        let path = self.resolve(name);
+        if let Some(policy) = &self.sandbox {
+            policy
+                .check_write(&path)
+                .map_err(|e| anyhow!("{}: {}", name, e))?;
+        }
+        if !plan.state.contains_key(&path) {
+            let contents = read(name, &path)?;
+            plan.original.insert(path.clone(), contents.clone());
+            plan.state.insert(path.clone(), contents);
+            plan.order.push((path.clone(), name.to_string()));
+        }
+        Ok(path)
+    }
+}
+
+impl Plan {
+    /// Put `written` back as the files read before the patch, newest first,
+    /// and remove the directories created for them. Returns the names of
+    /// files that could not be restored.
+    fn roll_back(&self, written: &[(PathBuf, String)], created_dirs: &[PathBuf]) -> Vec<String> {
+        let mut unrestored = Vec::new();
+        for (path, name) in written.iter().rev() {
+            if put(path, name, &self.original[path], &mut Vec::new()).is_err() {
+                unrestored.push(name.clone());
+            }
+        }
+        for dir in created_dirs.iter().rev() {
+            let _ = std::fs::remove_dir(dir);
+        }
+        unrestored
+    }
+
+    /// Every file the patch touches, for checkpoints
+    fn paths(&self) -> Vec<PathBuf> {
+        self.order.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// Write every touched file. If one cannot be written, the files already
+    /// written are put back as they were before reporting the error.
+    fn write(self) -> Result<String> {
+        let mut created_dirs = Vec::new();
+        for (done, (path, name)) in self.order.iter().enumerate() {
+            if let Err(e) = put(path, name, &self.state[path], &mut created_dirs) {
+                // The failed write may have left the file half written
+                let _ = put(path, name, &self.original[path], &mut Vec::new());
+                let unrestored = self.roll_back(&self.order[..done], &created_dirs);
+                if unrestored.is_empty() {
+                    return Err(e.context("the patch was rolled back; no files were changed"));
+                }
+                return Err(e.context(format!(
+                    "the patch was only partly rolled back; could not restore: {}",
+                    unrestored.join(", ")
+                )));
+            }
+        }
+
+        if self.summary.is_empty() {
+            return Ok("Patch contained no content changes".to_string());
+        }
+        let mut out = format!(
+            "Applied patch to {}:\n  {}",
+            files(self.summary.len()),
+            self.summary.join("\n  ")
+        );
+        if !self.notes.is_empty() {
+            out.push('\n');
+            out.push_str(&self.notes.join("\n"));
+        }
+        Ok(out)
+    }
+}
+
+impl crate::tools::Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "ApplyPatch"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let plan = self.plan(&args)?;
+            let paths = plan.paths();
+            checkpointed(ctx.checkpoints, "ApplyPatch", paths, || plan.write())
+        })
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
+/// Make `path` read `contents`, or delete it for `None`, recording the
+/// directories created for it
+fn put(
+    path: &Path,
+    name: &str,
+    contents: &Contents,
+    created_dirs: &mut Vec<PathBuf>,
+) -> Result<()> {
+    match contents {
+        Some(contents) => {
+            if let Some(parent) = path.parent() {
+                let missing: Vec<PathBuf> = parent
+                    .ancestors()
+                    .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+                    .map(Path::to_path_buf)
+                    .collect();
+                created_dirs.extend(missing.into_iter().rev());
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory for {}", name))?;
+            }
+            // Writing in place keeps the file's permissions (and any symlink);
+            // a new file takes those of the file it came from
+            let fresh = !path.exists();
+            std::fs::write(path, &contents.bytes)
+                .with_context(|| format!("Failed to write file: {}", name))?;
+            match &contents.permissions {
+                Some(permissions) if fresh => std::fs::set_permissions(path, permissions.clone())
+                    .with_context(|| format!("Failed to set permissions: {}", name)),
+                _ => Ok(()),
+            }
+        }
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to delete file: {}", name))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+impl Default for ApplyPatchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hunks(n: usize) -> String {
+    if n == 1 {
+        "1 hunk".to_string()
+    } else {
+        format!("{} hunks", n)
+    }
+}
+
+fn files(n: usize) -> String {
+    if n == 1 {
+        "1 file".to_string()
+    } else {
+        format!("{} files", n)
+    }
+}
+
+fn read(name: &str, path: &Path) -> Result<Contents> {
+    if path.is_dir() {
+        bail!("{}: is a directory", name);
+    }
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let permissions = std::fs::metadata(path).ok().map(|m| m.permissions());
+            Ok(Some(FileContents::new(bytes, permissions)))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!("{}: failed to read: {}", name, e)),
+    }
+}
+
+fn existing<'a>(name: &str, contents: &'a Contents) -> Result<&'a FileContents> {
+    contents
+        .as_ref()
+        .ok_or_else(|| anyhow!("{}: no such file", name))
+}
+
+/// Split a patch into file sections
+fn parse(patch: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            let (old, new) = split_git_paths(rest);
+            let (old_path, new_path) = strip_prefixes(Some(old), Some(new), true);
+            current = Some(FilePatch {
+                old_path,
+                new_path,
+                git: true,
+                ..FilePatch::default()
+            });
+            i += 1;
+        } else if is_file_header(&lines, i) {
+            let section = match current.take() {
+                Some(file) if file.git && !file.has_headers && file.hunks.is_empty() => file,
+                finished => {
+                    files.extend(finished);
+                    FilePatch::default()
+                }
+            };
+            let (old_path, new_path) = strip_prefixes(
+                header_path(&line[4..]),
+                header_path(&lines[i + 1][4..]),
+                section.git,
+            );
+            current = Some(FilePatch {
+                old_path,
+                new_path,
+                has_headers: true,
+                ..section
+            });
+            i += 2;
+        } else if line.starts_with("@@") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| anyhow!("hunk '{}' comes before any ---/+++ file header", line))?;
+            let (hunk, next) = parse_hunk(&lines, i)?;
+            file.hunks.push(hunk);
+            i = next;
+        } else {
+            if let Some(file) = current.as_mut() {
+                if let Some(path) = line.strip_prefix("rename from ") {
+                    file.old_path = Some(path.to_string());
+                } else if let Some(path) = line.strip_prefix("rename to ") {
+                    file.new_path = Some(path.to_string());
+                } else if line.starts_with("new file mode") {
+                    file.old_path = None;
+                } else if line.starts_with("deleted file mode") {
+                    file.new_path = None;
+                } else if line.starts_with("GIT binary patch") || line.starts_with("Binary files ")
+                {
+                    file.binary = true;
+                }
+            }
+            i += 1;
+        }
+    }
+    files.extend(current.take());
+
+    if files.is_empty() {
+        bail!("No file changes found; expected a unified diff with ---/+++ headers and @@ hunks");
+    }
+    Ok(files)
+}
+
+fn is_file_header(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+}
+
+/// `a/x b/y` from a `diff --git` line. Ambiguous when paths contain " b/";
+/// then the split that names the same file on both sides wins.
+fn split_git_paths(rest: &str) -> (String, String) {
+    let splits: Vec<usize> = rest.match_indices(" b/").map(|(i, _)| i).collect();
+    let pick = splits
+        .iter()
+        .find(|&&i| rest[..i].strip_prefix("a/") == Some(&rest[i + 3..]))
+        .or(splits.first());
+    match pick {
+        Some(&i) => (rest[..i].to_string(), rest[i + 1..].to_string()),
+        None => (rest.to_string(), rest.to_string()),
+    }
+}
+
+/// The path of a `---`/`+++` line: up to a tab (diff puts timestamps there),
+/// `None` for /dev/null
+fn header_path(text: &str) -> Option<String> {
+    let path = text
+        .split('\t')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .trim_matches('"');
+    (path != "/dev/null" && !path.is_empty()).then(|| path.to_string())
+}
+
+/// Drop `a/` and `b/` from the paths of a `diff --git` line or a `---`/`+++`
+/// pair: always in a git diff, otherwise only when both paths have their
+/// prefix. `rename from`/`rename to` paths never carry one.
+fn strip_prefixes(
+    old: Option<String>,
+    new: Option<String>,
+    git: bool,
+) -> (Option<String>, Option<String>) {
+    let prefixed =
+        |path: &Option<String>, prefix: &str| path.as_deref().is_none_or(|p| p.starts_with(prefix));
+    let has_prefix =
+        git || (prefixed(&old, "a/") && prefixed(&new, "b/") && (old.is_some() || new.is_some()));
+    if !has_prefix {
+        return (old, new);
+    }
+    let strip = |path: Option<String>, prefix: &str| {
+        path.map(|p| p.strip_prefix(prefix).map_or(p.clone(), str::to_string))
+    };
+    (strip(old, "a/"), strip(new, "b/"))
+}
+
+/// Parse the hunk whose header is `lines[start]`, returning it and the index
+/// after it. Line counts in the header are not trusted: the hunk runs until
+/// the next header or a line that is not part of a diff.
+fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
+    let header = lines[start];
+    let old_start = header
+        .split_whitespace()
+        .nth(1)
+        .and_then(|range| range.strip_prefix('-'))
+        .and_then(|range| range.split(',').next())
+        .and_then(|n| n.parse().ok());
+    let mut hunk = Hunk {
+        header: header.trim_end().to_string(),
+        old_start,
+        lines: Vec::new(),
+        old_no_eol: false,
+        new_no_eol: false,
+    };
+    // Blank lines with their leading space lost; dropped if they end the hunk
+    let mut bare_blanks = 0;
+
+    let mut i = start + 1;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("@@") || line.starts_with("diff --git ") || is_file_header(lines, i) {
+            break;
+        }
+        let entry = match line.chars().next() {
+            Some(' ') => (Line::Context, &line[1..]),
+            Some('-') => (Line::Remove, &line[1..]),
+            Some('+') => (Line::Add, &line[1..]),
+            Some('\\') => {
+                match hunk.lines.last() {
+                    Some((Line::Remove, _)) => hunk.old_no_eol = true,
+                    Some((Line::Add, _)) => hunk.new_no_eol = true,
+                    _ => {
+                        hunk.old_no_eol = true;
+                        hunk.new_no_eol = true;
+                    }
+                }
+                i += 1;
+                continue;
+            }
+            None => (Line::Context, ""),
+            Some(_) => break,
+        };
+        if line.is_empty() {
+            bare_blanks += 1;
+        } else {
+            bare_blanks = 0;
+        }
+        hunk.lines.push((entry.0, entry.1.to_string()));
+        i += 1;
+    }
+    hunk.lines.truncate(hunk.lines.len() - bare_blanks);
+
+    if hunk.lines.is_empty() {
+        bail!("hunk '{}' has no lines", hunk.header);
+    }
+    Ok((hunk, i))
+}
+
+/// How a hunk's old lines were found in the file
+#[derive(Clone, Copy)]
+enum Match {
+    Exact,
+    TrailingWhitespace,
+    Whitespace,
+}
+
+impl Match {
+    const ALL: [Match; 3] = [Match::Exact, Match::TrailingWhitespace, Match::Whitespace];
+
+    fn eq(self, file_line: &str, patch_line: &str) -> bool {
+        match self {
+            Match::Exact => file_line == patch_line,
+            Match::TrailingWhitespace => file_line.trim_end() == patch_line.trim_end(),
+            Match::Whitespace => file_line.trim() == patch_line.trim(),
+        }
+    }
+}
+
+/// Apply `hunks` to `content`. Every line keeps its own ending; added lines
+/// take the ending most of the file's lines have.
+fn apply_hunks(
+    name: &str,
+    content: &str,
+    hunks: &[Hunk],
+    notes: &mut Vec<String>,
+) -> Result<String> {
+    let crlf = content.matches("\r\n").count();
+    let eol = if crlf > 0 && crlf * 2 >= content.matches('\n').count() {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut ends_with_eol = content.is_empty() || content.ends_with('\n');
+    // Each line's text and its ending, empty for a last line without one
+    let mut lines: Vec<(String, &str)> = content
+        .split_inclusive('\n')
+        .map(|line| match line.strip_suffix('\n') {
+            Some(text) => match text.strip_suffix('\r') {
+                Some(text) => (text.to_string(), "\r\n"),
+                None => (text.to_string(), "\n"),
+            },
+            None => (line.to_string(), ""),
+        })
+        .collect();
+    let mut failures = Vec::new();
+
+    // Where the file's lines are now relative to the numbering the patch uses
+    let mut offset: isize = 0;
+    // Hunks apply in order, each after the one before
+    let mut floor = 0;
+    for (n, hunk) in hunks.iter().enumerate() {
+        let label = format!("hunk {} ({})", n + 1, hunk.header);
+        // 0-based; an insertion-only hunk numbers the line it goes after
+        let base = hunk.old_start.map(|start| {
+            if hunk.lines.iter().all(|(kind, _)| *kind == Line::Add) {
+                start
+            } else {
+                start.saturating_sub(1)
+            }
+        });
+        let expected = match base {
+            Some(base) => {
+                (base as isize + offset).clamp(floor as isize, lines.len() as isize) as usize
+            }
+            None => floor,
+        };
+
+        let Some((pos, lead, trail, how)) = locate(&lines, hunk, expected, floor) else {
+            let first = hunk
+                .lines
+                .iter()
+                .find(|(kind, _)| *kind != Line::Add)
+                .map(|(_, text)| text.as_str())
+                .unwrap_or("");
+            failures.push(format!(
+                "{}: {}: context not found near line {} (first expected line: {:?})",
+                name,
+                label,
+                expected + 1,
+                first
+            ));
+            continue;
+        };
+
+        let body = &hunk.lines[lead..hunk.lines.len() - trail];
+        let mut at = pos;
+        let mut replacement = Vec::new();
+        for (kind, text) in body {
+            match kind {
+                // Keep the file's own text where the match ignored whitespace
+                Line::Context => {
+                    replacement.push(lines[at].clone());
+                    at += 1;
+                }
+                Line::Remove => at += 1,
+                Line::Add => replacement.push((text.clone(), eol)),
+            }
+        }
+        let added = replacement.len();
+        lines.splice(pos..at, replacement);
+
+        let mut how_notes = Vec::new();
+        if pos != expected {
+            how_notes.push(format!("offset {:+}", pos as isize - expected as isize));
+        }
+        match how {
+            Match::Exact => {}
+            Match::TrailingWhitespace => how_notes.push("ignoring trailing whitespace".to_string()),
+            Match::Whitespace => how_notes.push("ignoring whitespace".to_string()),
+        }
+        if lead + trail > 0 {
+            how_notes.push(format!("fuzz {}", lead.max(trail)));
+        }
+        if !how_notes.is_empty() {
+            notes.push(format!(
+                "{}: {} applied at line {} ({})",
+                name,
+                label,
+                pos + 1,
+                how_notes.join(", ")
+            ));
+        }
+
+        if let Some(base) = base {
+            offset = (pos as isize - lead as isize) - base as isize + added as isize
+                - (at - pos) as isize;
+        }
+        floor = pos + added;
+        if hunk.new_no_eol {
+            ends_with_eol = false;
+        } else if hunk.old_no_eol {
+            ends_with_eol = true;
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(anyhow!(failures.join("\n- ")));
+    }
+    let last = lines.len().saturating_sub(1);
+    let mut out = String::new();
+    for (i, (text, end)) in lines.iter().enumerate() {
+        out.push_str(text);
+        if i < last || ends_with_eol {
+            out.push_str(if end.is_empty() { eol } else { end });
+        }
+    }
+    Ok(out)
+}
+
+/// Find where `hunk` applies at or after `floor`, nearest `expected`: the
+/// start of its old lines, the context lines dropped from the front and back,
+/// and how the lines matched
+fn locate(
+    lines: &[(String, &str)],
+    hunk: &Hunk,
+    expected: usize,
+    floor: usize,
+) -> Option<(usize, usize, usize, Match)> {
+    let leading = hunk
+        .lines
+        .iter()
+        .take_while(|(kind, _)| *kind == Line::Context)
+        .count();
+    let trailing = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|(kind, _)| *kind == Line::Context)
+        .count();
+
+    let mut attempts = vec![(0, 0)];
+    for fuzz in 1..=MAX_FUZZ {
+        for (lead, trail) in [(fuzz, fuzz), (fuzz, 0), (0, fuzz)] {
+            if lead <= leading && trail <= trailing && lead + trail < hunk.lines.len() {
+                attempts.push((lead, trail));
+            }
+        }
+    }
+
+    for (lead, trail) in attempts {
+        let old: Vec<&str> = hunk.lines[lead..hunk.lines.len() - trail]
+            .iter()
+            .filter(|(kind, _)| *kind != Line::Add)
+            .map(|(_, text)| text.as_str())
+            .collect();
+        // Nothing to match (an insertion): it goes where the header says
+        if old.is_empty() {
+            return Some((expected, lead, trail, Match::Exact));
+        }
+        let want = expected + lead;
+        for how in Match::ALL {
+            let Some(last) = lines.len().checked_sub(old.len()) else {
+                break;
+            };
+            let found = (floor..=last)
+                .filter(|&p| {
+                    old.iter()
+                        .enumerate()
+                        .all(|(k, text)| how.eq(&lines[p + k].0, text))
+                })
+                .min_by_key(|&p| p.abs_diff(want));
+            if let Some(pos) = found {
+                return Some((pos, lead, trail, how));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn apply(dir: &TempDir, patch: &str) -> Result<String> {
+        let tool = ApplyPatchTool::with_cwd(Some(dir.path().to_path_buf()));
+        tool.plan(&serde_json::json!({ "patch": patch }))?.write()
+    }
+
+    fn read(dir: &TempDir, name: &str) -> String {
+        std::fs::read_to_string(dir.path().join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_apply_patch_spec() {
+        let spec = ApplyPatchTool::new().get_spec();
+        assert_eq!(spec.function.name, "ApplyPatch");
+        assert_eq!(spec.function.parameters["required"][0], "patch");
+    }
+
+    #[test]
+    fn test_applies_git_diff_with_create_delete_and_rename() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {\n    old();\n}\n").unwrap();
+        std::fs::write(dir.path().join("gone.txt"), "bye\n").unwrap();
+        std::fs::write(dir.path().join("before.txt"), "same\nline\n").unwrap();
+
+        let patch = "\
+diff --git a/main.rs b/main.rs
+index 1111111..2222222 100644
+--- a/main.rs
++++ b/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+ }
+diff --git a/src/lib.rs b/src/lib.rs
+new file mode 100644
+--- /dev/null
++++ b/src/lib.rs
+@@ -0,0 +1,2 @@
++pub mod a;
++pub mod b;
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/before.txt b/after.txt
+similarity index 80%
+rename from before.txt
+rename to after.txt
+--- a/before.txt
++++ b/after.txt
+@@ -1,2 +1,2 @@
+ same
+-line
++row
+";
+        let out = apply(&dir, patch).unwrap();
+
+        assert_eq!(read(&dir, "main.rs"), "fn main() {\n    new();\n}\n");
+        assert_eq!(read(&dir, "src/lib.rs"), "pub mod a;\npub mod b;\n");
+        assert!(!dir.path().join("gone.txt").exists());
+        assert!(!dir.path().join("before.txt").exists());
+        assert_eq!(read(&dir, "after.txt"), "same\nrow\n");
+        assert!(out.contains("Applied patch to 4 files"), "{}", out);
+        assert!(out.contains("M main.rs (1 hunk)"), "{}", out);
+        assert!(out.contains("R before.txt -> after.txt"), "{}", out);
+    }
+
+    #[test]
+    fn test_hunks_found_by_context_despite_wrong_numbers_and_whitespace() {
+        let dir = TempDir::new().unwrap();
+        let body: String = (1..=30).map(|n| format!("line {}\n", n)).collect();
+        std::fs::write(
+            dir.path().join("a.txt"),
+            body.replace("line 20\n", "line 20   \n"),
+        )
+        .unwrap();
+
+        // Numbers off by fourteen and a trailing space lost
+        let patch = "\
+--- a.txt
++++ a.txt
+@@ -5,3 +5,3 @@
+ line 19
+-line 20
++twenty
+ line 21
+@@ -40,2 +40,3 @@
+ line 30
++line 31
+";
+        let out = apply(&dir, patch).unwrap();
+        let text = read(&dir, "a.txt");
+        assert!(text.contains("line 19\ntwenty\nline 21\n"), "{}", text);
+        assert!(text.ends_with("line 30\nline 31\n"), "{}", text);
+        assert!(out.contains("offset"), "{}", out);
+        assert!(out.contains("ignoring trailing whitespace"), "{}", out);
+    }
+
+    #[test]
+    fn test_failed_hunk_changes_no_file() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "three\nfour\n").unwrap();
+
+        let patch = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++2
+--- a/b.txt
++++ b/b.txt
+@@ -1,2 +1,2 @@
+ three
+-five
++5
+--- /dev/null
++++ b/c.txt
+@@ -0,0 +1 @@
++new
+";
+        let err = apply(&dir, patch).unwrap_err().to_string();
+        assert!(err.contains("no files were changed"), "{}", err);
+        assert!(err.contains("b.txt: hunk 1 (@@ -1,2 +1,2 @@)"), "{}", err);
+        assert!(err.contains("\"three\""), "{}", err);
+        assert!(!err.contains("a.txt:"), "{}", err);
+        assert_eq!(read(&dir, "a.txt"), "one\ntwo\n");
+        assert!(!dir.path().join("c.txt").exists());
+    }
+
+    #[test]
+    fn test_failed_write_rolls_back() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        std::fs::write(dir.path().join("gone.txt"), "bye\n").unwrap();
+        let gone_mode = std::fs::Permissions::from_mode(0o750);
+        std::fs::set_permissions(dir.path().join("gone.txt"), gone_mode).unwrap();
+
+        let patch = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-one
++1
+--- a/gone.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+--- /dev/null
++++ b/sub/dir/new.txt
+@@ -0,0 +1 @@
++new
+--- /dev/null
++++ b/late.txt
+@@ -0,0 +1 @@
++late
+";
+        let tool = ApplyPatchTool::with_cwd(Some(dir.path().to_path_buf()));
+        let plan = tool.plan(&serde_json::json!({ "patch": patch })).unwrap();
+        // Something takes the last file's place between planning and writing
+        std::fs::create_dir(dir.path().join("late.txt")).unwrap();
+
+        let err = format!("{:#}", plan.write().unwrap_err());
+        assert!(
+            err.contains("rolled back; no files were changed"),
+            "{}",
+            err
+        );
+        assert!(err.contains("late.txt"), "{}", err);
+        assert_eq!(read(&dir, "a.txt"), "one\n");
+        assert_eq!(read(&dir, "gone.txt"), "bye\n");
+        let mode = std::fs::metadata(dir.path().join("gone.txt"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert!(!dir.path().join("sub").exists());
+    }
+
+    #[test]
+    fn test_rename_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.sh");
+        std::fs::write(&path, "#!/bin/sh\necho hi\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let patch = "\
+diff --git a/run.sh b/bin/run.sh
+similarity index 80%
+rename from run.sh
+rename to bin/run.sh
+--- a/run.sh
++++ b/bin/run.sh
+@@ -1,2 +1,2 @@
+ #!/bin/sh
+-echo hi
++echo bye
+";
+        apply(&dir, patch).unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(read(&dir, "bin/run.sh"), "#!/bin/sh\necho bye\n");
+        let mode = std::fs::metadata(dir.path().join("bin/run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn test_rename_paths_keep_leading_a_and_b() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        std::fs::write(dir.path().join("a/x.txt"), "x\n").unwrap();
+
+        let patch = "\
+diff --git a/a/x.txt b/b/x.txt
+similarity index 100%
+rename from a/x.txt
+rename to b/x.txt
+";
+        apply(&dir, patch).unwrap();
+        assert!(!dir.path().join("a/x.txt").exists());
+        assert_eq!(read(&dir, "b/x.txt"), "x\n");
+    }
+
+    #[test]
+    fn test_keeps_crlf_and_missing_final_newline() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("win.txt"), "a\r\nb\r\nc").unwrap();
+
+        let patch = "\
+--- win.txt
++++ win.txt
+@@ -2,2 +2,2 @@
+ b
+-c
+\\ No newline at end of file
++C
+\\ No newline at end of file
+";
+        apply(&dir, patch).unwrap();
+        assert_eq!(read(&dir, "win.txt"), "a\r\nb\r\nC");
+    }
+
+    #[test]
+    fn test_mixed_line_endings_kept_per_line() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("mixed.txt"), "a\nb\r\nc\nd\n").unwrap();
+
+        let patch = "\
+--- mixed.txt
++++ mixed.txt
+@@ -3,2 +3,3 @@
+ c
+-d
++D
++E
+";
+        apply(&dir, patch).unwrap();
+        assert_eq!(read(&dir, "mixed.txt"), "a\nb\r\nc\nD\nE\n");
+    }
+
+    #[test]
+    fn test_pure_rename_copies_bytes() {
+        let dir = TempDir::new().unwrap();
+        let bytes = b"\x89PNG\r\n\x1a\n\xff\x00line\r\nend\n".to_vec();
+        std::fs::write(dir.path().join("logo.png"), &bytes).unwrap();
+
+        let patch = "\
+diff --git a/logo.png b/img/logo.png
+similarity index 100%
+rename from logo.png
+rename to img/logo.png
+";
+        apply(&dir, patch).unwrap();
+        assert!(!dir.path().join("logo.png").exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("img/logo.png")).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn test_refuses_to_create_existing_or_delete_mismatched_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "kept\nextra\n").unwrap();
+
+        let create = "--- /dev/null\n+++ b/a.txt\n@@ -0,0 +1 @@\n+x\n";
+        let err = apply(&dir, create).unwrap_err().to_string();
+        assert!(err.contains("already exists"), "{}", err);
+
+        let delete = "--- a/a.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-kept\n";
+        let err = apply(&dir, delete).unwrap_err().to_string();
+        assert!(err.contains("not deleting"), "{}", err);
+        assert_eq!(read(&dir, "a.txt"), "kept\nextra\n");
+
+        assert!(apply(&dir, "just some text").is_err());
+    }
+}
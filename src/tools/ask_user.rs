@@ -0,0 +1,248 @@
+//! The `AskUser` tool: a clarifying question put to the user mid-run.
+//!
+//! The front end answers it through the `UserPrompt` in the call's
+//! `ToolContext`: `--chat` prompts inline, and the webapp sends an `ask_user`
+//! event and waits for the client to POST the answer. Single-shot and
+//! scheduled runs have nobody to ask, so the model is told to go on with its
+//! best judgement (`--ask-user-reply` changes what it is told).
+
+use crate::models::Tool;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+/// Most choices a question may offer
+const MAX_OPTIONS: usize = 10;
+
+/// What the model is told when nobody can answer
+pub const DEFAULT_UNATTENDED_REPLY: &str = "No user is available to answer questions in this run. Proceed with your best judgement, and state the assumptions you made in your final answer.";
+
+/// A question for the user, with the choices offered (none for free text)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Question {
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+impl Question {
+    /// Read what the user typed: an option's number picks that option,
+    /// anything else is the answer itself, and nothing dismisses the question
+    pub fn resolve(&self, input: &str) -> Answer {
+        let input = input.trim();
+        if input.is_empty() {
+            return Answer::Dismissed;
+        }
+        let picked = input
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| self.options.get(i));
+        Answer::Given(picked.map_or(input, String::as_str).to_string())
+    }
+}
+
+/// How a question was answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    /// The user's reply
+    Given(String),
+    /// The user closed the question without replying
+    Dismissed,
+    /// Nobody can answer in this run; what to tell the model instead
+    Unavailable(String),
+}
+
+/// Someone the model can put a question to while it works
+pub trait UserPrompt: Send + Sync {
+    /// Put `question` to the user and wait for the answer
+    fn ask<'a>(&'a self, question: &'a Question) -> BoxFuture<'a, Result<Answer>>;
+}
+
+/// Stands in for the user in runs nobody is watching, answering every
+/// question with the same reply
+pub struct Unattended {
+    reply: String,
+}
+
+impl Unattended {
+    pub fn new(reply: impl Into<String>) -> Self {
+        Self {
+            reply: reply.into(),
+        }
+    }
+}
+
+impl Default for Unattended {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNATTENDED_REPLY)
+    }
+}
+
+impl UserPrompt for Unattended {
+    fn ask<'a>(&'a self, _question: &'a Question) -> BoxFuture<'a, Result<Answer>> {
+        Box::pin(async move { Ok(Answer::Unavailable(self.reply.clone())) })
+    }
+}
+
+/// AskUser: ask the user a clarifying question and wait for the answer
+pub struct AskUserTool;
+
+impl AskUserTool {
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "AskUser",
+            "Ask the user a clarifying question and wait for the answer. Use it when the request is ambiguous and a wrong guess would waste real work, not for things you can find out yourself. Offer options when the likely answers are known; the user can still reply in their own words. In runs without a user you are told to proceed on your own judgement.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "question": {
+                        "type": "string",
+                        "description": "The question, answerable on its own"
+                    },
+                    "options": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Choices to offer, most likely first (optional)"
+                    }
+                },
+                "required": ["question"]
+            }),
+        )
+    }
+
+    /// The question in `args`
+    pub fn parse(args: &serde_json::Value) -> Result<Question> {
+        let question = args["question"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| anyhow!("Missing 'question' parameter"))?;
+        let options = match &args["options"] {
+            serde_json::Value::Null => Vec::new(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .ok_or_else(|| anyhow!("'options' must be non-empty strings"))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => bail!("'options' must be a list of strings"),
+        };
+        if options.len() > MAX_OPTIONS {
+            bail!("At most {} options", MAX_OPTIONS);
+        }
+        Ok(Question {
+            question: question.to_string(),
+            options,
+        })
+    }
+
+    pub async fn execute(&self, args: serde_json::Value, ctx: ToolContext<'_>) -> Result<String> {
+        let question = Self::parse(&args)?;
+        let answer = match ctx.user {
+            Some(user) => user.ask(&question).await?,
+            None => Answer::Unavailable(DEFAULT_UNATTENDED_REPLY.to_string()),
+        };
+        Ok(match answer {
+            Answer::Given(reply) => format!("The user answered: {}", reply),
+            Answer::Dismissed => "The user dismissed the question without answering. Proceed with your best judgement.".to_string(),
+            Answer::Unavailable(reply) => reply,
+        })
+    }
+}
+
+impl crate::tools::Tool for AskUserTool {
+    fn name(&self) -> &str {
+        "AskUser"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(AskUserTool::execute(self, args, ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::BackgroundJobs;
+
+    /// Answers with whatever was typed in advance
+    struct Typed(&'static str);
+
+    impl UserPrompt for Typed {
+        fn ask<'a>(&'a self, question: &'a Question) -> BoxFuture<'a, Result<Answer>> {
+            Box::pin(async move { Ok(question.resolve(self.0)) })
+        }
+    }
+
+    async fn ask(user: Option<&dyn UserPrompt>, args: serde_json::Value) -> Result<String> {
+        let jobs = BackgroundJobs::new();
+        let ctx = ToolContext {
+            user,
+            ..ToolContext::new(&jobs)
+        };
+        AskUserTool.execute(args, ctx).await
+    }
+
+    #[test]
+    fn test_resolve_picks_options_by_number() {
+        let question = Question {
+            question: "Which database?".to_string(),
+            options: vec!["Postgres".to_string(), "SQLite".to_string()],
+        };
+        assert_eq!(question.resolve(" 2 "), Answer::Given("SQLite".to_string()));
+        assert_eq!(question.resolve("3"), Answer::Given("3".to_string()));
+        assert_eq!(
+            question.resolve("MySQL, actually"),
+            Answer::Given("MySQL, actually".to_string())
+        );
+        assert_eq!(question.resolve("  "), Answer::Dismissed);
+    }
+
+    #[tokio::test]
+    async fn test_execute_reports_answer_dismissal_and_absence() {
+        let args = serde_json::json!({"question": "Which one?", "options": ["a", "b"]});
+        assert_eq!(
+            ask(Some(&Typed("1")), args.clone()).await.unwrap(),
+            "The user answered: a"
+        );
+        assert!(ask(Some(&Typed("")), args.clone())
+            .await
+            .unwrap()
+            .starts_with("The user dismissed"));
+        assert_eq!(
+            ask(None, args.clone()).await.unwrap(),
+            DEFAULT_UNATTENDED_REPLY
+        );
+        assert_eq!(
+            ask(Some(&Unattended::new("Assume staging.")), args)
+                .await
+                .unwrap(),
+            "Assume staging."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_bad_questions() {
+        for args in [
+            serde_json::json!({}),
+            serde_json::json!({"question": "  "}),
+            serde_json::json!({"question": "Q?", "options": "a or b"}),
+            serde_json::json!({"question": "Q?", "options": ["a", ""]}),
+            serde_json::json!({"question": "Q?", "options": vec!["x"; 11]}),
+        ] {
+            assert!(ask(None, args.clone()).await.is_err(), "{}", args);
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Background jobs started by `Bash` with `run_in_background`.
+//!
+//! Each job runs in its own process group with stdout and stderr collected
+//! into a buffer that `BashOutput` drains. Jobs belong to one session's
+//! `BackgroundJobs` table and are killed when it is cleared or dropped.
+
+use crate::env_policy::EnvPolicy;
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+/// Unread output kept per job; older output is dropped past this
+const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+/// Most output a single BashOutput call returns
+const MAX_READ_BYTES: usize = 32 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JobStatus {
+    Running,
+    Exited(Option<i32>),
+    Killed,
+}
+
+#[derive(Default)]
+struct JobOutput {
+    /// Output not yet returned by BashOutput
+    unread: Vec<u8>,
+    /// Bytes discarded because nobody read them in time
+    dropped: usize,
+}
+
+struct Job {
+    command: String,
+    started: Instant,
+    pid: Option<u32>,
+    output: Mutex<JobOutput>,
+    status: Mutex<JobStatus>,
+    kill_tx: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        *self.status.lock().unwrap()
+    }
+
+    fn kill(&self) {
+        let mut status = self.status.lock().unwrap();
+        if *status != JobStatus::Running {
+            return;
+        }
+        *status = JobStatus::Killed;
+
+        // The job's shell leads its own process group; take the whole subtree
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            unsafe {
+                libc::killpg(pid as i32, libc::SIGKILL);
+            }
+        }
+        if let Some(tx) = self.kill_tx.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+    }
+
+    fn describe_status(&self) -> String {
+        match self.status() {
+            JobStatus::Running => format!(
+                "running (pid {}, {}s)",
+                self.pid.unwrap_or(0),
+                self.started.elapsed().as_secs()
+            ),
+            JobStatus::Exited(Some(code)) => format!("exited with code {}", code),
+            JobStatus::Exited(None) => "exited (killed by a signal)".to_string(),
+            JobStatus::Killed => "killed".to_string(),
+        }
+    }
+}
+
+/// The background jobs of one session
+pub struct BackgroundJobs {
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+    next_id: AtomicU64,
+}
+
+impl BackgroundJobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn bash_output_spec() -> Tool {
+        make_tool(
+            "BashOutput",
+            "Read new output from a background job started with Bash run_in_background, along \
+             with its status (running, exited with code N, or killed). Each call returns only \
+             output produced since the previous call. Omit id to list all background jobs.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Job id returned by Bash, e.g. \"bg_1\""
+                    }
+                }
+            }),
+        )
+    }
+
+    pub fn kill_bash_spec() -> Tool {
+        make_tool(
+            "KillBash",
+            "Stop a background job started with Bash run_in_background, killing its whole process tree.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Job id returned by Bash, e.g. \"bg_1\""
+                    }
+                },
+                "required": ["id"]
+            }),
+        )
+    }
+
+    /// Start `command` in `shell -c` (confined by `sandbox`, if given, with
+    /// the environment `env` allows) and return the job's handle message
+    pub fn start(
+        &self,
+        shell: &str,
+        command: &str,
+        cwd: Option<&Path>,
+        sandbox: Option<&SandboxPolicy>,
+        env: &EnvPolicy,
+    ) -> Result<String> {
+        let mut cmd = Command::new(shell);
+        cmd.arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        env.apply(&mut cmd);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        #[cfg(unix)]
+        cmd.process_group(0);
+        if let Some(policy) = sandbox {
+            policy.restrict_command(&mut cmd)?;
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to spawn shell: {}", shell))?;
+        let pid = child.id();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let job = Arc::new(Job {
+            command: command.to_string(),
+            started: Instant::now(),
+            pid,
+            output: Mutex::new(JobOutput::default()),
+            status: Mutex::new(JobStatus::Running),
+            kill_tx: Mutex::new(Some(kill_tx)),
+        });
+
+        if let Some(stdout) = stdout {
+            tokio::spawn(collect_output(stdout, job.clone()));
+        }
+        if let Some(stderr) = stderr {
+            tokio::spawn(collect_output(stderr, job.clone()));
+        }
+        let waiter = job.clone();
+        tokio::spawn(async move {
+            let exit = tokio::select! {
+                status = child.wait() => status.ok().map(|s| s.code()),
+                // Killed, or the job table was dropped
+                _ = kill_rx => {
+                    let _ = child.kill().await;
+                    None
+                }
+            };
+            let mut status = waiter.status.lock().unwrap();
+            if *status == JobStatus::Running {
+                *status = match exit {
+                    Some(code) => JobStatus::Exited(code),
+                    None => JobStatus::Killed,
+                };
+            }
+        });
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.jobs.lock().unwrap().insert(id, job);
+        Ok(format!(
+            "Started background job bg_{} (pid {}).\nUse BashOutput with id \"bg_{}\" to read its output and status, and KillBash to stop it.",
+            id,
+            pid.map_or_else(|| "unknown".to_string(), |p| p.to_string()),
+            id
+        ))
+    }
+
+    /// BashOutput: drain a job's new output, or list jobs when no id is given
+    pub fn execute_output(&self, args: serde_json::Value) -> Result<String> {
+        let Some(id) = args["id"].as_str() else {
+            return Ok(self.list());
+        };
+        let job = self.lookup(id)?;
+
+        let status = job.describe_status();
+        let mut output = job.output.lock().unwrap();
+        let mut end = output.unread.len().min(MAX_READ_BYTES);
+        // Don't split a UTF-8 character across reads
+        if let Err(e) = std::str::from_utf8(&output.unread[..end]) {
+            if e.error_len().is_none() && e.valid_up_to() > 0 {
+                end = e.valid_up_to();
+            }
+        }
+        let chunk: Vec<u8> = output.unread.drain(..end).collect();
+        let remaining = output.unread.len();
+        let dropped = std::mem::take(&mut output.dropped);
+        drop(output);
+
+        let mut result = format!("[{}: {}]\n", id, status);
+        if dropped > 0 {
+            result.push_str(&format!(
+                "[{} bytes of earlier output were dropped before being read]\n",
+                dropped
+            ));
+        }
+        if chunk.is_empty() {
+            result.push_str("(no new output)");
+        } else {
+            result.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        if remaining > 0 {
+            result.push_str(&format!(
+                "\n[{} more bytes buffered; call BashOutput again]",
+                remaining
+            ));
+        }
+        Ok(result)
+    }
+
+    /// KillBash: stop a job and its process tree
+    pub fn execute_kill(&self, args: serde_json::Value) -> Result<String> {
+        let id = args["id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'id' parameter"))?;
+        let job = self.lookup(id)?;
+        if job.status() != JobStatus::Running {
+            return Ok(format!("{} already {}", id, job.describe_status()));
+        }
+        job.kill();
+        Ok(format!("Killed {}", id))
+    }
+
+    /// Kill every running job (the session ended or was cancelled)
+    pub fn kill_all(&self) {
+        for job in self.jobs.lock().unwrap().values() {
+            job.kill();
+        }
+    }
+
+    fn list(&self) -> String {
+        let jobs = self.jobs.lock().unwrap();
+        if jobs.is_empty() {
+            return "No background jobs".to_string();
+        }
+        jobs.iter()
+            .map(|(id, job)| format!("bg_{}  {}  {}", id, job.describe_status(), job.command))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    fn lookup(&self, id: &str) -> Result<Arc<Job>> {
+        id.strip_prefix("bg_")
+            .and_then(|n| n.parse().ok())
+            .and_then(|n| self.get(n))
+            .ok_or_else(|| anyhow::anyhow!("No background job '{}'", id))
+    }
+}
+
+impl Default for BackgroundJobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for BackgroundJobs {
+    fn drop(&mut self) {
+        self.kill_all();
+    }
+}
+
+/// BashOutput, reading the calling session's jobs
+pub struct BashOutputTool;
+
+impl crate::tools::Tool for BashOutputTool {
+    fn name(&self) -> &str {
+        "BashOutput"
+    }
+
+    fn spec(&self) -> Tool {
+        BackgroundJobs::bash_output_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { ctx.jobs.execute_output(args) })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+}
+
+/// KillBash, stopping one of the calling session's jobs
+pub struct KillBashTool;
+
+impl crate::tools::Tool for KillBashTool {
+    fn name(&self) -> &str {
+        "KillBash"
+    }
+
+    fn spec(&self) -> Tool {
+        BackgroundJobs::kill_bash_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { ctx.jobs.execute_kill(args) })
+    }
+}
+
+/// Append a job's stdout or stderr to its buffer until the stream closes
+async fn collect_output(mut stream: impl tokio::io::AsyncRead + Unpin, job: Arc<Job>) {
+    let mut buf = [0u8; 8192];
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let mut output = job.output.lock().unwrap();
+                output.unread.extend_from_slice(&buf[..n]);
+                let excess = output.unread.len().saturating_sub(MAX_BUFFERED_BYTES);
+                if excess > 0 {
+                    output.unread.drain(..excess);
+                    output.dropped += excess;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{sleep, Duration};
+
+    fn start(jobs: &BackgroundJobs, command: &str) -> String {
+        jobs.start("/bin/sh", command, None, None, &EnvPolicy::default()).unwrap()
+    }
+
+    async fn wait_for_exit(jobs: &BackgroundJobs, id: &str) -> String {
+        for _ in 0..100 {
+            let job = jobs.lookup(id).unwrap();
+            if job.status() != JobStatus::Running {
+                // Let the readers drain the closed pipes
+                sleep(Duration::from_millis(50)).await;
+                return jobs
+                    .execute_output(serde_json::json!({ "id": id }))
+                    .unwrap();
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        panic!("job {} did not exit", id);
+    }
+
+    #[tokio::test]
+    async fn test_background_job_output_and_status() {
+        let jobs = BackgroundJobs::new();
+        let started = start(&jobs, "echo first; echo oops >&2; exit 4");
+        assert!(
+            started.starts_with("Started background job bg_1"),
+            "{}",
+            started
+        );
+
+        let result = wait_for_exit(&jobs, "bg_1").await;
+        assert!(
+            result.starts_with("[bg_1: exited with code 4]"),
+            "{}",
+            result
+        );
+        assert!(result.contains("first\n"), "{}", result);
+        assert!(result.contains("oops\n"), "{}", result);
+
+        // Output is only returned once
+        let again = jobs
+            .execute_output(serde_json::json!({"id": "bg_1"}))
+            .unwrap();
+        assert_eq!(again, "[bg_1: exited with code 4]\n(no new output)");
+    }
+
+    #[tokio::test]
+    async fn test_background_job_kill_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let jobs = BackgroundJobs::new();
+        start(&jobs, &format!("sleep 1; touch '{}'", marker.display()));
+        start(&jobs, "true");
+
+        let list = jobs.execute_output(serde_json::json!({})).unwrap();
+        assert!(list.contains("bg_1  running"), "{}", list);
+        assert!(list.contains("bg_2"), "{}", list);
+
+        assert_eq!(
+            jobs.execute_kill(serde_json::json!({"id": "bg_1"}))
+                .unwrap(),
+            "Killed bg_1"
+        );
+        let result = wait_for_exit(&jobs, "bg_1").await;
+        assert!(result.starts_with("[bg_1: killed]"), "{}", result);
+
+        sleep(Duration::from_millis(1200)).await;
+        assert!(!marker.exists(), "killed job kept running");
+        assert!(jobs
+            .execute_kill(serde_json::json!({"id": "bg_1"}))
+            .unwrap()
+            .contains("already killed"));
+    }
+
+    #[tokio::test]
+    async fn test_dropping_jobs_kills_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        {
+            let jobs = BackgroundJobs::new();
+            start(&jobs, &format!("sleep 1; touch '{}'", marker.display()));
+            sleep(Duration::from_millis(100)).await;
+        }
+        sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists(), "job outlived its session");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_job_ids() {
+        let jobs = BackgroundJobs::new();
+        assert_eq!(
+            jobs.execute_output(serde_json::json!({})).unwrap(),
+            "No background jobs"
+        );
+        assert!(jobs
+            .execute_output(serde_json::json!({"id": "bg_9"}))
+            .is_err());
+        assert!(jobs
+            .execute_kill(serde_json::json!({"id": "nope"}))
+            .is_err());
+        assert!(jobs.execute_kill(serde_json::json!({})).is_err());
+    }
+}
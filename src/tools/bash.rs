@@ -1,11 +1,23 @@
+use crate::env_policy::EnvPolicy;
 use crate::models::Tool;
+use crate::output_store::SpillSlot;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::capture::{finish, read_chunk, Capture, OutputLimit};
 use crate::tools::make_tool;
+use crate::tools::shell::PersistentShell;
+use crate::tools::{BackgroundJobs, ProgressSender, ToolContext};
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader};
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 
+/// Seconds a command may run unless the call says otherwise
+pub(super) const DEFAULT_TIMEOUT_SECS: u64 = 600; // 10 minutes
+
 /// Kills a child's entire process group with SIGKILL when dropped, unless
 /// disarmed. Combined with spawning the shell into its own process group, this
 /// reaps the whole subprocess tree when the command finishes early — i.e. when
@@ -32,6 +44,10 @@ impl Drop for ProcessGroupKiller {
 pub struct BashTool {
     default_timeout: u64,
     cwd: Option<PathBuf>,
+    /// Set in persistent mode: every command runs in this one shell
+    shell: Option<PersistentShell>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+    env: Arc<EnvPolicy>,
 }
 
 impl BashTool {
@@ -43,12 +59,75 @@ impl BashTool {
     /// so concurrent runs with different directories cannot race each other.
     pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
         Self {
-            default_timeout: 600, // 10 minutes
+            default_timeout: DEFAULT_TIMEOUT_SECS,
             cwd,
+            shell: None,
+            sandbox: None,
+            env: Arc::new(EnvPolicy::default()),
+        }
+    }
+
+    /// Run every command, background and persistent ones included, under
+    /// the policy's Landlock rules
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        if self.shell.is_some() {
+            self = self.persistent();
+        }
+        self
+    }
+
+    /// Start every command, background and persistent ones included, with
+    /// the environment `policy` allows
+    pub fn with_env(mut self, policy: Arc<EnvPolicy>) -> Self {
+        self.env = policy;
+        if self.shell.is_some() {
+            self = self.persistent();
         }
+        self
+    }
+
+    /// Run every command in one long-lived shell (started in `cwd`) instead
+    /// of a fresh `$SHELL -c`, so `cd`, `export` and sourced environments
+    /// carry over between calls
+    pub fn persistent(mut self) -> Self {
+        self.shell = Some(PersistentShell::new(
+            self.cwd.clone(),
+            self.sandbox.clone(),
+            self.env.clone(),
+        ));
+        self
     }
 
     pub fn get_spec(&self) -> Tool {
+        if self.shell.is_some() {
+            return make_tool(
+                "Bash",
+                "Execute a shell command and return the output. Commands run in one persistent shell with full system access: the working directory, exported variables and activated environments carry over between calls. Set restart to start a fresh shell.",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "command": {
+                            "type": "string",
+                            "description": "The shell command to execute"
+                        },
+                        "timeout": {
+                            "type": "integer",
+                            "description": "Timeout in seconds (default: 600). A command that times out kills the shell."
+                        },
+                        "run_in_background": {
+                            "type": "boolean",
+                            "description": "Start the command in its own shell and return a job id immediately, for servers and long builds. Read its output with BashOutput and stop it with KillBash."
+                        },
+                        "restart": {
+                            "type": "boolean",
+                            "description": "Kill the shell and start a fresh one before running the command (default: false)"
+                        }
+                    },
+                    "required": ["command"]
+                }),
+            );
+        }
         make_tool(
             "Bash",
             "Execute a shell command and return the output. Commands run in the user's default shell with full system access.",
@@ -62,6 +141,10 @@ impl BashTool {
                     "timeout": {
                         "type": "integer",
                         "description": "Timeout in seconds (default: 600)"
+                    },
+                    "run_in_background": {
+                        "type": "boolean",
+                        "description": "Return a job id immediately instead of waiting, for servers and long builds. Read its output with BashOutput and stop it with KillBash."
                     }
                 },
                 "required": ["command"]
@@ -69,7 +152,48 @@ impl BashTool {
         )
     }
 
+    /// Start a `run_in_background` command as a job in `jobs`. It always gets
+    /// its own shell, in the configured cwd, even in persistent mode.
+    pub fn start_background(
+        &self,
+        args: serde_json::Value,
+        jobs: &BackgroundJobs,
+    ) -> Result<String> {
+        let command = args["command"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))?;
+        jobs.start(
+            &user_shell(),
+            command,
+            self.cwd.as_deref(),
+            self.sandbox.as_deref(),
+            &self.env,
+        )
+    }
+
+    #[cfg(test)]
     pub async fn execute(&self, args: serde_json::Value) -> Result<String> {
+        self.execute_with_progress(args, None).await
+    }
+
+    /// Like `execute`, also sending each stdout/stderr line to `progress` as
+    /// the command prints it
+    #[cfg(test)]
+    pub async fn execute_with_progress(
+        &self,
+        args: serde_json::Value,
+        progress: Option<&ProgressSender>,
+    ) -> Result<String> {
+        self.run(args, progress, None).await
+    }
+
+    /// Run the command, leaving output too large to return whole in `spill`
+    async fn run(
+        &self,
+        args: serde_json::Value,
+        progress: Option<&ProgressSender>,
+        spill: Option<&SpillSlot>,
+    ) -> Result<String> {
         let command = args["command"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))?;
@@ -78,79 +202,194 @@ impl BashTool {
             .as_u64()
             .unwrap_or(self.default_timeout);
 
-        // Detect shell
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        if let Some(shell) = &self.shell {
+            let mut notes = Vec::new();
+            if args["restart"].as_bool().unwrap_or(false) {
+                shell.restart().await;
+                notes.push("[shell restarted]".to_string());
+            }
+            let output = shell.run(command, timeout_secs, progress).await?;
+            if output.restarted {
+                notes.push(
+                    "[previous shell was killed or exited; started a new one, so the working directory and environment were reset]"
+                        .to_string(),
+                );
+            }
+            let mut result = finish(output.stdout, output.stderr, output.exit_code, spill)
+                .context("Failed to collect command output")?;
+            if let Some(note) = output.limit_note {
+                result.push('\n');
+                result.push_str(&note);
+            }
+            if output.shell_exited {
+                result.push_str("\n[shell exited; the next command starts a new shell]");
+            }
+            if !notes.is_empty() {
+                notes.push(result);
+                result = notes.join("\n");
+            }
+            return Ok(result);
+        }
 
-        // Build the command. kill_on_drop reaps the shell leader if this future
-        // is dropped (e.g. the user cancels); on unix we also put it in its own
-        // process group so the whole subtree can be killed (see the guard below).
+        let shell = user_shell();
         let mut cmd = Command::new(&shell);
-        cmd.arg("-c")
-            .arg(command)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .kill_on_drop(true);
+        cmd.arg("-c").arg(command);
+        self.env.apply(&mut cmd);
         if let Some(dir) = &self.cwd {
             cmd.current_dir(dir);
         }
-        #[cfg(unix)]
-        cmd.process_group(0);
-
-        let child = cmd
-            .spawn()
-            .with_context(|| format!("Failed to spawn shell: {}", shell))?;
-
-        // Arm a process-group killer for the whole subtree; disarmed on success.
-        #[cfg(unix)]
-        let mut group_killer = child.id().map(|pid| ProcessGroupKiller {
-            pgid: pid as i32,
-            armed: true,
-        });
-
-        // Wait with timeout
-        let output = timeout(Duration::from_secs(timeout_secs), child.wait_with_output())
-            .await
-            .with_context(|| format!("Command timed out after {} seconds", timeout_secs))?
-            .with_context(|| "Failed to execute command")?;
-
-        // Completed normally — don't signal a (possibly recycled) process group.
-        #[cfg(unix)]
-        if let Some(g) = group_killer.as_mut() {
-            g.armed = false;
+        if let Some(policy) = &self.sandbox {
+            policy.restrict_command(&mut cmd)?;
         }
+        run_command(cmd, None, timeout_secs, progress, spill).await
+    }
+}
 
-        // Combine stdout and stderr
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+/// Run `cmd` to completion and format its output like Bash's, streaming lines
+/// to `progress`. `stdin`, when given, is written to the process and closed.
+/// The process gets its own process group, and the whole group is killed on
+/// timeout, when it prints more than the output limit, or when this future is
+/// dropped (the user cancelled). Output too large to return whole is
+/// previewed, with the full output left in `spill`.
+pub(super) async fn run_command(
+    mut cmd: Command,
+    stdin: Option<Vec<u8>>,
+    timeout_secs: u64,
+    progress: Option<&ProgressSender>,
+    spill: Option<&SpillSlot>,
+) -> Result<String> {
+    // kill_on_drop reaps the leader if this future is dropped; on unix we also
+    // put it in its own process group so the whole subtree can be killed (see
+    // the guard below).
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if stdin.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let program = cmd.as_std().get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to spawn {}", program))?;
+
+    // Arm a process-group killer for the whole subtree; disarmed on success.
+    #[cfg(unix)]
+    let mut group_killer = child.id().map(|pid| ProcessGroupKiller {
+        pgid: pid as i32,
+        armed: true,
+    });
+
+    // Read both pipes line by line as the command runs, then wait for it
+    let limit = OutputLimit::new(child.id());
+    let stdout = child.stdout.take().context("stdout not piped")?;
+    let stderr = child.stderr.take().context("stderr not piped")?;
+    let input = child.stdin.take().zip(stdin);
+    let run = async {
+        let write = async {
+            if let Some((mut pipe, bytes)) = input {
+                // A command that does not read its input is not an error
+                let _ = pipe.write_all(&bytes).await;
+            }
+            Ok(())
+        };
+        let (stdout, stderr, ()) = tokio::try_join!(
+            read_lines(stdout, progress, &limit),
+            read_lines(stderr, progress, &limit),
+            write
+        )?;
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>((stdout, stderr, status))
+    };
+    let (stdout, stderr, status) = timeout(Duration::from_secs(timeout_secs), run)
+        .await
+        .with_context(|| format!("Command timed out after {} seconds", timeout_secs))?
+        .with_context(|| "Failed to execute command")?;
+
+    // Completed normally — don't signal a (possibly recycled) process group.
+    #[cfg(unix)]
+    if let Some(g) = group_killer.as_mut() {
+        g.armed = false;
+    }
 
-        let mut result = String::new();
+    let mut result = finish(stdout, stderr, status.code(), spill)
+        .context("Failed to collect command output")?;
+    if limit.exceeded() {
+        result.push('\n');
+        result.push_str(&limit.note());
+    }
+    Ok(result)
+}
 
-        if !stdout.is_empty() {
-            result.push_str(&stdout);
+/// Read `pipe` to EOF, sending each line to `progress` as it arrives. Output
+/// past `limit` is read and discarded until the killed command closes the pipe.
+async fn read_lines(
+    pipe: impl AsyncRead + Unpin,
+    progress: Option<&ProgressSender>,
+    limit: &OutputLimit,
+) -> std::io::Result<Capture> {
+    let mut reader = BufReader::new(pipe);
+    let mut out = Capture::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if read_chunk(&mut reader, &mut line).await? == 0 {
+            return Ok(out);
         }
-
-        if !stderr.is_empty() {
-            if !result.is_empty() {
-                result.push_str("\n");
-            }
-            result.push_str("[stderr]\n");
-            result.push_str(&stderr);
+        if !limit.charge(line.len()) {
+            continue;
+        }
+        if let Some(tx) = progress {
+            let _ = tx.try_send(progress_line(&line));
         }
+        out.write(&line)?;
+    }
+}
+
+/// One line of output as shown in progress: lossy UTF-8, line ending dropped
+pub(super) fn progress_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line)
+        .trim_end_matches(['\n', '\r'])
+        .to_string()
+}
+
+/// The user's default shell
+pub(super) fn user_shell() -> String {
+    std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+}
 
-        // Add exit code if non-zero
-        if !output.status.success() {
-            let code = output.status.code().unwrap_or(-1);
-            if !result.is_empty() {
-                result.push_str("\n");
+impl crate::tools::Tool for BashTool {
+    fn name(&self) -> &str {
+        "Bash"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            if args["run_in_background"].as_bool().unwrap_or(false) {
+                self.start_background(args, ctx.jobs)
+            } else {
+                self.run(args, ctx.progress, ctx.spill).await
             }
-            result.push_str(&format!("[exit code: {}]", code));
-        }
+        })
+    }
 
-        if result.is_empty() {
-            result = "(no output)".to_string();
-        }
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        // Goes through `sandboxed` so a persistent shell is restarted under the policy
+        *self = std::mem::take(self).sandboxed(policy);
+    }
 
-        Ok(result)
+    fn set_env_policy(&mut self, policy: Arc<EnvPolicy>) {
+        *self = std::mem::take(self).with_env(policy);
     }
 }
 
@@ -213,6 +452,121 @@ mod tests {
         assert_ne!(std::env::current_dir().unwrap(), expected);
     }
 
+    fn persistent_in(dir: &std::path::Path) -> BashTool {
+        BashTool::with_cwd(Some(dir.to_path_buf())).persistent()
+    }
+
+    async fn run(tool: &BashTool, command: &str) -> String {
+        tool.execute(serde_json::json!({ "command": command }))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_persistent_spec_offers_restart() {
+        let spec = BashTool::new().persistent().get_spec();
+        assert!(spec.function.parameters["properties"]["restart"].is_object());
+        assert!(BashTool::new().get_spec().function.parameters["properties"]["restart"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_persistent_shell_keeps_cwd_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        let tool = persistent_in(&root);
+
+        assert_eq!(
+            run(&tool, "cd sub && export GREETING=hi").await,
+            "(no output)"
+        );
+        assert_eq!(
+            run(&tool, "pwd -P; echo $GREETING").await,
+            format!("{}\nhi\n", root.join("sub").display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_persistent_shell_exit_codes_stderr_and_quoting() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = persistent_in(dir.path());
+
+        assert_eq!(
+            run(&tool, "echo out; echo err >&2; false").await,
+            "out\n\n[stderr]\nerr\n\n[exit code: 1]"
+        );
+        // Output without a trailing newline and embedded single quotes
+        assert_eq!(run(&tool, "printf '%s' 'it'\\''s'").await, "it's");
+        // Syntax errors are reported without losing the shell
+        assert!(run(&tool, "if then").await.contains("[exit code:"));
+        // Commands that read stdin see EOF instead of the next command
+        assert_eq!(run(&tool, "cat; echo done").await, "done\n");
+        assert_eq!(run(&tool, "echo still here").await, "still here\n");
+    }
+
+    #[tokio::test]
+    async fn test_persistent_shell_restart_is_explicit() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let tool = persistent_in(&root);
+
+        run(&tool, "cd /").await;
+        let result = tool
+            .execute(serde_json::json!({"command": "pwd -P", "restart": true}))
+            .await
+            .unwrap();
+        assert_eq!(result, format!("[shell restarted]\n{}\n", root.display()));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_shell_exit_and_timeout_start_new_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = persistent_in(dir.path());
+
+        let result = run(&tool, "export X=1; exit 3").await;
+        assert!(result.contains("[exit code: 3]"), "{}", result);
+        assert!(result.contains("[shell exited"), "{}", result);
+
+        let result = run(&tool, "echo ${X:-unset}").await;
+        assert!(
+            result.starts_with("[previous shell was killed or exited"),
+            "{}",
+            result
+        );
+        assert!(result.ends_with("unset\n"), "{}", result);
+
+        let err = tool
+            .execute(serde_json::json!({"command": "sleep 5", "timeout": 1}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(run(&tool, "echo ok").await.ends_with("ok\n"));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_shell_killed_when_future_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = persistent_in(dir.path());
+        let marker = dir.path().join("marker");
+        let cmd = format!("sleep 1; touch '{}'", marker.display());
+
+        {
+            let fut = tool.execute(serde_json::json!({ "command": cmd }));
+            tokio::pin!(fut);
+            tokio::select! {
+                _ = &mut fut => panic!("command should not have completed"),
+                _ = tokio::time::sleep(Duration::from_millis(200)) => {}
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(
+            !marker.exists(),
+            "shell kept running after the future was dropped"
+        );
+        assert!(run(&tool, "echo next").await.ends_with("next\n"));
+    }
+
     // When the execute future is dropped (e.g. the user cancels with Escape),
     // the spawned command must be killed — its side effects must not happen.
     #[tokio::test]
@@ -241,4 +595,115 @@ mod tests {
             "subprocess kept running after the future was dropped"
         );
     }
+
+    fn drain(rx: &mut tokio::sync::mpsc::Receiver<String>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_progress_lines_match_final_output() {
+        let (tx, mut rx) = crate::tools::progress_channel();
+        let result = BashTool::new()
+            .execute_with_progress(serde_json::json!({"command": "echo one; echo two"}), Some(&tx))
+            .await
+            .unwrap();
+        assert_eq!(result, "one\ntwo\n");
+        assert_eq!(drain(&mut rx), ["one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn test_progress_arrives_before_command_exits() {
+        let (tx, mut rx) = crate::tools::progress_channel();
+        let tool = BashTool::new();
+        let fut = tool.execute_with_progress(
+            serde_json::json!({"command": "echo early; sleep 5"}),
+            Some(&tx),
+        );
+        tokio::pin!(fut);
+        tokio::select! {
+            _ = &mut fut => panic!("command should still be running"),
+            line = rx.recv() => assert_eq!(line.as_deref(), Some("early")),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_persistent_progress_skips_sentinel() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = persistent_in(dir.path());
+        let (tx, mut rx) = crate::tools::progress_channel();
+
+        let run = |command: &str| {
+            tool.execute_with_progress(serde_json::json!({ "command": command }), Some(&tx))
+        };
+        run("echo a; echo; echo b >&2").await.unwrap();
+        let mut lines = drain(&mut rx);
+        lines.sort();
+        assert_eq!(lines, ["", "a", "b"]);
+
+        // No trailing newline: the partial line is still reported once
+        run("printf 'x'").await.unwrap();
+        assert_eq!(drain(&mut rx), ["x"]);
+    }
+
+    #[tokio::test]
+    async fn test_large_output_is_previewed_and_spilled() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = BackgroundJobs::new();
+        for tool in [BashTool::new(), persistent_in(dir.path())] {
+            let spill = SpillSlot::default();
+            let ctx = ToolContext {
+                spill: Some(&spill),
+                ..ToolContext::new(&jobs)
+            };
+            let result = crate::tools::Tool::execute(
+                &tool,
+                serde_json::json!({"command": "seq 1 300000"}),
+                ctx,
+            )
+            .await
+            .unwrap();
+            assert!(result.starts_with("1\n2\n"));
+            assert!(result.ends_with("299999\n300000"));
+            assert!(result.len() < 1024);
+
+            let mut store = crate::output_store::OutputStore::new();
+            let (id, _) = store.adopt(spill.take().unwrap(), &result);
+            assert_eq!(store.get_metadata(&id).map(|m| m.0), Some(300_000));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_sandboxed_bash_is_confined_to_workspace() {
+        let workspace = tempfile::tempdir().unwrap();
+        // The system's other temporary directory: sandboxed commands may
+        // always write /tmp, but not /var/tmp
+        let outside = tempfile::tempdir_in("/var/tmp").unwrap();
+        std::fs::write(outside.path().join("secret"), "key").unwrap();
+        let policy = SandboxPolicy::resolve(
+            "workspace",
+            workspace.path(),
+            &std::collections::BTreeMap::new(),
+        )
+        .unwrap();
+        let tool = BashTool::with_cwd(Some(workspace.path().to_path_buf())).sandboxed(Arc::new(policy));
+
+        let result = match tool
+            .execute(serde_json::json!({"command": "echo ok > inside.txt && cat inside.txt"}))
+            .await
+        {
+            Ok(result) => result,
+            // Kernel without Landlock: the sandbox refuses to run anything
+            Err(e) if e.to_string().contains("cannot enforce") => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(result, "ok\n");
+
+        let secret = outside.path().join("secret");
+        let result = run(&tool, &format!("cat '{}'", secret.display())).await;
+        assert!(result.contains("[exit code:"), "{}", result);
+        let result = run(&tool, &format!("echo x > '{}'", outside.path().join("new").display())).await;
+        assert!(result.contains("[exit code:"), "{}", result);
+        assert!(!outside.path().join("new").exists());
+    }
 }
@@ -0,0 +1,333 @@
+//! Bounded capture of command output for Bash and custom tools.
+//!
+//! Each of a command's streams is kept in memory up to `SPILL_BYTES` and
+//! written to a temp file past that, so a runaway `cat` or log loop costs disk
+//! rather than memory. An `OutputLimit` shared by the streams kills the
+//! command's process group once it has printed `MAX_OUTPUT_BYTES` in total.
+//!
+//! A result that spilled is returned as a head/tail preview. Its file is left
+//! in the call's `SpillSlot` for the output store to adopt as is; without a
+//! slot (the webapp) the file is deleted.
+
+use crate::output_store::{SpillSlot, SpilledOutput};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Output kept in memory per stream before the rest goes to a temp file
+const SPILL_BYTES: usize = 1024 * 1024;
+
+/// Output a command may print before it is killed
+pub(super) const MAX_OUTPUT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Longest piece of a line read at once, so output without newlines is
+/// still counted against the limit as it arrives
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// Lines of a spilled result shown from each end in its preview
+const PREVIEW_LINES: usize = 10;
+
+/// Bytes read from each end of a spilled result for its preview
+const PREVIEW_BYTES: u64 = 8 * 1024;
+
+/// Total output allowed across a command's streams
+pub(super) struct OutputLimit {
+    max: u64,
+    used: AtomicU64,
+    /// Process group killed when the limit is passed
+    pgid: Option<u32>,
+}
+
+impl OutputLimit {
+    pub fn new(pgid: Option<u32>) -> Self {
+        Self::with_max(MAX_OUTPUT_BYTES, pgid)
+    }
+
+    fn with_max(max: u64, pgid: Option<u32>) -> Self {
+        Self {
+            max,
+            used: AtomicU64::new(0),
+            pgid,
+        }
+    }
+
+    /// Count `n` more bytes of output. False once over the limit, which
+    /// kills the process group the first time it happens.
+    pub fn charge(&self, n: usize) -> bool {
+        let before = self.used.fetch_add(n as u64, Ordering::SeqCst);
+        if before + n as u64 <= self.max {
+            return true;
+        }
+        #[cfg(unix)]
+        if before <= self.max {
+            if let Some(pgid) = self.pgid {
+                unsafe {
+                    libc::killpg(pgid as i32, libc::SIGKILL);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.used.load(Ordering::SeqCst) > self.max
+    }
+
+    /// Appended to the result of a command killed for its output
+    pub fn note(&self) -> String {
+        format!(
+            "[output passed the {} MB limit; the command was killed and the rest discarded]",
+            self.max / (1024 * 1024)
+        )
+    }
+}
+
+/// Read through the next newline into `line`, or `MAX_CHUNK` bytes of a
+/// longer line. Returns the bytes read; 0 at EOF.
+pub(super) async fn read_chunk(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    line: &mut Vec<u8>,
+) -> io::Result<usize> {
+    let start = line.len();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            break;
+        }
+        let room = MAX_CHUNK - (line.len() - start);
+        let (take, done) = match available.iter().take(room).position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (available.len().min(room), available.len() >= room),
+        };
+        line.extend_from_slice(&available[..take]);
+        reader.consume(take);
+        if done {
+            break;
+        }
+    }
+    Ok(line.len() - start)
+}
+
+/// Output of one stream: in memory up to `SPILL_BYTES`, then in a temp file
+#[derive(Default)]
+pub(super) struct Capture {
+    memory: Vec<u8>,
+    file: Option<BufWriter<NamedTempFile>>,
+    len: u64,
+    newlines: u64,
+    last: Option<u8>,
+}
+
+impl Capture {
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() && self.memory.len() + bytes.len() > SPILL_BYTES {
+            let mut file = BufWriter::new(NamedTempFile::new()?);
+            file.write_all(&std::mem::take(&mut self.memory))?;
+            self.file = Some(file);
+        }
+        match &mut self.file {
+            Some(file) => file.write_all(bytes)?,
+            None => self.memory.extend_from_slice(bytes),
+        }
+        self.len += bytes.len() as u64;
+        self.newlines += bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+        self.last = bytes.last().copied();
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `other`, streaming its file rather than reading it into memory
+    fn append(&mut self, other: Capture) -> io::Result<()> {
+        let Some(file) = other.file else {
+            return self.write(&other.memory);
+        };
+        let mut source = file.into_inner().map_err(|e| e.into_error())?;
+        source.seek(SeekFrom::Start(0))?;
+        if self.file.is_none() {
+            let mut file = BufWriter::new(NamedTempFile::new()?);
+            file.write_all(&std::mem::take(&mut self.memory))?;
+            self.file = Some(file);
+        }
+        io::copy(
+            &mut source,
+            self.file.as_mut().expect("file was just created"),
+        )?;
+        self.len += other.len;
+        self.newlines += other.newlines;
+        self.last = other.last;
+        Ok(())
+    }
+
+    /// Lines as `str::lines` counts them
+    fn lines(&self) -> usize {
+        let partial = self.last.is_some_and(|b| b != b'\n');
+        (self.newlines + partial as u64) as usize
+    }
+
+    /// The text of a capture that stayed in memory, or a preview of one that
+    /// spilled, whose file is then left in `spill`
+    fn into_result(self, spill: Option<&SpillSlot>) -> io::Result<String> {
+        let lines = self.lines();
+        let Some(file) = self.file else {
+            return Ok(String::from_utf8_lossy(&self.memory).into_owned());
+        };
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+
+        file.seek(SeekFrom::Start(0))?;
+        let mut head = Vec::new();
+        Read::by_ref(&mut file)
+            .take(PREVIEW_BYTES)
+            .read_to_end(&mut head)?;
+        let head = String::from_utf8_lossy(&head);
+        let head: Vec<&str> = head.lines().take(PREVIEW_LINES).collect();
+
+        let tail_start = self.len.saturating_sub(PREVIEW_BYTES);
+        file.seek(SeekFrom::Start(tail_start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+        let tail = String::from_utf8_lossy(&tail);
+        // Drop the partial line the read started in
+        let tail = match tail.split_once('\n') {
+            Some((_, rest)) if tail_start > 0 => rest,
+            _ => &tail,
+        };
+        let tail: Vec<&str> = tail.lines().collect();
+        let tail = &tail[tail.len().saturating_sub(PREVIEW_LINES)..];
+
+        let preview = format!(
+            "{}\n[... output too large to return whole: {} lines, {:.1} MB; showing the first and last {} lines ...]\n{}",
+            head.join("\n"),
+            lines,
+            self.len as f64 / (1024.0 * 1024.0),
+            PREVIEW_LINES,
+            tail.join("\n")
+        );
+        if let Some(slot) = spill {
+            slot.put(SpilledOutput::new(file.into_temp_path(), self.len, lines));
+        }
+        Ok(preview)
+    }
+}
+
+/// Combine a command's streams into its result: stdout, then stderr under
+/// `[stderr]`, then a non-zero exit code (-1 when the process was killed by a
+/// signal). A result over `SPILL_BYTES` comes back as a preview, with the
+/// full output left in `spill`.
+pub(super) fn finish(
+    stdout: Capture,
+    stderr: Capture,
+    exit_code: Option<i32>,
+    spill: Option<&SpillSlot>,
+) -> io::Result<String> {
+    let mut result = stdout;
+
+    if !stderr.is_empty() {
+        if !result.is_empty() {
+            result.write(b"\n")?;
+        }
+        result.write(b"[stderr]\n")?;
+        result.append(stderr)?;
+    }
+
+    if exit_code != Some(0) {
+        if !result.is_empty() {
+            result.write(b"\n")?;
+        }
+        result.write(format!("[exit code: {}]", exit_code.unwrap_or(-1)).as_bytes())?;
+    }
+
+    if result.is_empty() {
+        return Ok("(no output)".to_string());
+    }
+    result.into_result(spill)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(text: &str) -> Capture {
+        let mut capture = Capture::default();
+        capture.write(text.as_bytes()).unwrap();
+        capture
+    }
+
+    #[test]
+    fn test_finish_small_output_stays_in_memory() {
+        let slot = SpillSlot::default();
+        let result = finish(capture("out\n"), capture("err\n"), Some(2), Some(&slot)).unwrap();
+        assert_eq!(result, "out\n\n[stderr]\nerr\n\n[exit code: 2]");
+        assert!(slot.take().is_none());
+        assert_eq!(
+            finish(Capture::default(), Capture::default(), Some(0), None).unwrap(),
+            "(no output)"
+        );
+    }
+
+    #[test]
+    fn test_finish_spills_large_output_to_file() {
+        let mut stdout = Capture::default();
+        for i in 0..200_000 {
+            stdout.write(format!("line {}\n", i).as_bytes()).unwrap();
+        }
+        assert!(stdout.file.is_some());
+        let mut stderr = Capture::default();
+        for _ in 0..100_000 {
+            stderr.write(b"warning: 0123456789\n").unwrap();
+        }
+        assert!(stderr.file.is_some());
+
+        let slot = SpillSlot::default();
+        let preview = finish(stdout, stderr, Some(1), Some(&slot)).unwrap();
+        assert!(preview.starts_with("line 0\nline 1\n"));
+        assert!(preview.contains("output too large to return whole"));
+        assert!(preview.ends_with("warning: 0123456789\n\n[exit code: 1]"));
+        assert!(preview.len() < 2048);
+
+        let mut store = crate::output_store::OutputStore::new();
+        let (id, _) = store.adopt(slot.take().unwrap(), &preview);
+        let bytes = 2_288_890 + "\n[stderr]\n".len() + 2_000_000 + "\n[exit code: 1]".len();
+        assert_eq!(store.get_metadata(&id), Some((300_004, bytes)));
+        let range = store.get_range(&id, 199_999, Some(200_003)).unwrap();
+        assert!(range.contains("line 199999\n\n[stderr]\nwarning: 0123456789\n"));
+    }
+
+    #[tokio::test]
+    async fn test_read_chunk_bounds_long_lines() {
+        let data = [vec![b'x'; MAX_CHUNK + 10], b"\nend".to_vec()].concat();
+        let mut reader = tokio::io::BufReader::new(&data[..]);
+        let mut chunks = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            if read_chunk(&mut reader, &mut line).await.unwrap() == 0 {
+                break;
+            }
+            chunks.push(line.len());
+        }
+        assert_eq!(chunks, [MAX_CHUNK, 11, 3]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_limit_kills_process_group() {
+        let mut cmd = tokio::process::Command::new("sleep");
+        cmd.arg("30").process_group(0);
+        let mut child = cmd.spawn().unwrap();
+
+        let limit = OutputLimit::with_max(10, child.id());
+        assert!(limit.charge(10));
+        assert!(!limit.exceeded());
+        assert!(!limit.charge(1));
+        assert!(limit.exceeded());
+        let status = child.wait().await.unwrap();
+        assert_eq!(status.code(), None);
+        assert!(limit.note().contains("limit"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Command-backed tools declared in TOML, one file per tool, in
+//! `~/.eunice/tools/` and a project's `.eunice/tools/`:
+//!
+//! ```toml
+//! name = "deploy_status"
+//! description = "Show the rollout status of a service"
+//! command = 'kubectl rollout status "deploy/$EUNICE_ARG_SERVICE"'
+//! timeout_secs = 120
+//!
+//! [parameters]
+//! type = "object"
+//! required = ["service"]
+//! properties.service = { type = "string", description = "Service name" }
+//! ```
+//!
+//! `command` runs in the user's shell like Bash; `script` instead names an
+//! executable, relative to the TOML file. Each argument is in the environment
+//! as `EUNICE_ARG_<NAME>`, and all of them as JSON in `EUNICE_ARGS`; with
+//! `input = "stdin"` the JSON is also written to the process's stdin.
+
+use crate::env_policy::EnvPolicy;
+use crate::models::Tool;
+use crate::output_store::SpillSlot;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::bash::{run_command, user_shell, DEFAULT_TIMEOUT_SECS};
+use crate::tools::{make_tool, ProgressSender, ToolContext, ToolRegistry};
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tokio::process::Command;
+
+/// Names the registry already answers to: those of its built-in tools
+fn reserved_names() -> &'static [String] {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        ToolRegistry::new()
+            .get_tools()
+            .into_iter()
+            .map(|tool| tool.function.name)
+            .collect()
+    })
+}
+
+/// A `~/.eunice/tools/<file>.toml`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomToolFile {
+    name: String,
+    description: String,
+    /// JSON schema of the arguments (default: no arguments)
+    #[serde(default)]
+    parameters: Option<serde_json::Value>,
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    script: Option<String>,
+    #[serde(default)]
+    input: Input,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+/// How arguments reach the process besides the environment
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Input {
+    #[default]
+    Env,
+    Stdin,
+}
+
+#[derive(Debug, Clone)]
+enum Program {
+    /// Run with `$SHELL -c`
+    Shell(String),
+    /// Executed directly
+    Script(PathBuf),
+}
+
+/// One tool loaded from a TOML file
+#[derive(Debug, Clone)]
+pub struct CustomTool {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+    program: Program,
+    input: Input,
+    timeout_secs: u64,
+    /// The file it came from, for messages
+    source: PathBuf,
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+    env: Arc<EnvPolicy>,
+}
+
+/// The custom tools offered by a registry
+#[derive(Debug, Clone, Default)]
+pub struct CustomTools {
+    tools: Vec<CustomTool>,
+}
+
+impl CustomTools {
+    /// Load `~/.eunice/tools/*.toml`, then `<project>/.eunice/tools/*.toml`
+    /// (the process working directory when None); a project tool replaces a
+    /// global one of the same name. Tools run in `project`. Files that fail
+    /// to load are skipped; their errors are returned for the caller to report.
+    pub fn load(project: Option<&Path>) -> (Self, Vec<String>) {
+        let mut dirs = Vec::new();
+        if let Some(home) = dirs::home_dir() {
+            dirs.push(home.join(".eunice").join("tools"));
+        }
+        let project_root = project.map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        dirs.push(project_root.join(".eunice").join("tools"));
+        Self::load_from(&dirs, project)
+    }
+
+    /// Load every `*.toml` in `dirs`, later directories winning
+    pub fn load_from(dirs: &[PathBuf], cwd: Option<&Path>) -> (Self, Vec<String>) {
+        let mut tools: Vec<CustomTool> = Vec::new();
+        let mut errors = Vec::new();
+        let mut seen_dirs = Vec::new();
+
+        for dir in dirs {
+            // ~ as the project directory would otherwise load everything twice
+            let Ok(canonical) = dir.canonicalize() else {
+                continue;
+            };
+            if seen_dirs.contains(&canonical) {
+                continue;
+            }
+            seen_dirs.push(canonical);
+
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect();
+            files.sort();
+
+            let mut in_this_dir: Vec<String> = Vec::new();
+            for file in files {
+                match CustomTool::load(&file, cwd) {
+                    Ok(tool) if in_this_dir.contains(&tool.name) => errors.push(format!(
+                        "custom tool '{}': defined twice in {}",
+                        tool.name,
+                        dir.display()
+                    )),
+                    Ok(tool) => {
+                        in_this_dir.push(tool.name.clone());
+                        tools.retain(|t| t.name != tool.name);
+                        tools.push(tool);
+                    }
+                    Err(e) => errors.push(format!("custom tool {}: {}", file.display(), e)),
+                }
+            }
+        }
+        (Self { tools }, errors)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn get_specs(&self) -> Vec<Tool> {
+        self.tools.iter().map(CustomTool::get_spec).collect()
+    }
+
+    #[allow(dead_code)] // the registry looks tools up itself; kept for library callers
+    pub fn get(&self, name: &str) -> Option<&CustomTool> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// The tools, for `ToolRegistry::with_custom_tools`
+    pub fn into_tools(self) -> Vec<CustomTool> {
+        self.tools
+    }
+}
+
+impl CustomTool {
+    fn load(path: &Path, cwd: Option<&Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let file: CustomToolFile = toml::from_str(&text)?;
+
+        let name_ok = !file.name.is_empty()
+            && file.name.len() <= 64
+            && file
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !name_ok {
+            bail!("name must be 1-64 letters, digits, '_' or '-'");
+        }
+        if reserved_names().contains(&file.name) || file.name.starts_with("mcp__") {
+            bail!("name '{}' is taken by a built-in or MCP tool", file.name);
+        }
+
+        let program = match (file.command, file.script) {
+            (Some(command), None) => Program::Shell(command),
+            (None, Some(script)) => {
+                let base = path.parent().unwrap_or(Path::new("."));
+                Program::Script(base.join(script))
+            }
+            (Some(_), Some(_)) => bail!("set command or script, not both"),
+            (None, None) => bail!("set command or script"),
+        };
+
+        let parameters = match file.parameters {
+            None => serde_json::json!({ "type": "object", "properties": {} }),
+            Some(schema) if schema["type"] == "object" => schema,
+            Some(_) => bail!("parameters must be a JSON schema with type = \"object\""),
+        };
+
+        Ok(Self {
+            name: file.name,
+            description: file.description,
+            parameters,
+            program,
+            input: file.input,
+            timeout_secs: file.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS),
+            source: path.to_path_buf(),
+            cwd: cwd.map(Path::to_path_buf),
+            sandbox: None,
+            env: Arc::new(EnvPolicy::default()),
+        })
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(&self.name, &self.description, self.parameters.clone())
+    }
+
+    pub async fn execute(
+        &self,
+        args: serde_json::Value,
+        progress: Option<&ProgressSender>,
+        spill: Option<&SpillSlot>,
+    ) -> Result<String> {
+        let args = match args {
+            serde_json::Value::Null => serde_json::json!({}),
+            serde_json::Value::Object(_) => args,
+            _ => return Err(anyhow!("arguments must be a JSON object")),
+        };
+
+        let mut cmd = match &self.program {
+            Program::Shell(command) => {
+                let mut cmd = Command::new(user_shell());
+                cmd.arg("-c").arg(command);
+                cmd
+            }
+            Program::Script(script) => Command::new(script),
+        };
+        self.env.apply(&mut cmd);
+        cmd.env("EUNICE_ARGS", args.to_string());
+        if let Some(map) = args.as_object() {
+            for (key, value) in map {
+                cmd.env(env_name(key), env_value(value));
+            }
+        }
+        if let Some(dir) = &self.cwd {
+            cmd.current_dir(dir);
+        }
+        if let Some(policy) = &self.sandbox {
+            policy.restrict_command(&mut cmd)?;
+        }
+
+        let stdin = (self.input == Input::Stdin).then(|| args.to_string().into_bytes());
+        run_command(cmd, stdin, self.timeout_secs, progress, spill)
+            .await
+            .map_err(|e| anyhow!("{} (custom tool from {})", e, self.source.display()))
+    }
+}
+
+impl crate::tools::Tool for CustomTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(CustomTool::execute(self, args, ctx.progress, ctx.spill))
+    }
+
+    /// Run the tool under the policy's Landlock rules
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        self.sandbox = Some(policy);
+    }
+
+    fn set_env_policy(&mut self, policy: Arc<EnvPolicy>) {
+        self.env = policy;
+    }
+}
+
+/// `EUNICE_ARG_<NAME>`: upper-cased, anything but letters and digits as `_`
+fn env_name(key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("EUNICE_ARG_{}", key)
+}
+
+/// Strings as-is, anything else as JSON
+fn env_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, body: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(file), body).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_command_tool_gets_args_in_env_and_cwd() {
+        let tools_dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let work_path = work.path().canonicalize().unwrap();
+        write(
+            tools_dir.path(),
+            "greet.toml",
+            r#"
+name = "greet"
+description = "Greet someone"
+command = 'echo "hi $EUNICE_ARG_WHO x$EUNICE_ARG_TIMES"; pwd -P; echo "$EUNICE_ARGS"'
+
+[parameters]
+type = "object"
+required = ["who"]
+properties.who = { type = "string" }
+properties.times = { type = "integer" }
+"#,
+        );
+
+        let (tools, errors) =
+            CustomTools::load_from(&[tools_dir.path().to_path_buf()], Some(&work_path));
+        assert!(errors.is_empty(), "{:?}", errors);
+        let spec = &tools.get_specs()[0];
+        assert_eq!(spec.function.name, "greet");
+        assert_eq!(spec.function.parameters["required"][0], "who");
+
+        let out = tools
+            .get("greet")
+            .unwrap()
+            .execute(serde_json::json!({"who": "Ada", "times": 2}), None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "hi Ada x2\n{}\n{{\"times\":2,\"who\":\"Ada\"}}\n",
+                work_path.display()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_script_tool_reads_json_on_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("count.sh");
+        std::fs::write(&script, "#!/bin/sh\ncat\necho\nexit 3\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        write(
+            dir.path(),
+            "count.toml",
+            "name = \"count\"\ndescription = \"d\"\nscript = \"count.sh\"\ninput = \"stdin\"\n",
+        );
+
+        let (tools, errors) = CustomTools::load_from(&[dir.path().to_path_buf()], None);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let out = tools
+            .get("count")
+            .unwrap()
+            .execute(serde_json::json!({"n": [1, 2]}), None, None)
+            .await
+            .unwrap();
+        assert_eq!(out, "{\"n\":[1,2]}\n\n[exit code: 3]");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_command() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "slow.toml",
+            "name = \"slow\"\ndescription = \"d\"\ncommand = \"sleep 5\"\ntimeout_secs = 1\n",
+        );
+        let (tools, _) = CustomTools::load_from(&[dir.path().to_path_buf()], None);
+        let err = tools
+            .get("slow")
+            .unwrap()
+            .execute(serde_json::Value::Null, None, None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("timed out after 1 seconds"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("slow.toml"), "{}", err);
+    }
+
+    #[test]
+    fn test_project_overrides_global_and_bad_files_are_reported() {
+        let global = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        write(
+            global.path(),
+            "a.toml",
+            "name = \"status\"\ndescription = \"global\"\ncommand = \"true\"\n",
+        );
+        write(
+            project.path(),
+            "a.toml",
+            "name = \"status\"\ndescription = \"project\"\ncommand = \"true\"\n",
+        );
+        for (file, body) in [
+            ("b.toml", "name = \"Bash\"\ndescription = \"d\"\ncommand = \"x\"\n"),
+            ("c.toml", "name = \"c\"\ndescription = \"d\"\n"),
+            ("d.toml", "name = \"d e\"\ndescription = \"d\"\ncommand = \"x\"\n"),
+            ("e.toml", "name = \"e\"\ndescription = \"d\"\ncommand = \"x\"\nparameters = { type = \"string\" }\n"),
+            ("f.toml", "name = \"f\"\ndescription = \"d\"\ncommand = \"x\"\ntypo = 1\n"),
+            ("g.toml", "name = \"status\"\ndescription = \"again\"\ncommand = \"true\"\n"),
+        ] {
+            write(project.path(), file, body);
+        }
+        write(project.path(), "notes.txt", "ignored");
+
+        let (tools, errors) = CustomTools::load_from(
+            &[global.path().to_path_buf(), project.path().to_path_buf()],
+            None,
+        );
+        let specs = tools.get_specs();
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].function.description, "project");
+
+        let errors = errors.join("\n");
+        for expected in [
+            "taken by a built-in",
+            "set command or script",
+            "letters, digits",
+            "type = \"object\"",
+            "unknown field",
+            "defined twice",
+        ] {
+            assert!(errors.contains(expected), "{}: {}", expected, errors);
+        }
+    }
+}
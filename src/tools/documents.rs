@@ -0,0 +1,750 @@
+//! Text extraction for the Read tool: PDF pages, DOCX paragraphs and
+//! tables, spreadsheet sheets as CSV, and Jupyter notebook cells with their
+//! outputs. Everything is done in-process so Read works on hosts without
+//! poppler, LibreOffice or Python.
+
+use anyhow::{anyhow, bail, Result};
+use calamine::{Data, Reader};
+use quick_xml::events::Event;
+use std::io::Read;
+use std::path::Path;
+
+/// Larger documents are described rather than extracted
+const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Most of a DOCX's `word/document.xml` that is decompressed; a small file
+/// can inflate to far more than it takes on disk
+const MAX_DOCX_XML_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Documents Read can turn into text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum DocumentKind {
+    Pdf,
+    Docx,
+    Spreadsheet,
+    Notebook,
+}
+
+impl DocumentKind {
+    /// By extension, or by magic bytes for PDFs
+    pub(super) fn detect(path: &Path, head: &[u8]) -> Option<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("pdf") => Some(Self::Pdf),
+            Some("docx") => Some(Self::Docx),
+            Some("xlsx" | "xlsm" | "xlsb" | "xls" | "ods") => Some(Self::Spreadsheet),
+            Some("ipynb") => Some(Self::Notebook),
+            _ if head.starts_with(b"%PDF") => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// Which parts of a document to extract
+#[derive(Debug, Default)]
+pub(super) struct Selection<'a> {
+    /// PDF pages: `3`, `1-5`, `2,4,7-9`
+    pub pages: Option<&'a str>,
+    /// Spreadsheet sheet: name or 1-based number
+    pub sheet: Option<&'a str>,
+}
+
+/// Outcome of an extraction that did not fail because of the request itself
+pub(super) enum Extraction {
+    Text(String),
+    /// The file could not be parsed; Read falls back to the binary summary
+    Failed(String),
+}
+
+/// Extract `path` as text. Errors are for selections that do not fit the
+/// document (an unknown sheet, a page past the end), which the model can fix.
+pub(super) fn extract(
+    path: &Path,
+    kind: DocumentKind,
+    selection: &Selection,
+) -> Result<Extraction> {
+    if selection.pages.is_some() && kind != DocumentKind::Pdf {
+        bail!("'pages' only applies to PDF documents");
+    }
+    if selection.sheet.is_some() && kind != DocumentKind::Spreadsheet {
+        bail!("'sheet' only applies to spreadsheets");
+    }
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size > MAX_DOCUMENT_BYTES {
+        return Ok(Extraction::Failed(format!(
+            "document is larger than {} MB",
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        )));
+    }
+
+    match kind {
+        DocumentKind::Pdf => pdf(path, selection.pages),
+        DocumentKind::Docx => Ok(match docx(path) {
+            Ok(text) => Extraction::Text(text),
+            Err(e) => Extraction::Failed(e.to_string()),
+        }),
+        DocumentKind::Spreadsheet => spreadsheet(path, selection.sheet),
+        DocumentKind::Notebook => Ok(match notebook(path) {
+            Ok(text) => Extraction::Text(text),
+            Err(e) => Extraction::Failed(e.to_string()),
+        }),
+    }
+}
+
+fn pdf(path: &Path, pages: Option<&str>) -> Result<Extraction> {
+    let bytes = std::fs::read(path)?;
+    // pdf-extract panics on some malformed files rather than returning an error
+    let extracted =
+        std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(&bytes));
+    let texts = match extracted {
+        Ok(Ok(texts)) => texts,
+        Ok(Err(e)) => return Ok(Extraction::Failed(e.to_string())),
+        Err(_) => return Ok(Extraction::Failed("unsupported PDF structure".to_string())),
+    };
+
+    let total = texts.len();
+    let selected = match pages {
+        Some(spec) => parse_pages(spec, total)?,
+        None => (1..=total).collect(),
+    };
+
+    let mut out = match pages {
+        Some(spec) => format!("[PDF: {} pages, showing {}]\n", total, spec.trim()),
+        None => format!("[PDF: {} pages]\n", total),
+    };
+    let mut empty = 0;
+    for page in &selected {
+        let text = tidy(&texts[page - 1]);
+        if text.is_empty() {
+            empty += 1;
+        }
+        out.push_str(&format!("\n--- Page {} ---\n{}\n", page, text));
+    }
+    if empty == selected.len() && !selected.is_empty() {
+        out.push_str("\n[No text layer found; the pages may be scanned images]\n");
+    }
+    Ok(Extraction::Text(out))
+}
+
+/// `3`, `1-5`, `2,4,7-9` (1-based, in the order given) checked against `total`
+fn parse_pages(spec: &str, total: usize) -> Result<Vec<usize>> {
+    let invalid = || anyhow!("Invalid pages '{}': use e.g. 3, 1-5 or 2,4,7-9", spec);
+    let mut pages = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((a, b)) => (a.trim(), b.trim()),
+            None => (part, part),
+        };
+        let start: usize = start.parse().map_err(|_| invalid())?;
+        let end: usize = end.parse().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+            return Err(invalid());
+        }
+        if end > total {
+            bail!(
+                "Page {} is past the end of the document ({} pages)",
+                end,
+                total
+            );
+        }
+        pages.extend(start..=end);
+    }
+    if pages.is_empty() {
+        return Err(invalid());
+    }
+    Ok(pages)
+}
+
+/// Trim trailing spaces and runs of blank lines left by layout extraction
+fn tidy(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim_matches('\n').to_string()
+}
+
+fn spreadsheet(path: &Path, sheet: Option<&str>) -> Result<Extraction> {
+    let mut workbook = match calamine::open_workbook_auto(path) {
+        Ok(workbook) => workbook,
+        Err(e) => return Ok(Extraction::Failed(e.to_string())),
+    };
+    let names = workbook.sheet_names();
+
+    let selected: Vec<String> = match sheet {
+        None => names.clone(),
+        Some(wanted) => {
+            let by_name = names.iter().find(|n| n.eq_ignore_ascii_case(wanted.trim()));
+            let by_number = wanted
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| names.get(i));
+            match by_name.or(by_number) {
+                Some(name) => vec![name.clone()],
+                None => bail!("No sheet '{}'; sheets are: {}", wanted, names.join(", ")),
+            }
+        }
+    };
+
+    let mut out = format!(
+        "[Spreadsheet: {} sheets: {}]\n",
+        names.len(),
+        names.join(", ")
+    );
+    for name in selected {
+        let range = match workbook.worksheet_range(&name) {
+            Ok(range) => range,
+            Err(e) => {
+                out.push_str(&format!(
+                    "\n--- Sheet: {} ---\n[Could not read: {}]\n",
+                    name, e
+                ));
+                continue;
+            }
+        };
+        let (rows, columns) = range.get_size();
+        out.push_str(&format!(
+            "\n--- Sheet: {} ({} rows x {} columns) ---\n",
+            name, rows, columns
+        ));
+        for row in range.rows() {
+            let cells: Vec<String> = row.iter().map(|cell| csv_field(&cell_text(cell))).collect();
+            out.push_str(cells.join(",").trim_end_matches(','));
+            out.push('\n');
+        }
+    }
+    Ok(Extraction::Text(out))
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(t) if t.time() == chrono::NaiveTime::MIN => t.format("%Y-%m-%d").to_string(),
+            Some(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => cell.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+/// Quote a CSV field when it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn docx(path: &Path) -> Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .map_err(|_| anyhow!("not a Word document (no word/document.xml)"))?;
+    let xml = read_capped(entry, MAX_DOCX_XML_BYTES, "word/document.xml")?;
+    docx_text(&xml)
+}
+
+/// All of `reader` as text, refusing it past `limit` bytes
+fn read_capped(reader: impl Read, limit: u64, name: &str) -> Result<String> {
+    let mut text = String::new();
+    reader.take(limit + 1).read_to_string(&mut text)?;
+    if text.len() as u64 > limit {
+        bail!(
+            "{} is larger than {} MB uncompressed",
+            name,
+            limit / (1024 * 1024)
+        );
+    }
+    Ok(text)
+}
+
+/// Paragraphs of `word/document.xml` as lines: headings as `#` lines, list
+/// items as `- `, table rows as `| a | b |`
+fn docx_text(xml: &str) -> Result<String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut prefix = String::new();
+    let mut in_text = false;
+    // Cells of the current table row, and paragraphs of the current cell
+    let mut row: Option<Vec<String>> = None;
+    let mut cell: Option<Vec<String>> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"t" => in_text = true,
+                b"tr" => row = Some(Vec::new()),
+                b"tc" => cell = Some(Vec::new()),
+                b"numPr" if prefix.is_empty() => prefix = "- ".to_string(),
+                b"p" => {
+                    paragraph.clear();
+                    prefix.clear();
+                }
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"tab" => paragraph.push('\t'),
+                b"br" | b"cr" => paragraph.push('\n'),
+                b"pStyle" => {
+                    if let Some(style) = e.try_get_attribute("w:val")? {
+                        let style = style.decode_and_unescape_value(reader.decoder())?;
+                        if let Some(level) = heading_level(&style) {
+                            prefix = format!("{} ", "#".repeat(level));
+                        }
+                    }
+                }
+                b"numPr" if prefix.is_empty() => prefix = "- ".to_string(),
+                _ => {}
+            },
+            Event::Text(e) if in_text => paragraph.push_str(&e.decode()?),
+            Event::GeneralRef(e) if in_text => {
+                if let Some(c) = e.resolve_char_ref()? {
+                    paragraph.push(c);
+                } else if let Some(s) = quick_xml::escape::resolve_predefined_entity(&e.decode()?) {
+                    paragraph.push_str(s);
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"p" => {
+                    let text = format!("{}{}", prefix, paragraph.trim_end());
+                    match cell.as_mut() {
+                        Some(cell) => cell.push(text),
+                        None => {
+                            out.push_str(&text);
+                            out.push('\n');
+                        }
+                    }
+                    paragraph.clear();
+                    prefix.clear();
+                }
+                b"tc" => {
+                    if let (Some(row), Some(cell)) = (row.as_mut(), cell.take()) {
+                        row.push(cell.join(" ").trim().replace('|', "\\|"));
+                    }
+                }
+                b"tr" => {
+                    if let Some(row) = row.take() {
+                        out.push_str(&format!("| {} |\n", row.join(" | ")));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    Ok(tidy(&out))
+}
+
+/// `Heading1`..`Heading9` and `Title`; Markdown stops at `######`, so
+/// `Heading7`..`Heading9` share level 6
+fn heading_level(style: &str) -> Option<usize> {
+    if style == "Title" {
+        return Some(1);
+    }
+    let level: usize = style.strip_prefix("Heading")?.parse().ok()?;
+    (1..=9).contains(&level).then_some(level.min(6))
+}
+
+fn notebook(path: &Path) -> Result<String> {
+    let text = std::fs::read_to_string(path)?;
+    let notebook: serde_json::Value = serde_json::from_str(&text)?;
+    let cells = notebook["cells"]
+        .as_array()
+        .ok_or_else(|| anyhow!("no cells array"))?;
+    let language = notebook["metadata"]["kernelspec"]["language"]
+        .as_str()
+        .or(notebook["metadata"]["language_info"]["name"].as_str())
+        .unwrap_or("");
+
+    let mut out = format!("[Notebook: {} cells", cells.len());
+    if !language.is_empty() {
+        out.push_str(&format!(", {}", language));
+    }
+    out.push_str("]\n");
+
+    for (i, cell) in cells.iter().enumerate() {
+        let kind = cell["cell_type"].as_str().unwrap_or("unknown");
+        let header = match cell["execution_count"].as_u64() {
+            Some(n) => format!("--- Cell {} [{}] In[{}] ---", i + 1, kind, n),
+            None => format!("--- Cell {} [{}] ---", i + 1, kind),
+        };
+        out.push_str(&format!(
+            "\n{}\n{}\n",
+            header,
+            joined(&cell["source"]).trim_end()
+        ));
+
+        for output in cell["outputs"].as_array().into_iter().flatten() {
+            let rendered = notebook_output(output);
+            if !rendered.is_empty() {
+                out.push_str(&format!("--- Output ---\n{}\n", rendered.trim_end()));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn notebook_output(output: &serde_json::Value) -> String {
+    match output["output_type"].as_str() {
+        Some("stream") => joined(&output["text"]),
+        Some("error") => {
+            let traceback: Vec<String> = output["traceback"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|line| line.as_str())
+                .map(strip_ansi)
+                .collect();
+            if traceback.is_empty() {
+                format!(
+                    "{}: {}",
+                    output["ename"].as_str().unwrap_or("Error"),
+                    output["evalue"].as_str().unwrap_or("")
+                )
+            } else {
+                traceback.join("\n")
+            }
+        }
+        Some("execute_result" | "display_data") => {
+            let data = &output["data"];
+            if !data["text/plain"].is_null() {
+                joined(&data["text/plain"])
+            } else if let Some(mime) = data.as_object().and_then(|d| d.keys().next()) {
+                format!("[{} output]", mime)
+            } else {
+                String::new()
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Notebook text fields are a string or a list of lines
+fn joined(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Drop the colour codes IPython puts in tracebacks
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ ... final byte in @..~
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) && c != '[' {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A zip archive of `(name, contents)` entries
+    pub(in crate::tools) fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in entries {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// A PDF with one Helvetica text line per page
+    pub(in crate::tools) fn write_pdf(path: &Path, pages: &[&str]) {
+        let n = pages.len();
+        // Objects: 1 catalog, 2 pages, 3 font, then a page and its content per page
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..n)
+                    .map(|i| format!("{} 0 R", 4 + 2 * i))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                n
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+        for (i, text) in pages.iter().enumerate() {
+            let stream = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                5 + 2 * i
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                stream.len(),
+                stream
+            ));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .as_bytes(),
+        );
+        std::fs::write(path, pdf).unwrap();
+    }
+
+    fn text(extraction: Extraction) -> String {
+        match extraction {
+            Extraction::Text(text) => text,
+            Extraction::Failed(e) => panic!("extraction failed: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_pdf_pages_and_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.pdf");
+        write_pdf(&path, &["First page", "Second page", "Third page"]);
+
+        let all = text(extract(&path, DocumentKind::Pdf, &Selection::default()).unwrap());
+        assert!(all.starts_with("[PDF: 3 pages]"), "{}", all);
+        assert!(all.contains("--- Page 2 ---\nSecond page"), "{}", all);
+
+        let selection = Selection {
+            pages: Some("1,3"),
+            sheet: None,
+        };
+        let some = text(extract(&path, DocumentKind::Pdf, &selection).unwrap());
+        assert!(
+            some.contains("First page") && some.contains("Third page"),
+            "{}",
+            some
+        );
+        assert!(!some.contains("Second page"), "{}", some);
+
+        let selection = Selection {
+            pages: Some("2-9"),
+            sheet: None,
+        };
+        let err = extract(&path, DocumentKind::Pdf, &selection).err().unwrap();
+        assert!(err.to_string().contains("past the end"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_pages() {
+        assert_eq!(parse_pages("2", 5).unwrap(), vec![2]);
+        assert_eq!(parse_pages("1-3, 5", 5).unwrap(), vec![1, 2, 3, 5]);
+        assert!(parse_pages("0", 5).is_err());
+        assert!(parse_pages("3-1", 5).is_err());
+        assert!(parse_pages("x", 5).is_err());
+        assert!(parse_pages("", 5).is_err());
+    }
+
+    #[test]
+    fn test_docx_paragraphs_headings_lists_and_tables() {
+        let xml = r#"<?xml version="1.0"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Report</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Tom &amp; </w:t></w:r><w:r><w:t>Jerry</w:t><w:tab/><w:t>x</w:t></w:r></w:p>
+<w:p><w:pPr><w:numPr><w:ilvl w:val="0"/></w:numPr></w:pPr><w:r><w:t>item</w:t></w:r></w:p>
+<w:tbl><w:tr><w:tc><w:p><w:r><w:t>a</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>b|c</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+</w:body></w:document>"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.docx");
+        write_zip(&path, &[("word/document.xml", xml)]);
+
+        let out = text(extract(&path, DocumentKind::Docx, &Selection::default()).unwrap());
+        assert_eq!(out, "# Report\nTom & Jerry\tx\n- item\n| a | b\\|c |");
+
+        let bad = dir.path().join("bad.docx");
+        write_zip(&bad, &[("other.xml", "<x/>")]);
+        assert!(matches!(
+            extract(&bad, DocumentKind::Docx, &Selection::default()).unwrap(),
+            Extraction::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn test_heading_levels() {
+        assert_eq!(heading_level("Title"), Some(1));
+        assert_eq!(heading_level("Heading2"), Some(2));
+        assert_eq!(heading_level("Heading8"), Some(6));
+        assert_eq!(heading_level("Heading10"), None);
+        assert_eq!(heading_level("Normal"), None);
+    }
+
+    #[test]
+    fn test_read_capped_refuses_oversized_entries() {
+        let five_mb = "x".repeat(5 * 1024 * 1024);
+        assert_eq!(read_capped("abc".as_bytes(), 3, "a").unwrap(), "abc");
+        let err = read_capped(five_mb.as_bytes(), 4 * 1024 * 1024, "word/document.xml")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "word/document.xml is larger than 4 MB uncompressed");
+    }
+
+    #[test]
+    fn test_xlsx_sheets_as_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.xlsx");
+        write_zip(
+            &path,
+            &[
+                (
+                    "[Content_Types].xml",
+                    r#"<?xml version="1.0"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/worksheets/sheet2.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#,
+                ),
+                (
+                    "_rels/.rels",
+                    r#"<?xml version="1.0"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#,
+                ),
+                (
+                    "xl/workbook.xml",
+                    r#"<?xml version="1.0"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Prices" sheetId="1" r:id="rId1"/><sheet name="Notes" sheetId="2" r:id="rId2"/></sheets></workbook>"#,
+                ),
+                (
+                    "xl/_rels/workbook.xml.rels",
+                    r#"<?xml version="1.0"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/></Relationships>"#,
+                ),
+                (
+                    "xl/worksheets/sheet1.xml",
+                    r#"<?xml version="1.0"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData><row r="1"><c r="A1" t="inlineStr"><is><t>item</t></is></c><c r="B1" t="inlineStr"><is><t>price</t></is></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>tea, green</t></is></c><c r="B2"><v>2.5</v></c></row></sheetData></worksheet>"#,
+                ),
+                (
+                    "xl/worksheets/sheet2.xml",
+                    r#"<?xml version="1.0"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData><row r="1"><c r="A1" t="inlineStr"><is><t>hello</t></is></c></row></sheetData></worksheet>"#,
+                ),
+            ],
+        );
+
+        let all = text(extract(&path, DocumentKind::Spreadsheet, &Selection::default()).unwrap());
+        assert!(
+            all.starts_with("[Spreadsheet: 2 sheets: Prices, Notes]"),
+            "{}",
+            all
+        );
+        assert!(
+            all.contains(
+                "--- Sheet: Prices (2 rows x 2 columns) ---\nitem,price\n\"tea, green\",2.5\n"
+            ),
+            "{}",
+            all
+        );
+        assert!(all.contains("--- Sheet: Notes"), "{}", all);
+
+        for wanted in ["notes", "2"] {
+            let selection = Selection {
+                pages: None,
+                sheet: Some(wanted),
+            };
+            let one = text(extract(&path, DocumentKind::Spreadsheet, &selection).unwrap());
+            assert!(one.contains("hello") && !one.contains("tea"), "{}", one);
+        }
+        let selection = Selection {
+            pages: None,
+            sheet: Some("Missing"),
+        };
+        let err = extract(&path, DocumentKind::Spreadsheet, &selection)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("sheets are: Prices, Notes"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_notebook_cells_and_outputs() {
+        let notebook = serde_json::json!({
+            "metadata": {"kernelspec": {"language": "python"}},
+            "cells": [
+                {"cell_type": "markdown", "source": ["# Title\n", "Intro"]},
+                {"cell_type": "code", "execution_count": 3, "source": "print(1)\n1 + 1",
+                 "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["1\n"]},
+                    {"output_type": "execute_result", "data": {"text/plain": ["2"]}},
+                    {"output_type": "display_data", "data": {"image/png": "iVBOR"}}
+                 ]},
+                {"cell_type": "code", "execution_count": 4, "source": "1/0",
+                 "outputs": [{"output_type": "error", "ename": "ZeroDivisionError",
+                              "evalue": "division by zero",
+                              "traceback": ["\u{1b}[0;31mZeroDivisionError\u{1b}[0m: division by zero"]}]}
+            ]
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nb.ipynb");
+        std::fs::write(&path, notebook.to_string()).unwrap();
+
+        let out = text(extract(&path, DocumentKind::Notebook, &Selection::default()).unwrap());
+        assert_eq!(
+            out,
+            "[Notebook: 3 cells, python]\n\
+             \n--- Cell 1 [markdown] ---\n# Title\nIntro\n\
+             \n--- Cell 2 [code] In[3] ---\nprint(1)\n1 + 1\n\
+             --- Output ---\n1\n--- Output ---\n2\n--- Output ---\n[image/png output]\n\
+             \n--- Cell 3 [code] In[4] ---\n1/0\n\
+             --- Output ---\nZeroDivisionError: division by zero\n"
+        );
+    }
+
+    #[test]
+    fn test_selectors_must_match_the_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nb.ipynb");
+        std::fs::write(&path, "{\"cells\": []}").unwrap();
+        let selection = Selection {
+            pages: Some("1"),
+            sheet: None,
+        };
+        assert!(extract(&path, DocumentKind::Notebook, &selection).is_err());
+        let selection = Selection {
+            pages: None,
+            sheet: Some("1"),
+        };
+        assert!(extract(&path, DocumentKind::Notebook, &selection).is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{checkpointed, make_tool, ToolContext};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Unchanged lines shown around an edit in the returned diff
+const DIFF_CONTEXT_LINES: usize = 2;
+
+/// Longest diff returned to the model; the rest is summarized
+const MAX_DIFF_LINES: usize = 60;
+
+/// Edit tool for replacing an exact string in a file
+pub struct EditTool {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+impl EditTool {
+    pub fn new() -> Self {
+        Self::with_cwd(None)
+    }
+
+    /// Resolve relative paths against `cwd` instead of the process working
+    /// directory. Per-tool so concurrent runs cannot race each other.
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.cwd {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "Edit",
+            "Replace an exact string in an existing file. old_string must match exactly once \
+             (include surrounding lines to make it unique) unless replace_all is set. \
+             Prefer this over Write for changing part of a file. Returns a diff of the change.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Absolute or relative path to the file"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "Exact text to replace, including whitespace and indentation"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "Text to replace it with"
+                    },
+                    "replace_all": {
+                        "type": "boolean",
+                        "description": "Replace every occurrence instead of requiring exactly one (default: false)"
+                    }
+                },
+                "required": ["path", "old_string", "new_string"]
+            }),
+        )
+    }
+
+    pub fn execute(&self, args: serde_json::Value) -> Result<String> {
+        let path_str = args["path"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter"))?;
+        let old_string = args["old_string"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'old_string' parameter"))?;
+        let new_string = args["new_string"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'new_string' parameter"))?;
+        let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+
+        if old_string.is_empty() {
+            return Err(anyhow::anyhow!(
+                "old_string must not be empty; use Write to create a file"
+            ));
+        }
+        if old_string == new_string {
+            return Err(anyhow::anyhow!("old_string and new_string are identical"));
+        }
+
+        let path = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_write(&path)?;
+        }
+        if path.is_dir() {
+            return Err(anyhow::anyhow!("Cannot edit a directory: {}", path_str));
+        }
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("Failed to read file: {}", path_str))?;
+        let content = String::from_utf8(bytes)
+            .map_err(|_| anyhow::anyhow!("Cannot edit binary file: {}", path_str))?;
+
+        // Models write "\n"; match and keep the file's own CRLF line endings
+        let (old_string, new_string) = if content.contains("\r\n") && !old_string.contains('\r') {
+            (
+                old_string.replace('\n', "\r\n"),
+                new_string.replace('\n', "\r\n"),
+            )
+        } else {
+            (old_string.to_string(), new_string.to_string())
+        };
+
+        let matches = content.matches(old_string.as_str()).count();
+        if matches == 0 {
+            return Err(anyhow::anyhow!(
+                "old_string not found in {}. It must match the file exactly, including whitespace.",
+                path_str
+            ));
+        }
+        if matches > 1 && !replace_all {
+            return Err(anyhow::anyhow!(
+                "old_string matches {} times in {}. Include more surrounding lines to make it unique, \
+                 or set replace_all to replace every occurrence.",
+                matches,
+                path_str
+            ));
+        }
+
+        let updated = if replace_all {
+            content.replace(old_string.as_str(), &new_string)
+        } else {
+            content.replacen(old_string.as_str(), &new_string, 1)
+        };
+
+        // Writing in place keeps the file's permissions (and any symlink)
+        std::fs::write(&path, &updated)
+            .with_context(|| format!("Failed to write file: {}", path_str))?;
+
+        let occurrences = if matches == 1 {
+            "1 occurrence".to_string()
+        } else {
+            format!("{} occurrences", matches)
+        };
+        Ok(format!(
+            "Edited {} ({} replaced)\n{}",
+            path_str,
+            occurrences,
+            compact_diff(&content, &updated)
+        ))
+    }
+}
+
+impl crate::tools::Tool for EditTool {
+    fn name(&self) -> &str {
+        "Edit"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let path = args["path"].as_str().map(|p| self.resolve(p));
+            checkpointed(ctx.checkpoints, "Edit", path.into_iter().collect(), || {
+                EditTool::execute(self, args)
+            })
+        })
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
+impl Default for EditTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unified-style diff of the one region that differs between `before` and
+/// `after`: from the first changed line to the last, with a little context
+fn compact_diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let start = prefix.saturating_sub(DIFF_CONTEXT_LINES);
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    let context_end = (old_end + DIFF_CONTEXT_LINES).min(old.len());
+
+    let mut lines = vec![format!(
+        "@@ -{},{} +{},{} @@",
+        start + 1,
+        context_end - start,
+        start + 1,
+        new_end + (context_end - old_end) - start
+    )];
+    lines.extend(old[start..prefix].iter().map(|l| format!(" {}", l)));
+    lines.extend(old[prefix..old_end].iter().map(|l| format!("-{}", l)));
+    lines.extend(new[prefix..new_end].iter().map(|l| format!("+{}", l)));
+    lines.extend(old[old_end..context_end].iter().map(|l| format!(" {}", l)));
+
+    if lines.len() > MAX_DIFF_LINES {
+        let hidden = lines.len() - MAX_DIFF_LINES;
+        lines.truncate(MAX_DIFF_LINES);
+        lines.push(format!("... ({} more diff lines)", hidden));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn edit(path: &Path, old: &str, new: &str) -> Result<String> {
+        EditTool::new().execute(serde_json::json!({
+            "path": path.to_str().unwrap(),
+            "old_string": old,
+            "new_string": new,
+        }))
+    }
+
+    #[test]
+    fn test_edit_tool_spec() {
+        let spec = EditTool::new().get_spec();
+        assert_eq!(spec.function.name, "Edit");
+        let required = spec.function.parameters["required"].as_array().unwrap();
+        assert_eq!(required.len(), 3);
+    }
+
+    #[test]
+    fn test_edit_replaces_unique_match_and_returns_diff() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(
+            &path,
+            "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n",
+        )
+        .unwrap();
+
+        let result = edit(&path, "let x = 1;", "let x = 2;").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn main() {\n    let x = 2;\n    println!(\"{}\", x);\n}\n"
+        );
+        assert!(result.contains("1 occurrence replaced"), "{}", result);
+        assert!(result.contains("-    let x = 1;"), "{}", result);
+        assert!(result.contains("+    let x = 2;"), "{}", result);
+        assert!(result.contains(" fn main() {"), "{}", result);
+        assert!(result.contains("@@ -1,4 +1,4 @@"), "{}", result);
+    }
+
+    #[test]
+    fn test_edit_rejects_missing_and_ambiguous_matches() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "foo\nbar\nfoo\n").unwrap();
+
+        let err = edit(&path, "baz", "qux").unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+
+        let err = edit(&path, "foo", "qux").unwrap_err();
+        assert!(err.to_string().contains("matches 2 times"), "{}", err);
+
+        // Nothing was written
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "foo\nbar\nfoo\n");
+    }
+
+    #[test]
+    fn test_edit_replace_all() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "foo\nbar\nfoo\n").unwrap();
+
+        let result = EditTool::new()
+            .execute(serde_json::json!({
+                "path": path.to_str().unwrap(),
+                "old_string": "foo",
+                "new_string": "baz",
+                "replace_all": true
+            }))
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "baz\nbar\nbaz\n");
+        assert!(result.contains("2 occurrences replaced"), "{}", result);
+    }
+
+    #[test]
+    fn test_edit_keeps_crlf_line_endings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("win.txt");
+        std::fs::write(&path, "one\r\ntwo\r\nthree\r\n").unwrap();
+
+        edit(&path, "one\ntwo", "one\nTWO\nextra").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "one\r\nTWO\r\nextra\r\nthree\r\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.sh");
+        std::fs::write(&path, "#!/bin/sh\necho hi\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        edit(&path, "echo hi", "echo bye").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn test_edit_rejects_empty_or_identical_strings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "foo\n").unwrap();
+
+        assert!(edit(&path, "", "x").is_err());
+        assert!(edit(&path, "foo", "foo").is_err());
+        assert!(edit(&dir.path().join("missing.txt"), "a", "b").is_err());
+    }
+
+    #[test]
+    fn test_compact_diff_insertion_and_deletion() {
+        let diff = compact_diff("a\nb\nc\nd\ne\nf\n", "a\nb\nc\nX\nd\ne\nf\n");
+        assert_eq!(diff, "@@ -2,4 +2,5 @@\n b\n c\n+X\n d\n e");
+
+        let diff = compact_diff("a\nb\nc\n", "a\nc\n");
+        assert_eq!(diff, "@@ -1,3 +1,2 @@\n a\n-b\n c");
+    }
+}
@@ -0,0 +1,130 @@
+use crate::models::Tool;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+
+pub const GET_OUTPUT_TOOL_NAME: &str = "get_output";
+
+/// get_output: read back a range of a result the agent truncated into its
+/// output store. Only the CLI keeps a store; the webapp removes this tool.
+pub struct GetOutputTool;
+
+impl GetOutputTool {
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            GET_OUTPUT_TOOL_NAME,
+            "Retrieve a range of lines from a previous tool output by its ID. Tool outputs that are too large are automatically truncated, showing the first and last 10 lines. Use this tool to retrieve the middle sections or re-read specific ranges.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The output ID (e.g., 'out_001') shown in the truncated output header."
+                    },
+                    "start": {
+                        "type": "integer",
+                        "description": "Start line number (0-indexed). Defaults to 0."
+                    },
+                    "end": {
+                        "type": "integer",
+                        "description": "End line number (exclusive). Defaults to start + 100."
+                    }
+                },
+                "required": ["id"]
+            }),
+        )
+    }
+}
+
+impl crate::tools::Tool for GetOutputTool {
+    fn name(&self) -> &str {
+        GET_OUTPUT_TOOL_NAME
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let outputs = ctx
+                .outputs
+                .ok_or_else(|| anyhow!("Output store not available"))?;
+            let id = args["id"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Missing id parameter"))?;
+            let start = args["start"].as_u64().map(|n| n as usize).unwrap_or(0);
+            let end = args["end"].as_u64().map(|n| n as usize);
+            outputs.get_range(id, start, end)
+        })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output_store::OutputStore;
+    use crate::tools::{BackgroundJobs, Tool as _};
+
+    #[test]
+    fn test_get_output_tool_spec() {
+        let spec = GetOutputTool.get_spec();
+        assert_eq!(spec.function.name, GET_OUTPUT_TOOL_NAME);
+        assert_eq!(spec.tool_type, "function");
+        assert!(spec.function.description.contains("Retrieve"));
+        assert!(spec.function.description.contains("truncated"));
+
+        // Check parameters
+        let params = &spec.function.parameters;
+        assert_eq!(params["type"], "object");
+        assert!(params["properties"]["id"]["type"].as_str() == Some("string"));
+        assert!(params["properties"]["start"]["type"].as_str() == Some("integer"));
+        assert!(params["properties"]["end"]["type"].as_str() == Some("integer"));
+
+        // Check required fields - only id is required
+        let required = params["required"].as_array().unwrap();
+        assert!(required.contains(&serde_json::json!("id")));
+        assert!(!required.contains(&serde_json::json!("start")));
+        assert!(!required.contains(&serde_json::json!("end")));
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_output() {
+        let mut store = OutputStore::new();
+
+        // Store some content
+        let lines: Vec<String> = (1..=200).map(|i| format!("line {}", i)).collect();
+        let content = lines.join("\n");
+        let (id, _) = store.store(content).unwrap();
+
+        let jobs = BackgroundJobs::new();
+        let ctx = ToolContext {
+            outputs: Some(&store),
+            ..ToolContext::new(&jobs)
+        };
+        let args = serde_json::json!({
+            "id": id,
+            "start": 50,
+            "end": 60
+        });
+
+        let result = GetOutputTool.execute(args, ctx).await.unwrap();
+        assert!(result.contains("line 51")); // 0-indexed
+        assert!(result.contains("line 60"));
+
+        // Without a store there is nothing to read
+        let err = GetOutputTool
+            .execute(serde_json::json!({"id": id}), ToolContext::new(&jobs))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not available"));
+    }
+}
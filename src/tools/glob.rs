@@ -0,0 +1,267 @@
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Most paths a single Glob returns
+const MAX_GLOB_RESULTS: usize = 200;
+
+/// Glob tool for finding files by name pattern
+pub struct GlobTool {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+impl GlobTool {
+    pub fn new() -> Self {
+        Self::with_cwd(None)
+    }
+
+    /// Resolve relative paths against `cwd` instead of the process working
+    /// directory. Per-tool so concurrent runs cannot race each other.
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.cwd {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "Glob",
+            "Find files by name pattern, newest first. Patterns match paths relative to the search \
+             directory: \"*.rs\" matches only at the top level, \"**/*.rs\" at any depth, \
+             \"src/**/*.{ts,tsx}\" under src. Skips hidden files and anything in .gitignore. \
+             Prefer this over find in Bash.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Glob pattern, e.g. \"**/*.rs\""
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to search in (default: the working directory)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        )
+    }
+
+    pub fn execute(&self, args: serde_json::Value) -> Result<String> {
+        let pattern = args["pattern"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'pattern' parameter"))?;
+        let path_str = args["path"].as_str().unwrap_or(".");
+
+        let root = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_read(&root)?;
+        }
+        if !root.is_dir() {
+            return Err(anyhow::anyhow!("Not a directory: {}", path_str));
+        }
+
+        let matcher = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?
+            .compile_matcher();
+
+        let mut matches: Vec<(SystemTime, PathBuf)> = walk_files(&root)
+            .filter_map(|file| {
+                let relative = file.strip_prefix(&root).ok()?;
+                if !matcher.is_match(relative) {
+                    return None;
+                }
+                let modified = file
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((modified, display_path(path_str, relative)))
+            })
+            .collect();
+
+        if matches.is_empty() {
+            return Ok(format!("No files match {} in {}", pattern, path_str));
+        }
+
+        // Newest first; ties by path so results are stable
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        let total = matches.len();
+        let mut out: Vec<String> = matches
+            .into_iter()
+            .take(MAX_GLOB_RESULTS)
+            .map(|(_, path)| path.display().to_string())
+            .collect();
+        if total > MAX_GLOB_RESULTS {
+            out.push(format!(
+                "[{} more files not shown; narrow the pattern or path]",
+                total - MAX_GLOB_RESULTS
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
+
+impl crate::tools::Tool for GlobTool {
+    fn name(&self) -> &str {
+        "Glob"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { GlobTool::execute(self, args) })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
+impl Default for GlobTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every file under `root`, skipping hidden entries and anything excluded by
+/// .gitignore / .ignore files, whether or not `root` is inside a git repo
+pub(super) fn walk_files(root: &Path) -> impl Iterator<Item = PathBuf> {
+    ignore::WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+/// Path shown to the model: relative to the search directory as it was given,
+/// so results can be passed straight back to Read or Edit
+pub(super) fn display_path(path_arg: &str, relative: &Path) -> PathBuf {
+    if path_arg == "." {
+        relative.to_path_buf()
+    } else {
+        Path::new(path_arg).join(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn glob(dir: &TempDir, args: serde_json::Value) -> String {
+        GlobTool::with_cwd(Some(dir.path().to_path_buf()))
+            .execute(args)
+            .unwrap()
+    }
+
+    fn touch(dir: &TempDir, path: &str, age_secs: u64) {
+        let full = dir.path().join(path);
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(&full, path).unwrap();
+        let mtime = SystemTime::now() - std::time::Duration::from_secs(age_secs);
+        std::fs::File::options()
+            .write(true)
+            .open(&full)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_glob_tool_spec() {
+        let spec = GlobTool::new().get_spec();
+        assert_eq!(spec.function.name, "Glob");
+    }
+
+    #[test]
+    fn test_glob_newest_first_and_depth() {
+        let dir = TempDir::new().unwrap();
+        touch(&dir, "old.rs", 300);
+        touch(&dir, "src/new.rs", 10);
+        touch(&dir, "src/mid.rs", 100);
+        touch(&dir, "notes.txt", 0);
+
+        assert_eq!(
+            glob(&dir, serde_json::json!({"pattern": "**/*.rs"})),
+            "src/new.rs\nsrc/mid.rs\nold.rs"
+        );
+        assert_eq!(glob(&dir, serde_json::json!({"pattern": "*.rs"})), "old.rs");
+        assert_eq!(
+            glob(&dir, serde_json::json!({"pattern": "*.rs", "path": "src"})),
+            "src/new.rs\nsrc/mid.rs"
+        );
+    }
+
+    #[test]
+    fn test_glob_respects_gitignore_and_hidden() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n*.log\n").unwrap();
+        touch(&dir, "src/lib.rs", 0);
+        touch(&dir, "target/debug/build.rs", 0);
+        touch(&dir, "debug.log", 0);
+        touch(&dir, ".hidden/secret.rs", 0);
+
+        assert_eq!(
+            glob(&dir, serde_json::json!({"pattern": "**/*"})),
+            "src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_glob_no_matches_and_errors() {
+        let dir = TempDir::new().unwrap();
+        touch(&dir, "a.txt", 0);
+
+        assert!(glob(&dir, serde_json::json!({"pattern": "*.rs"})).starts_with("No files match"));
+
+        let tool = GlobTool::with_cwd(Some(dir.path().to_path_buf()));
+        assert!(tool.execute(serde_json::json!({"pattern": "a["})).is_err());
+        assert!(tool
+            .execute(serde_json::json!({"pattern": "*", "path": "missing"}))
+            .is_err());
+        assert!(tool.execute(serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_glob_caps_results() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..(MAX_GLOB_RESULTS + 5) {
+            std::fs::write(dir.path().join(format!("f{}.txt", i)), "").unwrap();
+        }
+
+        let result = glob(&dir, serde_json::json!({"pattern": "*.txt"}));
+        assert_eq!(result.lines().count(), MAX_GLOB_RESULTS + 1);
+        assert!(result.ends_with("[5 more files not shown; narrow the pattern or path]"));
+    }
+}
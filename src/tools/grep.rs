@@ -0,0 +1,496 @@
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::glob::{display_path, walk_files};
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default and maximum number of result lines a single Grep returns
+const DEFAULT_HEAD_LIMIT: usize = 200;
+const MAX_HEAD_LIMIT: usize = 1000;
+
+/// Matched lines longer than this are cut (minified files, data blobs)
+const MAX_LINE_CHARS: usize = 300;
+
+/// Files with a NUL byte in this prefix are treated as binary and skipped
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Files larger than this are skipped rather than read into memory
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Skipped large files named in the note; the rest are only counted
+const MAX_SKIPPED_NAMES: usize = 5;
+
+/// How Grep reports what it found
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputMode {
+    FilesWithMatches,
+    Content,
+    Count,
+}
+
+/// Grep tool for searching file contents by regex
+pub struct GrepTool {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+impl GrepTool {
+    pub fn new() -> Self {
+        Self::with_cwd(None)
+    }
+
+    /// Resolve relative paths against `cwd` instead of the process working
+    /// directory. Per-tool so concurrent runs cannot race each other.
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.cwd {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "Grep",
+            "Search file contents with a regular expression (Rust regex syntax). By default lists \
+             the files that match; output_mode \"content\" shows matching lines as path:line:text \
+             (with optional context lines) and \"count\" shows matches per file. Skips hidden, \
+             binary and .gitignore'd files, and files over 10 MB. Prefer this over grep or rg in \
+             Bash.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression to search for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory to search (default: the working directory)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Only search files matching this glob, e.g. \"*.rs\" or \"src/**/*.ts\""
+                    },
+                    "output_mode": {
+                        "type": "string",
+                        "enum": ["files_with_matches", "content", "count"],
+                        "description": "What to return (default: files_with_matches)"
+                    },
+                    "context": {
+                        "type": "integer",
+                        "description": "Lines of context before and after each match (content mode only)"
+                    },
+                    "case_insensitive": {
+                        "type": "boolean",
+                        "description": "Ignore case when matching (default: false)"
+                    },
+                    "head_limit": {
+                        "type": "integer",
+                        "description": "Maximum result lines to return (default: 200, max: 1000)"
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        )
+    }
+
+    pub fn execute(&self, args: serde_json::Value) -> Result<String> {
+        let pattern = args["pattern"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'pattern' parameter"))?;
+        let path_str = args["path"].as_str().unwrap_or(".");
+        let mode = match args["output_mode"].as_str() {
+            None | Some("files_with_matches") => OutputMode::FilesWithMatches,
+            Some("content") => OutputMode::Content,
+            Some("count") => OutputMode::Count,
+            Some(other) => {
+                return Err(anyhow::anyhow!(
+                    "Invalid output_mode '{}': use files_with_matches, content or count",
+                    other
+                ))
+            }
+        };
+        let context = args["context"].as_u64().unwrap_or(0) as usize;
+        let head_limit = args["head_limit"]
+            .as_u64()
+            .map(|l| (l as usize).clamp(1, MAX_HEAD_LIMIT))
+            .unwrap_or(DEFAULT_HEAD_LIMIT);
+
+        let regex = regex::RegexBuilder::new(pattern)
+            .case_insensitive(args["case_insensitive"].as_bool().unwrap_or(false))
+            .build()
+            .with_context(|| format!("Invalid regex: {}", pattern))?;
+        let filter = args["glob"].as_str().map(FileFilter::new).transpose()?;
+
+        let root = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_read(&root)?;
+        }
+        let files: Vec<(PathBuf, PathBuf)> = if root.is_file() {
+            vec![(root.clone(), PathBuf::from(path_str))]
+        } else if root.is_dir() {
+            let mut files: Vec<_> = walk_files(&root)
+                .filter_map(|file| {
+                    let relative = file.strip_prefix(&root).ok()?.to_path_buf();
+                    if filter.as_ref().is_some_and(|f| !f.is_match(&relative)) {
+                        return None;
+                    }
+                    let shown = display_path(path_str, &relative);
+                    Some((file, shown))
+                })
+                .collect();
+            files.sort_by(|a, b| a.1.cmp(&b.1));
+            files
+        } else {
+            return Err(anyhow::anyhow!("Path not found: {}", path_str));
+        };
+
+        // Collect one line past the limit so we know whether anything was cut
+        let mut out: Vec<String> = Vec::new();
+        let mut too_large: Vec<String> = Vec::new();
+        for (file, shown) in &files {
+            if out.len() > head_limit {
+                break;
+            }
+            let shown = shown.display();
+            if std::fs::metadata(file).is_ok_and(|meta| meta.len() > MAX_FILE_BYTES) {
+                too_large.push(shown.to_string());
+                continue;
+            }
+            let Some(text) = read_text(file) else {
+                continue;
+            };
+            let lines: Vec<&str> = text.lines().collect();
+            let matched: Vec<usize> = lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| regex.is_match(line))
+                .map(|(i, _)| i)
+                .collect();
+            if matched.is_empty() {
+                continue;
+            }
+
+            match mode {
+                OutputMode::FilesWithMatches => out.push(shown.to_string()),
+                OutputMode::Count => out.push(format!("{}:{}", shown, matched.len())),
+                OutputMode::Content => {
+                    for (start, end) in context_ranges(&matched, context, lines.len()) {
+                        if out.len() > head_limit {
+                            break;
+                        }
+                        // Separate non-adjacent groups, as grep -C does
+                        if context > 0 && !out.is_empty() {
+                            out.push("--".to_string());
+                        }
+                        for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+                            let sep = if matched.binary_search(&i).is_ok() {
+                                ':'
+                            } else {
+                                '-'
+                            };
+                            out.push(format!("{}{}{}{}{}", shown, sep, i + 1, sep, clip(line)));
+                        }
+                    }
+                }
+            }
+        }
+
+        if out.is_empty() {
+            out.push(format!("No matches for {} in {}", pattern, path_str));
+        } else if out.len() > head_limit {
+            out.truncate(head_limit);
+            out.push(format!(
+                "[Results capped at {} lines; narrow the pattern, path or glob, or raise head_limit]",
+                head_limit
+            ));
+        }
+        if !too_large.is_empty() {
+            out.push(skipped_note(&too_large));
+        }
+        Ok(out.join("\n"))
+    }
+}
+
+impl crate::tools::Tool for GrepTool {
+    fn name(&self) -> &str {
+        "Grep"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { GrepTool::execute(self, args) })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
+impl Default for GrepTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `glob` filter: a pattern without `/` matches file names at any depth
+/// (like `rg -g`), one with `/` matches the path from the search root
+struct FileFilter {
+    matcher: globset::GlobMatcher,
+    match_name: bool,
+}
+
+impl FileFilter {
+    fn new(pattern: &str) -> Result<Self> {
+        let matcher = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?
+            .compile_matcher();
+        Ok(Self {
+            matcher,
+            match_name: !pattern.contains('/'),
+        })
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        if self.match_name {
+            relative
+                .file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        } else {
+            self.matcher.is_match(relative)
+        }
+    }
+}
+
+/// The note naming files skipped for their size
+fn skipped_note(names: &[String]) -> String {
+    let mut listed = names[..names.len().min(MAX_SKIPPED_NAMES)].join(", ");
+    if names.len() > MAX_SKIPPED_NAMES {
+        listed.push_str(&format!(" and {} more", names.len() - MAX_SKIPPED_NAMES));
+    }
+    let files = if names.len() == 1 { "file" } else { "files" };
+    format!(
+        "[Skipped {} {} over {} MB: {}]",
+        names.len(),
+        files,
+        MAX_FILE_BYTES / (1024 * 1024),
+        listed
+    )
+}
+
+/// File contents as text, or None for unreadable or binary files. At most
+/// `MAX_FILE_BYTES` are read, should the file have grown since it was sized.
+fn read_text(path: &Path) -> Option<String> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(MAX_FILE_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Merge each matched line index +/- `context` into sorted, non-overlapping
+/// half-open ranges of lines to print
+fn context_ranges(matched: &[usize], context: usize, line_count: usize) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in matched {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(line_count);
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+fn clip(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(MAX_LINE_CHARS).collect();
+    format!("{}...", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "fn one() {}\n// TODO: two\nfn three() {}\nfn four() {}\n// todo five\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "# Title\nTODO docs\n").unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "TODO ignored\n").unwrap();
+        std::fs::write(dir.path().join("blob.bin"), b"TODO\0binary").unwrap();
+        dir
+    }
+
+    fn grep(dir: &TempDir, args: serde_json::Value) -> String {
+        GrepTool::with_cwd(Some(dir.path().to_path_buf()))
+            .execute(args)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_grep_tool_spec() {
+        let spec = GrepTool::new().get_spec();
+        assert_eq!(spec.function.name, "Grep");
+    }
+
+    #[test]
+    fn test_grep_files_with_matches_skips_ignored_and_binary() {
+        let dir = setup();
+        assert_eq!(
+            grep(&dir, serde_json::json!({"pattern": "TODO"})),
+            "README.md\nsrc/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_grep_skips_and_names_large_files() {
+        let dir = setup();
+        let big = std::fs::File::create(dir.path().join("big.log")).unwrap();
+        big.set_len(MAX_FILE_BYTES + 1).unwrap();
+        assert_eq!(
+            grep(&dir, serde_json::json!({"pattern": "TODO"})),
+            "README.md\nsrc/lib.rs\n[Skipped 1 file over 10 MB: big.log]"
+        );
+    }
+
+    #[test]
+    fn test_grep_count_and_case_insensitive() {
+        let dir = setup();
+        assert_eq!(
+            grep(
+                &dir,
+                serde_json::json!({"pattern": "todo", "output_mode": "count"})
+            ),
+            "src/lib.rs:1"
+        );
+        assert_eq!(
+            grep(
+                &dir,
+                serde_json::json!({"pattern": "todo", "output_mode": "count", "case_insensitive": true})
+            ),
+            "README.md:1\nsrc/lib.rs:2"
+        );
+    }
+
+    #[test]
+    fn test_grep_content_with_context() {
+        let dir = setup();
+        assert_eq!(
+            grep(
+                &dir,
+                serde_json::json!({"pattern": "^fn", "path": "src/lib.rs", "output_mode": "content"})
+            ),
+            "src/lib.rs:1:fn one() {}\nsrc/lib.rs:3:fn three() {}\nsrc/lib.rs:4:fn four() {}"
+        );
+        assert_eq!(
+            grep(
+                &dir,
+                serde_json::json!({"pattern": "three", "output_mode": "content", "context": 1, "glob": "*.rs"})
+            ),
+            "src/lib.rs-2-// TODO: two\nsrc/lib.rs:3:fn three() {}\nsrc/lib.rs-4-fn four() {}"
+        );
+    }
+
+    #[test]
+    fn test_grep_glob_filter_and_path() {
+        let dir = setup();
+        assert_eq!(
+            grep(&dir, serde_json::json!({"pattern": "TODO", "glob": "*.md"})),
+            "README.md"
+        );
+        assert_eq!(
+            grep(
+                &dir,
+                serde_json::json!({"pattern": "TODO", "glob": "src/**/*.rs"})
+            ),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            grep(&dir, serde_json::json!({"pattern": "TODO", "path": "src"})),
+            "src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_grep_head_limit() {
+        let dir = setup();
+        let result = grep(
+            &dir,
+            serde_json::json!({"pattern": "fn", "output_mode": "content", "head_limit": 2}),
+        );
+        let lines: Vec<_> = result.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[2].starts_with("[Results capped at 2 lines"),
+            "{}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_grep_no_matches_and_errors() {
+        let dir = setup();
+        assert!(
+            grep(&dir, serde_json::json!({"pattern": "nothing here"})).starts_with("No matches")
+        );
+
+        let tool = GrepTool::with_cwd(Some(dir.path().to_path_buf()));
+        assert!(tool.execute(serde_json::json!({"pattern": "("})).is_err());
+        assert!(tool
+            .execute(serde_json::json!({"pattern": "x", "output_mode": "lines"}))
+            .is_err());
+        assert!(tool
+            .execute(serde_json::json!({"pattern": "x", "path": "missing"}))
+            .is_err());
+    }
+
+    #[test]
+    fn test_context_ranges_merge() {
+        assert_eq!(context_ranges(&[0, 2, 10], 1, 12), vec![(0, 4), (9, 12)]);
+        assert_eq!(context_ranges(&[5], 0, 12), vec![(5, 6)]);
+        assert_eq!(context_ranges(&[11], 3, 12), vec![(8, 12)]);
+    }
+}
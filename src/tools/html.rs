@@ -0,0 +1,437 @@
+//! HTML to markdown conversion for WebFetch.
+//!
+//! Keeps what a model needs to read a page (headings, paragraphs, links,
+//! lists, tables, code) and drops the rest: scripts, styles, navigation,
+//! forms and other page chrome.
+
+use reqwest::Url;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Elements whose content is never page text
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "form",
+    "button", "input", "select", "textarea", "head",
+];
+
+/// Page chrome, dropped when extracting the main content
+const CHROME: &[&str] = &["nav", "footer", "aside"];
+
+/// Tried in order to find the main content; the body is the fallback
+const MAIN_CONTENT: &[&str] = &["main", "[role=main]", "article", "#content", "#main"];
+
+/// Nesting past which elements are flattened to their text, so a deeply
+/// nested page cannot exhaust the stack
+const MAX_DEPTH: usize = 256;
+
+/// A page converted to markdown
+pub(super) struct Page {
+    pub title: Option<String>,
+    pub markdown: String,
+}
+
+/// Convert `html` to markdown, resolving links against `base`. Unless
+/// `full_page` is set, only the main content is kept.
+pub(super) fn html_to_markdown(html: &str, base: Option<&Url>, full_page: bool) -> Page {
+    let doc = Html::parse_document(html);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|s| doc.select(&s).next())
+        .map(|t| collapse_whitespace(&t.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+
+    let body = Selector::parse("body")
+        .ok()
+        .and_then(|s| doc.select(&s).next())
+        .unwrap_or_else(|| doc.root_element());
+    let root = if full_page {
+        body
+    } else {
+        MAIN_CONTENT
+            .iter()
+            .filter_map(|sel| Selector::parse(sel).ok())
+            .find_map(|sel| doc.select(&sel).next())
+            .unwrap_or(body)
+    };
+
+    let mut renderer = Renderer::new(base, !full_page);
+    renderer.children(root);
+    Page {
+        title,
+        markdown: renderer.finish(),
+    }
+}
+
+struct Renderer<'u> {
+    out: String,
+    base: Option<&'u Url>,
+    skip_chrome: bool,
+    /// Elements entered above the one being rendered
+    depth: usize,
+}
+
+impl<'u> Renderer<'u> {
+    fn new(base: Option<&'u Url>, skip_chrome: bool) -> Self {
+        Self {
+            out: String::new(),
+            base,
+            skip_chrome,
+            depth: 0,
+        }
+    }
+
+    fn finish(self) -> String {
+        self.out.trim().to_string()
+    }
+
+    /// Render `element`'s children in a fresh renderer and return the text
+    fn sub(&self, element: ElementRef) -> String {
+        let mut sub = Renderer {
+            depth: self.depth,
+            ..Renderer::new(self.base, self.skip_chrome)
+        };
+        sub.children(element);
+        sub.finish()
+    }
+
+    /// `element`'s content on one line
+    fn inline(&self, element: ElementRef) -> String {
+        collapse_whitespace(&self.sub(element))
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.element(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Flowing text: whitespace collapsed, no space at the start of a line
+    fn text(&mut self, text: &str) {
+        let collapsed = collapse_runs(text);
+        let at_line_start = self.out.is_empty() || self.out.ends_with([' ', '\n']);
+        self.out.push_str(if at_line_start {
+            collapsed.trim_start()
+        } else {
+            &collapsed
+        });
+    }
+
+    /// End the current paragraph
+    fn block(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push_str(if self.out.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            });
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.trim_trailing_spaces();
+        self.out.push('\n');
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let kept = self.out.trim_end_matches(' ').len();
+        self.out.truncate(kept);
+    }
+
+    fn element(&mut self, element: ElementRef) {
+        let el = element.value();
+        let name = el.name();
+        if SKIPPED.contains(&name)
+            || el.attr("hidden").is_some()
+            || (self.skip_chrome && CHROME.contains(&name))
+        {
+            return;
+        }
+        if self.depth >= MAX_DEPTH {
+            self.text(&element.text().collect::<String>());
+            return;
+        }
+
+        self.depth += 1;
+        self.markup(element);
+        self.depth -= 1;
+    }
+
+    /// Render `element` as the markdown for its tag
+    fn markup(&mut self, element: ElementRef) {
+        let el = element.value();
+        let name = el.name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = self.inline(element);
+                if !text.is_empty() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.block();
+                    self.out.push_str(&"#".repeat(level));
+                    self.out.push(' ');
+                    self.out.push_str(&text);
+                    self.block();
+                }
+            }
+            "br" => self.line_break(),
+            "hr" => {
+                self.block();
+                self.out.push_str("---");
+                self.block();
+            }
+            "pre" => {
+                let code: String = element.text().collect();
+                let code = code.trim_matches('\n');
+                if !code.trim().is_empty() {
+                    self.block();
+                    self.out.push_str("```\n");
+                    self.out.push_str(code);
+                    self.out.push_str("\n```");
+                    self.block();
+                }
+            }
+            "a" => {
+                let text = self.inline(element);
+                match el.attr("href").and_then(|href| self.link(href)) {
+                    Some(href) if !text.is_empty() => {
+                        self.out.push_str(&format!("[{}]({})", text, href))
+                    }
+                    _ => self.out.push_str(&text),
+                }
+            }
+            "img" => {
+                let alt = el.attr("alt").map(collapse_whitespace).unwrap_or_default();
+                if let Some(src) = el.attr("src").and_then(|src| self.link(src)) {
+                    if !alt.is_empty() {
+                        self.out.push_str(&format!("![{}]({})", alt, src));
+                    }
+                }
+            }
+            "strong" | "b" => self.wrapped(element, "**"),
+            "em" | "i" => self.wrapped(element, "*"),
+            "code" | "kbd" | "samp" => self.wrapped(element, "`"),
+            "ul" | "ol" => self.list(element, name == "ol"),
+            "blockquote" => {
+                let quoted = self.sub(element);
+                if !quoted.is_empty() {
+                    self.block();
+                    let lines: Vec<String> = quoted
+                        .lines()
+                        .map(|line| format!("> {}", line).trim_end().to_string())
+                        .collect();
+                    self.out.push_str(&lines.join("\n"));
+                    self.block();
+                }
+            }
+            "table" => self.table(element),
+            "dt" => {
+                let term = self.inline(element);
+                if !term.is_empty() {
+                    self.block();
+                    self.out.push_str(&format!("**{}**", term));
+                    self.line_break();
+                }
+            }
+            "p" | "div" | "section" | "article" | "main" | "header" | "footer" | "nav"
+            | "aside" | "figure" | "figcaption" | "dl" | "dd" | "details" | "summary"
+            | "address" | "li" | "body" | "html" => {
+                self.block();
+                self.children(element);
+                self.block();
+            }
+            _ => self.children(element),
+        }
+    }
+
+    /// Inline content between `marker`s, e.g. **bold**
+    fn wrapped(&mut self, element: ElementRef, marker: &str) {
+        let text = self.inline(element);
+        if !text.is_empty() {
+            self.out.push_str(marker);
+            self.out.push_str(&text);
+            self.out.push_str(marker);
+        }
+    }
+
+    fn list(&mut self, element: ElementRef, ordered: bool) {
+        self.block();
+        let items = element
+            .child_elements()
+            .filter(|child| child.value().name() == "li");
+        for (i, item) in items.enumerate() {
+            let content = self.sub(item);
+            if content.is_empty() {
+                continue;
+            }
+            let bullet = if ordered {
+                format!("{}. ", i + 1)
+            } else {
+                "- ".to_string()
+            };
+            let indent = " ".repeat(bullet.len());
+            for (n, line) in content.lines().enumerate() {
+                if n == 0 {
+                    self.out.push_str(&bullet);
+                } else if !line.is_empty() {
+                    self.out.push_str(&indent);
+                }
+                self.out.push_str(line);
+                self.out.push('\n');
+            }
+        }
+        self.block();
+    }
+
+    fn table(&mut self, element: ElementRef) {
+        let Ok(row_selector) = Selector::parse("tr") else {
+            return;
+        };
+        let rows: Vec<Vec<String>> = element
+            .select(&row_selector)
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| self.inline(cell).replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect();
+        let Some(columns) = rows.iter().map(Vec::len).max() else {
+            return;
+        };
+
+        self.block();
+        for (i, row) in rows.iter().enumerate() {
+            let mut cells = row.clone();
+            cells.resize(columns, String::new());
+            self.out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            if i == 0 {
+                self.out
+                    .push_str(&format!("|{}\n", " --- |".repeat(columns)));
+            }
+        }
+        self.block();
+    }
+
+    /// Absolute URL for a link, or None for links that go nowhere useful
+    fn link(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return None;
+        }
+        Some(match self.base.and_then(|base| base.join(href).ok()) {
+            Some(url) => url.to_string(),
+            None => href.to_string(),
+        })
+    }
+}
+
+/// Whitespace runs collapsed to single spaces, keeping a leading/trailing one
+fn collapse_runs(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                out.push(' ');
+            }
+            in_space = true;
+        } else {
+            out.push(c);
+            in_space = false;
+        }
+    }
+    out
+}
+
+/// All whitespace collapsed to single spaces and trimmed
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        let base = Url::parse("https://example.com/docs/page.html").unwrap();
+        html_to_markdown(html, Some(&base), false).markdown
+    }
+
+    #[test]
+    fn test_blocks_and_inline_markup() {
+        let md = convert(
+            "<html><head><title> My  Page </title><style>p{}</style></head><body>\
+             <h1>Hello</h1><p>Some <b>bold</b> and <em>em</em>\n  text with <code>x()</code>.</p>\
+             <p>Line one<br>Line two</p><script>alert(1)</script></body></html>",
+        );
+        assert_eq!(
+            md,
+            "# Hello\n\nSome **bold** and *em* text with `x()`.\n\nLine one\nLine two"
+        );
+    }
+
+    #[test]
+    fn test_title_links_and_images() {
+        let base = Url::parse("https://example.com/docs/page.html").unwrap();
+        let page = html_to_markdown(
+            "<title>T</title><p><a href=\"/a\">abs</a> <a href=\"b.html\">rel</a> \
+             <a href=\"#top\">anchor</a> <img src=\"i.png\" alt=\"pic\"><img src=\"x.png\"></p>",
+            Some(&base),
+            false,
+        );
+        assert_eq!(page.title.as_deref(), Some("T"));
+        assert_eq!(
+            page.markdown,
+            "[abs](https://example.com/a) [rel](https://example.com/docs/b.html) anchor \
+             ![pic](https://example.com/docs/i.png)"
+        );
+    }
+
+    #[test]
+    fn test_lists_tables_and_code() {
+        let md = convert(
+            "<ul><li>one</li><li>two<ol><li>a</li><li>b</li></ol></li></ul>\
+             <table><tr><th>k</th><th>v</th></tr><tr><td>x</td><td>1|2</td></tr></table>\
+             <pre><code>fn main() {\n    go();\n}</code></pre>",
+        );
+        assert_eq!(
+            md,
+            "- one\n- two\n\n  1. a\n  2. b\n\n\
+             | k | v |\n| --- | --- |\n| x | 1\\|2 |\n\n\
+             ```\nfn main() {\n    go();\n}\n```"
+        );
+    }
+
+    #[test]
+    fn test_main_content_extraction() {
+        let html = "<body><nav><a href=\"/\">Home</a></nav><header>Site</header>\
+                    <main><h2>Article</h2><p>Body text</p><aside>Ad</aside></main>\
+                    <footer>Copyright</footer></body>";
+        assert_eq!(convert(html), "## Article\n\nBody text");
+
+        let full = html_to_markdown(html, None, true).markdown;
+        assert!(full.contains("[Home](/)"), "{}", full);
+        assert!(full.contains("Copyright"), "{}", full);
+    }
+
+    #[test]
+    fn test_deep_nesting_is_flattened() {
+        let depth = 2_000;
+        let html = format!(
+            "{}<p>deep <b>text</b></p>{}",
+            "<div><blockquote>".repeat(depth),
+            "</blockquote></div>".repeat(depth)
+        );
+        let md = convert(&html);
+        assert!(md.ends_with("deep text"), "{}", &md[md.len().saturating_sub(100)..]);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Levels shown below the listed directory unless `depth` says otherwise
+const DEFAULT_DEPTH: usize = 3;
+const MAX_DEPTH: usize = 10;
+
+/// Entries listed unless `limit` says otherwise
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 1000;
+
+/// Entries counted before the walk gives up, so a huge tree cannot stall the call
+const MAX_WALK: usize = 100_000;
+
+/// Ignore file read alongside .gitignore, for things only eunice should skip
+const IGNORE_FILE: &str = ".euniceignore";
+
+/// ListDir tool: a directory tree with sizes and file counts
+pub struct ListDirTool {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+impl ListDirTool {
+    pub fn new() -> Self {
+        Self::with_cwd(None)
+    }
+
+    /// Resolve relative paths against `cwd` instead of the process working
+    /// directory. Per-tool so concurrent runs cannot race each other.
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.cwd {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "ListDir",
+            "Show a directory tree, directories first, with each directory's file count and \
+             total size. Skips hidden files and anything in .gitignore or .euniceignore, so \
+             target/ and node_modules/ stay out of the way. Use it to get oriented in a project \
+             instead of ls -R or find in Bash.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to list (default: the working directory)"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Levels to expand below it (default: 3, max: 10)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum entries to list (default: 200, max: 1000)"
+                    }
+                }
+            }),
+        )
+    }
+
+    pub fn execute(&self, args: serde_json::Value) -> Result<String> {
+        let path_str = args["path"].as_str().unwrap_or(".");
+        let depth = args["depth"]
+            .as_u64()
+            .map(|d| (d as usize).clamp(1, MAX_DEPTH))
+            .unwrap_or(DEFAULT_DEPTH);
+        let limit = args["limit"]
+            .as_u64()
+            .map(|l| (l as usize).clamp(1, MAX_LIMIT))
+            .unwrap_or(DEFAULT_LIMIT);
+
+        let root = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_read(&root)?;
+        }
+        if !root.is_dir() {
+            return Err(anyhow::anyhow!("Not a directory: {}", path_str));
+        }
+
+        let (tree, complete) = Dir::walk(&root);
+        let mut out = Vec::new();
+        out.push(format!(
+            "{}/ ({})",
+            path_str.trim_end_matches('/'),
+            tree.summary()
+        ));
+        let expanded = tree.expand(depth, limit);
+        tree.render(Path::new(""), "", &expanded, &mut out);
+
+        let collapsed = expanded.values().filter(|&&shown| shown == 0).count();
+        if collapsed > 0 {
+            out.push(format!(
+                "[{} entry limit reached: {} not expanded; list one of them or raise limit]",
+                limit,
+                plural(collapsed, "directory", "directories")
+            ));
+        }
+        if !complete {
+            out.push(format!(
+                "[stopped counting after {} entries; sizes and counts are partial]",
+                MAX_WALK
+            ));
+        }
+        Ok(out.join("\n"))
+    }
+}
+
+/// A directory's contents and totals over everything below it
+#[derive(Default)]
+struct Dir {
+    dirs: BTreeMap<String, Dir>,
+    files: BTreeMap<String, u64>,
+    file_count: u64,
+    bytes: u64,
+}
+
+impl Dir {
+    /// Everything under `root` the ignore files allow. False when the walk
+    /// stopped at `MAX_WALK` entries.
+    fn walk(root: &Path) -> (Dir, bool) {
+        let mut tree = Dir::default();
+        let walker = ignore::WalkBuilder::new(root)
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILE)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.depth() > 0);
+        for (seen, entry) in walker.enumerate() {
+            if seen == MAX_WALK {
+                return (tree, false);
+            }
+            let Ok(relative) = entry.path().strip_prefix(root) else {
+                continue;
+            };
+            let names: Vec<String> = relative
+                .iter()
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect();
+            let Some((name, parents)) = names.split_last() else {
+                continue;
+            };
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                tree.dir_mut(parents).dirs.entry(name.clone()).or_default();
+            } else {
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                tree.add_file(parents, name, size);
+            }
+        }
+        (tree, true)
+    }
+
+    fn dir_mut(&mut self, path: &[String]) -> &mut Dir {
+        path.iter()
+            .fold(self, |dir, name| dir.dirs.entry(name.clone()).or_default())
+    }
+
+    /// Add a file, counting it in every directory above it
+    fn add_file(&mut self, path: &[String], name: &str, size: u64) {
+        let mut dir = self;
+        for name in path {
+            dir.file_count += 1;
+            dir.bytes += size;
+            dir = dir.dirs.entry(name.clone()).or_default();
+        }
+        dir.file_count += 1;
+        dir.bytes += size;
+        dir.files.insert(name.to_string(), size);
+    }
+
+    fn entries(&self) -> usize {
+        self.dirs.len() + self.files.len()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{}, {}",
+            plural(self.file_count as usize, "file", "files"),
+            format_size(self.bytes)
+        )
+    }
+
+    /// How many entries to list in each directory to show, breadth first so
+    /// the levels nearest the top are listed before anything deeper. 0 marks
+    /// a directory within `depth` the limit left unexpanded.
+    fn expand(&self, depth: usize, limit: usize) -> HashMap<PathBuf, usize> {
+        let mut expanded = HashMap::new();
+        let mut budget = limit;
+        let mut queue = VecDeque::from([(PathBuf::new(), self, 1)]);
+        while let Some((path, dir, level)) = queue.pop_front() {
+            if dir.entries() == 0 {
+                continue;
+            }
+            let shown = dir.entries().min(budget);
+            budget -= shown;
+            expanded.insert(path.clone(), shown);
+            if level < depth {
+                for (name, child) in dir.dirs.iter().take(shown) {
+                    queue.push_back((path.join(name), child, level + 1));
+                }
+            }
+        }
+        expanded
+    }
+
+    /// Append the listed entries of the directory at `path`, then a line
+    /// summing up any that did not fit
+    fn render(
+        &self,
+        path: &Path,
+        indent: &str,
+        expanded: &HashMap<PathBuf, usize>,
+        out: &mut Vec<String>,
+    ) {
+        let shown = expanded.get(path).copied().unwrap_or(0);
+        let child_indent = format!("{}  ", indent);
+        let mut listed = 0;
+        for (name, dir) in self.dirs.iter().take(shown) {
+            out.push(format!("{}  {}/ ({})", indent, name, dir.summary()));
+            dir.render(&path.join(name), &child_indent, expanded, out);
+            listed += 1;
+        }
+        for (name, size) in self.files.iter().take(shown - listed) {
+            out.push(format!("{}  {} ({})", indent, name, format_size(*size)));
+        }
+
+        if shown > 0 && shown < self.entries() {
+            let hidden_dirs = self.dirs.len().saturating_sub(shown);
+            let hidden_files = self.files.len() - (shown - listed);
+            let (files, bytes) = self
+                .dirs
+                .values()
+                .skip(shown)
+                .fold((hidden_files as u64, 0), |(files, bytes), dir| {
+                    (files + dir.file_count, bytes + dir.bytes)
+                });
+            let bytes = bytes + self.files.values().skip(shown - listed).sum::<u64>();
+            let dirs = match hidden_dirs {
+                0 => String::new(),
+                n => format!("{}, ", plural(n, "directory", "directories")),
+            };
+            out.push(format!(
+                "{}  ... {} more ({}{}, {})",
+                indent,
+                self.entries() - shown,
+                dirs,
+                plural(files as usize, "file", "files"),
+                format_size(bytes)
+            ));
+        }
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1_048_576 {
+        format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+impl crate::tools::Tool for ListDirTool {
+    fn name(&self) -> &str {
+        "ListDir"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { ListDirTool::execute(self, args) })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
+impl Default for ListDirTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn list(dir: &TempDir, args: serde_json::Value) -> String {
+        ListDirTool::with_cwd(Some(dir.path().to_path_buf()))
+            .execute(args)
+            .unwrap()
+    }
+
+    fn write(dir: &TempDir, path: &str, size: usize) {
+        let full = dir.path().join(path);
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(full, vec![b'x'; size]).unwrap();
+    }
+
+    #[test]
+    fn test_list_dir_tree_with_counts_and_depth() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "Cargo.toml", 100);
+        write(&dir, "src/main.rs", 2048);
+        write(&dir, "src/tools/a.rs", 10);
+        write(&dir, "src/tools/deep/b.rs", 20);
+        std::fs::create_dir(dir.path().join("empty")).unwrap();
+
+        assert_eq!(
+            list(&dir, serde_json::json!({})),
+            "./ (4 files, 2.1 KB)\n\
+             \x20 empty/ (0 files, 0 B)\n\
+             \x20 src/ (3 files, 2.0 KB)\n\
+             \x20   tools/ (2 files, 30 B)\n\
+             \x20     deep/ (1 file, 20 B)\n\
+             \x20     a.rs (10 B)\n\
+             \x20   main.rs (2.0 KB)\n\
+             \x20 Cargo.toml (100 B)"
+        );
+        assert_eq!(
+            list(&dir, serde_json::json!({"path": "src", "depth": 1})),
+            "src/ (3 files, 2.0 KB)\n  tools/ (2 files, 30 B)\n  main.rs (2.0 KB)"
+        );
+    }
+
+    #[test]
+    fn test_list_dir_respects_ignore_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.path().join(IGNORE_FILE), "fixtures/\n").unwrap();
+        write(&dir, "src/lib.rs", 1);
+        write(&dir, "target/debug/big", 5000);
+        write(&dir, "fixtures/data.json", 5000);
+        write(&dir, ".hidden/x", 1);
+
+        assert_eq!(
+            list(&dir, serde_json::json!({})),
+            "./ (1 file, 1 B)\n  src/ (1 file, 1 B)\n    lib.rs (1 B)"
+        );
+    }
+
+    #[test]
+    fn test_list_dir_limit_collapses_breadth_first() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..5 {
+            write(&dir, &format!("f{}.txt", i), 10);
+        }
+        for i in 0..3 {
+            write(&dir, &format!("sub/g{}.txt", i), 100);
+        }
+
+        assert_eq!(
+            list(&dir, serde_json::json!({"limit": 4})),
+            "./ (8 files, 350 B)\n\
+             \x20 sub/ (3 files, 300 B)\n\
+             \x20 f0.txt (10 B)\n\
+             \x20 f1.txt (10 B)\n\
+             \x20 f2.txt (10 B)\n\
+             \x20 ... 2 more (2 files, 20 B)\n\
+             [4 entry limit reached: 1 directory not expanded; list one of them or raise limit]"
+        );
+    }
+
+    #[test]
+    fn test_list_dir_errors() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "a.txt", 1);
+        let tool = ListDirTool::with_cwd(Some(dir.path().to_path_buf()));
+        assert!(tool
+            .execute(serde_json::json!({"path": "missing"}))
+            .is_err());
+        assert!(tool.execute(serde_json::json!({"path": "a.txt"})).is_err());
+    }
+}
@@ -1,22 +1,162 @@
+mod apply_patch;
+mod ask_user;
+mod background;
 mod bash;
+mod capture;
+mod custom;
+mod documents;
+mod edit;
+mod get_output;
+mod glob;
+mod grep;
+mod html;
+mod list_dir;
 mod read;
+mod shell;
 mod skill;
+pub mod todo;
+mod web_fetch;
 mod write;
 
+pub use apply_patch::ApplyPatchTool;
+pub use ask_user::{Answer, AskUserTool, Question, Unattended, UserPrompt};
+pub use background::{BackgroundJobs, BashOutputTool, KillBashTool};
 pub use bash::BashTool;
+pub use custom::CustomTools;
+pub use edit::EditTool;
+pub use get_output::{GetOutputTool, GET_OUTPUT_TOOL_NAME};
+pub use glob::GlobTool;
+pub use grep::GrepTool;
+pub use list_dir::ListDirTool;
 pub use read::ReadTool;
 pub use skill::SkillTool;
+pub use todo::{TodoItem, TodoList, TodoStatus, TodoTool};
+pub use web_fetch::WebFetchTool;
 pub use write::WriteTool;
 
-use crate::models::{FunctionSpec, Tool};
+use crate::checkpoints::Checkpoints;
+use crate::env_policy::EnvPolicy;
+use crate::mcp::McpServers;
+use crate::models::{FunctionSpec, Tool as ToolSpec};
+use crate::output_store::{OutputStore, SpillSlot};
+use crate::redact::Redactor;
+use crate::sandbox::SandboxPolicy;
 use anyhow::Result;
+use futures::future::BoxFuture;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-/// Registry of built-in tools
+/// Receives output lines from a running tool (currently Bash) as they are
+/// produced, so the display can show progress before the tool returns.
+/// Bounded: tools `try_send`, dropping lines while the display is behind;
+/// the tool's result still has them all.
+pub type ProgressSender = tokio::sync::mpsc::Sender<String>;
+
+/// Lines a progress channel holds before tools start dropping them
+const PROGRESS_CAPACITY: usize = 256;
+
+/// A channel for a tool's progress lines
+pub fn progress_channel() -> (ProgressSender, tokio::sync::mpsc::Receiver<String>) {
+    tokio::sync::mpsc::channel(PROGRESS_CAPACITY)
+}
+
+/// Per-call state a tool may use besides its arguments. The CLI uses the
+/// registry's own (`ToolRegistry::context`); the webapp passes each
+/// session's job table and checkpoints.
+#[derive(Clone, Copy)]
+pub struct ToolContext<'a> {
+    /// Background jobs started by Bash and read by BashOutput/KillBash
+    pub jobs: &'a BackgroundJobs,
+    /// Where Write and Edit snapshot files before changing them
+    pub checkpoints: Option<&'a Checkpoints>,
+    /// Output lines sent while the tool runs
+    pub progress: Option<&'a ProgressSender>,
+    /// Earlier truncated results, for get_output
+    pub outputs: Option<&'a OutputStore>,
+    /// Where Bash and custom tools leave output too large to return whole,
+    /// for the output store to adopt
+    pub spill: Option<&'a SpillSlot>,
+    /// The session's task list, kept by Todo
+    pub todos: Option<&'a TodoList>,
+    /// Who AskUser puts its questions to; none in unattended runs
+    pub user: Option<&'a dyn UserPrompt>,
+}
+
+impl<'a> ToolContext<'a> {
+    /// A context with only a job table
+    pub fn new(jobs: &'a BackgroundJobs) -> Self {
+        Self {
+            jobs,
+            checkpoints: None,
+            progress: None,
+            outputs: None,
+            spill: None,
+            todos: None,
+            user: None,
+        }
+    }
+}
+
+/// A tool the model can call. The built-ins implement it, and so can
+/// programs embedding eunice: register an implementation with
+/// `ToolRegistry::register`.
+pub trait Tool: Send + Sync {
+    /// The name the model calls the tool by; must match `spec`
+    fn name(&self) -> &str;
+
+    /// The specification sent to the model
+    fn spec(&self) -> ToolSpec;
+
+    /// Run one call. An error is shown to the model as `Error: ...`.
+    /// Dropping the future should stop the work (the user cancelled).
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>>;
+
+    /// Whether the tool caps its own output (Read's offset/limit, Grep's
+    /// result limit), so the agent should not head/tail-truncate its
+    /// results into the output store
+    fn limits_own_output(&self) -> bool {
+        false
+    }
+
+    /// Confine the tool to `policy` (a scheduled agent's `sandbox`
+    /// profile). Tools that touch files, run commands or use the network
+    /// should honour it; the default ignores it.
+    fn confine(&mut self, _policy: Arc<SandboxPolicy>) {}
+
+    /// Start subprocesses with the environment `policy` allows. Tools that
+    /// spawn commands should honour it; the default ignores it.
+    fn set_env_policy(&mut self, _policy: Arc<EnvPolicy>) {}
+}
+
+/// The tools offered to the model
 pub struct ToolRegistry {
-    bash: BashTool,
-    read: ReadTool,
-    write: WriteTool,
-    skill: SkillTool,
+    tools: Vec<Box<dyn Tool>>,
+    cwd: Option<PathBuf>,
+    /// Tools from MCP servers, shared by every registry in the process.
+    /// Their lists can change mid-session, so they are read on each call
+    /// rather than registered.
+    mcp: Option<Arc<McpServers>>,
+    /// Kept so tools registered after `with_sandbox` are confined too
+    sandbox: Option<Arc<SandboxPolicy>>,
+    /// Kept so tools registered after `with_env_policy` get it too
+    env: Option<Arc<EnvPolicy>>,
+    /// Checkpoints for callers that use `execute`; the webapp passes each
+    /// session's store in its `ToolContext`
+    checkpoints: Option<Checkpoints>,
+    /// Background jobs for callers that use `execute`; the webapp keeps its
+    /// own per-session tables
+    jobs: BackgroundJobs,
+    /// Task list for callers that use `context`; the webapp loads each
+    /// session's from its store
+    todos: TodoList,
+    /// Masks secrets in results; the agent loops run every result through it
+    redactor: Redactor,
+    /// Who `context` hands to AskUser; the webapp sets one per run instead
+    user: Option<Arc<dyn UserPrompt>>,
 }
 
 impl ToolRegistry {
@@ -24,40 +164,227 @@ impl ToolRegistry {
         Self::with_cwd(None)
     }
 
-    /// Registry whose filesystem tools operate in `cwd`. Skills are global
-    /// (`~/.eunice/skills`), so SkillTool is unaffected.
-    pub fn with_cwd(cwd: Option<std::path::PathBuf>) -> Self {
-        Self {
-            bash: BashTool::with_cwd(cwd.clone()),
-            read: ReadTool::with_cwd(cwd.clone()),
-            write: WriteTool::with_cwd(cwd),
-            skill: SkillTool::new(),
+    /// Registry of the built-in tools, whose filesystem tools operate in
+    /// `cwd`. Skills are global (`~/.eunice/skills`), so SkillTool is
+    /// unaffected.
+    pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
+        let mut registry = Self {
+            tools: Vec::new(),
+            cwd: cwd.clone(),
+            mcp: None,
+            sandbox: None,
+            env: None,
+            checkpoints: None,
+            jobs: BackgroundJobs::new(),
+            todos: TodoList::new(),
+            redactor: Redactor::from_env(),
+            user: None,
+        };
+        registry.register(BashTool::with_cwd(cwd.clone()));
+        registry.register(BashOutputTool);
+        registry.register(KillBashTool);
+        registry.register(ReadTool::with_cwd(cwd.clone()));
+        registry.register(WriteTool::with_cwd(cwd.clone()));
+        registry.register(EditTool::with_cwd(cwd.clone()));
+        registry.register(ApplyPatchTool::with_cwd(cwd.clone()));
+        registry.register(GlobTool::with_cwd(cwd.clone()));
+        registry.register(ListDirTool::with_cwd(cwd.clone()));
+        registry.register(GrepTool::with_cwd(cwd));
+        match WebFetchTool::new() {
+            Ok(tool) => {
+                registry.register(tool);
+            }
+            Err(e) => eprintln!("Warning: WebFetch disabled: {:#}", e),
+        }
+        registry.register(SkillTool::new());
+        registry.register(TodoTool);
+        registry.register(AskUserTool);
+        registry.register(GetOutputTool);
+        registry
+    }
+
+    /// Add `tool`, confined to the registry's sandbox and environment policy
+    /// if it has them. A tool of the same name is replaced in place and
+    /// returned.
+    pub fn register(&mut self, tool: impl Tool + 'static) -> Option<Box<dyn Tool>> {
+        self.register_boxed(Box::new(tool))
+    }
+
+    /// `register` for a tool that is already boxed
+    pub fn register_boxed(&mut self, mut tool: Box<dyn Tool>) -> Option<Box<dyn Tool>> {
+        if let Some(policy) = &self.sandbox {
+            tool.confine(policy.clone());
+        }
+        if let Some(policy) = &self.env {
+            tool.set_env_policy(policy.clone());
+        }
+        match self.tools.iter().position(|t| t.name() == tool.name()) {
+            Some(i) => Some(std::mem::replace(&mut self.tools[i], tool)),
+            None => {
+                self.tools.push(tool);
+                None
+            }
+        }
+    }
+
+    /// Remove the tool called `name`, returning it
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn Tool>> {
+        let i = self.tools.iter().position(|t| t.name() == name)?;
+        Some(self.tools.remove(i))
+    }
+
+    /// `register` as a builder
+    pub fn with_tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.register(tool);
+        self
+    }
+
+    /// `remove` as a builder
+    pub fn without_tool(mut self, name: &str) -> Self {
+        self.remove(name);
+        self
+    }
+
+    /// The registered tool called `name` (MCP tools are not included)
+    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == name)
+            .map(|t| t.as_ref())
+    }
+
+    /// Run Bash commands in one long-lived shell for the life of this
+    /// registry, so directory and environment changes carry over between calls
+    pub fn with_persistent_shell(self) -> Self {
+        let bash = BashTool::with_cwd(self.cwd.clone()).persistent();
+        self.with_tool(bash)
+    }
+
+    /// Offer the tools of the configured MCP servers alongside the built-ins
+    pub fn with_mcp(mut self, mcp: Arc<McpServers>) -> Self {
+        self.mcp = Some(mcp);
+        self
+    }
+
+    /// Snapshot files into `checkpoints` before Write and Edit change them
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// The store `with_checkpoints` set, for `/undo` and `/rewind`
+    pub fn checkpoints(&self) -> Option<&Checkpoints> {
+        self.checkpoints.as_ref()
+    }
+
+    /// Offer command-backed tools loaded with `CustomTools::load`
+    pub fn with_custom_tools(mut self, custom: CustomTools) -> Self {
+        for tool in custom.into_tools() {
+            self.register(tool);
         }
+        self
+    }
+
+    /// Mask secrets with `redactor` (from `~/.eunice/redact.toml` and the key
+    /// pool) rather than the environment-only default
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Confine every registered tool, and any registered later, to `policy`
+    /// (a scheduled agent's `sandbox` profile)
+    pub fn with_sandbox(mut self, policy: SandboxPolicy) -> Self {
+        let policy = Arc::new(policy);
+        for tool in &mut self.tools {
+            tool.confine(policy.clone());
+        }
+        self.sandbox = Some(policy);
+        self
+    }
+
+    /// Start every registered tool's subprocesses, and those of any tool
+    /// registered later, with the environment `policy` allows
+    pub fn with_env_policy(mut self, policy: EnvPolicy) -> Self {
+        let policy = Arc::new(policy);
+        for tool in &mut self.tools {
+            tool.set_env_policy(policy.clone());
+        }
+        self.env = Some(policy);
+        self
+    }
+
+    /// Put AskUser questions to `user` (the chat prompt, or a fixed reply for
+    /// unattended runs)
+    pub fn with_user(mut self, user: Arc<dyn UserPrompt>) -> Self {
+        self.user = Some(user);
+        self
     }
 
     /// Get all tool specifications for the API
-    pub fn get_tools(&self) -> Vec<Tool> {
-        vec![
-            self.bash.get_spec(),
-            self.read.get_spec(),
-            self.write.get_spec(),
-            self.skill.get_spec(),
-        ]
+    pub fn get_tools(&self) -> Vec<ToolSpec> {
+        let mut tools: Vec<ToolSpec> = self.tools.iter().map(|t| t.spec()).collect();
+        if let Some(mcp) = &self.mcp {
+            tools.extend(mcp.get_tools());
+        }
+        tools
     }
 
     /// Check if a tool name is handled by this registry
     pub fn has_tool(&self, name: &str) -> bool {
-        matches!(name, "Bash" | "Read" | "Write" | "Skill")
+        self.get(name).is_some() || self.mcp.as_ref().is_some_and(|mcp| mcp.has_tool(name))
+    }
+
+    /// Whether a tool caps its own output, so the agent should not
+    /// head/tail-truncate its results into the output store
+    pub fn limits_own_output(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|t| t.limits_own_output())
+    }
+
+    /// The task list `context` hands to Todo, for front ends to show
+    pub fn todos(&self) -> &TodoList {
+        &self.todos
     }
 
-    /// Execute a tool by name
+    /// The redactor tool results go through before the model or storage sees them
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Kill this registry's background jobs (the session ended or was cancelled)
+    pub fn kill_background_jobs(&self) {
+        self.jobs.kill_all();
+    }
+
+    /// A context using this registry's own background jobs, checkpoints,
+    /// task list and user
+    pub fn context(&self) -> ToolContext<'_> {
+        ToolContext {
+            checkpoints: self.checkpoints.as_ref(),
+            todos: Some(&self.todos),
+            user: self.user.as_deref(),
+            ..ToolContext::new(&self.jobs)
+        }
+    }
+
+    /// Execute a tool by name with the registry's own context
+    #[cfg(test)]
     pub async fn execute(&self, name: &str, args: serde_json::Value) -> Result<String> {
-        match name {
-            "Bash" => self.bash.execute(args).await,
-            "Read" => self.read.execute(args),
-            "Write" => self.write.execute(args),
-            "Skill" => self.skill.execute(args).await,
-            _ => Err(anyhow::anyhow!("Unknown tool: {}", name)),
+        self.execute_with_context(name, args, self.context()).await
+    }
+
+    /// Execute a tool by name with the given jobs, checkpoints, progress
+    /// channel and output store
+    pub async fn execute_with_context(
+        &self,
+        name: &str,
+        args: serde_json::Value,
+        ctx: ToolContext<'_>,
+    ) -> Result<String> {
+        match (self.get(name), &self.mcp) {
+            (Some(tool), _) => tool.execute(args, ctx).await,
+            (None, Some(mcp)) => mcp.execute(name, args).await,
+            (None, None) => Err(anyhow::anyhow!("Unknown tool: {}", name)),
         }
     }
 }
@@ -68,9 +395,28 @@ impl Default for ToolRegistry {
     }
 }
 
+/// Run a tool that changes `paths`, keeping the files' previous contents in
+/// `checkpoints` once the tool succeeds
+fn checkpointed(
+    checkpoints: Option<&Checkpoints>,
+    tool: &str,
+    paths: Vec<PathBuf>,
+    run: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    let Some(checkpoints) = checkpoints.filter(|_| !paths.is_empty()) else {
+        return run();
+    };
+    let pending = checkpoints.prepare(&paths)?;
+    let output = run()?;
+    match checkpoints.record(tool, pending) {
+        Ok(_) => Ok(output),
+        Err(e) => Ok(format!("{}\n[checkpoint not saved: {}]", output, e)),
+    }
+}
+
 /// Helper to create a Tool spec
-pub fn make_tool(name: &str, description: &str, parameters: serde_json::Value) -> Tool {
-    Tool {
+pub fn make_tool(name: &str, description: &str, parameters: serde_json::Value) -> ToolSpec {
+    ToolSpec {
         tool_type: "function".to_string(),
         function: FunctionSpec {
             name: name.to_string(),
@@ -88,25 +434,229 @@ mod tests {
     fn test_registry_has_all_tools() {
         let registry = ToolRegistry::new();
         let tools = registry.get_tools();
-        assert_eq!(tools.len(), 4);
+        assert_eq!(tools.len(), 15);
 
         let names: Vec<_> = tools.iter().map(|t| t.function.name.as_str()).collect();
         assert!(names.contains(&"Bash"));
+        assert!(names.contains(&"BashOutput"));
+        assert!(names.contains(&"KillBash"));
         assert!(names.contains(&"Read"));
         assert!(names.contains(&"Write"));
+        assert!(names.contains(&"Edit"));
+        assert!(names.contains(&"ApplyPatch"));
+        assert!(names.contains(&"Glob"));
+        assert!(names.contains(&"ListDir"));
+        assert!(names.contains(&"Grep"));
+        assert!(names.contains(&"WebFetch"));
         assert!(names.contains(&"Skill"));
+        assert!(names.contains(&"AskUser"));
+        assert!(names.contains(&"Todo"));
+        assert!(names.contains(&"get_output"));
     }
 
     #[test]
     fn test_has_tool() {
         let registry = ToolRegistry::new();
         assert!(registry.has_tool("Bash"));
+        assert!(registry.has_tool("BashOutput"));
+        assert!(registry.has_tool("KillBash"));
         assert!(registry.has_tool("Read"));
         assert!(registry.has_tool("Write"));
+        assert!(registry.has_tool("Edit"));
+        assert!(registry.has_tool("Glob"));
+        assert!(registry.has_tool("Grep"));
+        assert!(registry.has_tool("WebFetch"));
         assert!(registry.has_tool("Skill"));
+        assert!(registry.has_tool("Todo"));
+        assert!(registry.has_tool("get_output"));
         assert!(!registry.has_tool("unknown"));
     }
 
+    #[tokio::test]
+    async fn test_bash_run_in_background_uses_given_job_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ToolRegistry::with_cwd(Some(dir.path().to_path_buf()));
+        let session_jobs = BackgroundJobs::new();
+
+        let started = registry
+            .execute_with_context(
+                "Bash",
+                serde_json::json!({"command": "echo bg > out.txt", "run_in_background": true}),
+                ToolContext::new(&session_jobs),
+            )
+            .await
+            .unwrap();
+        assert!(
+            started.starts_with("Started background job bg_1"),
+            "{}",
+            started
+        );
+
+        // The registry's own table knows nothing about the session's job
+        assert!(registry
+            .execute("BashOutput", serde_json::json!({"id": "bg_1"}))
+            .await
+            .is_err());
+        let status = registry
+            .execute_with_context(
+                "BashOutput",
+                serde_json::json!({"id": "bg_1"}),
+                ToolContext::new(&session_jobs),
+            )
+            .await
+            .unwrap();
+        assert!(status.starts_with("[bg_1: "), "{}", status);
+
+        // Runs in the registry's cwd
+        for _ in 0..50 {
+            if dir.path().join("out.txt").exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(dir.path().join("out.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_confines_filesystem_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "key").unwrap();
+        let policy = crate::sandbox::SandboxPolicy::resolve(
+            "workspace",
+            dir.path(),
+            &std::collections::BTreeMap::new(),
+        )
+        .unwrap();
+        let registry = ToolRegistry::with_cwd(Some(dir.path().to_path_buf())).with_sandbox(policy);
+
+        let secret = outside.path().join("secret").display().to_string();
+        registry
+            .execute("Write", serde_json::json!({"path": "notes.txt", "content": "hi"}))
+            .await
+            .unwrap();
+        assert!(registry
+            .execute("Read", serde_json::json!({"path": "notes.txt"}))
+            .await
+            .is_ok());
+        for (tool, args) in [
+            ("Read", serde_json::json!({ "path": secret })),
+            ("Write", serde_json::json!({"path": "../escape.txt", "content": "x"})),
+            ("Edit", serde_json::json!({"path": secret, "old_string": "key", "new_string": "k"})),
+            ("ApplyPatch", serde_json::json!({"patch": format!("--- /dev/null\n+++ {}\n@@ -0,0 +1 @@\n+x\n", secret)})),
+            ("Glob", serde_json::json!({"pattern": "*", "path": "/"})),
+            ("ListDir", serde_json::json!({"path": "/"})),
+            ("Grep", serde_json::json!({ "pattern": "key", "path": secret })),
+        ] {
+            let err = registry.execute(tool, args).await.unwrap_err();
+            assert!(err.to_string().contains("outside the sandbox"), "{}: {}", tool, err);
+        }
+    }
+
+    #[test]
+    fn test_limits_own_output() {
+        let registry = ToolRegistry::new();
+        assert!(registry.limits_own_output("Read"));
+        assert!(registry.limits_own_output("Grep"));
+        assert!(!registry.limits_own_output("Bash"));
+        assert!(!registry.limits_own_output("WebFetch"));
+        assert!(!registry.limits_own_output("unknown"));
+    }
+
+    /// A tool an embedding program might add
+    struct Echo {
+        sandboxed: bool,
+    }
+
+    impl Tool for Echo {
+        fn name(&self) -> &str {
+            "Echo"
+        }
+
+        fn spec(&self) -> ToolSpec {
+            make_tool("Echo", "Echo the text back", serde_json::json!({"type": "object"}))
+        }
+
+        fn execute<'a>(
+            &'a self,
+            args: serde_json::Value,
+            ctx: ToolContext<'a>,
+        ) -> BoxFuture<'a, Result<String>> {
+            Box::pin(async move {
+                if let Some(progress) = ctx.progress {
+                    progress.try_send("echoing".to_string()).ok();
+                }
+                let text = args["text"].as_str().unwrap_or_default();
+                Ok(format!("{} (sandboxed: {})", text, self.sandboxed))
+            })
+        }
+
+        fn limits_own_output(&self) -> bool {
+            true
+        }
+
+        fn confine(&mut self, _policy: Arc<SandboxPolicy>) {
+            self.sandboxed = true;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_remove_tools() {
+        let mut registry = ToolRegistry::new();
+        assert!(registry.register(Echo { sandboxed: false }).is_none());
+        assert!(registry.has_tool("Echo"));
+        assert!(registry.limits_own_output("Echo"));
+        assert_eq!(registry.get_tools().last().unwrap().function.name, "Echo");
+
+        let (tx, mut rx) = progress_channel();
+        let ctx = ToolContext {
+            progress: Some(&tx),
+            ..registry.context()
+        };
+        let out = registry
+            .execute_with_context("Echo", serde_json::json!({"text": "hi"}), ctx)
+            .await
+            .unwrap();
+        assert_eq!(out, "hi (sandboxed: false)");
+        assert_eq!(rx.try_recv().unwrap(), "echoing");
+
+        // Registering a name again replaces the tool where it was
+        let position = |registry: &ToolRegistry, name: &str| {
+            let tools = registry.get_tools();
+            tools.iter().position(|t| t.function.name == name)
+        };
+        let read_at = position(&registry, "Read");
+        let old = registry.register(ReadTool::new()).unwrap();
+        assert_eq!(old.name(), "Read");
+        assert_eq!(position(&registry, "Read"), read_at);
+
+        let removed = registry.remove("Echo").unwrap();
+        assert_eq!(removed.name(), "Echo");
+        assert!(!registry.has_tool("Echo"));
+        assert!(registry.remove("Echo").is_none());
+        assert!(registry.execute("Echo", serde_json::json!({})).await.is_err());
+        assert!(!ToolRegistry::new().without_tool("Bash").has_tool("Bash"));
+    }
+
+    #[tokio::test]
+    async fn test_tools_registered_after_sandbox_are_confined() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = crate::sandbox::SandboxPolicy::resolve(
+            "workspace",
+            dir.path(),
+            &std::collections::BTreeMap::new(),
+        )
+        .unwrap();
+        let registry = ToolRegistry::with_cwd(Some(dir.path().to_path_buf()))
+            .with_sandbox(policy)
+            .with_tool(Echo { sandboxed: false });
+        let out = registry
+            .execute("Echo", serde_json::json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(out, "hi (sandboxed: true)");
+    }
+
     #[tokio::test]
     async fn test_with_cwd_resolves_relative_paths() {
         let dir = tempfile::tempdir().unwrap();
@@ -134,6 +684,18 @@ mod tests {
             .unwrap();
         assert_eq!(read, "scoped");
 
+        registry
+            .execute(
+                "Edit",
+                serde_json::json!({"path": "note.txt", "old_string": "scoped", "new_string": "edited"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("note.txt")).unwrap(),
+            "edited"
+        );
+
         assert!(dir.path().join("note.txt").exists());
         assert!(dir.path().join("sub").join("nested.txt").exists());
 
@@ -143,6 +705,55 @@ mod tests {
         assert!(!process_cwd.join("sub").join("nested.txt").exists());
     }
 
+    #[tokio::test]
+    async fn test_custom_tools_are_offered_and_dispatched() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("shout.toml"),
+            "name = \"shout\"\ndescription = \"Upper-case text\"\ncommand = 'echo \"$EUNICE_ARG_TEXT\" | tr a-z A-Z'\n",
+        )
+        .unwrap();
+        let (custom, errors) = CustomTools::load_from(&[dir.path().to_path_buf()], None);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let registry = ToolRegistry::new().with_custom_tools(custom);
+
+        assert!(registry.has_tool("shout"));
+        assert!(registry.get_tools().iter().any(|t| t.function.name == "shout"));
+        let out = registry
+            .execute("shout", serde_json::json!({"text": "hey"}))
+            .await
+            .unwrap();
+        assert_eq!(out, "HEY\n");
+        assert!(registry.execute("whisper", serde_json::json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_and_edit_are_checkpointed() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = Checkpoints::in_root(root.path(), "session").unwrap();
+        let registry =
+            ToolRegistry::with_cwd(Some(dir.path().to_path_buf())).with_checkpoints(store);
+
+        let write = serde_json::json!({"path": "a.txt", "content": "one\n"});
+        registry.execute("Write", write).await.unwrap();
+        let edit = serde_json::json!({"path": "a.txt", "old_string": "one", "new_string": "two"});
+        registry.execute("Edit", edit).await.unwrap();
+        // A failed edit changes nothing and leaves no checkpoint
+        let edit = serde_json::json!({"path": "a.txt", "old_string": "zzz", "new_string": "x"});
+        assert!(registry.execute("Edit", edit).await.is_err());
+
+        let store = registry.checkpoints().unwrap();
+        let tools: Vec<String> = store.list().unwrap().into_iter().map(|c| c.tool).collect();
+        assert_eq!(tools, vec!["Write", "Edit"]);
+
+        store.undo(1).unwrap();
+        let path = dir.path().join("a.txt");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\n");
+        store.undo(1).unwrap();
+        assert!(!path.exists());
+    }
+
     #[tokio::test]
     async fn test_new_resolves_relative_paths_against_process_cwd() {
         let registry = ToolRegistry::new();
@@ -1,11 +1,24 @@
 use crate::models::Tool;
-use crate::tools::make_tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::documents::{self, DocumentKind, Extraction, Selection};
+use crate::tools::{make_tool, ToolContext};
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Most bytes of file content a single Read returns
+const MAX_READ_BYTES: usize = 100 * 1024;
+
+/// Most entries listed when the path is a directory
+const MAX_DIR_ENTRIES: usize = 500;
 
 /// Read tool for reading file contents
 pub struct ReadTool {
     cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
 }
 
 impl ReadTool {
@@ -16,7 +29,13 @@ impl ReadTool {
     /// Resolve relative paths against `cwd` instead of the process working
     /// directory. Per-tool so concurrent runs cannot race each other.
     pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
-        Self { cwd }
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
     }
 
     fn resolve(&self, path: &str) -> PathBuf {
@@ -29,13 +48,38 @@ impl ReadTool {
     pub fn get_spec(&self) -> Tool {
         make_tool(
             "Read",
-            "Read the contents of a file. Returns the file content as a string. For binary files, returns a message indicating the file type.",
+            "Read the contents of a file. Returns the file content as a string. For large files, \
+             use offset and limit to read a range of lines; output is capped at 100KB. \
+             Set line_numbers to prefix each line with its number. PDF, DOCX, spreadsheet \
+             (XLSX, XLS, ODS) and Jupyter notebook files are returned as extracted text; use \
+             pages and sheet to select parts. For other binary files, returns a message \
+             indicating the file type. For directories, lists the entries.",
             serde_json::json!({
                 "type": "object",
                 "properties": {
                     "path": {
                         "type": "string",
                         "description": "Absolute or relative path to the file"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Line number to start reading from, 1-based (default: 1)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of lines to read (default: until the size cap)"
+                    },
+                    "line_numbers": {
+                        "type": "boolean",
+                        "description": "Prefix each line with its line number, like cat -n (default: false)"
+                    },
+                    "pages": {
+                        "type": "string",
+                        "description": "PDF pages to extract, e.g. \"3\", \"1-5\" or \"2,4,7-9\" (default: all)"
+                    },
+                    "sheet": {
+                        "type": "string",
+                        "description": "Spreadsheet sheet to extract, by name or 1-based number (default: all)"
                     }
                 },
                 "required": ["path"]
@@ -47,43 +91,273 @@ impl ReadTool {
         let path_str = args["path"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter"))?;
+        let offset = args["offset"].as_u64().unwrap_or(1).max(1) as usize;
+        let limit = args["limit"].as_u64().map(|l| l as usize);
+        let line_numbers = args["line_numbers"].as_bool().unwrap_or(false);
+        let sheet = match &args["sheet"] {
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            other => other.as_str().map(str::to_string),
+        };
+        let selection = Selection {
+            pages: args["pages"].as_str(),
+            sheet: sheet.as_deref(),
+        };
 
         let path = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_read(&path)?;
+        }
 
         // Check if file exists
         if !path.exists() {
             return Err(anyhow::anyhow!("File not found: {}", path_str));
         }
 
-        // Check if it's a directory
         if path.is_dir() {
-            return Err(anyhow::anyhow!("Path is a directory, not a file: {}", path_str));
+            return list_directory(&path, path_str);
         }
 
-        // Read the file
-        let content = std::fs::read(&path)
+        let file =
+            File::open(&path).with_context(|| format!("Failed to read file: {}", path_str))?;
+        let mut reader = BufReader::new(file);
+
+        // Sniff the start of the file; binary files get a description instead
+        let head = reader
+            .fill_buf()
             .with_context(|| format!("Failed to read file: {}", path_str))?;
 
-        // Check if it's valid UTF-8 (text file)
-        match String::from_utf8(content.clone()) {
-            Ok(text) => Ok(text),
-            Err(_) => {
-                // Binary file - return info about it
-                let size = content.len();
-                let extension = path.extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or("unknown");
-
-                // Try to guess the file type from magic bytes
-                let file_type = detect_binary_type(&content, extension);
-
-                Ok(format!(
-                    "[Binary file: {} bytes, type: {}]\nUse the Bash tool to process this file with appropriate commands.",
-                    size, file_type
-                ))
+        // Documents come back as text; ones that fail to parse are described
+        // like any other binary file, with the reason
+        let mut extraction_failure = None;
+        if let Some(kind) = DocumentKind::detect(&path, head) {
+            match documents::extract(&path, kind, &selection)? {
+                Extraction::Text(text) => {
+                    return read_lines(text.as_bytes(), offset, limit, line_numbers)
+                        .with_context(|| format!("Failed to read file: {}", path_str));
+                }
+                Extraction::Failed(reason) => extraction_failure = Some(reason),
+            }
+        } else if selection.pages.is_some() || selection.sheet.is_some() {
+            return Err(anyhow::anyhow!(
+                "'pages' and 'sheet' only apply to PDF documents and spreadsheets"
+            ));
+        }
+
+        if is_binary(head) {
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("unknown");
+
+            // Try to guess the file type from magic bytes
+            let file_type = detect_binary_type(head, extension);
+
+            let failure = extraction_failure
+                .map(|reason| format!("\nText extraction failed: {}", reason))
+                .unwrap_or_default();
+            return Ok(format!(
+                "[Binary file: {} bytes, type: {}]{}\nUse the Bash tool to process this file with appropriate commands.",
+                size, file_type, failure
+            ));
+        }
+
+        read_lines(reader, offset, limit, line_numbers)
+            .with_context(|| format!("Failed to read file: {}", path_str))
+    }
+}
+
+/// Stream lines `offset..offset+limit` (1-based) out of `reader`, stopping
+/// at `MAX_READ_BYTES` or the end of the range, whichever comes first. Notes
+/// are appended when the range or the cap cut the file short, so the model
+/// knows how to continue.
+fn read_lines(
+    mut reader: impl BufRead,
+    offset: usize,
+    limit: Option<usize>,
+    line_numbers: bool,
+) -> std::io::Result<String> {
+    let end = limit.map(|l| offset + l);
+    let mut out = String::new();
+    let mut line = Vec::new();
+    let mut line_no = 0;
+    let mut last_shown = 0;
+    let mut capped = false;
+    let mut more = false;
+
+    loop {
+        line.clear();
+        // Lines before the range are only counted
+        let keep = if line_no + 1 >= offset {
+            MAX_READ_BYTES
+        } else {
+            0
+        };
+        let read = read_line_capped(&mut reader, &mut line, keep)?;
+        if read == 0 {
+            break;
+        }
+        line_no += 1;
+        if end.is_some_and(|end| line_no >= end) {
+            more = true;
+            break;
+        }
+        if line_no < offset {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        let text = if line_numbers {
+            format!("{:>6}\t{}", line_no, text)
+        } else {
+            text.into_owned()
+        };
+
+        if read > line.len() || out.len() + text.len() > MAX_READ_BYTES {
+            capped = true;
+            if last_shown == 0 {
+                // A single line over the cap (minified JSON, say): show its start
+                let mut end = MAX_READ_BYTES.min(text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                out.push_str(&text[..end]);
+                last_shown = line_no;
             }
+            break;
+        }
+        out.push_str(&text);
+        last_shown = line_no;
+    }
+
+    if offset > line_no && line_no > 0 {
+        return Ok(format!(
+            "[offset {} is past the end of the file ({} lines)]",
+            offset, line_no
+        ));
+    }
+
+    if capped {
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "\n[Output capped at {}KB after line {}. Use offset={} to read more.]",
+            MAX_READ_BYTES / 1024,
+            last_shown,
+            last_shown + 1
+        ));
+    } else if more {
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push_str(&format!(
+            "\n[Showing lines {}-{}. Use offset={} to read more.]",
+            offset,
+            last_shown,
+            last_shown + 1
+        ));
+    }
+
+    Ok(out)
+}
+
+/// Read one line, newline included, into `line`, keeping at most `keep`
+/// bytes of it and skipping the rest. Returns the bytes consumed; 0 at EOF.
+fn read_line_capped(
+    reader: &mut impl BufRead,
+    line: &mut Vec<u8>,
+    keep: usize,
+) -> std::io::Result<usize> {
+    let mut consumed = 0;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(consumed);
+        }
+        let (len, done) = match chunk.iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (chunk.len(), false),
+        };
+        let room = keep.saturating_sub(line.len());
+        line.extend_from_slice(&chunk[..len.min(room)]);
+        reader.consume(len);
+        consumed += len;
+        if done {
+            return Ok(consumed);
+        }
+    }
+}
+
+/// Treat content as binary if the sniffed prefix has NUL bytes or is not
+/// UTF-8 (allowing a multi-byte character cut off at the end of the buffer)
+fn is_binary(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => false,
+        Err(e) => e.error_len().is_some(),
+    }
+}
+
+/// List a directory's entries, directories first, each marked with a trailing `/`
+fn list_directory(path: &Path, path_str: &str) -> Result<String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)
+        .with_context(|| format!("Failed to list directory: {}", path_str))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            dirs.push(format!("{}/", name));
+        } else {
+            files.push(name);
         }
     }
+    dirs.sort();
+    files.sort();
+
+    let total = dirs.len() + files.len();
+    let mut out = format!("[Directory: {}, {} entries]\n", path_str, total);
+    for name in dirs.iter().chain(&files).take(MAX_DIR_ENTRIES) {
+        out.push_str(name);
+        out.push('\n');
+    }
+    if total > MAX_DIR_ENTRIES {
+        out.push_str(&format!(
+            "[... {} more entries not shown]\n",
+            total - MAX_DIR_ENTRIES
+        ));
+    }
+    Ok(out)
+}
+
+impl crate::tools::Tool for ReadTool {
+    fn name(&self) -> &str {
+        "Read"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move { ReadTool::execute(self, args) })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
 }
 
 impl Default for ReadTool {
@@ -212,6 +486,191 @@ mod tests {
         assert!(result.contains("PNG image"));
     }
 
+    fn numbered_file(lines: usize) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for i in 1..=lines {
+            writeln!(file, "line {}", i).unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn test_read_offset_and_limit() {
+        let file = numbered_file(10);
+        let result = ReadTool::new()
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "offset": 3,
+                "limit": 2
+            }))
+            .unwrap();
+        assert_eq!(
+            result,
+            "line 3\nline 4\n\n[Showing lines 3-4. Use offset=5 to read more.]"
+        );
+
+        // A range reaching the end of the file needs no note
+        let result = ReadTool::new()
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "offset": 9
+            }))
+            .unwrap();
+        assert_eq!(result, "line 9\nline 10\n");
+    }
+
+    #[test]
+    fn test_read_line_numbers() {
+        let file = numbered_file(3);
+        let result = ReadTool::new()
+            .execute(serde_json::json!({
+                "path": file.path().to_str().unwrap(),
+                "offset": 2,
+                "line_numbers": true
+            }))
+            .unwrap();
+        assert_eq!(result, "     2\tline 2\n     3\tline 3\n");
+    }
+
+    #[test]
+    fn test_read_offset_past_end() {
+        let file = numbered_file(3);
+        let result = ReadTool::new()
+            .execute(serde_json::json!({"path": file.path().to_str().unwrap(), "offset": 7}))
+            .unwrap();
+        assert!(
+            result.contains("past the end of the file (3 lines)"),
+            "{}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_read_caps_output_bytes() {
+        let mut file = NamedTempFile::new().unwrap();
+        let line = "x".repeat(99);
+        for _ in 0..2000 {
+            writeln!(file, "{}", line).unwrap();
+        }
+
+        let result = ReadTool::new()
+            .execute(serde_json::json!({"path": file.path().to_str().unwrap()}))
+            .unwrap();
+
+        // 100-byte lines: 1024 of them fit in 100KB
+        assert!(result.len() < MAX_READ_BYTES + 200);
+        assert!(
+            result.ends_with(
+                "[Output capped at 100KB after line 1024. Use offset=1025 to read more.]"
+            ),
+            "{}",
+            &result[result.len() - 120..]
+        );
+    }
+
+    #[test]
+    fn test_read_stops_at_end_of_range() {
+        /// Fails if read: nothing past the range should be
+        struct Unreadable;
+        impl std::io::Read for Unreadable {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("read past the range"))
+            }
+        }
+
+        let lines = "one\ntwo\nthree\n".as_bytes();
+        let reader = std::io::BufReader::with_capacity(4, std::io::Read::chain(lines, Unreadable));
+        let result = read_lines(reader, 2, Some(1), false).unwrap();
+        assert_eq!(
+            result,
+            "two\n\n[Showing lines 2-2. Use offset=3 to read more.]"
+        );
+    }
+
+    #[test]
+    fn test_read_line_capped_skips_past_keep() {
+        let mut reader = "abcdef\nxy".as_bytes();
+        let mut line = Vec::new();
+        assert_eq!(read_line_capped(&mut reader, &mut line, 3).unwrap(), 7);
+        assert_eq!(line, b"abc");
+        line.clear();
+        assert_eq!(read_line_capped(&mut reader, &mut line, 3).unwrap(), 2);
+        assert_eq!(line, b"xy");
+        assert_eq!(read_line_capped(&mut reader, &mut line, 3).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_caps_single_long_line() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", "é".repeat(MAX_READ_BYTES)).unwrap();
+
+        let result = ReadTool::new()
+            .execute(serde_json::json!({"path": file.path().to_str().unwrap()}))
+            .unwrap();
+        assert!(result.len() <= MAX_READ_BYTES + 200);
+        assert!(
+            result.contains("after line 1."),
+            "{}",
+            &result[result.len() - 120..]
+        );
+    }
+
+    #[test]
+    fn test_read_directory_lists_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+
+        let result = ReadTool::with_cwd(Some(dir.path().to_path_buf()))
+            .execute(serde_json::json!({"path": "."}))
+            .unwrap();
+        assert_eq!(result, "[Directory: ., 3 entries]\nsrc/\na.txt\nb.txt\n");
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text"));
+        assert!(is_binary(b"nul\0byte"));
+        assert!(is_binary(&[0x89, 0x50, 0x4E, 0x47, 0xFF]));
+        // A multi-byte character split at the end of the sniffed buffer is still text
+        assert!(!is_binary(&"é".as_bytes()[..1]));
+    }
+
+    #[test]
+    fn test_read_extracts_documents_through_offset_and_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        crate::tools::documents::tests::write_pdf(&path, &["Alpha", "Beta"]);
+
+        let tool = ReadTool::with_cwd(Some(dir.path().to_path_buf()));
+        let args = serde_json::json!({"path": "report.pdf", "pages": "2", "line_numbers": true});
+        let result = tool.execute(args).unwrap();
+        assert!(result.contains("[PDF: 2 pages, showing 2]"), "{}", result);
+        assert!(result.contains("\tBeta"), "{}", result);
+        assert!(!result.contains("Alpha"), "{}", result);
+
+        let args = serde_json::json!({"path": "report.pdf", "offset": 2, "limit": 2});
+        let result = tool.execute(args).unwrap();
+        assert!(result.starts_with("\n--- Page 1 ---\n"), "{}", result);
+        assert!(result.contains("Use offset=4"), "{}", result);
+    }
+
+    #[test]
+    fn test_read_unparseable_document_falls_back_to_binary_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("broken.docx"), b"PK\x03\x04not really a zip\x00").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "text").unwrap();
+
+        let tool = ReadTool::with_cwd(Some(dir.path().to_path_buf()));
+        let result = tool.execute(serde_json::json!({"path": "broken.docx"})).unwrap();
+        assert!(result.contains("Microsoft Word document"), "{}", result);
+        assert!(result.contains("Text extraction failed"), "{}", result);
+
+        let args = serde_json::json!({"path": "notes.txt", "pages": "1"});
+        assert!(tool.execute(args).is_err());
+    }
+
     #[test]
     fn test_detect_binary_types() {
         assert_eq!(detect_binary_type(&[0x89, 0x50, 0x4E, 0x47], ""), "PNG image");
@@ -0,0 +1,312 @@
+//! Long-lived shell for the Bash tool's persistent mode.
+//!
+//! Commands are written to the shell's stdin and followed by sentinel lines on
+//! stdout and stderr carrying the exit code, so `cd`, `export` and sourced
+//! environments carry over from one call to the next.
+
+use crate::env_policy::EnvPolicy;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::bash::progress_line;
+use crate::tools::capture::{read_chunk, Capture, OutputLimit};
+use crate::tools::ProgressSender;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+
+/// Shells whose syntax the command wrapper is written for; anything else
+/// (fish, nu, ...) falls back to /bin/sh
+const POSIX_SHELLS: &[&str] = &["bash", "zsh", "sh", "dash", "ksh"];
+
+/// Output of one command run in the persistent shell
+pub(super) struct ShellOutput {
+    pub stdout: Capture,
+    pub stderr: Capture,
+    /// None if the shell was killed by a signal
+    pub exit_code: Option<i32>,
+    /// Set when the command printed more than the output limit, so the shell
+    /// was killed; says so for the result
+    pub limit_note: Option<String>,
+    /// The command ended the shell; the next call starts a new one
+    pub shell_exited: bool,
+    /// A new shell was started for this command because the previous one was
+    /// killed or exited, so directory and environment changes were lost
+    pub restarted: bool,
+}
+
+/// One shell kept alive across Bash calls. Commands are serialized; a call
+/// that is cancelled or times out kills the shell's process group, and the
+/// next call starts a fresh shell.
+pub(super) struct PersistentShell {
+    cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
+    env: Arc<EnvPolicy>,
+    process: Mutex<Option<ShellProcess>>,
+    /// A shell has been started before, so starting another is a restart
+    started: AtomicBool,
+}
+
+impl PersistentShell {
+    pub fn new(
+        cwd: Option<PathBuf>,
+        sandbox: Option<Arc<SandboxPolicy>>,
+        env: Arc<EnvPolicy>,
+    ) -> Self {
+        Self {
+            cwd,
+            sandbox,
+            env,
+            process: Mutex::new(None),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Kill the current shell, if any; the next command starts a new one
+    pub async fn restart(&self) {
+        self.process.lock().await.take();
+        self.started.store(false, Ordering::SeqCst);
+    }
+
+    pub async fn run(
+        &self,
+        command: &str,
+        timeout_secs: u64,
+        progress: Option<&ProgressSender>,
+    ) -> Result<ShellOutput> {
+        let mut slot = self.process.lock().await;
+
+        let mut restarted = false;
+        if slot.is_none() {
+            *slot = Some(ShellProcess::spawn(
+                self.cwd.as_ref(),
+                self.sandbox.as_deref(),
+                &self.env,
+            )?);
+            restarted = self.started.swap(true, Ordering::SeqCst);
+        }
+
+        // Dropping the shell kills its process group. Until the command
+        // finishes, this guard does that if the future is dropped (the user
+        // cancelled) or we bail out below (timeout, broken pipe).
+        let mut guard = KillOnDrop {
+            slot: &mut slot,
+            armed: true,
+        };
+        let shell = guard.slot.as_mut().expect("shell was just started");
+
+        let result = timeout(Duration::from_secs(timeout_secs), shell.run(command, progress))
+            .await
+            .with_context(|| {
+                format!(
+                    "Command timed out after {} seconds; the shell was killed and will be restarted",
+                    timeout_secs
+                )
+            })??;
+
+        // Keep the shell for the next call unless the command ended it
+        guard.armed = result.shell_exited;
+        Ok(ShellOutput {
+            restarted,
+            ..result
+        })
+    }
+}
+
+/// Drops (and so kills) the shell in `slot` unless disarmed
+struct KillOnDrop<'a> {
+    slot: &'a mut Option<ShellProcess>,
+    armed: bool,
+}
+
+impl Drop for KillOnDrop<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.slot.take();
+        }
+    }
+}
+
+struct ShellProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+}
+
+impl ShellProcess {
+    fn spawn(
+        cwd: Option<&PathBuf>,
+        sandbox: Option<&SandboxPolicy>,
+        env: &EnvPolicy,
+    ) -> Result<Self> {
+        let shell = std::env::var("SHELL")
+            .ok()
+            .filter(|s| {
+                let name = std::path::Path::new(s)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("");
+                POSIX_SHELLS.contains(&name)
+            })
+            .unwrap_or_else(|| "/bin/sh".to_string());
+
+        // Own process group so the whole subtree can be killed (see Drop)
+        let mut cmd = Command::new(&shell);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        env.apply(&mut cmd);
+        if let Some(dir) = cwd {
+            cmd.current_dir(dir);
+        }
+        #[cfg(unix)]
+        cmd.process_group(0);
+        if let Some(policy) = sandbox {
+            policy.restrict_command(&mut cmd)?;
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to spawn shell: {}", shell))?;
+        let stdin = child.stdin.take().context("shell stdin not piped")?;
+        let stdout = BufReader::new(child.stdout.take().context("shell stdout not piped")?);
+        let stderr = BufReader::new(child.stderr.take().context("shell stderr not piped")?);
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+        })
+    }
+
+    async fn run(
+        &mut self,
+        command: &str,
+        progress: Option<&ProgressSender>,
+    ) -> Result<ShellOutput> {
+        let sentinel = format!("__EUNICE_DONE_{}__", uuid::Uuid::new_v4().simple());
+
+        // eval keeps a syntax error from killing the shell, and stdin from
+        // /dev/null keeps the command from eating the script we send next
+        let script = format!(
+            "eval '{}' < /dev/null\n__eunice_ec=$?\nprintf '\\n%s:%d\\n' '{s}' \"$__eunice_ec\"\nprintf '\\n%s\\n' '{s}' >&2\n",
+            command.replace('\'', "'\\''"),
+            s = sentinel
+        );
+        self.stdin
+            .write_all(script.as_bytes())
+            .await
+            .context("Failed to send command to shell")?;
+        self.stdin
+            .flush()
+            .await
+            .context("Failed to send command to shell")?;
+
+        let limit = OutputLimit::new(self.child.id());
+        let (stdout, stderr) = tokio::join!(
+            read_until_sentinel(&mut self.stdout, &sentinel, progress, &limit),
+            read_until_sentinel(&mut self.stderr, &sentinel, progress, &limit)
+        );
+        let (stdout, code) = stdout?;
+        let (stderr, _) = stderr?;
+
+        let exit_code = match code {
+            Some(code) => Some(code),
+            // The command exited the shell; report the shell's own status
+            None => self.child.wait().await.ok().and_then(|s| s.code()),
+        };
+        Ok(ShellOutput {
+            stdout,
+            stderr,
+            exit_code,
+            limit_note: limit.exceeded().then(|| limit.note()),
+            shell_exited: code.is_none(),
+            restarted: false,
+        })
+    }
+}
+
+impl Drop for ShellProcess {
+    fn drop(&mut self) {
+        // kill_on_drop only reaps the shell itself; take its children too
+        #[cfg(unix)]
+        if let Some(pid) = self.child.id() {
+            unsafe {
+                libc::killpg(pid as i32, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Read lines until the sentinel line, returning the output before it and the
+/// exit code carried on the line (stdout only). None for the code means the
+/// stream ended first: the shell exited. Lines are also sent to `progress`.
+/// Output past `limit` is discarded until the killed shell closes the pipe.
+async fn read_until_sentinel(
+    reader: &mut (impl tokio::io::AsyncBufRead + Unpin),
+    sentinel: &str,
+    progress: Option<&ProgressSender>,
+    limit: &OutputLimit,
+) -> Result<(Capture, Option<i32>)> {
+    let mut out = Capture::default();
+    let mut line = Vec::new();
+    // Blank lines are held back until more output follows: the last one may
+    // be the newline printed ahead of the sentinel rather than the command's
+    let mut held_blank = 0;
+    // Likewise the newline ending the last line written to `out`
+    let mut held_newline = false;
+    loop {
+        line.clear();
+        if read_chunk(reader, &mut line).await? == 0 {
+            if held_newline {
+                out.write(b"\n")?;
+            }
+            return Ok((out, None));
+        }
+        if let Some(rest) = line.strip_prefix(sentinel.as_bytes()) {
+            if let Some(tx) = progress {
+                for _ in 1..held_blank {
+                    let _ = tx.try_send(String::new());
+                }
+            }
+            // The sentinel is printed after a newline of its own, which is
+            // the held one; drop it
+            let code = String::from_utf8_lossy(rest)
+                .trim()
+                .trim_start_matches(':')
+                .parse()
+                .ok();
+            return Ok((out, Some(code.unwrap_or(0))));
+        }
+        if !limit.charge(line.len()) {
+            continue;
+        }
+        if let Some(tx) = progress {
+            if line == b"\n" {
+                held_blank += 1;
+            } else {
+                for _ in 0..std::mem::take(&mut held_blank) {
+                    let _ = tx.try_send(String::new());
+                }
+                let _ = tx.try_send(progress_line(&line));
+            }
+        }
+        if std::mem::take(&mut held_newline) {
+            out.write(b"\n")?;
+        }
+        match line.strip_suffix(b"\n") {
+            Some(text) => {
+                out.write(text)?;
+                held_newline = true;
+            }
+            None => out.write(&line)?,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::models::Tool;
 use crate::skills;
-use crate::tools::make_tool;
+use crate::tools::{make_tool, ToolContext};
 use anyhow::Result;
+use futures::future::BoxFuture;
 
 /// Skill tool for discovering skills from ~/.eunice/skills/
 pub struct SkillTool;
@@ -36,6 +37,24 @@ impl SkillTool {
     }
 }
 
+impl crate::tools::Tool for SkillTool {
+    fn name(&self) -> &str {
+        "Skill"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(SkillTool::execute(self, args))
+    }
+}
+
 impl Default for SkillTool {
     fn default() -> Self {
         Self::new()
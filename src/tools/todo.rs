@@ -0,0 +1,300 @@
+//! The `Todo` tool: a structured task list the model keeps for itself.
+//!
+//! Every call replaces the whole list. The list lives in a `TodoList` owned
+//! by the session (the CLI registry's, or one the webapp loads from and saves
+//! to its session store), so front ends can show it and the agent loops can
+//! put it back into context after compaction.
+
+use crate::models::Tool;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Longest list the tool accepts; a plan, not a backlog
+const MAX_ITEMS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    #[serde(alias = "completed")]
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub content: String,
+    pub status: TodoStatus,
+}
+
+/// A session's task list, shared between the Todo tool and whatever shows
+/// it. Clones share the same list.
+#[derive(Debug, Clone, Default)]
+pub struct TodoList {
+    inner: Arc<Mutex<TodoState>>,
+}
+
+#[derive(Debug, Default)]
+struct TodoState {
+    items: Vec<TodoItem>,
+    /// Bumped on every change, so callers can tell whether a tool call
+    /// touched the list without comparing it
+    revision: u64,
+}
+
+impl TodoList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A list restored from a session store
+    pub fn with_items(items: Vec<TodoItem>) -> Self {
+        let list = Self::new();
+        list.inner.lock().unwrap().items = items;
+        list
+    }
+
+    pub fn items(&self) -> Vec<TodoItem> {
+        self.inner.lock().unwrap().items.clone()
+    }
+
+    pub fn replace(&self, items: Vec<TodoItem>) {
+        let mut state = self.inner.lock().unwrap();
+        state.items = items;
+        state.revision += 1;
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.inner.lock().unwrap().revision
+    }
+}
+
+/// One line per task: `[x]` done, `[>]` in progress, `[ ]` pending
+pub fn checklist(items: &[TodoItem]) -> String {
+    items
+        .iter()
+        .map(|item| {
+            let mark = match item.status {
+                TodoStatus::Done => "[x]",
+                TodoStatus::InProgress => "[>]",
+                TodoStatus::Pending => "[ ]",
+            };
+            format!("{} {}", mark, item.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// "2/5 tasks done"
+pub fn progress(items: &[TodoItem]) -> String {
+    let done = items
+        .iter()
+        .filter(|item| item.status == TodoStatus::Done)
+        .count();
+    format!("{}/{} tasks done", done, items.len())
+}
+
+/// The list as a note for the model, put back at the start of the history
+/// after compaction has summarised away the calls that built it
+pub fn context_note(items: &[TodoItem]) -> Option<String> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "## Current Task List\n\n{}\n\n[Your task list from the Todo tool ({}). Keep it up to date with Todo as you work.]",
+        checklist(items),
+        progress(items)
+    ))
+}
+
+/// Todo: replace the session's task list
+pub struct TodoTool;
+
+impl TodoTool {
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "Todo",
+            "Keep a task list for multi-step work. Each call replaces the whole list, so send every task with its current status. Mark a task in_progress before starting it (only one at a time) and done as soon as it is finished. The list is shown to the user and kept with the session, including after the conversation is compacted. Skip it for simple one-step requests.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "todos": {
+                        "type": "array",
+                        "description": "The complete task list, in order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "content": {
+                                    "type": "string",
+                                    "description": "What the task is, in a short imperative sentence"
+                                },
+                                "status": {
+                                    "type": "string",
+                                    "enum": ["pending", "in_progress", "done"]
+                                }
+                            },
+                            "required": ["content", "status"]
+                        }
+                    }
+                },
+                "required": ["todos"]
+            }),
+        )
+    }
+
+    pub fn execute(&self, args: serde_json::Value, list: &TodoList) -> Result<String> {
+        let todos = args
+            .get("todos")
+            .cloned()
+            .ok_or_else(|| anyhow!("Missing 'todos' parameter"))?;
+        let items: Vec<TodoItem> = serde_json::from_value(todos).map_err(|e| {
+            anyhow!(
+                "'todos' must be a list of {{content, status}} with status pending, in_progress or done: {}",
+                e
+            )
+        })?;
+        let items: Vec<TodoItem> = items
+            .into_iter()
+            .map(|item| TodoItem {
+                content: item.content.trim().to_string(),
+                status: item.status,
+            })
+            .collect();
+
+        if items.len() > MAX_ITEMS {
+            bail!("At most {} tasks; merge or drop some", MAX_ITEMS);
+        }
+        if items.iter().any(|item| item.content.is_empty()) {
+            bail!("Every task needs content");
+        }
+        let in_progress = items
+            .iter()
+            .filter(|item| item.status == TodoStatus::InProgress)
+            .count();
+        if in_progress > 1 {
+            bail!("Only one task can be in_progress at a time");
+        }
+
+        list.replace(items.clone());
+        if items.is_empty() {
+            return Ok("Task list cleared".to_string());
+        }
+        Ok(format!("{}\n({})", checklist(&items), progress(&items)))
+    }
+}
+
+impl crate::tools::Tool for TodoTool {
+    fn name(&self) -> &str {
+        "Todo"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let list = ctx
+                .todos
+                .ok_or_else(|| anyhow!("No task list in this session"))?;
+            TodoTool::execute(self, args, list)
+        })
+    }
+
+    fn limits_own_output(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(content: &str, status: TodoStatus) -> TodoItem {
+        TodoItem {
+            content: content.to_string(),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_execute_replaces_list() {
+        let list = TodoList::new();
+        let out = TodoTool
+            .execute(
+                serde_json::json!({"todos": [
+                    {"content": "Write parser", "status": "done"},
+                    {"content": " Add tests ", "status": "in_progress"},
+                    {"content": "Update docs", "status": "pending"}
+                ]}),
+                &list,
+            )
+            .unwrap();
+        assert_eq!(
+            out,
+            "[x] Write parser\n[>] Add tests\n[ ] Update docs\n(1/3 tasks done)"
+        );
+        assert_eq!(list.items()[1], item("Add tests", TodoStatus::InProgress));
+        assert_eq!(list.revision(), 1);
+
+        // "completed" is accepted for done
+        TodoTool
+            .execute(
+                serde_json::json!({"todos": [{"content": "Ship", "status": "completed"}]}),
+                &list,
+            )
+            .unwrap();
+        assert_eq!(list.items(), vec![item("Ship", TodoStatus::Done)]);
+
+        let out = TodoTool
+            .execute(serde_json::json!({"todos": []}), &list)
+            .unwrap();
+        assert_eq!(out, "Task list cleared");
+        assert!(list.items().is_empty());
+        assert_eq!(list.revision(), 3);
+    }
+
+    #[test]
+    fn test_execute_rejects_bad_lists_without_changing_it() {
+        let list = TodoList::with_items(vec![item("Keep me", TodoStatus::Pending)]);
+        for args in [
+            serde_json::json!({}),
+            serde_json::json!({"todos": "do things"}),
+            serde_json::json!({"todos": [{"content": "x", "status": "blocked"}]}),
+            serde_json::json!({"todos": [{"content": "  ", "status": "pending"}]}),
+            serde_json::json!({"todos": [
+                {"content": "a", "status": "in_progress"},
+                {"content": "b", "status": "in_progress"}
+            ]}),
+        ] {
+            assert!(TodoTool.execute(args.clone(), &list).is_err(), "{}", args);
+        }
+        assert_eq!(list.items(), vec![item("Keep me", TodoStatus::Pending)]);
+        assert_eq!(list.revision(), 0);
+    }
+
+    #[test]
+    fn test_context_note() {
+        assert!(context_note(&[]).is_none());
+        let note = context_note(&[
+            item("Plan", TodoStatus::Done),
+            item("Build", TodoStatus::Pending),
+        ])
+        .unwrap();
+        assert!(note.contains("[x] Plan\n[ ] Build"));
+        assert!(note.contains("1/2 tasks done"));
+    }
+
+    #[test]
+    fn test_status_serializes_snake_case() {
+        let json = serde_json::to_value(item("a", TodoStatus::InProgress)).unwrap();
+        assert_eq!(json, serde_json::json!({"content": "a", "status": "in_progress"}));
+    }
+}
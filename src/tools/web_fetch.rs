@@ -0,0 +1,323 @@
+use crate::models::Tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::html::html_to_markdown;
+use crate::tools::{make_tool, ToolContext};
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use reqwest::Url;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most bytes of a response body WebFetch downloads
+const MAX_FETCH_BYTES: usize = 5 * 1024 * 1024;
+
+/// Whole-request timeout, connect through last byte
+const FETCH_TIMEOUT_SECS: u64 = 30;
+
+/// Most redirects followed before giving up
+const MAX_REDIRECTS: usize = 5;
+
+/// WebFetch tool for reading web pages and HTTP APIs
+pub struct WebFetchTool {
+    client: reqwest::Client,
+    sandbox: Option<Arc<SandboxPolicy>>,
+}
+
+impl WebFetchTool {
+    /// Fails when the HTTP client cannot be built (e.g. no TLS backend)
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+            .user_agent(concat!("eunice/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("failed to build the HTTP client")?;
+        Ok(Self {
+            client,
+            sandbox: None,
+        })
+    }
+
+    pub fn get_spec(&self) -> Tool {
+        make_tool(
+            "WebFetch",
+            "Fetch a URL over HTTP(S). HTML pages are converted to markdown, keeping the main \
+             content and dropping navigation, scripts and other page chrome; JSON is \
+             pretty-printed; plain text is returned as is. Use for documentation, articles and \
+             HTTP APIs. Binary content (images, PDFs, archives) is refused.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "Absolute http:// or https:// URL"
+                    },
+                    "full_page": {
+                        "type": "boolean",
+                        "description": "Convert the whole HTML page, including navigation and footers (default: false, main content only)"
+                    }
+                },
+                "required": ["url"]
+            }),
+        )
+    }
+
+    pub async fn execute(&self, args: serde_json::Value) -> Result<String> {
+        let url_str = args["url"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Missing 'url' parameter"))?;
+        let full_page = args["full_page"].as_bool().unwrap_or(false);
+
+        if let Some(policy) = &self.sandbox {
+            if !policy.network {
+                return Err(anyhow!(
+                    "network access is outside the sandbox: profile '{}' sets network = false",
+                    policy.profile
+                ));
+            }
+        }
+
+        let url = Url::parse(url_str).with_context(|| format!("Invalid URL: {}", url_str))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!(
+                "Unsupported URL scheme '{}': only http and https can be fetched",
+                url.scheme()
+            ));
+        }
+
+        let mut response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch {}", url))?;
+        let final_url = response.url().clone();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+
+        let kind = ContentKind::of(&mime).ok_or_else(|| {
+            anyhow!(
+                "{} returned {} content, which WebFetch cannot read as text",
+                final_url,
+                if mime.is_empty() { "untyped" } else { &mime }
+            )
+        })?;
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("Failed to read response from {}", final_url))?
+        {
+            let room = MAX_FETCH_BYTES - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let text = String::from_utf8_lossy(&body);
+
+        let mut out = format!("URL: {}\n", final_url);
+        if !content_type.is_empty() {
+            out.push_str(&format!("Content-Type: {}\n", content_type));
+        }
+        if !status.is_success() {
+            out.push_str(&format!("Status: {}\n", status));
+        }
+        let content = match kind {
+            ContentKind::Html => {
+                let page = html_to_markdown(&text, Some(&final_url), full_page);
+                if let Some(title) = page.title {
+                    out.push_str(&format!("Title: {}\n", title));
+                }
+                page.markdown
+            }
+            // A truncated document will not parse; show it as it came
+            ContentKind::Json => serde_json::from_str::<serde_json::Value>(&text)
+                .ok()
+                .and_then(|value| serde_json::to_string_pretty(&value).ok())
+                .unwrap_or_else(|| text.into_owned()),
+            ContentKind::Text => text.into_owned(),
+        };
+        out.push('\n');
+        out.push_str(&content);
+        if truncated {
+            out.push_str(&format!(
+                "\n\n[Response truncated: only the first {} MB were downloaded]",
+                MAX_FETCH_BYTES / (1024 * 1024)
+            ));
+        }
+        Ok(out)
+    }
+}
+
+impl crate::tools::Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "WebFetch"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        _ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(WebFetchTool::execute(self, args))
+    }
+
+    /// Refuse to fetch when the policy denies network access
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        self.sandbox = Some(policy);
+    }
+}
+
+/// How a response body is turned into text
+enum ContentKind {
+    Html,
+    Json,
+    Text,
+}
+
+impl ContentKind {
+    /// None for binary types; an untyped body is treated as text
+    fn of(mime: &str) -> Option<Self> {
+        match mime {
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "application/json" => Some(Self::Json),
+            m if m.ends_with("+json") => Some(Self::Json),
+            ""
+            | "application/xml"
+            | "application/javascript"
+            | "application/x-yaml"
+            | "application/yaml"
+            | "application/toml" => Some(Self::Text),
+            m if m.starts_with("text/") || m.ends_with("+xml") => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, response::Redirect, routing::get, Router};
+
+    /// Serve a few fixed responses on a local port and return the base URL
+    async fn spawn_server() -> String {
+        let app = Router::new()
+            .route(
+                "/page",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                        "<html><head><title>Docs</title></head><body><nav>Menu</nav>\
+                         <main><h1>Intro</h1><p>See <a href=\"/api\">the API</a>.</p></main></body></html>",
+                    )
+                }),
+            )
+            .route(
+                "/api",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "application/json")],
+                        "{\"ok\":true,\"items\":[1]}",
+                    )
+                }),
+            )
+            .route("/moved", get(|| async { Redirect::temporary("/page") }))
+            .route("/loop", get(|| async { Redirect::temporary("/loop") }))
+            .route(
+                "/big",
+                get(|| async { "x".repeat(MAX_FETCH_BYTES + 1000) }),
+            )
+            .route(
+                "/image",
+                get(|| async { ([(header::CONTENT_TYPE, "image/png")], vec![0u8; 16]) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn fetch(url: String) -> Result<String> {
+        WebFetchTool::new()
+            .unwrap()
+            .execute(serde_json::json!({ "url": url }))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_html_follows_redirect_and_converts() {
+        let base = spawn_server().await;
+        let out = fetch(format!("{}/moved", base)).await.unwrap();
+
+        assert!(out.starts_with(&format!("URL: {}/page\n", base)), "{}", out);
+        assert!(out.contains("Title: Docs\n"), "{}", out);
+        assert!(
+            out.ends_with(&format!("\n\n# Intro\n\nSee [the API]({}/api).", base)),
+            "{}",
+            out
+        );
+        assert!(!out.contains("Menu"), "{}", out);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_json_and_limits() {
+        let base = spawn_server().await;
+
+        let out = fetch(format!("{}/api", base)).await.unwrap();
+        assert!(
+            out.contains("\n{\n  \"items\": [\n    1\n  ],\n  \"ok\": true\n}"),
+            "{}",
+            out
+        );
+
+        let out = fetch(format!("{}/big", base)).await.unwrap();
+        assert!(out.ends_with("[Response truncated: only the first 5 MB were downloaded]"));
+        assert!(out.len() < MAX_FETCH_BYTES + 200);
+
+        let err = fetch(format!("{}/loop", base)).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("redirect"), "{:#}", err);
+
+        let err = fetch(format!("{}/image", base)).await.unwrap_err();
+        assert!(err.to_string().contains("image/png"), "{}", err);
+
+        let err = fetch("file:///etc/passwd".to_string()).await.unwrap_err();
+        assert!(err.to_string().contains("only http and https"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_offline_sandbox_refuses_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy =
+            SandboxPolicy::resolve("offline", dir.path(), &std::collections::BTreeMap::new())
+                .unwrap();
+        let mut tool = WebFetchTool::new().unwrap();
+        crate::tools::Tool::confine(&mut tool, Arc::new(policy));
+        let err = tool
+            .execute(serde_json::json!({"url": "http://127.0.0.1:1/"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside the sandbox"), "{}", err);
+    }
+}
@@ -1,11 +1,15 @@
 use crate::models::Tool;
-use crate::tools::make_tool;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{checkpointed, make_tool, ToolContext};
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Write tool for writing content to files
 pub struct WriteTool {
     cwd: Option<PathBuf>,
+    sandbox: Option<Arc<SandboxPolicy>>,
 }
 
 impl WriteTool {
@@ -16,7 +20,13 @@ impl WriteTool {
     /// Resolve relative paths against `cwd` instead of the process working
     /// directory. Per-tool so concurrent runs cannot race each other.
     pub fn with_cwd(cwd: Option<PathBuf>) -> Self {
-        Self { cwd }
+        Self { cwd, sandbox: None }
+    }
+
+    /// Refuse paths outside the policy's roots
+    pub fn sandboxed(mut self, policy: Arc<SandboxPolicy>) -> Self {
+        self.sandbox = Some(policy);
+        self
     }
 
     fn resolve(&self, path: &str) -> PathBuf {
@@ -57,6 +67,9 @@ impl WriteTool {
             .ok_or_else(|| anyhow::anyhow!("Missing 'content' parameter"))?;
 
         let path = self.resolve(path_str);
+        if let Some(policy) = &self.sandbox {
+            policy.check_write(&path)?;
+        }
 
         // Create parent directories if needed
         if let Some(parent) = path.parent() {
@@ -82,6 +95,33 @@ impl WriteTool {
     }
 }
 
+impl crate::tools::Tool for WriteTool {
+    fn name(&self) -> &str {
+        "Write"
+    }
+
+    fn spec(&self) -> Tool {
+        self.get_spec()
+    }
+
+    fn execute<'a>(
+        &'a self,
+        args: serde_json::Value,
+        ctx: ToolContext<'a>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let path = args["path"].as_str().map(|p| self.resolve(p));
+            checkpointed(ctx.checkpoints, "Write", path.into_iter().collect(), || {
+                WriteTool::execute(self, args)
+            })
+        })
+    }
+
+    fn confine(&mut self, policy: Arc<SandboxPolicy>) {
+        *self = std::mem::take(self).sandboxed(policy);
+    }
+}
+
 impl Default for WriteTool {
     fn default() -> Self {
         Self::new()
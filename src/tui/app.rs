@@ -1,6 +1,7 @@
 //! TUI application using r3bl_tui's readline_async for proper output coordination.
 
 use crate::agent::{self, AgentStatus};
+use crate::checkpoints;
 use crate::client::Client;
 use crate::compact::CompactionConfig;
 use crate::display_sink::TuiDisplaySink;
@@ -8,13 +9,14 @@ use crate::models::Message;
 use crate::models::ProviderInfo;
 use crate::output_store::OutputStore;
 use crate::theme;
-use crate::tools::ToolRegistry;
+use crate::tools::{Answer, Question, ToolRegistry, UserPrompt};
 use crate::usage::SessionUsage;
 
 use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{Clear, ClearType},
 };
@@ -62,6 +64,8 @@ const COMMANDS: &[(&str, &str)] = &[
     ("/help", "Show this help"),
     ("/clear", "Clear conversation history"),
     ("/status", "Show current status"),
+    ("/undo", "Undo the last file change"),
+    ("/rewind", "List file changes; /rewind <n> undoes the last n"),
     ("/quit", "Exit TUI mode"),
 ];
 
@@ -115,11 +119,12 @@ pub async fn run_tui_mode(
     client: &Client,
     provider_info: &ProviderInfo,
     initial_prompt: Option<&str>,
+    tool_registry: &ToolRegistry,
 ) -> Result<()> {
     if std::env::var("EUNICE_TUI_CLASSIC").is_ok() {
-        return run_tui_classic(client, provider_info, initial_prompt).await;
+        return run_tui_classic(client, provider_info, initial_prompt, tool_registry).await;
     }
-    run_tui_framed(client, provider_info, initial_prompt).await
+    run_tui_framed(client, provider_info, initial_prompt, tool_registry).await
 }
 
 /// A stdout writer that translates `\n` -> `\r\n` so agent output renders correctly while the
@@ -152,19 +157,93 @@ fn raw_print(s: &str) {
     let _ = w.flush();
 }
 
+/// Held while AskUser reads an answer, so the cancel monitors leave its key presses alone
+static INPUT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// The next key press for a cancel monitor, if one comes within 50ms. The
+/// input lock is held from poll to read, so a key typed for an AskUser answer
+/// is never taken here; while AskUser reads, this only waits.
+async fn next_key() -> Option<KeyEvent> {
+    if let Ok(_input) = INPUT_LOCK.try_lock() {
+        if !event::poll(Duration::from_millis(50)).unwrap_or(false) {
+            return None;
+        }
+        return match event::read() {
+            Ok(Event::Key(key_event)) => Some(key_event),
+            _ => None,
+        };
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    None
+}
+
+/// Answers AskUser questions at the terminal, in a framed box like the prompt's
+struct TerminalUser;
+
+impl TerminalUser {
+    fn read_answer(question: &Question) -> std::io::Result<Answer> {
+        let _input = INPUT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // The classic path only enables raw mode while it polls for cancel keys
+        let was_raw = crossterm::terminal::is_raw_mode_enabled()?;
+        if !was_raw {
+            crossterm::terminal::enable_raw_mode()?;
+        }
+        raw_print(&format!("\r\n{YELLOW}? {}{RESET}\r\n", question.question));
+        let panel: Vec<String> = question
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("  {DIM}{}.{RESET} {}", i + 1, option))
+            .collect();
+        let footer = if panel.is_empty() {
+            "↵ answer · empty to skip"
+        } else {
+            "↵ answer · number picks an option · empty to skip"
+        };
+        let result = frame_editor::read_line_framed(&[], "answer", footer, &panel);
+        if !was_raw {
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+        let answer = match result? {
+            LineResult::Line(line) => question.resolve(&line),
+            LineResult::Eof | LineResult::Interrupted => Answer::Dismissed,
+        };
+        let shown = match &answer {
+            Answer::Given(reply) => reply.as_str(),
+            _ => "(skipped)",
+        };
+        raw_print(&format!("{DIM}  → {shown}{RESET}\r\n"));
+        Ok(answer)
+    }
+}
+
+impl UserPrompt for TerminalUser {
+    fn ask<'a>(&'a self, question: &'a Question) -> BoxFuture<'a, Result<Answer>> {
+        let question = question.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || TerminalUser::read_answer(&question))
+                .await?
+                .map_err(|e| anyhow!("input error: {}", e))
+        })
+    }
+}
+
+/// The user AskUser questions go to in `--chat`
+pub fn terminal_user() -> Arc<dyn UserPrompt> {
+    Arc::new(TerminalUser)
+}
+
 /// Poll for Esc / Ctrl+C during generation and signal cancellation. Does NOT toggle raw mode
 /// (the framed session keeps raw mode enabled throughout).
 async fn monitor_cancel_raw(cancel_tx: watch::Sender<bool>) {
     loop {
-        if event::poll(Duration::from_millis(50)).unwrap_or(false) {
-            if let Ok(Event::Key(key_event)) = event::read() {
-                if key_event.code == KeyCode::Esc
-                    || (key_event.code == KeyCode::Char('c')
-                        && key_event.modifiers.contains(KeyModifiers::CONTROL))
-                {
-                    let _ = cancel_tx.send(true);
-                    return;
-                }
+        if let Some(key_event) = next_key().await {
+            if key_event.code == KeyCode::Esc
+                || (key_event.code == KeyCode::Char('c')
+                    && key_event.modifiers.contains(KeyModifiers::CONTROL))
+            {
+                let _ = cancel_tx.send(true);
+                return;
             }
         }
         tokio::task::yield_now().await;
@@ -229,9 +308,9 @@ async fn run_tui_framed(
     client: &Client,
     provider_info: &ProviderInfo,
     initial_prompt: Option<&str>,
+    tool_registry: &ToolRegistry,
 ) -> Result<()> {
     let model = provider_info.resolved_model.clone();
-    let tool_registry = ToolRegistry::new();
     let tool_count = tool_registry.get_tools().len();
     let mut conversation_history: Vec<Message> = Vec::new();
     let mut input_history: Vec<String> = Vec::new();
@@ -252,7 +331,7 @@ async fn run_tui_framed(
     if let Some(p) = initial_prompt {
         raw_print(&format!("\r\n{}\r\n", theme::user_bar(p)));
         run_generation(
-            client, &model, p, &tool_registry,
+            client, &model, p, tool_registry,
             &mut conversation_history, &mut output_store, &mut session_usage,
         )
         .await;
@@ -260,7 +339,8 @@ async fn run_tui_framed(
     }
 
     loop {
-        let line = match frame_editor::read_line_framed(&input_history, "eunice", footer) {
+        let panel = theme::todo_panel_with_width(&tool_registry.todos().items(), theme::term_cols());
+        let line = match frame_editor::read_line_framed(&input_history, "eunice", footer, &panel) {
             Ok(LineResult::Line(s)) => s,
             Ok(LineResult::Eof) | Ok(LineResult::Interrupted) => break,
             Err(e) => {
@@ -278,11 +358,14 @@ async fn run_tui_framed(
         }
         match input.as_str() {
             "/help" | "/h" | "/?" => {
-                raw_print(&format!("\r\n{DIM}Commands:{RESET}\r\n  /help  /clear  /status  /quit\r\n"));
+                raw_print(&format!(
+                    "\r\n{DIM}Commands:{RESET}\r\n  /help  /clear  /status  /undo  /rewind [n]  /quit\r\n"
+                ));
                 continue;
             }
             "/clear" | "/c" => {
                 conversation_history.clear();
+                tool_registry.todos().replace(Vec::new());
                 raw_print(&format!("\r\n{GREEN}Conversation history cleared.{RESET}\r\n"));
                 continue;
             }
@@ -295,13 +378,17 @@ async fn run_tui_framed(
             }
             _ => {}
         }
+        if let Some(text) = checkpoints::slash_command(tool_registry.checkpoints(), &input) {
+            raw_print(&format!("\r\n{DIM}{text}{RESET}\r\n"));
+            continue;
+        }
 
         raw_print(&format!("\r\n{}\r\n", theme::user_bar(&input)));
         if !input.starts_with('/') && input_history.last() != Some(&input) {
             input_history.push(input.clone());
         }
         run_generation(
-            client, &model, &input, &tool_registry,
+            client, &model, &input, tool_registry,
             &mut conversation_history, &mut output_store, &mut session_usage,
         )
         .await;
@@ -325,6 +412,7 @@ async fn run_tui_classic(
     client: &Client,
     provider_info: &ProviderInfo,
     initial_prompt: Option<&str>,
+    tool_registry: &ToolRegistry,
 ) -> Result<()> {
     // Create readline context with custom prompt
     let prompt = format!("{PURPLE}›{RESET} ");
@@ -338,6 +426,7 @@ async fn run_tui_classic(
             client,
             &provider_info.resolved_model,
             initial_prompt,
+            tool_registry,
         )
         .await;
     };
@@ -350,8 +439,6 @@ async fn run_tui_classic(
     // Print header and status
     print_header(&mut shared_writer)?;
 
-    // Create tool registry and count tools
-    let tool_registry = ToolRegistry::new();
     let tool_count = tool_registry.get_tools().len();
 
     print_status(&mut shared_writer, &provider_info.resolved_model, tool_count)?;
@@ -376,7 +463,7 @@ async fn run_tui_classic(
             client,
             provider_info,
             prompt_text,
-            &tool_registry,
+            tool_registry,
             &mut conversation_history,
             &mut output_store,
             &mut session_usage,
@@ -436,6 +523,7 @@ async fn run_tui_classic(
                         }
                         "/clear" | "/c" => {
                             conversation_history.clear();
+                            tool_registry.todos().replace(Vec::new());
                             writeln!(sw, "\n{GREEN}Conversation history cleared.{RESET}\n")?;
                         }
                         "/status" | "/s" => {
@@ -471,10 +559,13 @@ async fn run_tui_classic(
                             writeln!(sw, "\n{DIM}Goodbye!{RESET}\n")?;
                             break;
                         }
-                        _ => {
-                            writeln!(sw, "\n{YELLOW}Unknown command: {}{RESET}", cmd)?;
-                            writeln!(sw, "{DIM}Type / for command menu{RESET}\n")?;
-                        }
+                        _ => match checkpoints::slash_command(tool_registry.checkpoints(), &cmd) {
+                            Some(text) => writeln!(sw, "\n{}\n", text)?,
+                            None => {
+                                writeln!(sw, "\n{YELLOW}Unknown command: {}{RESET}", cmd)?;
+                                writeln!(sw, "{DIM}Type / for command menu{RESET}\n")?;
+                            }
+                        },
                     }
                     continue;
                 }
@@ -494,7 +585,7 @@ async fn run_tui_classic(
                     client,
                     provider_info,
                     input,
-                    &tool_registry,
+                    tool_registry,
                     &mut conversation_history,
                     &mut output_store,
                     &mut session_usage,
@@ -543,29 +634,26 @@ async fn monitor_cancel_keys(cancel_tx: watch::Sender<bool>) {
     let mut last_ctrlc_time: Option<Instant> = None;
 
     loop {
-        // Poll for events with a small timeout
-        if event::poll(Duration::from_millis(50)).unwrap_or(false) {
-            if let Ok(Event::Key(key_event)) = event::read() {
-                // Check for Escape key
-                if key_event.code == KeyCode::Esc {
-                    let _ = cancel_tx.send(true);
-                    return;
-                }
+        if let Some(key_event) = next_key().await {
+            // Check for Escape key
+            if key_event.code == KeyCode::Esc {
+                let _ = cancel_tx.send(true);
+                return;
+            }
 
-                // Check for Ctrl+C
-                if key_event.code == KeyCode::Char('c')
-                    && key_event.modifiers.contains(KeyModifiers::CONTROL)
-                {
-                    let now = Instant::now();
-                    if let Some(prev_time) = last_ctrlc_time {
-                        if now.duration_since(prev_time) < Duration::from_millis(500) {
-                            // Double Ctrl+C within 500ms
-                            let _ = cancel_tx.send(true);
-                            return;
-                        }
+            // Check for Ctrl+C
+            if key_event.code == KeyCode::Char('c')
+                && key_event.modifiers.contains(KeyModifiers::CONTROL)
+            {
+                let now = Instant::now();
+                if let Some(prev_time) = last_ctrlc_time {
+                    if now.duration_since(prev_time) < Duration::from_millis(500) {
+                        // Double Ctrl+C within 500ms
+                        let _ = cancel_tx.send(true);
+                        return;
                     }
-                    last_ctrlc_time = Some(now);
                 }
+                last_ctrlc_time = Some(now);
             }
         }
 
@@ -20,7 +20,7 @@ const PROMPT_COLS: usize = 2;
 const CHROME_ROWS: u16 = 3;
 
 /// Slash commands offered for Tab-completion.
-const SLASH_COMMANDS: &[&str] = &["/help", "/clear", "/status", "/quit"];
+const SLASH_COMMANDS: &[&str] = &["/help", "/clear", "/status", "/undo", "/rewind", "/quit"];
 
 pub enum LineResult {
     Line(String),
@@ -70,6 +70,7 @@ fn ensure_reserved(stdout: &mut io::Stdout, reserved: u16, need: u16) -> io::Res
 }
 
 /// Draw the box anchored at the bottom of the screen, then position the cursor in the buffer.
+/// `panel` lines (the task list) sit between the bottom rule and the footer.
 fn draw_box(
     stdout: &mut io::Stdout,
     reserved: u16,
@@ -77,12 +78,14 @@ fn draw_box(
     cursor_pos: usize,
     label: &str,
     footer: &str,
+    panel: &[String],
 ) -> io::Result<()> {
     let cols = theme::term_cols();
     let rows = term_rows();
     let buf_chars = buffer.chars().count();
     let brows = buffer_rows(buf_chars, cols) as u16;
-    let h = brows + CHROME_ROWS;
+    let prows = panel.len() as u16;
+    let h = brows + CHROME_ROWS + prows;
     let region_top = rows.saturating_sub(reserved);
     let box_top = rows.saturating_sub(h);
 
@@ -99,7 +102,12 @@ fn draw_box(
     stdout.execute(MoveTo(0, box_top + 1 + brows))?;
     write!(stdout, "{}", theme::rule_with_width(cols, None))?;
 
-    stdout.execute(MoveTo(0, box_top + 2 + brows))?;
+    for (i, line) in panel.iter().enumerate() {
+        stdout.execute(MoveTo(0, box_top + 2 + brows + i as u16))?;
+        write!(stdout, "{}", line)?;
+    }
+
+    stdout.execute(MoveTo(0, box_top + 2 + brows + prows))?;
     write!(stdout, "{}", theme::footer(footer))?;
 
     // Position the cursor within the buffer.
@@ -122,13 +130,20 @@ fn erase_region(stdout: &mut io::Stdout, reserved: u16) -> io::Result<()> {
     Ok(())
 }
 
-/// Read one line of input inside the framed, expanding box. Caller owns raw mode.
-pub fn read_line_framed(history: &[String], label: &str, footer: &str) -> io::Result<LineResult> {
+/// Read one line of input inside the framed, expanding box, with `panel` lines shown above the
+/// footer. Caller owns raw mode.
+pub fn read_line_framed(
+    history: &[String],
+    label: &str,
+    footer: &str,
+    panel: &[String],
+) -> io::Result<LineResult> {
     let mut stdout = io::stdout();
     let cols = theme::term_cols();
+    let prows = panel.len() as u16;
 
     // Reserve initial space for an empty box at the bottom (scrolls the screen up).
-    let mut reserved = box_height(0, cols);
+    let mut reserved = box_height(0, cols) + prows;
     {
         let rows = term_rows();
         stdout.execute(MoveTo(0, rows.saturating_sub(1)))?;
@@ -143,7 +158,7 @@ pub fn read_line_framed(history: &[String], label: &str, footer: &str) -> io::Re
     let mut history_pos: usize = history.len();
     let mut saved_input = String::new();
 
-    draw_box(&mut stdout, reserved, &buffer, cursor_pos, label, footer)?;
+    draw_box(&mut stdout, reserved, &buffer, cursor_pos, label, footer, panel)?;
 
     loop {
         if event::poll(Duration::from_millis(100))? {
@@ -271,9 +286,9 @@ pub fn read_line_framed(history: &[String], label: &str, footer: &str) -> io::Re
                 }
 
                 if redraw {
-                    let need = box_height(buffer.chars().count(), theme::term_cols());
+                    let need = box_height(buffer.chars().count(), theme::term_cols()) + prows;
                     reserved = ensure_reserved(&mut stdout, reserved, need)?;
-                    draw_box(&mut stdout, reserved, &buffer, cursor_pos, label, footer)?;
+                    draw_box(&mut stdout, reserved, &buffer, cursor_pos, label, footer, panel)?;
                 }
             }
         }
@@ -311,6 +326,7 @@ mod tests {
     fn first_match_completes_slash_commands() {
         assert_eq!(first_match("/he"), Some("/help"));
         assert_eq!(first_match("/cl"), Some("/clear"));
+        assert_eq!(first_match("/re"), Some("/rewind"));
         assert_eq!(first_match("hello"), None);
         assert_eq!(first_match("/help"), None);
     }
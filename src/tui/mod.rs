@@ -11,4 +11,4 @@
 mod app;
 mod frame_editor;
 
-pub use app::run_tui_mode;
+pub use app::{run_tui_mode, terminal_user};
@@ -71,7 +71,7 @@ impl SessionUsage {
 /// Get pricing per 1M tokens (input, output) for a model
 fn get_pricing(model: &str, provider: &Provider) -> (f64, f64) {
     match provider {
-        Provider::Gemini => {
+        Provider::Gemini | Provider::VertexAI => {
             // Gemini pricing as of 2025
             if model.contains("flash") {
                 (0.075, 0.30)  // gemini-2.0-flash, gemini-2.5-flash
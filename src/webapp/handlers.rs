@@ -1,12 +1,18 @@
 use super::persistence::SessionMetadata;
 use super::scheduler;
 use super::server::AppState;
+use crate::checkpoints::{self, Checkpoint};
+use crate::agent::{complete_tool_calls, CONTINUE_PROMPT, MAX_LENGTH_CONTINUATIONS};
 use crate::client::Client;
-use crate::compact::{compact_context, is_context_exhausted_error, CompactionConfig};
+use crate::compact::{compact_context, is_context_exhausted_error, reattach_todos, CompactionConfig};
 use crate::key_rotation::{BadKeyAction, RateLimitAction};
-use crate::models::{Message, ProviderInfo};
-use crate::tools::ToolRegistry;
+use crate::models::{BuiltinToolOutput, Citation, FinishReason, Message, ProviderInfo};
+use crate::redact;
+use crate::tools::{
+    progress_channel, Answer, Question, TodoItem, TodoList, ToolContext, ToolRegistry, UserPrompt,
+};
 use crate::usage::SessionUsage;
+use anyhow::bail;
 use axum::{
     extract::State,
     http::HeaderMap,
@@ -14,6 +20,7 @@ use axum::{
     Json,
 };
 use chrono::Local;
+use futures::future::BoxFuture;
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
@@ -21,7 +28,7 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{broadcast, mpsc, watch};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
 use tokio_stream::StreamExt;
 
@@ -215,9 +222,11 @@ pub async fn delete_session(
     State(state): State<Arc<AppState>>,
     Json(request): Json<DeleteSessionRequest>,
 ) -> Json<DeleteSessionResponse> {
+    state.end_session_jobs(&request.session_id).await;
     match state.storage.delete_session(&request.session_id).await {
         Ok(deleted) => {
             if deleted {
+                state.end_session_checkpoints(&request.session_id).await;
                 log(&format!("Deleted session: {}", &request.session_id[..8.min(request.session_id.len())]));
             }
             Json(DeleteSessionResponse { deleted })
@@ -256,6 +265,8 @@ pub struct HistoryToolCall {
 pub struct SessionHistoryResponse {
     exists: bool,
     messages: Vec<HistoryMessage>,
+    /// The session's Todo list, for the tasks sidebar
+    todos: Vec<TodoItem>,
 }
 
 /// Get session history
@@ -271,10 +282,10 @@ pub async fn get_session_history(
         if let Some(ref user) = authenticated_user {
             match state.storage.get_or_create_user_session(user).await {
                 Ok(session) => session.id,
-                Err(_) => return Json(SessionHistoryResponse { exists: false, messages: vec![] }),
+                Err(_) => return Json(SessionHistoryResponse { exists: false, messages: vec![], todos: vec![] }),
             }
         } else {
-            return Json(SessionHistoryResponse { exists: false, messages: vec![] });
+            return Json(SessionHistoryResponse { exists: false, messages: vec![], todos: vec![] });
         }
     } else {
         // Validate session ownership for authenticated users
@@ -282,7 +293,7 @@ pub async fn get_session_history(
             if let Ok(Some(session)) = state.storage.get_session(&request.session_id).await {
                 if let Some(ref session_user) = session.user_id {
                     if session_user != user {
-                        return Json(SessionHistoryResponse { exists: false, messages: vec![] });
+                        return Json(SessionHistoryResponse { exists: false, messages: vec![], todos: vec![] });
                     }
                 }
             }
@@ -332,11 +343,13 @@ pub async fn get_session_history(
             Json(SessionHistoryResponse {
                 exists: true,
                 messages,
+                todos: state.storage.get_todos(&session_id).await.unwrap_or_default(),
             })
         }
         _ => Json(SessionHistoryResponse {
             exists: false,
             messages: vec![],
+            todos: vec![],
         }),
     }
 }
@@ -381,6 +394,157 @@ pub async fn clear_session(
     })
 }
 
+/// Whether the caller may see a session: it must be a webapp session, and
+/// one owned by a user is hidden from everyone else
+async fn may_access_session(headers: &HeaderMap, state: &AppState, session_id: &str) -> bool {
+    match state.storage.get_session(session_id).await {
+        Ok(Some(session)) => match session.user_id {
+            Some(owner) => extract_user_identity(headers).is_some_and(|user| user == owner),
+            None => true,
+        },
+        _ => false,
+    }
+}
+
+/// Checkpoints request
+#[derive(Deserialize)]
+pub struct CheckpointsRequest {
+    session_id: String,
+}
+
+/// Checkpoints response: the session's file changes, oldest first
+#[derive(Serialize)]
+pub struct CheckpointsResponse {
+    checkpoints: Vec<Checkpoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// List the file checkpoints of a session
+pub async fn list_checkpoints(
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CheckpointsRequest>,
+) -> Json<CheckpointsResponse> {
+    let result = if may_access_session(&headers, &state, &request.session_id).await {
+        state
+            .session_checkpoints(&request.session_id)
+            .await
+            .and_then(|store| store.list())
+    } else {
+        Err(anyhow::anyhow!("Session not found"))
+    };
+    Json(match result {
+        Ok(checkpoints) => CheckpointsResponse { checkpoints, error: None },
+        Err(e) => CheckpointsResponse { checkpoints: vec![], error: Some(e.to_string()) },
+    })
+}
+
+/// Rewind request: undo checkpoint `checkpoint` and everything after it, or
+/// the last `steps` changes (default 1)
+#[derive(Deserialize)]
+pub struct RewindRequest {
+    session_id: String,
+    #[serde(default)]
+    checkpoint: Option<u64>,
+    #[serde(default)]
+    steps: Option<usize>,
+}
+
+/// Rewind response: the restored checkpoints, newest first
+#[derive(Serialize)]
+pub struct RewindResponse {
+    restored: Vec<Checkpoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Restore files from a session's checkpoints
+pub async fn rewind_session(
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RewindRequest>,
+) -> Json<RewindResponse> {
+    let running = state
+        .storage
+        .get_runtime_state(&request.session_id)
+        .await
+        .is_some_and(|(_, _, running)| running);
+    let result = if !may_access_session(&headers, &state, &request.session_id).await {
+        Err(anyhow::anyhow!("Session not found"))
+    } else if running {
+        // Its tools would be recording checkpoints while files are restored
+        Err(anyhow::anyhow!(
+            "A query is running in this session; wait for it or cancel it before rewinding"
+        ))
+    } else {
+        state
+            .session_checkpoints(&request.session_id)
+            .await
+            .and_then(|store| match request.checkpoint {
+                Some(id) => store.rewind_to(id),
+                None => store.undo(request.steps.unwrap_or(1)),
+            })
+    };
+    Json(match result {
+        Ok(restored) => {
+            if !restored.is_empty() {
+                log(&format!(
+                    "Rewound {} file change(s) in session {}:\n{}",
+                    restored.len(),
+                    &request.session_id[..8.min(request.session_id.len())],
+                    checkpoints::describe(&restored)
+                ));
+            }
+            RewindResponse { restored, error: None }
+        }
+        Err(e) => RewindResponse { restored: vec![], error: Some(e.to_string()) },
+    })
+}
+
+/// Answer to the question a session's run is waiting on; no answer (or an
+/// empty one) dismisses it
+#[derive(Deserialize)]
+pub struct AnswerRequest {
+    session_id: String,
+    id: String,
+    #[serde(default)]
+    answer: Option<String>,
+}
+
+/// Answer response
+#[derive(Serialize)]
+pub struct AnswerResponse {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Answer an AskUser question
+pub async fn answer_question(
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<AnswerRequest>,
+) -> Json<AnswerResponse> {
+    let pending = if may_access_session(&headers, &state, &request.session_id).await {
+        let mut questions = state.questions.lock().await;
+        match questions.get(&request.session_id) {
+            Some(pending) if pending.id == request.id => questions.remove(&request.session_id),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let Some(pending) = pending else {
+        return Json(AnswerResponse {
+            ok: false,
+            error: Some("No such question is waiting for an answer".to_string()),
+        });
+    };
+    let _ = pending.reply.send(request.answer);
+    Json(AnswerResponse { ok: true, error: None })
+}
+
 /// Scheduled agents endpoint - config plus live run state
 pub async fn agents(State(state): State<Arc<AppState>>) -> Json<scheduler::AgentsResponse> {
     let server_model = state.provider_info.resolved_model.clone();
@@ -839,12 +1003,19 @@ pub async fn save_agent(
             enabled,
             timeout_secs,
             working_dir: blank_to_none(working_dir),
+            // Not in the form either: an update leaves the file's `gemini_tools`,
+            // `sandbox`, `env` and `pass_env` as they are, and a newly created agent
+            // starts without them.
+            gemini_tools: Vec::new(),
+            sandbox: None,
+            env: Default::default(),
+            pass_env: Vec::new(),
         };
 
         Ok(EditPlan {
             mutation: crate::agents::AgentMutation::Upsert {
                 original_name,
-                spec,
+                spec: Box::new(spec),
             },
             prompt_write: prompt_file.map(|path| (path, prompt)),
         })
@@ -936,6 +1107,8 @@ pub struct SessionEventsRequest {
 pub enum SseEvent {
     Thinking { elapsed_seconds: u64 },
     ToolCall { name: String, arguments: String },
+    /// Output lines from a tool that is still running (Bash), batched
+    ToolProgress { line: String },
     ToolResult { name: String, result: String, truncated: bool },
     Response { content: String },
     /// Streaming chunk (partial response)
@@ -947,9 +1120,53 @@ pub enum SseEvent {
     SessionId { session_id: String },
     /// Token usage summary for this query
     Usage { input_tokens: u64, output_tokens: u64, cached_tokens: u64, estimated_cost: f64 },
+    /// Code Gemini ran server-side with code execution
+    CodeExecution { language: String, code: String },
+    /// Outcome and output of that code
+    CodeResult { outcome: String, output: String },
+    /// Search queries and sources behind a grounded answer
+    Citations { queries: Vec<String>, sources: Vec<Citation> },
+    /// The session's whole Todo list, sent whenever the Todo tool changes it
+    Todos { items: Vec<TodoItem> },
+    /// An AskUser question the run is waiting on; answered at /api/session/answer
+    AskUser { id: String, question: String, options: Vec<String> },
     Done,
 }
 
+impl SseEvent {
+    /// The SSE `event:` name for this event
+    fn event_name(&self) -> &'static str {
+        match self {
+            SseEvent::Thinking { .. } => "thinking",
+            SseEvent::ToolCall { .. } => "tool_call",
+            SseEvent::ToolProgress { .. } => "tool_progress",
+            SseEvent::ToolResult { .. } => "tool_result",
+            SseEvent::Response { .. } => "response",
+            SseEvent::StreamChunk { .. } => "stream_chunk",
+            SseEvent::Info { .. } => "info",
+            SseEvent::Error { .. } => "error",
+            SseEvent::SessionId { .. } => "session_id",
+            SseEvent::Usage { .. } => "usage",
+            SseEvent::CodeExecution { .. } => "code_execution",
+            SseEvent::CodeResult { .. } => "code_result",
+            SseEvent::Citations { .. } => "citations",
+            SseEvent::Todos { .. } => "todos",
+            SseEvent::AskUser { .. } => "ask_user",
+            SseEvent::Done => "done",
+        }
+    }
+}
+
+impl From<BuiltinToolOutput> for SseEvent {
+    fn from(output: BuiltinToolOutput) -> Self {
+        match output {
+            BuiltinToolOutput::Code { language, code } => SseEvent::CodeExecution { language, code },
+            BuiltinToolOutput::CodeResult { outcome, output } => SseEvent::CodeResult { outcome, output },
+            BuiltinToolOutput::Citations { queries, sources } => SseEvent::Citations { queries, sources },
+        }
+    }
+}
+
 /// Session events endpoint - replay stored events and subscribe to live events if query is running
 pub async fn session_events(
     headers: HeaderMap,
@@ -1034,18 +1251,7 @@ pub async fn session_events(
     // Convert channel to SSE stream
     let combined_stream = ReceiverStream::new(rx).map(|event| {
         let data = serde_json::to_string(&event).unwrap_or_default();
-        let event_type = match &event {
-            SseEvent::Thinking { .. } => "thinking",
-            SseEvent::ToolCall { .. } => "tool_call",
-            SseEvent::ToolResult { .. } => "tool_result",
-            SseEvent::Response { .. } => "response",
-            SseEvent::StreamChunk { .. } => "stream_chunk",
-            SseEvent::Info { .. } => "info",
-            SseEvent::Error { .. } => "error",
-            SseEvent::SessionId { .. } => "session_id",
-            SseEvent::Usage { .. } => "usage",
-            SseEvent::Done => "done",
-        };
+        let event_type = event.event_name();
         Ok(axum::response::sse::Event::default()
             .event(event_type)
             .data(data))
@@ -1074,6 +1280,109 @@ pub(super) struct RunContext {
     pub tool_registry: Arc<ToolRegistry>,
 }
 
+/// How often a running tool's progress lines are sent to the client
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Most progress lines sent per interval; the rest are only counted
+const PROGRESS_BATCH_LINES: usize = 200;
+
+/// Progress lines collected since the last `tool_progress` event
+#[derive(Default)]
+struct ProgressBatch {
+    lines: Vec<String>,
+    skipped: usize,
+}
+
+impl ProgressBatch {
+    fn push(&mut self, line: String) {
+        if self.lines.len() < PROGRESS_BATCH_LINES {
+            self.lines.push(line);
+        } else {
+            self.skipped += 1;
+        }
+    }
+
+    /// The collected lines as one event, emptying the batch
+    fn take(&mut self) -> Option<SseEvent> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let mut line = std::mem::take(&mut self.lines).join("\n");
+        match std::mem::take(&mut self.skipped) {
+            0 => {}
+            skipped => line.push_str(&format!("\n[... {} more lines]", skipped)),
+        }
+        Some(SseEvent::ToolProgress { line })
+    }
+}
+
+/// How long a run waits for an AskUser answer before going on with the
+/// unattended reply
+const ASK_USER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A question a session's run is waiting on
+pub struct PendingQuestion {
+    id: String,
+    reply: oneshot::Sender<Option<String>>,
+}
+
+/// Puts AskUser questions to the browser: sends an `ask_user` event and waits
+/// for the answer to be POSTed, for the run to be cancelled, or for
+/// `ASK_USER_TIMEOUT`
+struct WebUser<'a> {
+    events: &'a EventSender,
+    cancel: watch::Receiver<bool>,
+}
+
+impl UserPrompt for WebUser<'_> {
+    fn ask<'a>(&'a self, question: &'a Question) -> BoxFuture<'a, anyhow::Result<Answer>> {
+        Box::pin(async move {
+            let state = &self.events.state;
+            let session_id = &self.events.session_id;
+            let id = uuid::Uuid::new_v4().to_string();
+            let (reply, answer) = oneshot::channel();
+            state
+                .questions
+                .lock()
+                .await
+                .insert(session_id.clone(), PendingQuestion { id: id.clone(), reply });
+            self.events.send(SseEvent::AskUser {
+                id: id.clone(),
+                question: question.question.clone(),
+                options: question.options.clone(),
+            }).await;
+
+            // None when nobody answered in time
+            let mut cancel = self.cancel.clone();
+            let answer = tokio::select! {
+                answer = answer => Some(answer.ok().flatten()),
+                _ = cancel.wait_for(|cancelled| *cancelled) => Some(None),
+                _ = tokio::time::sleep(ASK_USER_TIMEOUT) => None,
+            };
+            let mut questions = state.questions.lock().await;
+            if questions.get(session_id).is_some_and(|pending| pending.id == id) {
+                questions.remove(session_id);
+            }
+            drop(questions);
+            if *self.cancel.borrow() {
+                bail!("Query cancelled");
+            }
+            match answer {
+                Some(answer) => Ok(answer.map_or(Answer::Dismissed, |text| question.resolve(&text))),
+                None => {
+                    self.events.send(SseEvent::Info {
+                        message: format!(
+                            "No answer after {} minutes; continuing without one",
+                            ASK_USER_TIMEOUT.as_secs() / 60
+                        ),
+                    }).await;
+                    state.unattended.ask(question).await
+                }
+            }
+        })
+    }
+}
+
 impl EventSender {
     pub(super) fn new(
         tx: mpsc::Sender<SseEvent>,
@@ -1125,6 +1434,8 @@ impl DisplaySink for WebappDisplaySink {
             DisplayEvent::ToolCall { name, arguments } => {
                 SseEvent::ToolCall { name, arguments }
             }
+            DisplayEvent::ToolProgress { line } => SseEvent::ToolProgress { line },
+            DisplayEvent::Todos { items } => SseEvent::Todos { items },
             DisplayEvent::ToolResult { result, limit } => {
                 let (display_result, truncated) = if result.lines().count() > limit && limit > 0 {
                     let lines: Vec<&str> = result.lines().take(limit).collect();
@@ -1138,6 +1449,15 @@ impl DisplaySink for WebappDisplaySink {
                     truncated,
                 }
             }
+            DisplayEvent::CodeExecution { language, code } => {
+                SseEvent::CodeExecution { language, code }
+            }
+            DisplayEvent::CodeExecutionResult { outcome, output } => {
+                SseEvent::CodeResult { outcome, output }
+            }
+            DisplayEvent::Citations { queries, sources } => {
+                SseEvent::Citations { queries, sources }
+            }
             DisplayEvent::Response { content } => {
                 SseEvent::Response { content }
             }
@@ -1294,18 +1614,7 @@ pub async fn query(
     // Convert channel to SSE stream
     let stream = ReceiverStream::new(rx).map(|event| {
         let data = serde_json::to_string(&event).unwrap_or_default();
-        let event_type = match &event {
-            SseEvent::Thinking { .. } => "thinking",
-            SseEvent::ToolCall { .. } => "tool_call",
-            SseEvent::ToolResult { .. } => "tool_result",
-            SseEvent::Response { .. } => "response",
-            SseEvent::StreamChunk { .. } => "stream_chunk",
-            SseEvent::Info { .. } => "info",
-            SseEvent::Error { .. } => "error",
-            SseEvent::SessionId { .. } => "session_id",
-            SseEvent::Usage { .. } => "usage",
-            SseEvent::Done => "done",
-        };
+        let event_type = event.event_name();
         Ok(axum::response::sse::Event::default()
             .event(event_type)
             .data(data))
@@ -1423,7 +1732,20 @@ pub(super) async fn run_agent_with_events(
     let compaction_config = CompactionConfig::default();
     let mut compaction_attempted = false;
 
+    // Replies continued after hitting the output token limit
+    let mut length_continuations = 0;
+
     // Agent loop
+    let jobs = state.session_jobs(&session_id).await;
+    let checkpoints = state.session_checkpoints(&session_id).await.ok();
+    let todos = TodoList::with_items(state.storage.get_todos(&session_id).await.unwrap_or_default());
+    // The server's redactor, not an agent registry's: it has the key pool and redact.toml
+    let redactor = state.tool_registry.redactor();
+    let mut redactions = 0;
+    // Scheduled runs have nobody watching to answer AskUser
+    let web_user = WebUser { events: &event_sender, cancel: cancel_rx.clone() };
+    let user: &dyn UserPrompt = if run_ctx.is_some() { &state.unattended } else { &web_user };
+
     let mut loop_iteration = 0;
     loop {
         loop_iteration += 1;
@@ -1436,6 +1758,7 @@ pub(super) async fn run_agent_with_events(
                 message: "Query cancelled".to_string(),
             }).await;
             run_error = Some("Query cancelled".to_string());
+            jobs.kill_all();
             break;
         }
 
@@ -1542,6 +1865,7 @@ pub(super) async fn run_agent_with_events(
                         }
                     };
                     conversation_history = compacted_msgs;
+                    reattach_todos(&mut conversation_history, &todos.items());
                     compaction_attempted = true;
                     log(&format!("[{}] Compacted to ~{} tokens", log_prefix, crate::compact::estimate_tokens(&conversation_history)));
                     continue; // retry with the fitting context
@@ -1562,13 +1886,36 @@ pub(super) async fn run_agent_with_events(
         }
 
         let choice = &response.choices[0];
+        let truncated = choice.finish_reason == Some(FinishReason::Length);
+        if !truncated {
+            length_continuations = 0;
+        }
 
-        // Add assistant message to history
+        // Safety blocks and recitation stops leave no usable reply; say why
+        if let Some(message) = choice.finish_reason.as_ref().and_then(FinishReason::error_message) {
+            log(&format!("[{}] {}", log_prefix, message));
+            event_sender.send(SseEvent::Error { message }).await;
+        }
+
+        // A reply cut off mid tool call can't run that call
+        let tool_calls = if truncated {
+            complete_tool_calls(choice.message.tool_calls.as_ref())
+        } else {
+            choice.message.tool_calls.clone()
+        };
+
+        // Add assistant message to history, with any built-in tool activity
+        // folded into the content so it is kept with the session
         conversation_history.push(Message::Assistant {
-            content: choice.message.content.clone(),
-            tool_calls: choice.message.tool_calls.clone(),
+            content: choice.history_content(),
+            tool_calls: tool_calls.clone(),
         });
 
+        // Surface what Gemini's built-in tools did this turn
+        for output in &choice.message.builtin_outputs {
+            event_sender.send(SseEvent::from(output.clone())).await;
+        }
+
         // Send response content if present
         if let Some(content) = &choice.message.content {
             if !content.is_empty() {
@@ -1585,20 +1932,35 @@ pub(super) async fn run_agent_with_events(
         }
 
         // Check for tool calls
-        let Some(tool_calls) = &choice.message.tool_calls else {
+        let Some(tool_calls) = tool_calls.filter(|calls| !calls.is_empty()) else {
+            // Ask for the rest of a reply that hit the output limit
+            if truncated {
+                if length_continuations < MAX_LENGTH_CONTINUATIONS {
+                    length_continuations += 1;
+                    log(&format!(
+                        "[{}] Response hit the output token limit, continuing ({}/{})",
+                        log_prefix, length_continuations, MAX_LENGTH_CONTINUATIONS
+                    ));
+                    conversation_history.push(Message::User {
+                        content: CONTINUE_PROMPT.to_string(),
+                    });
+                    continue;
+                }
+                event_sender.send(SseEvent::Error {
+                    message: format!(
+                        "Response still truncated after {} continuations",
+                        MAX_LENGTH_CONTINUATIONS
+                    ),
+                }).await;
+            }
             log(&format!("[{}] No tool calls, loop complete", log_prefix));
             break;
         };
 
-        if tool_calls.is_empty() {
-            log(&format!("[{}] Empty tool calls, loop complete", log_prefix));
-            break;
-        }
-
         log(&format!("[{}] Processing {} tool call(s)", log_prefix, tool_calls.len()));
 
         // Execute tool calls
-        for tool_call in tool_calls {
+        for tool_call in &tool_calls {
             let tool_name = &tool_call.function.name;
             let arguments = &tool_call.function.arguments;
 
@@ -1615,11 +1977,68 @@ pub(super) async fn run_agent_with_events(
 
             // Execute tool
             let tool_result = if tool_registry.has_tool(tool_name) {
-                tool_registry.execute(tool_name, args).await
-                    .unwrap_or_else(|e| format!("Error: {}", e))
+                // Stream Bash output lines to the client while the tool runs
+                let (progress_tx, mut progress_rx) = progress_channel();
+                let ctx = ToolContext {
+                    checkpoints: checkpoints.as_deref(),
+                    progress: Some(&progress_tx),
+                    todos: Some(&todos),
+                    user: Some(user),
+                    ..ToolContext::new(&jobs)
+                };
+                let todos_revision = todos.revision();
+                let exec = tool_registry.execute_with_context(tool_name, args, ctx);
+                tokio::pin!(exec);
+                let mut batch = ProgressBatch::default();
+                let mut masker = redactor.lines();
+                let mut flush = tokio::time::interval(PROGRESS_INTERVAL);
+                let result = loop {
+                    tokio::select! {
+                        biased;
+                        _ = flush.tick() => {
+                            if let Some(event) = batch.take() {
+                                event_sender.send(event).await;
+                            }
+                        }
+                        Some(line) = progress_rx.recv() => {
+                            if let Some(line) = masker.mask(&line) {
+                                batch.push(line);
+                            }
+                        }
+                        result = &mut exec => break result,
+                    }
+                };
+                while let Ok(line) = progress_rx.try_recv() {
+                    if let Some(line) = masker.mask(&line) {
+                        batch.push(line);
+                    }
+                }
+                if let Some(event) = batch.take() {
+                    event_sender.send(event).await;
+                }
+                if todos.revision() != todos_revision {
+                    let items = todos.items();
+                    if let Err(e) = state.storage.set_todos(&session_id, &items).await {
+                        log(&format!("[{}] Failed to save task list: {:#}", log_prefix, e));
+                    }
+                    event_sender.send(SseEvent::Todos { items }).await;
+                }
+                result.unwrap_or_else(|e| format!("Error: {}", e))
             } else {
                 format!("Error: Unknown tool '{}'", tool_name)
             };
+            let (tool_result, redacted) = redactor.redact(&tool_result);
+            if redacted > 0 {
+                redactions += redacted;
+                event_sender.send(SseEvent::Info {
+                    message: format!(
+                        "Redacted {} from {} output ({} this run)",
+                        redact::describe(redacted),
+                        tool_name,
+                        redactions
+                    ),
+                }).await;
+            }
 
             let result_preview = if tool_result.len() > 80 {
                 format!("{}...", &tool_result[..80])
@@ -1654,6 +2073,18 @@ pub(super) async fn run_agent_with_events(
     // Stop thinking timer
     thinking_handle.abort();
 
+    // A scheduled run is the whole session; nothing will read its jobs later
+    if run_ctx.is_some() {
+        state.end_session_jobs(&session_id).await;
+    }
+
+    // Tool results are already redacted; this catches secrets pasted into
+    // prompts or repeated in replies before anything is written
+    let conversation_history: Vec<Message> = conversation_history
+        .iter()
+        .map(|msg| redactor.redact_message(msg).0)
+        .collect();
+
     // Save updated history to storage and mark query as complete
     let _ = state.storage.set_history(&session_id, &conversation_history).await;
     state.storage.set_runtime_state(&session_id, Vec::new(), None, false).await;
@@ -1703,6 +2134,7 @@ pub(super) async fn run_agent_with_events(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env_policy::EnvPolicy;
     use tempfile::TempDir;
 
     const INLINE_AND_FILE: &str = r#"
@@ -1721,6 +2153,22 @@ enabled = false
 timeout_secs = 900
 "#;
 
+    #[test]
+    fn test_progress_batch_joins_lines_and_counts_overflow() {
+        let mut batch = ProgressBatch::default();
+        assert!(batch.take().is_none());
+        for n in 0..PROGRESS_BATCH_LINES + 3 {
+            batch.push(n.to_string());
+        }
+        let Some(SseEvent::ToolProgress { line }) = batch.take() else {
+            panic!("expected a progress event");
+        };
+        assert!(line.starts_with("0\n1\n"));
+        let last = format!("\n{}\n[... 3 more lines]", PROGRESS_BATCH_LINES - 1);
+        assert!(line.ends_with(&last), "{}", line);
+        assert!(batch.take().is_none());
+    }
+
     #[test]
     fn test_blank_to_none_treats_absent_null_and_empty_alike() {
         assert_eq!(blank_to_none(None), None);
@@ -1972,7 +2420,7 @@ timeout_secs = 900
         )
         .unwrap();
         let config = crate::agents::load_agents_file(&path, &|_| Ok(())).unwrap();
-        let registry = scheduler::AgentRegistry::new(config, "server-model").unwrap();
+        let registry = scheduler::AgentRegistry::new(config, "server-model", None, EnvPolicy::default()).unwrap();
         (path, registry)
     }
 
@@ -2014,7 +2462,7 @@ timeout_secs = 900
             Ok(EditPlan {
                 mutation: crate::agents::AgentMutation::Upsert {
                     original_name: Some("daily-digest".to_string()),
-                    spec: crate::agents::AgentSpec {
+                    spec: Box::new(crate::agents::AgentSpec {
                         name: "daily-digest".to_string(),
                         schedule: "not a cron".to_string(),
                         model: None,
@@ -2023,7 +2471,11 @@ timeout_secs = 900
                         enabled: true,
                         timeout_secs: 600,
                         working_dir: None,
-                    },
+                        gemini_tools: Vec::new(),
+                        sandbox: None,
+                        env: Default::default(),
+                        pass_env: Vec::new(),
+                    }),
                 },
                 prompt_write: None,
             })
@@ -2078,7 +2530,7 @@ timeout_secs = 900
         )
         .unwrap();
         let config = crate::agents::load_agents_file(&path, &|_| Ok(())).unwrap();
-        let registry = Arc::new(scheduler::AgentRegistry::new(config, "server-model").unwrap());
+        let registry = Arc::new(scheduler::AgentRegistry::new(config, "server-model", None, EnvPolicy::default()).unwrap());
         let fingerprint = registry.status().await.fingerprint;
 
         // Two saves prepared against the same fingerprint, targeting different agents.
@@ -3,4 +3,4 @@ pub mod persistence;
 mod scheduler;
 mod server;
 
-pub use server::run_server;
+pub use server::{run_server, ServerOptions};
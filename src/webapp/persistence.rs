@@ -12,9 +12,13 @@ use std::sync::Arc;
 use tokio::sync::{broadcast, Mutex, RwLock};
 
 use crate::models::Message;
+use crate::tools::TodoItem;
 
 use super::handlers::SseEvent;
 
+/// Progress events kept for replay while a tool runs
+const MAX_REPLAYED_PROGRESS: usize = 50;
+
 /// Cyberpunk adjectives (William Gibson inspired)
 const ADJECTIVES: &[&str] = &[
     "chrome", "neon", "black", "ice", "sprawl", "ghost", "razor", "wire",
@@ -97,6 +101,9 @@ pub struct SessionMetadata {
     /// Only ever set for agent-run sessions; `None` for interactive chats and for
     /// rows written before the column existed.
     pub run_status: Option<RunStatus>,
+    /// The session's Todo list, for the "2/5 tasks" shown beside it. Empty, not
+    /// absent, when there is none.
+    pub todos: Vec<TodoItem>,
 }
 
 /// In-memory session for runtime use
@@ -109,6 +116,7 @@ pub struct MemorySession {
     pub created_at: i64,
     pub updated_at: i64,
     pub history: Vec<Message>,
+    pub todos: Vec<TodoItem>,
     pub events: Vec<SseEvent>,
     pub event_tx: Option<broadcast::Sender<SseEvent>>,
     pub query_running: bool,
@@ -126,6 +134,7 @@ impl MemorySession {
             created_at: now,
             updated_at: now,
             history: Vec::new(),
+            todos: Vec::new(),
             events: Vec::new(),
             event_tx: None,
             query_running: false,
@@ -198,6 +207,7 @@ impl SessionStorage {
                 user_id TEXT,
                 agent_name TEXT,
                 run_status TEXT,
+                todos TEXT,
                 created_at INTEGER NOT NULL,
                 updated_at INTEGER NOT NULL
             );
@@ -243,6 +253,10 @@ impl SessionStorage {
             conn.execute("ALTER TABLE sessions ADD COLUMN run_status TEXT", [])?;
         }
 
+        if !columns.iter().any(|c| c == "todos") {
+            conn.execute("ALTER TABLE sessions ADD COLUMN todos TEXT", [])?;
+        }
+
         // Indexed after the column is guaranteed to exist: an index on a missing
         // column is an error, which would abort init_schema on an old database.
         conn.execute_batch(
@@ -406,6 +420,55 @@ impl SessionStorage {
         }
     }
 
+    /// The session's Todo list; empty when it has none or the session is unknown
+    pub async fn get_todos(&self, session_id: &str) -> Result<Vec<TodoItem>> {
+        match self {
+            SessionStorage::Memory(store) => {
+                let store = store.read().await;
+                Ok(store.get(session_id).map(|s| s.todos.clone()).unwrap_or_default())
+            }
+            SessionStorage::Sqlite { conn, .. } => {
+                let conn = conn.lock().await;
+                let todos: Option<String> = match conn.query_row(
+                    "SELECT todos FROM sessions WHERE id = ?",
+                    [session_id],
+                    |row| row.get(0),
+                ) {
+                    Ok(todos) => todos,
+                    Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                    Err(e) => return Err(e.into()),
+                };
+                Ok(parse_todos(todos.as_deref()))
+            }
+        }
+    }
+
+    /// Replace the session's Todo list. Like `set_run_status`, leaves `updated_at` alone.
+    pub async fn set_todos(&self, session_id: &str, todos: &[TodoItem]) -> Result<()> {
+        match self {
+            SessionStorage::Memory(store) => {
+                let mut store = store.write().await;
+                if let Some(session) = store.get_mut(session_id) {
+                    session.todos = todos.to_vec();
+                }
+                Ok(())
+            }
+            SessionStorage::Sqlite { conn, .. } => {
+                let conn = conn.lock().await;
+                let json = if todos.is_empty() {
+                    None
+                } else {
+                    Some(serde_json::to_string(todos)?)
+                };
+                conn.execute(
+                    "UPDATE sessions SET todos = ? WHERE id = ?",
+                    params![json, session_id],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
     /// Get a session by ID
     pub async fn get_session(&self, session_id: &str) -> Result<Option<SessionRecord>> {
         match self {
@@ -467,6 +530,7 @@ impl SessionStorage {
                             relative_time: format_relative_time(s.updated_at, now),
                             agent_name: s.agent_name.clone(),
                             run_status: s.run_status,
+                            todos: s.todos.clone(),
                         }
                     })
                     .collect();
@@ -484,7 +548,7 @@ impl SessionStorage {
                         "SELECT s.id, s.name, s.updated_at,
                                 (SELECT COUNT(*) FROM events e WHERE e.session_id = s.id
                                  AND e.event_type IN ('user_message', 'assistant_message')) as turn_count,
-                                s.agent_name, s.run_status
+                                s.agent_name, s.run_status, s.todos
                          FROM sessions s WHERE s.user_id = ? ORDER BY s.updated_at DESC"
                     )?;
                     let rows = stmt.query_map([uid], |row| {
@@ -495,10 +559,11 @@ impl SessionStorage {
                             row.get::<_, i64>(3)?,
                             row.get::<_, Option<String>>(4)?,
                             row.get::<_, Option<String>>(5)?,
+                            row.get::<_, Option<String>>(6)?,
                         ))
                     })?;
                     for row in rows {
-                        let (id, name, updated_at, turn_count, agent_name, run_status) = row?;
+                        let (id, name, updated_at, turn_count, agent_name, run_status, todos) = row?;
                         sessions.push(SessionMetadata {
                             id,
                             name,
@@ -507,6 +572,7 @@ impl SessionStorage {
                             relative_time: format_relative_time(updated_at, now),
                             agent_name,
                             run_status: run_status.as_deref().and_then(RunStatus::from_str),
+                            todos: parse_todos(todos.as_deref()),
                         });
                     }
                 } else {
@@ -514,7 +580,7 @@ impl SessionStorage {
                         "SELECT s.id, s.name, s.updated_at,
                                 (SELECT COUNT(*) FROM events e WHERE e.session_id = s.id
                                  AND e.event_type IN ('user_message', 'assistant_message')) as turn_count,
-                                s.agent_name, s.run_status
+                                s.agent_name, s.run_status, s.todos
                          FROM sessions s ORDER BY s.updated_at DESC"
                     )?;
                     let rows = stmt.query_map([], |row| {
@@ -525,10 +591,11 @@ impl SessionStorage {
                             row.get::<_, i64>(3)?,
                             row.get::<_, Option<String>>(4)?,
                             row.get::<_, Option<String>>(5)?,
+                            row.get::<_, Option<String>>(6)?,
                         ))
                     })?;
                     for row in rows {
-                        let (id, name, updated_at, turn_count, agent_name, run_status) = row?;
+                        let (id, name, updated_at, turn_count, agent_name, run_status, todos) = row?;
                         sessions.push(SessionMetadata {
                             id,
                             name,
@@ -537,6 +604,7 @@ impl SessionStorage {
                             relative_time: format_relative_time(updated_at, now),
                             agent_name,
                             run_status: run_status.as_deref().and_then(RunStatus::from_str),
+                            todos: parse_todos(todos.as_deref()),
                         });
                     }
                 }
@@ -567,6 +635,7 @@ impl SessionStorage {
                             relative_time: format_relative_time(s.updated_at, now),
                             agent_name: s.agent_name.clone(),
                             run_status: s.run_status,
+                            todos: s.todos.clone(),
                         }
                     })
                     .collect();
@@ -582,7 +651,7 @@ impl SessionStorage {
                     "SELECT s.id, s.name, s.updated_at,
                             (SELECT COUNT(*) FROM events e WHERE e.session_id = s.id
                              AND e.event_type IN ('user_message', 'assistant_message')) as turn_count,
-                            s.agent_name, s.run_status
+                            s.agent_name, s.run_status, s.todos
                      FROM sessions s WHERE s.agent_name = ? ORDER BY s.updated_at DESC LIMIT ?"
                 )?;
                 let rows = stmt.query_map(params![agent_name, limit as i64], |row| {
@@ -593,12 +662,13 @@ impl SessionStorage {
                         row.get::<_, i64>(3)?,
                         row.get::<_, Option<String>>(4)?,
                         row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
                     ))
                 })?;
 
                 let mut sessions = Vec::new();
                 for row in rows {
-                    let (id, name, updated_at, turn_count, agent_name, run_status) = row?;
+                    let (id, name, updated_at, turn_count, agent_name, run_status, todos) = row?;
                     sessions.push(SessionMetadata {
                         id,
                         name,
@@ -607,6 +677,7 @@ impl SessionStorage {
                         relative_time: format_relative_time(updated_at, now),
                         agent_name,
                         run_status: run_status.as_deref().and_then(RunStatus::from_str),
+                        todos: parse_todos(todos.as_deref()),
                     });
                 }
                 Ok(sessions)
@@ -919,13 +990,13 @@ impl SessionStorage {
             SessionStorage::Memory(store) => {
                 let mut store = store.write().await;
                 if let Some(session) = store.get_mut(session_id) {
-                    session.events.push(event);
+                    push_replayed(&mut session.events, event);
                 }
             }
             SessionStorage::Sqlite { runtime, .. } => {
                 let mut runtime = runtime.write().await;
                 if let Some(state) = runtime.get_mut(session_id) {
-                    state.events.push(event);
+                    push_replayed(&mut state.events, event);
                 }
             }
         }
@@ -1061,6 +1132,7 @@ impl SessionStorage {
                 for session in store.values_mut() {
                     if session.user_id.as_deref() == Some(user_id) {
                         session.history.clear();
+                        session.todos.clear();
                         session.events.clear();
                         break;
                     }
@@ -1088,6 +1160,12 @@ impl SessionStorage {
     }
 }
 
+/// A `sessions.todos` value. NULL, or JSON that doesn't parse, reads as no list.
+fn parse_todos(json: Option<&str>) -> Vec<TodoItem> {
+    json.and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
 /// Format a Unix timestamp as relative time
 pub fn format_relative_time(timestamp: i64, now: i64) -> String {
     let diff = now - timestamp;
@@ -1138,10 +1216,30 @@ pub fn format_relative_time(timestamp: i64, now: i64) -> String {
     }
 }
 
+/// Add `event` to a session's replay buffer. Progress is only replayed for the
+/// tool still running, and only its latest few events: the result replaces
+/// it on the client anyway.
+fn push_replayed(events: &mut Vec<SseEvent>, event: SseEvent) {
+    let is_progress = |event: &SseEvent| matches!(event, SseEvent::ToolProgress { .. });
+    match &event {
+        SseEvent::ToolResult { .. } => events.retain(|event| !is_progress(event)),
+        SseEvent::ToolProgress { .. }
+            if events.iter().filter(|event| is_progress(event)).count() >= MAX_REPLAYED_PROGRESS =>
+        {
+            if let Some(oldest) = events.iter().position(is_progress) {
+                events.remove(oldest);
+            }
+        }
+        _ => {}
+    }
+    events.push(event);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::models::Message;
+    use crate::tools::TodoStatus;
 
     #[test]
     fn test_format_relative_time() {
@@ -1735,6 +1833,58 @@ mod tests {
         assert!(history.is_empty() || !history.is_empty()); // May or may not have history
     }
 
+    #[tokio::test]
+    async fn test_sqlite_storage_history_keeps_tool_call_metadata() {
+        use crate::models::{FunctionCall, ToolCall, ToolCallMetadata};
+
+        let (storage, _temp_dir) = create_temp_sqlite_storage();
+        let session = storage.create_session(None).await.unwrap();
+
+        let assistant = Message::Assistant {
+            content: None,
+            tool_calls: Some(vec![ToolCall {
+                id: "call_abc".to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: "Bash".to_string(),
+                    arguments: r#"{"command":"ls"}"#.to_string(),
+                },
+                provider_metadata: Some(ToolCallMetadata {
+                    thought_signature: Some("sig123".to_string()),
+                }),
+            }]),
+        };
+        let tool = Message::Tool {
+            tool_call_id: "call_abc".to_string(),
+            content: "a.txt".to_string(),
+        };
+        storage
+            .append_event(&session.id, "assistant_message", &serde_json::to_string(&assistant).unwrap())
+            .await
+            .unwrap();
+        storage
+            .append_event(&session.id, "tool_message", &serde_json::to_string(&tool).unwrap())
+            .await
+            .unwrap();
+
+        let history = storage.get_history(&session.id).await.unwrap();
+        assert_eq!(history.len(), 2);
+        match &history[0] {
+            Message::Assistant { tool_calls: Some(calls), .. } => {
+                assert_eq!(calls[0].id, "call_abc");
+                assert_eq!(
+                    calls[0].provider_metadata,
+                    Some(ToolCallMetadata { thought_signature: Some("sig123".to_string()) })
+                );
+            }
+            other => panic!("Expected Assistant message with tool calls, got {:?}", other),
+        }
+        match &history[1] {
+            Message::Tool { tool_call_id, .. } => assert_eq!(tool_call_id, "call_abc"),
+            other => panic!("Expected Tool message, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_sqlite_storage_list_sessions() {
         let (storage, _temp_dir) = create_temp_sqlite_storage();
@@ -2384,6 +2534,67 @@ mod tests {
         assert_eq!(still_null, None);
     }
 
+    #[tokio::test]
+    async fn test_todos_round_trip() {
+        let (sqlite, _temp_dir) = create_temp_sqlite_storage();
+        let memory = SessionStorage::new_memory();
+        let todos = vec![
+            TodoItem { content: "Fetch feeds".to_string(), status: TodoStatus::Done },
+            TodoItem { content: "Write digest".to_string(), status: TodoStatus::InProgress },
+        ];
+
+        for storage in [&sqlite, &memory] {
+            let created = storage.create_session(None).await.unwrap();
+            assert!(storage.get_todos(&created.id).await.unwrap().is_empty());
+
+            storage.set_todos(&created.id, &todos).await.unwrap();
+            assert_eq!(storage.get_todos(&created.id).await.unwrap(), todos);
+            let sessions = storage.list_sessions(None).await.unwrap();
+            let meta = sessions.iter().find(|s| s.id == created.id).unwrap();
+            assert_eq!(meta.todos, todos);
+
+            storage.set_todos(&created.id, &[]).await.unwrap();
+            assert!(storage.get_todos(&created.id).await.unwrap().is_empty());
+            assert!(storage.get_todos("missing").await.unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_migrate_schema_adds_todos_to_old_database() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("old.db");
+        create_old_schema_db(&db_path);
+
+        let conn = Connection::open(&db_path).unwrap();
+        assert!(!column_names(&conn, "sessions").contains(&"todos".to_string()));
+        SessionStorage::migrate_schema(&conn).unwrap();
+        assert!(column_names(&conn, "sessions").contains(&"todos".to_string()));
+        assert!(parse_todos(None).is_empty());
+        assert!(parse_todos(Some("not json")).is_empty());
+    }
+
+    #[test]
+    fn test_replay_keeps_only_recent_progress_of_running_tool() {
+        let progress = |n: usize| SseEvent::ToolProgress { line: n.to_string() };
+        let mut events = Vec::new();
+        for n in 0..MAX_REPLAYED_PROGRESS + 10 {
+            push_replayed(&mut events, progress(n));
+        }
+        assert_eq!(events.len(), MAX_REPLAYED_PROGRESS);
+        assert!(matches!(&events[0], SseEvent::ToolProgress { line } if line == "10"));
+
+        push_replayed(
+            &mut events,
+            SseEvent::ToolResult {
+                name: "Bash".to_string(),
+                result: "done".to_string(),
+                truncated: false,
+            },
+        );
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], SseEvent::ToolResult { .. }));
+    }
+
     #[test]
     fn test_session_metadata_serialization_shape() {
         let meta = SessionMetadata {
@@ -2394,6 +2605,10 @@ mod tests {
             relative_time: "2h ago".to_string(),
             agent_name: Some("daily-digest".to_string()),
             run_status: Some(RunStatus::Success),
+            todos: vec![TodoItem {
+                content: "Fetch feeds".to_string(),
+                status: TodoStatus::Done,
+            }],
         };
 
         let json: serde_json::Value = serde_json::to_value(&meta).unwrap();
@@ -2405,11 +2620,13 @@ mod tests {
             "relative_time",
             "agent_name",
             "run_status",
+            "todos",
         ] {
             assert!(json.get(field).is_some(), "missing field {}", field);
         }
-        assert_eq!(json.as_object().unwrap().len(), 7);
+        assert_eq!(json.as_object().unwrap().len(), 8);
         assert_eq!(json["run_status"], "success");
+        assert_eq!(json["todos"][0]["status"], "done");
 
         // An untagged chat serializes both optional fields as null, not as an
         // omitted key, so the frontend can read them unconditionally.
@@ -2421,10 +2638,12 @@ mod tests {
             relative_time: "just now".to_string(),
             agent_name: None,
             run_status: None,
+            todos: Vec::new(),
         };
         let json: serde_json::Value = serde_json::to_value(&plain).unwrap();
         assert!(json["agent_name"].is_null());
         assert!(json["run_status"].is_null());
-        assert_eq!(json.as_object().unwrap().len(), 7);
+        assert_eq!(json["todos"], serde_json::json!([]));
+        assert_eq!(json.as_object().unwrap().len(), 8);
     }
 }
@@ -6,8 +6,12 @@ use crate::agents::{
     restricts_both_day_fields, AgentsConfig, LoadedAgent,
 };
 use crate::client::Client;
-use crate::models::{Message, ProviderInfo};
-use crate::tools::ToolRegistry;
+use crate::env_policy::EnvPolicy;
+use crate::mcp::McpServers;
+use crate::models::{GeminiBuiltinTool, Message, ProviderInfo};
+use crate::provider::require_native_gemini_api;
+use crate::sandbox::SandboxPolicy;
+use crate::tools::{todo, CustomTools, ToolRegistry, GET_OUTPUT_TOOL_NAME};
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local};
 use serde::Serialize;
@@ -58,8 +62,10 @@ pub struct RunState {
 }
 
 /// Per-agent execution context, built when a config is loaded. `client` is present
-/// only when the agent resolves to a different model than the server, `tool_registry`
-/// only when the agent sets `working_dir`; both fall back to the server's at run time.
+/// only when the agent resolves to a different model than the server or enables
+/// `gemini_tools`, `tool_registry` only when the agent sets `working_dir` (and with
+/// it, optionally, `sandbox`) or `env`/`pass_env`; both fall back to the server's at
+/// run time.
 #[derive(Clone)]
 struct AgentContext {
     /// What this context was built from. A reload keeps the context as-is while these
@@ -67,6 +73,10 @@ struct AgentContext {
     /// Ollama over HTTP.
     model: Option<String>,
     working_dir: Option<PathBuf>,
+    gemini_tools: Vec<GeminiBuiltinTool>,
+    sandbox: Option<SandboxPolicy>,
+    /// The server's environment policy with the agent's `env` and `pass_env`
+    env: EnvPolicy,
     client: Option<(Arc<Client>, Arc<ProviderInfo>)>,
     tool_registry: Option<Arc<ToolRegistry>>,
 }
@@ -84,6 +94,10 @@ pub struct AgentRegistry {
     edit_lock: Mutex<()>,
     source_path: PathBuf,
     server_model: String,
+    /// Offered to agents with their own tool registry, unless sandboxed
+    mcp: Option<Arc<McpServers>>,
+    /// The server's environment policy, extended per agent
+    env: EnvPolicy,
 }
 
 struct RegistryInner {
@@ -159,9 +173,21 @@ pub fn server_timezone() -> Option<String> {
 impl AgentRegistry {
     /// Build from a validated config. Constructs a per-agent Client only for agents whose
     /// model differs from `server_model`, and a per-agent ToolRegistry only for agents
-    /// with a `working_dir`. Agents needing neither share the server's.
-    pub fn new(config: AgentsConfig, server_model: &str) -> Result<Self> {
-        let contexts = build_contexts(&config.agents, server_model, &HashMap::new())?;
+    /// with a `working_dir` or their own environment. Agents needing neither share the
+    /// server's.
+    pub fn new(
+        config: AgentsConfig,
+        server_model: &str,
+        mcp: Option<Arc<McpServers>>,
+        env: EnvPolicy,
+    ) -> Result<Self> {
+        let contexts = build_contexts(
+            &config.agents,
+            server_model,
+            &HashMap::new(),
+            mcp.as_ref(),
+            &env,
+        )?;
         let source_path = config.source_path.clone();
         let fingerprint = fingerprint(&source_path, &config.agents);
 
@@ -178,6 +204,8 @@ impl AgentRegistry {
             edit_lock: Mutex::new(()),
             source_path,
             server_model: server_model.to_string(),
+            mcp,
+            env,
         })
     }
 
@@ -246,8 +274,10 @@ impl AgentRegistry {
         // HTTP probe, so it cannot run on a runtime worker.
         let agents = config.agents.clone();
         let server_model = self.server_model.clone();
+        let mcp = self.mcp.clone();
+        let env = self.env.clone();
         let contexts = tokio::task::spawn_blocking(move || {
-            build_contexts(&agents, &server_model, &previous_contexts)
+            build_contexts(&agents, &server_model, &previous_contexts, mcp.as_ref(), &env)
         })
         .await
         .map_err(|e| anyhow!("context build task failed: {}", e))??;
@@ -442,52 +472,89 @@ impl AgentRegistry {
     }
 }
 
-/// Build one context per agent, carrying over any whose `model` and `working_dir` are
-/// unchanged. Every agent gets an entry, including those that need neither a dedicated
+/// Build one context per agent, carrying over any whose `model`, `working_dir`,
+/// `gemini_tools`, `sandbox` and environment are unchanged. Every agent gets an entry, including those that need neither a dedicated
 /// client nor a dedicated tool registry, so a later reload can recognise them as
-/// unchanged instead of re-running provider detection.
+/// unchanged instead of re-running provider detection. MCP servers run outside
+/// any sandbox, so a sandboxed agent's registry does not offer their tools;
+/// custom tools come from the working directory's `.eunice/tools` and run
+/// under the agent's sandbox.
 fn build_contexts(
     agents: &[LoadedAgent],
     server_model: &str,
     previous: &HashMap<String, AgentContext>,
+    mcp: Option<&Arc<McpServers>>,
+    env: &EnvPolicy,
 ) -> Result<HashMap<String, AgentContext>> {
     let mut contexts: HashMap<String, AgentContext> = HashMap::new();
 
     for agent in agents {
+        let agent_env = env
+            .clone()
+            .with_vars(agent.env.clone())
+            .with_pass(agent.pass_env.clone());
         if let Some(existing) = previous.get(&agent.name) {
-            if existing.model == agent.model && existing.working_dir == agent.working_dir {
+            if existing.model == agent.model
+                && existing.working_dir == agent.working_dir
+                && existing.gemini_tools == agent.gemini_tools
+                && existing.sandbox == agent.sandbox
+                && existing.env == agent_env
+            {
                 contexts.insert(agent.name.clone(), existing.clone());
                 continue;
             }
         }
 
-        let client = match &agent.model {
-            Some(model) if model != server_model => {
-                let info = detect_provider_isolated(model).map_err(|e| {
-                    anyhow!("agent '{}': unknown model '{}': {}", agent.name, model, e)
-                })?;
-                if info.resolved_model == server_model {
-                    None
-                } else {
-                    let client = Client::new(&info).map_err(|e| {
-                        anyhow!("agent '{}': could not create client: {}", agent.name, e)
-                    })?;
-                    Some((Arc::new(client), Arc::new(info)))
+        // The server's client never sends Gemini built-in tools on an agent's behalf,
+        // so enabling them costs a dedicated client even on the server's model.
+        let own_model = agent.model.as_deref().filter(|model| *model != server_model);
+        let client = if own_model.is_some() || !agent.gemini_tools.is_empty() {
+            let model = own_model.unwrap_or(server_model);
+            let mut info = detect_provider_isolated(model).map_err(|e| {
+                anyhow!("agent '{}': unknown model '{}': {}", agent.name, model, e)
+            })?;
+            if info.resolved_model == server_model && agent.gemini_tools.is_empty() {
+                None
+            } else {
+                if !agent.gemini_tools.is_empty() {
+                    require_native_gemini_api(&mut info)
+                        .map_err(|e| anyhow!("agent '{}': gemini_tools: {}", agent.name, e))?;
                 }
+                let mut client = Client::new(&info).map_err(|e| {
+                    anyhow!("agent '{}': could not create client: {}", agent.name, e)
+                })?;
+                client.set_gemini_tools(agent.gemini_tools.clone());
+                Some((Arc::new(client), Arc::new(info)))
             }
-            _ => None,
+        } else {
+            None
         };
 
-        let tool_registry = agent
-            .working_dir
-            .as_ref()
-            .map(|dir| Arc::new(ToolRegistry::with_cwd(Some(dir.clone()))));
+        let own_registry = agent.working_dir.is_some() || agent_env != *env;
+        let tool_registry = own_registry.then(|| {
+            let (custom, errors) = CustomTools::load(agent.working_dir.as_deref());
+            for error in errors {
+                log(&format!("agent '{}': {}", agent.name, error));
+            }
+            let registry = ToolRegistry::with_cwd(agent.working_dir.clone())
+                .with_custom_tools(custom)
+                .with_env_policy(agent_env.clone())
+                .without_tool(GET_OUTPUT_TOOL_NAME);
+            Arc::new(match (&agent.sandbox, mcp) {
+                (Some(policy), _) => registry.with_sandbox(policy.clone()),
+                (None, Some(mcp)) => registry.with_mcp(mcp.clone()),
+                (None, None) => registry,
+            })
+        });
 
         contexts.insert(
             agent.name.clone(),
             AgentContext {
                 model: agent.model.clone(),
                 working_dir: agent.working_dir.clone(),
+                gemini_tools: agent.gemini_tools.clone(),
+                sandbox: agent.sandbox.clone(),
+                env: agent_env,
                 client,
                 tool_registry,
             },
@@ -823,13 +890,18 @@ async fn execute_run(
         }
     };
 
+    // How far the agent got through its own Todo list, if it kept one
+    let tasks = match state.storage.get_todos(&session.id).await {
+        Ok(todos) if !todos.is_empty() => format!(" ({})", todo::progress(&todos)),
+        _ => String::new(),
+    };
     let failure = match outcome {
         Ok(()) => {
-            log(&format!("[{}] run complete", agent.name));
+            log(&format!("[{}] run complete{}", agent.name, tasks));
             None
         }
         Err(message) => {
-            log(&format!("[{}] run failed: {}", agent.name, message));
+            log(&format!("[{}] run failed{}: {}", agent.name, tasks, message));
             Some(message)
         }
     };
@@ -928,6 +1000,10 @@ mod tests {
             enabled,
             timeout_secs: 600,
             working_dir: None,
+            gemini_tools: Vec::new(),
+            sandbox: None,
+            env: Default::default(),
+            pass_env: Vec::new(),
         }
     }
 
@@ -958,6 +1034,8 @@ mod tests {
             edit_lock: Mutex::new(()),
             source_path: PathBuf::from("/tmp/agents.toml"),
             server_model: "server-model".to_string(),
+            mcp: None,
+            env: EnvPolicy::default(),
         }
     }
 
@@ -966,7 +1044,8 @@ mod tests {
         let path = dir.path().join("agents.toml");
         std::fs::write(&path, body).unwrap();
         let config = crate::agents::load_agents_file(&path, &|_| Ok(())).unwrap();
-        let registry = AgentRegistry::new(config, "server-model").unwrap();
+        let registry =
+            AgentRegistry::new(config, "server-model", None, EnvPolicy::default()).unwrap();
         (path, registry)
     }
 
@@ -1216,6 +1295,8 @@ mod tests {
             &[agent("plain", "0 9 * * *", true)],
             "server-model",
             &HashMap::new(),
+            None,
+            &EnvPolicy::default(),
         )
         .unwrap();
 
@@ -1230,12 +1311,12 @@ mod tests {
         a.working_dir = Some(dir.path().to_path_buf());
 
         let first =
-            build_contexts(std::slice::from_ref(&a), "server-model", &HashMap::new()).unwrap();
+            build_contexts(std::slice::from_ref(&a), "server-model", &HashMap::new(), None, &EnvPolicy::default()).unwrap();
         // A schedule change must not cost a rebuild.
         let mut rescheduled = a.clone();
         rescheduled.schedule_expr = "0 10 * * *".to_string();
         let second =
-            build_contexts(std::slice::from_ref(&rescheduled), "server-model", &first).unwrap();
+            build_contexts(std::slice::from_ref(&rescheduled), "server-model", &first, None, &EnvPolicy::default()).unwrap();
 
         assert!(Arc::ptr_eq(
             first["a"].tool_registry.as_ref().unwrap(),
@@ -1251,12 +1332,12 @@ mod tests {
         a.working_dir = Some(dir.path().to_path_buf());
 
         let first =
-            build_contexts(std::slice::from_ref(&a), "server-model", &HashMap::new()).unwrap();
+            build_contexts(std::slice::from_ref(&a), "server-model", &HashMap::new(), None, &EnvPolicy::default()).unwrap();
 
         let mut moved = a.clone();
         moved.working_dir = Some(elsewhere.path().to_path_buf());
         let rebuilt =
-            build_contexts(std::slice::from_ref(&moved), "server-model", &first).unwrap();
+            build_contexts(std::slice::from_ref(&moved), "server-model", &first, None, &EnvPolicy::default()).unwrap();
         assert!(!Arc::ptr_eq(
             first["a"].tool_registry.as_ref().unwrap(),
             rebuilt["a"].tool_registry.as_ref().unwrap()
@@ -1266,7 +1347,7 @@ mod tests {
         // Equal to the server model, so this rebuild resolves nothing over the network.
         remodelled.model = Some("server-model".to_string());
         let rebuilt =
-            build_contexts(std::slice::from_ref(&remodelled), "server-model", &first).unwrap();
+            build_contexts(std::slice::from_ref(&remodelled), "server-model", &first, None, &EnvPolicy::default()).unwrap();
         assert!(!Arc::ptr_eq(
             first["a"].tool_registry.as_ref().unwrap(),
             rebuilt["a"].tool_registry.as_ref().unwrap()
@@ -1274,6 +1355,30 @@ mod tests {
         assert_eq!(rebuilt["a"].model.as_deref(), Some("server-model"));
     }
 
+    #[test]
+    fn test_build_contexts_gives_agent_env_its_own_registry() {
+        let mut a = agent("a", "0 9 * * *", true);
+        a.pass_env = vec!["OPENAI_API_KEY".to_string()];
+        let base = EnvPolicy::default();
+
+        let first =
+            build_contexts(std::slice::from_ref(&a), "server-model", &HashMap::new(), None, &base)
+                .unwrap();
+        assert!(first["a"].tool_registry.is_some());
+        assert!(first["a"].env.inherits("OPENAI_API_KEY"));
+        assert!(!base.inherits("OPENAI_API_KEY"));
+
+        let mut changed = a.clone();
+        changed.env.insert("REGION".to_string(), "eu".to_string());
+        let rebuilt =
+            build_contexts(std::slice::from_ref(&changed), "server-model", &first, None, &base)
+                .unwrap();
+        assert!(!Arc::ptr_eq(
+            first["a"].tool_registry.as_ref().unwrap(),
+            rebuilt["a"].tool_registry.as_ref().unwrap()
+        ));
+    }
+
     #[tokio::test]
     async fn test_apply_keeps_run_state_only_for_surviving_agents() {
         let registry = registry(vec![
@@ -1467,6 +1572,7 @@ mod tests {
                     relative_time: "2h ago".to_string(),
                     agent_name: Some("daily-digest".to_string()),
                     run_status: Some(RunStatus::Success),
+                    todos: Vec::new(),
                 }],
             }],
         };
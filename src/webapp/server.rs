@@ -1,15 +1,20 @@
+use crate::checkpoints::Checkpoints;
 use crate::client::Client;
+use crate::env_policy::EnvPolicy;
+use crate::mcp::McpServers;
 use crate::models::{ProviderInfo, WebappConfig};
-use crate::tools::ToolRegistry;
+use crate::redact::Redactor;
+use crate::tools::{BackgroundJobs, CustomTools, ToolRegistry, Unattended, GET_OUTPUT_TOOL_NAME};
 use anyhow::Result;
 use axum::{
     routing::{get, post},
     Router,
 };
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{watch, Mutex};
 
-use super::handlers;
+use super::handlers::{self, PendingQuestion};
 use super::persistence::SessionStorage;
 use super::scheduler::{self, AgentRegistry};
 
@@ -28,6 +33,71 @@ pub struct AppState {
     pub system_prompt: Option<String>,
     /// Scheduled agents, when an agents file was supplied
     pub agents: Option<Arc<AgentRegistry>>,
+    /// Background jobs per session id. Sessions share the tool registry, so
+    /// jobs are tracked here; dropping a table kills its jobs.
+    pub background_jobs: Mutex<HashMap<String, Arc<BackgroundJobs>>>,
+    /// Checkpoint store per session id. Runs and the rewind endpoint share
+    /// one, so its lock serializes their index updates.
+    pub checkpoints: Mutex<HashMap<String, Arc<Checkpoints>>>,
+    /// The AskUser question each session's run is waiting on
+    pub questions: Mutex<HashMap<String, PendingQuestion>>,
+    /// Answers AskUser in scheduled runs
+    pub unattended: Unattended,
+}
+
+impl AppState {
+    /// The background job table for a session, created on first use
+    pub async fn session_jobs(&self, session_id: &str) -> Arc<BackgroundJobs> {
+        self.background_jobs
+            .lock()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Kill and forget a session's background jobs
+    pub async fn end_session_jobs(&self, session_id: &str) {
+        if let Some(jobs) = self.background_jobs.lock().await.remove(session_id) {
+            jobs.kill_all();
+        }
+    }
+
+    /// The checkpoint store for a session, opened on first use
+    pub async fn session_checkpoints(&self, session_id: &str) -> Result<Arc<Checkpoints>> {
+        let mut stores = self.checkpoints.lock().await;
+        if let Some(store) = stores.get(session_id) {
+            return Ok(store.clone());
+        }
+        let store = Arc::new(Checkpoints::for_session(session_id)?);
+        stores.insert(session_id.to_string(), store.clone());
+        Ok(store)
+    }
+
+    /// Delete a session's checkpoints and forget its store
+    pub async fn end_session_checkpoints(&self, session_id: &str) {
+        let store = self.checkpoints.lock().await.remove(session_id);
+        let store = match store {
+            Some(store) => Ok(store),
+            None => Checkpoints::for_session(session_id).map(Arc::new),
+        };
+        if let Ok(store) = store {
+            let _ = store.remove();
+        }
+    }
+}
+
+/// How the webapp's sessions and scheduled agents run
+pub struct ServerOptions {
+    /// System prompt for interactive sessions
+    pub system_prompt: Option<String>,
+    /// Scheduled agents, when an agents file was supplied
+    pub agents: Option<crate::agents::AgentsConfig>,
+    pub mcp: Option<Arc<McpServers>>,
+    /// Environment variables commands may see (`--pass-env`)
+    pub env: EnvPolicy,
+    /// What AskUser tells the model in scheduled runs (`--ask-user-reply`)
+    pub ask_user_reply: Option<String>,
 }
 
 /// Run the webapp server
@@ -35,9 +105,16 @@ pub async fn run_server(
     webapp_config: WebappConfig,
     client: Client,
     provider_info: ProviderInfo,
-    system_prompt: Option<String>,
-    agents: Option<crate::agents::AgentsConfig>,
+    options: ServerOptions,
 ) -> Result<()> {
+    let ServerOptions {
+        system_prompt,
+        agents,
+        mcp,
+        env,
+        ask_user_reply,
+    } = options;
+
     // Initialize storage: persistent sessions.db by default, in-memory
     // when --no-persist is set or the database cannot be opened
     let storage = if webapp_config.persist {
@@ -56,8 +133,22 @@ pub async fn run_server(
         SessionStorage::new_memory()
     };
 
-    // Create tool registry
-    let tool_registry = ToolRegistry::new();
+    // Create tool registry. Its redactor masks secrets for every session,
+    // scheduled runs included.
+    let (custom, errors) = CustomTools::load(None);
+    let (redactor, redact_errors) = Redactor::load(client.api_keys());
+    for error in errors.into_iter().chain(redact_errors) {
+        eprintln!("Warning: {}", error);
+    }
+    let tool_registry = match &mcp {
+        Some(mcp) => ToolRegistry::new().with_mcp(mcp.clone()),
+        None => ToolRegistry::new(),
+    }
+    .with_custom_tools(custom)
+    .with_redactor(redactor)
+    .with_env_policy(env.clone())
+    // Results are sent whole, so there is no output store to read from
+    .without_tool(GET_OUTPUT_TOOL_NAME);
     let tool_count = tool_registry.get_tools().len();
 
     println!("Tools available: {}", tool_count);
@@ -70,7 +161,7 @@ pub async fn run_server(
         Some(config) => {
             let count = config.agents.len();
             let source = config.source_path.display().to_string();
-            let registry = AgentRegistry::new(config, &provider_info.resolved_model)?;
+            let registry = AgentRegistry::new(config, &provider_info.resolved_model, mcp, env)?;
             println!("Scheduled agents: {} (from {})", count, source);
             Some(Arc::new(registry))
         }
@@ -86,6 +177,10 @@ pub async fn run_server(
         storage,
         system_prompt,
         agents,
+        background_jobs: Mutex::new(HashMap::new()),
+        checkpoints: Mutex::new(HashMap::new()),
+        questions: Mutex::new(HashMap::new()),
+        unattended: ask_user_reply.map(Unattended::new).unwrap_or_default(),
     });
 
     scheduler::spawn(state.clone());
@@ -102,6 +197,9 @@ pub async fn run_server(
         .route("/api/session/history", post(handlers::get_session_history))
         .route("/api/session/clear", post(handlers::clear_session))
         .route("/api/session/events", post(handlers::session_events))
+        .route("/api/session/checkpoints", post(handlers::list_checkpoints))
+        .route("/api/session/rewind", post(handlers::rewind_session))
+        .route("/api/session/answer", post(handlers::answer_question))
         .route("/api/agents", get(handlers::agents))
         .route("/api/agents/get", post(handlers::get_agent))
         .route("/api/agents/save", post(handlers::save_agent))